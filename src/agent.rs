@@ -0,0 +1,205 @@
+//! Contains the agent, which keeps the vault unlocked for a while.
+//!
+//! `hmu agent start` unlocks the vault, then starts a background process that holds the unlocked
+//! vault's password in memory and hands it to other `hmu` processes over a Unix socket in the
+//! runtime directory. Only the current user may access the socket. Subcommands use the agent
+//! instead of prompting for the master password while it is running, which is what lets programs
+//! that cannot answer a prompt, such as Docker, read secrets from the vault.
+
+use std::{path::PathBuf, time::Duration};
+
+#[cfg(unix)]
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::{net::UnixStream, process::CommandExt},
+    path::Path,
+    process::{self, Command, Stdio},
+    sync::Arc,
+    thread,
+};
+
+use crate::{errors::HimitsuError, utils::runtime};
+
+/// Asks the agent for the vault's password.
+#[cfg(unix)]
+const PASSWORD_REQUEST: &str = "password";
+/// Asks the agent to lock the vault and exit.
+#[cfg(unix)]
+const LOCK_REQUEST: &str = "lock";
+/// The longest request the agent reads.
+#[cfg(unix)]
+const MAX_REQUEST_LENGTH: u64 = 64;
+/// How long the agent waits for a client to send its request.
+#[cfg(unix)]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Get the path to the agent's socket.
+pub fn get_socket_path() -> Result<PathBuf, HimitsuError> {
+    Ok(runtime::get_runtime_dir()?.join("agent.sock"))
+}
+
+/// Connect to the agent. Returns `None` if the agent is not running.
+#[cfg(unix)]
+fn connect() -> Option<UnixStream> {
+    UnixStream::connect(get_socket_path().ok()?).ok()
+}
+
+/// Send a request to the agent. Returns the agent's answer, or `None` if the agent is not
+/// running.
+#[cfg(unix)]
+fn send_request(request: &str) -> Option<String> {
+    let mut stream = connect()?;
+    writeln!(stream, "{request}").ok()?;
+
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer).ok()?;
+
+    Some(answer.trim_end_matches('\n').to_string())
+}
+
+/// Check whether the agent is running.
+#[cfg(unix)]
+pub fn is_running() -> bool {
+    connect().is_some()
+}
+
+/// The agent is not available on this platform, so it is never running.
+#[cfg(not(unix))]
+pub fn is_running() -> bool {
+    false
+}
+
+/// Get the unlocked vault's password from the agent, if the agent is running.
+#[cfg(unix)]
+pub fn request_password() -> Option<String> {
+    send_request(PASSWORD_REQUEST).filter(|password| !password.is_empty())
+}
+
+/// The agent is not available on this platform, so the password is always prompted for.
+#[cfg(not(unix))]
+pub fn request_password() -> Option<String> {
+    None
+}
+
+/// Ask the agent to lock the vault. Returns whether the agent was running.
+#[cfg(unix)]
+pub fn stop_agent() -> bool {
+    send_request(LOCK_REQUEST).is_some()
+}
+
+/// The agent is not available on this platform, so it was never running.
+#[cfg(not(unix))]
+pub fn stop_agent() -> bool {
+    false
+}
+
+/// Start the agent in the background with the unlocked vault's password. The agent locks the
+/// vault once `timeout` has passed.
+#[cfg(unix)]
+pub fn start_agent(password: &str, timeout: &str) -> Result<(), HimitsuError> {
+    let mut agent = Command::new(env::current_exe()?)
+        .args(["agent", "serve", "--timeout", timeout])
+        .process_group(0)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    agent
+        .stdin
+        .take()
+        .ok_or_else(|| HimitsuError::AgentError("Could not start the agent!".to_string()))?
+        .write_all(password.as_bytes())?;
+
+    for _ in 0..50 {
+        if is_running() {
+            return Ok(());
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    Err(HimitsuError::AgentError(
+        "The agent did not start in time!".to_string(),
+    ))
+}
+
+/// The agent needs Unix sockets, which are not available on this platform.
+#[cfg(not(unix))]
+pub fn start_agent(_password: &str, _timeout: &str) -> Result<(), HimitsuError> {
+    Err(HimitsuError::AgentError(
+        "The agent is only available on Unix-like systems!".to_string(),
+    ))
+}
+
+/// Serve the vault's password until `timeout` has passed or the agent is asked to lock the vault.
+/// Every client is served on its own thread, so a client that never sends its request does not
+/// hold up the others.
+#[cfg(unix)]
+pub fn serve(password: &str, timeout: Duration) -> Result<(), HimitsuError> {
+    let socket_path = get_socket_path()?;
+    let listener = runtime::bind_socket(&socket_path)?;
+
+    let timeout_socket_path = socket_path.clone();
+    thread::spawn(move || {
+        thread::sleep(timeout);
+
+        lock_vault(&timeout_socket_path);
+    });
+
+    let password = Arc::new(password.to_string());
+    for stream in listener.incoming().flatten() {
+        let password = Arc::clone(&password);
+        let socket_path = socket_path.clone();
+
+        thread::spawn(move || handle_connection(stream, &password, &socket_path));
+    }
+
+    Ok(())
+}
+
+/// The agent needs Unix sockets, which are not available on this platform.
+#[cfg(not(unix))]
+pub fn serve(_password: &str, _timeout: Duration) -> Result<(), HimitsuError> {
+    Err(HimitsuError::AgentError(
+        "The agent is only available on Unix-like systems!".to_string(),
+    ))
+}
+
+/// Answer a client's request. Clients that do not send a request within `REQUEST_TIMEOUT` are
+/// disconnected.
+#[cfg(unix)]
+fn handle_connection(mut stream: UnixStream, password: &str, socket_path: &Path) {
+    if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err() {
+        return;
+    }
+
+    let mut request = String::new();
+    if BufReader::new((&stream).take(MAX_REQUEST_LENGTH))
+        .read_line(&mut request)
+        .is_err()
+    {
+        return;
+    }
+
+    match request.trim() {
+        PASSWORD_REQUEST => {
+            let _ = writeln!(stream, "{password}");
+        }
+        LOCK_REQUEST => {
+            let _ = writeln!(stream);
+
+            lock_vault(socket_path);
+        }
+        _ => {}
+    }
+}
+
+/// Lock the vault by removing the agent's socket and stopping the agent.
+#[cfg(unix)]
+fn lock_vault(socket_path: &Path) -> ! {
+    let _ = fs::remove_file(socket_path);
+
+    process::exit(0);
+}
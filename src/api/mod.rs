@@ -0,0 +1,8 @@
+//! Contains the JSON API that lets local applications use the vault.
+//!
+//! `hmu serve` listens on a Unix socket only the current user may access. Applications send one
+//! JSON request per line and authenticate with an API token created by `hmu api-token add`.
+//! Each token may only access the categories and tags it was created for.
+
+pub mod server;
+pub mod tokens;
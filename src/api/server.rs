@@ -0,0 +1,522 @@
+//! Contains the server behind `hmu serve`.
+//!
+//! Every request is a single line of JSON such as
+//! `{"id": 1, "token": "hmu_...", "method": "get", "params": {"label": "db"}}`, and is answered
+//! with a single line containing either a `result` or an `error`. Every request is recorded in the
+//! audit log under the name of the token's application, including refused requests.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    api::tokens::{self, TokenRegistry},
+    audit::log,
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    lookup::{
+        expiry,
+        modify::{self, RemovalEvent},
+        secure,
+    },
+    models::{
+        api::{ApiParams, ApiRequest, ApiResponse, ApiToken},
+        log::LogAction,
+        metadata::{Anatomy, LookupTable, Protection, SecretType},
+        totp::TotpSecret,
+    },
+    totp,
+    utils::{dates, git, runtime},
+};
+
+/// The category secrets are added to if a `put` request does not name one.
+const DEFAULT_CATEGORY: &str = "unclassified";
+
+/// Get the path to the API's socket. Defaults to `api.sock` in the runtime directory.
+pub fn get_socket_path(socket: &Option<PathBuf>) -> Result<PathBuf, HimitsuError> {
+    match socket {
+        Some(socket_path) => Ok(socket_path.clone()),
+        None => Ok(runtime::get_runtime_dir()?.join("api.sock")),
+    }
+}
+
+/// Get the label a request is about.
+fn get_label(params: &ApiParams) -> Result<&str, HimitsuError> {
+    params
+        .label
+        .as_deref()
+        .ok_or_else(|| HimitsuError::ApiError("The \"label\" parameter is missing!".to_string()))
+}
+
+/// Describe the secret a request is about for the audit log, if it names one.
+fn describe_request(params: &ApiParams) -> Option<String> {
+    match (&params.category, &params.label) {
+        (Some(category), Some(label)) => Some(format!("{category}/{label}")),
+        (None, Some(label)) => Some(label.clone()),
+        _ => None,
+    }
+}
+
+/// Refuse protected secrets, since the API cannot ask for the master password or a passphrase.
+fn check_unprotected(anatomy: &Anatomy) -> Result<(), HimitsuError> {
+    if anatomy.protection == Protection::None {
+        Ok(())
+    } else {
+        Err(HimitsuError::ApiError(format!(
+            "\"{}\" is protected and cannot be used through the API!",
+            anatomy.label
+        )))
+    }
+}
+
+/// Find the secret a request is about among the secrets the token may access.
+pub fn find_secret<'a>(
+    lookup_table: &'a LookupTable,
+    token: &ApiToken,
+    params: &ApiParams,
+) -> Result<(&'a String, &'a Anatomy), HimitsuError> {
+    let label = get_label(params)?;
+    let now = dates::get_now();
+
+    let mut found_matches = lookup_table
+        .table
+        .iter()
+        .filter(|(_, anatomy)| {
+            anatomy.label == label
+                && !expiry::has_lapsed(anatomy, now)
+                && params
+                    .category
+                    .as_ref()
+                    .is_none_or(|category| anatomy.category.eq_ignore_ascii_case(category))
+                && tokens::is_in_scope(token, &anatomy.category, &anatomy.tags)
+        })
+        .collect::<Vec<(&String, &Anatomy)>>();
+
+    match found_matches.len() {
+        0 => Err(HimitsuError::ApiError(format!(
+            "No secret labeled \"{label}\" is accessible with this token!"
+        ))),
+        1 => Ok(found_matches.remove(0)),
+        _ => Err(HimitsuError::ApiError(format!(
+            "Several secrets are labeled \"{label}\". Pass a \"category\" as well!"
+        ))),
+    }
+}
+
+/// List the secrets the token may access, without their values.
+fn list_secrets(token: &ApiToken, password: &str) -> Result<Value, HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+    let now = dates::get_now();
+
+    let mut secrets = lookup_table
+        .table
+        .values()
+        .filter(|anatomy| {
+            tokens::is_in_scope(token, &anatomy.category, &anatomy.tags)
+                && !expiry::has_lapsed(anatomy, now)
+        })
+        .collect::<Vec<&Anatomy>>();
+    secrets.sort_by(|first, second| {
+        (&first.category, &first.label).cmp(&(&second.category, &second.label))
+    });
+
+    Ok(serde_json::to_value(secrets)?)
+}
+
+/// Decrypt a secret. TOTP secrets return their current code instead of their seed.
+fn get_secret(
+    token: &ApiToken,
+    params: &ApiParams,
+    password: &str,
+) -> Result<(String, Value), HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+    let (hash_id, anatomy) = find_secret(&lookup_table, token, params)?;
+    check_unprotected(anatomy)?;
+
+    let mut secret =
+        decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(hash_id)?, password)?;
+    if anatomy.secret_type == SecretType::Totp {
+        let totp_secret: TotpSecret = serde_json::from_str(&secret)?;
+        secret = totp::generate_code(&totp_secret, totp::get_unix_time())?;
+    }
+
+    modify::update_last_accessed(hash_id, password)?;
+
+    Ok((
+        log::describe_secret(anatomy),
+        json!({
+            "category": anatomy.category,
+            "label": anatomy.label,
+            "secret": secret,
+        }),
+    ))
+}
+
+/// Add a secret, or replace the value of the secret with the same label and category.
+fn put_secret(
+    token: &ApiToken,
+    params: &ApiParams,
+    password: &str,
+) -> Result<(LogAction, String, Value), HimitsuError> {
+    let label = get_label(params)?;
+    let secret = params.secret.clone().ok_or_else(|| {
+        HimitsuError::ApiError("The \"secret\" parameter is missing!".to_string())
+    })?;
+    let category = params
+        .category
+        .as_deref()
+        .unwrap_or(DEFAULT_CATEGORY)
+        .to_lowercase();
+    let tags = params
+        .tags
+        .as_ref()
+        .map(|tags| tags.iter().map(|tag| tag.to_lowercase()).collect());
+
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+    let existing = lookup_table
+        .table
+        .iter()
+        .find(|(_, anatomy)| anatomy.label == label && anatomy.category == category);
+
+    let (anatomy, replaced_hash) = match existing {
+        Some((hash_id, anatomy)) => {
+            if !tokens::is_in_scope(token, &anatomy.category, &anatomy.tags) {
+                return Err(HimitsuError::ApiError(format!(
+                    "\"{label}\" is not accessible with this token!"
+                )));
+            }
+            check_unprotected(anatomy)?;
+            if anatomy.secret_type != SecretType::Password {
+                return Err(HimitsuError::ApiError(format!(
+                    "\"{label}\" is a TOTP secret and cannot be replaced through the API!"
+                )));
+            }
+
+            let current_secret =
+                decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(hash_id)?, password)?;
+
+            let mut new_anatomy = anatomy.clone();
+            new_anatomy.last_modified = Some(dates::get_timestamp());
+            if current_secret != secret {
+                new_anatomy.last_rotated = new_anatomy.last_modified.clone();
+            }
+            if let Some(tags) = tags {
+                new_anatomy.tags = tags;
+            }
+
+            (new_anatomy, Some(hash_id.clone()))
+        }
+        None => (
+            Anatomy::create_from(category, label.to_string(), tags.unwrap_or_default()),
+            None,
+        ),
+    };
+
+    // Every check runs before the vault is touched, so a refused request never changes it.
+    if !tokens::is_in_scope(token, &anatomy.category, &anatomy.tags) {
+        return Err(HimitsuError::ApiError(format!(
+            "This token may not store secrets in \"{}\" with these tags!",
+            anatomy.category
+        )));
+    }
+
+    let action = match &replaced_hash {
+        Some(replaced_hash) => {
+            let hash_id = modify::replace_secret(replaced_hash, &anatomy, password, &secret)?;
+            git::record_change("update", &hash_id)?;
+
+            LogAction::Edit
+        }
+        None => {
+            let hash_id = encrypt::encrypt_secret(&anatomy, password, secret)?;
+            git::record_change("add", &hash_id)?;
+
+            LogAction::Add
+        }
+    };
+
+    Ok((
+        action,
+        log::describe_secret(&anatomy),
+        json!({
+            "category": anatomy.category,
+            "label": anatomy.label,
+        }),
+    ))
+}
+
+/// Remove a secret.
+fn delete_secret(
+    token: &ApiToken,
+    params: &ApiParams,
+    password: &str,
+) -> Result<String, HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+    let (hash_id, anatomy) = find_secret(&lookup_table, token, params)?;
+    check_unprotected(anatomy)?;
+
+    modify::remove_in_lookup_table(hash_id, password, RemovalEvent::Remove)?;
+    git::record_change("remove", hash_id)?;
+
+    Ok(log::describe_secret(anatomy))
+}
+
+/// Run the method a request calls. Returns the event to record and the method's result.
+fn run_method(
+    request: &ApiRequest,
+    token: &ApiToken,
+    password: &str,
+) -> Result<(LogAction, Option<String>, Value), HimitsuError> {
+    match request.method.as_str() {
+        "delete" => delete_secret(token, &request.params, password)
+            .map(|secret| (LogAction::Remove, Some(secret), Value::Bool(true))),
+        "get" => get_secret(token, &request.params, password)
+            .map(|(secret, result)| (LogAction::Use, Some(secret), result)),
+        "list" => list_secrets(token, password).map(|result| (LogAction::List, None, result)),
+        "put" => put_secret(token, &request.params, password)
+            .map(|(action, secret, result)| (action, Some(secret), result)),
+        method => Err(HimitsuError::ApiError(format!(
+            "\"{method}\" is not a method! Call \"list\", \"get\", \"put\", or \"delete\"."
+        ))),
+    }
+}
+
+/// Answer a single request and record it in the audit log. The token is checked against the
+/// registry as it is now, so a revoked token is refused right away.
+pub fn handle_request(line: &str, registry: &mut TokenRegistry, password: &str) -> ApiResponse {
+    let request: ApiRequest = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(error) => {
+            log::record_event_as(LogAction::Denied, None, "api:unknown".to_string());
+
+            return ApiResponse {
+                error: Some(format!("The request is not valid JSON: {error}")),
+                id: Value::Null,
+                result: None,
+            };
+        }
+    };
+
+    let tokens = match registry.get_tokens(password) {
+        Ok(tokens) => tokens,
+        Err(error) => {
+            return ApiResponse {
+                error: Some(format!("The API tokens could not be read: {error}")),
+                id: request.id,
+                result: None,
+            };
+        }
+    };
+
+    let outcome = match tokens::find_token(tokens, &request.token) {
+        Some(token) => {
+            let client = format!("api:{}", token.name);
+
+            match run_method(&request, token, password) {
+                Ok((action, secret, result)) => {
+                    log::record_event_as(action, secret, client);
+
+                    Ok(result)
+                }
+                Err(error) => {
+                    log::record_event_as(
+                        LogAction::Denied,
+                        describe_request(&request.params),
+                        client,
+                    );
+
+                    Err(error)
+                }
+            }
+        }
+        None => {
+            log::record_event_as(
+                LogAction::Denied,
+                describe_request(&request.params),
+                "api:unknown".to_string(),
+            );
+
+            Err(HimitsuError::ApiError(
+                "The API token is invalid!".to_string(),
+            ))
+        }
+    };
+
+    match outcome {
+        Ok(result) => ApiResponse {
+            error: None,
+            id: request.id,
+            result: Some(result),
+        },
+        Err(error) => ApiResponse {
+            error: Some(error.to_string()),
+            id: request.id,
+            result: None,
+        },
+    }
+}
+
+/// Answer every request sent over a connection until the application disconnects.
+#[cfg(unix)]
+fn handle_connection(stream: UnixStream, state: &Mutex<(TokenRegistry, String)>) {
+    let reader = match stream.try_clone() {
+        Ok(reader) => BufReader::new(reader),
+        Err(_) => return,
+    };
+    let mut writer = stream;
+
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => line,
+            Err(_) => return,
+        };
+
+        let response = match state.lock() {
+            Ok(mut state) => {
+                let (registry, password) = &mut *state;
+
+                handle_request(&line, registry, password)
+            }
+            Err(_) => return,
+        };
+
+        match serde_json::to_string(&response) {
+            Ok(response) if writeln!(writer, "{response}").is_ok() => {}
+            _ => return,
+        }
+    }
+}
+
+/// Serve the API until the process is stopped. Every application is served on its own thread,
+/// while requests are answered one at a time so they never modify the vault at once.
+#[cfg(unix)]
+pub fn serve(
+    socket_path: &Path,
+    registry: TokenRegistry,
+    password: String,
+) -> Result<(), HimitsuError> {
+    let listener = runtime::bind_socket(socket_path)?;
+    let state = Arc::new(Mutex::new((registry, password)));
+
+    for stream in listener.incoming().flatten() {
+        let state = Arc::clone(&state);
+
+        thread::spawn(move || handle_connection(stream, &state));
+    }
+
+    Ok(())
+}
+
+/// The API is served over a Unix socket, which is not available on this platform.
+#[cfg(not(unix))]
+pub fn serve(
+    _socket_path: &Path,
+    _registry: TokenRegistry,
+    _password: String,
+) -> Result<(), HimitsuError> {
+    Err(HimitsuError::ApiError(
+        "The API is only available on Unix-like systems!".to_string(),
+    ))
+}
+
+#[cfg(test)]
+pub mod test_server {
+    use std::{env, fs};
+
+    use rand::{rngs::OsRng, RngCore};
+
+    use super::*;
+
+    /// Test whether a token revoked while the API is served is refused by the next request.
+    #[test]
+    fn test_handle_request_after_revoking() {
+        let tokens_path = env::temp_dir().join(format!("himitsu-tokens-{}", OsRng.next_u64()));
+        let token = ApiToken {
+            categories: vec!["cloud".to_string()],
+            date_created: dates::get_timestamp(),
+            hash: tokens::hash_token("hmu_secret"),
+            name: "reporting".to_string(),
+            tags: Vec::new(),
+        };
+        tokens::store_tokens_in(&tokens_path, "password", &[token]).unwrap();
+
+        let mut registry = TokenRegistry::open(&tokens_path, "password").unwrap();
+        let request = r#"{"id": 1, "token": "hmu_secret", "method": "rename", "params": {}}"#;
+
+        let response = handle_request(request, &mut registry, "password");
+        assert!(response.error.unwrap().contains("is not a method"));
+
+        tokens::store_tokens_in(&tokens_path, "password", &[]).unwrap();
+
+        let response = handle_request(request, &mut registry, "password");
+        assert_eq!(
+            response.error.unwrap(),
+            HimitsuError::ApiError("The API token is invalid!".to_string()).to_string()
+        );
+
+        fs::remove_file(tokens_path).unwrap();
+    }
+
+    /// Test whether requests only find secrets the token may access.
+    #[test]
+    fn test_find_secret_in_scope() {
+        let mut lookup_table = LookupTable::new();
+        for (hash_id, category, tags) in [
+            ("cloud", "cloud", vec![]),
+            ("personal", "personal", vec![]),
+            ("shared", "personal", vec!["prod".to_string()]),
+        ] {
+            lookup_table.table.insert(
+                hash_id.to_string(),
+                Anatomy::create_from(category.to_string(), "db".to_string(), tags),
+            );
+        }
+        let token = ApiToken {
+            categories: vec!["cloud".to_string()],
+            date_created: dates::get_timestamp(),
+            hash: tokens::hash_token("hmu_secret"),
+            name: "reporting".to_string(),
+            tags: vec!["prod".to_string()],
+        };
+
+        let params = |category: Option<&str>| ApiParams {
+            category: category.map(str::to_string),
+            label: Some("db".to_string()),
+            ..Default::default()
+        };
+
+        assert!(find_secret(&lookup_table, &token, &params(None)).is_err());
+        assert_eq!(
+            find_secret(&lookup_table, &token, &params(Some("cloud")))
+                .unwrap()
+                .0,
+            "cloud"
+        );
+        assert_eq!(
+            find_secret(&lookup_table, &token, &params(Some("personal")))
+                .unwrap()
+                .0,
+            "shared"
+        );
+
+        lookup_table.table.insert(
+            "lapsed".to_string(),
+            Anatomy {
+                expires_at: Some("01-01-2000 00:00:00".to_string()),
+                temporary: true,
+                ..Anatomy::create_for_test("cloud", "db", "01-01-2022 00:00:00")
+            },
+        );
+        lookup_table.table.remove("cloud");
+        assert!(find_secret(&lookup_table, &token, &params(Some("cloud"))).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Contains functions for the API tokens applications use to access `hmu serve`.
+//!
+//! Tokens are only shown once, when they are created. The registry stores each token's SHA-256
+//! hash and scope, and is sealed with the vault's password so its scopes cannot be widened
+//! without unlocking the vault.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use data_encoding::HEXLOWER;
+use directories::ProjectDirs;
+use rand::{rngs::OsRng, RngCore};
+use ring::digest::{digest, SHA256};
+
+use crate::{
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    models::api::ApiToken,
+    utils::dates,
+};
+
+/// The prefix of every API token, which makes tokens easy to spot in config files and logs.
+const TOKEN_PREFIX: &str = "hmu_";
+
+/// Get the path to the sealed `api_tokens` file.
+pub fn get_tokens_path() -> Result<PathBuf, HimitsuError> {
+    match ProjectDirs::from("", "", "himitsu") {
+        Some(project_directory) => Ok(project_directory.config_dir().join("api_tokens")),
+        None => Err(HimitsuError::ApplicationError),
+    }
+}
+
+/// Retrieve every API token.
+pub fn get_tokens(password: &str) -> Result<Vec<ApiToken>, HimitsuError> {
+    get_tokens_in(&get_tokens_path()?, password)
+}
+
+/// Retrieve every API token from a particular `api_tokens` file.
+pub fn get_tokens_in(tokens_path: &Path, password: &str) -> Result<Vec<ApiToken>, HimitsuError> {
+    if tokens_path.exists() {
+        Ok(serde_json::from_str(&decrypt::open_with_passphrase(
+            password,
+            fs::read_to_string(tokens_path)?.trim(),
+        )?)?)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Seal and store every API token.
+fn store_tokens(password: &str, tokens: &[ApiToken]) -> Result<(), HimitsuError> {
+    store_tokens_in(&get_tokens_path()?, password, tokens)
+}
+
+/// Seal every API token and store them in a particular `api_tokens` file.
+pub fn store_tokens_in(
+    tokens_path: &Path,
+    password: &str,
+    tokens: &[ApiToken],
+) -> Result<(), HimitsuError> {
+    if let Some(parent) = tokens_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(
+        tokens_path,
+        encrypt::seal_with_passphrase(password, &serde_json::to_string(tokens)?)?,
+    )?;
+
+    Ok(())
+}
+
+/// Hash a token for the registry.
+pub fn hash_token(token: &str) -> String {
+    HEXLOWER.encode(digest(&SHA256, token.as_bytes()).as_ref())
+}
+
+/// Create a new API token for an application. Returns the token, which is not stored anywhere.
+pub fn create_token(
+    name: &str,
+    categories: &[String],
+    tags: &[String],
+    password: &str,
+) -> Result<String, HimitsuError> {
+    let mut tokens = get_tokens(password)?;
+    if tokens.iter().any(|token| token.name == name) {
+        return Err(HimitsuError::ApiError(format!(
+            "A token named \"{name}\" already exists!"
+        )));
+    }
+
+    let mut random_bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut random_bytes);
+    let token = format!("{TOKEN_PREFIX}{}", HEXLOWER.encode(&random_bytes));
+
+    tokens.push(ApiToken {
+        categories: categories
+            .iter()
+            .map(|category| category.to_lowercase())
+            .collect(),
+        date_created: dates::get_timestamp(),
+        hash: hash_token(&token),
+        name: name.to_string(),
+        tags: tags.iter().map(|tag| tag.to_lowercase()).collect(),
+    });
+    store_tokens(password, &tokens)?;
+
+    Ok(token)
+}
+
+/// Revoke an application's API token. Returns whether the token existed.
+pub fn remove_token(name: &str, password: &str) -> Result<bool, HimitsuError> {
+    let mut tokens = get_tokens(password)?;
+    let count = tokens.len();
+
+    tokens.retain(|token| token.name != name);
+    if tokens.len() == count {
+        return Ok(false);
+    }
+
+    store_tokens(password, &tokens)?;
+
+    Ok(true)
+}
+
+/// Find the registered token matching the token an application sent.
+pub fn find_token<'a>(tokens: &'a [ApiToken], token: &str) -> Option<&'a ApiToken> {
+    let hash = hash_token(token);
+
+    tokens.iter().find(|registered| registered.hash == hash)
+}
+
+/// Contains the API token registry while the API is served. The registry is read again whenever
+/// its file changes, so tokens created or revoked with `hmu api-token` take effect right away.
+pub struct TokenRegistry {
+    /// When the registry was last modified and how long it was when it was read, or `None` if it
+    /// did not exist.
+    stamp: Option<(SystemTime, u64)>,
+    /// The path to the sealed `api_tokens` file.
+    tokens_path: PathBuf,
+    /// The tokens the registry held when it was read.
+    tokens: Vec<ApiToken>,
+}
+
+impl TokenRegistry {
+    /// Read the registry stored in a particular `api_tokens` file.
+    pub fn open(tokens_path: &Path, password: &str) -> Result<Self, HimitsuError> {
+        Ok(Self {
+            stamp: get_stamp(tokens_path),
+            tokens_path: tokens_path.to_path_buf(),
+            tokens: get_tokens_in(tokens_path, password)?,
+        })
+    }
+
+    /// Get the current tokens, reading the registry again if its file changed since it was
+    /// last read.
+    pub fn get_tokens(&mut self, password: &str) -> Result<&[ApiToken], HimitsuError> {
+        let stamp = get_stamp(&self.tokens_path);
+
+        if stamp != self.stamp {
+            self.tokens = get_tokens_in(&self.tokens_path, password)?;
+            self.stamp = stamp;
+        }
+
+        Ok(&self.tokens)
+    }
+}
+
+/// Get when a file was last modified and how long it is, or `None` if it does not exist.
+fn get_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Check whether a token may access a secret with this category and these tags.
+pub fn is_in_scope(token: &ApiToken, category: &str, tags: &[String]) -> bool {
+    token
+        .categories
+        .iter()
+        .any(|scoped_category| scoped_category.eq_ignore_ascii_case(category))
+        || tags.iter().any(|tag| token.tags.contains(tag))
+}
+
+#[cfg(test)]
+pub mod test_tokens {
+    use super::*;
+
+    /// Test whether tokens are found by their hash and only access their categories and tags.
+    #[test]
+    fn test_token_scope() {
+        let token = ApiToken {
+            categories: vec!["cloud".to_string()],
+            date_created: dates::get_timestamp(),
+            hash: hash_token("hmu_secret"),
+            name: "reporting".to_string(),
+            tags: vec!["prod".to_string()],
+        };
+        let tokens = vec![token];
+
+        assert!(find_token(&tokens, "hmu_secret").is_some());
+        assert!(find_token(&tokens, "hmu_guess").is_none());
+
+        let token = &tokens[0];
+        assert!(is_in_scope(token, "Cloud", &[]));
+        assert!(is_in_scope(token, "personal", &["prod".to_string()]));
+        assert!(!is_in_scope(token, "personal", &["dev".to_string()]));
+    }
+}
@@ -0,0 +1,315 @@
+//! Contains functions for checking secrets against a downloaded Have I Been Pwned corpus.
+//!
+//! Have I Been Pwned publishes the SHA-1 hashes of breached passwords, along with how often each
+//! password was seen, in two forms:
+//!
+//! * A single file of `HASH:COUNT` lines sorted by hash.
+//! * Range files named after the first 5 characters of the hashes they contain, each made of
+//!   `SUFFIX:COUNT` lines sorted by suffix. A directory of range files covers every hash.
+//!
+//! Both are sorted, so lookups seek through the file with binary search instead of reading it.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use data_encoding::HEXUPPER;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use serde::Serialize;
+
+use crate::errors::HimitsuError;
+
+use super::DecryptedSecret;
+
+/// The number of hash characters a range file is named after.
+const PREFIX_LENGTH: usize = 5;
+/// The number of characters in a hex encoded SHA-1 hash.
+const HASH_LENGTH: usize = 40;
+
+/// Contains variants for each form of corpus.
+#[derive(Debug)]
+pub enum Corpus {
+    /// A directory of range files.
+    Directory(PathBuf),
+    /// A single file of full hashes.
+    HashFile(PathBuf),
+    /// A single range file.
+    RangeFile {
+        /// The path to the range file.
+        path: PathBuf,
+        /// The prefix shared by every hash in the range file.
+        prefix: String,
+    },
+}
+
+/// Contains a stored secret that was found in the corpus.
+#[derive(Debug, Serialize)]
+pub struct BreachMatch {
+    /// The secret's category.
+    pub category: String,
+    /// The secret's label.
+    pub label: String,
+    /// How many times the secret was seen in breaches.
+    pub prevalence: u64,
+}
+
+/// Get the uppercase hex encoded SHA-1 hash of a secret.
+pub fn get_sha1_hash(secret: &str) -> String {
+    HEXUPPER.encode(digest(&SHA1_FOR_LEGACY_USE_ONLY, secret.as_bytes()).as_ref())
+}
+
+/// Split a corpus line into its hash (or hash suffix) and prevalence count.
+fn parse_line(line: &str) -> Option<(&str, u64)> {
+    let (hash, count) = line.trim().split_once(':')?;
+
+    Some((hash, count.trim().parse().ok()?))
+}
+
+/// Figure out which form of corpus a path points to.
+pub fn open_corpus(path: &Path) -> Result<Corpus, HimitsuError> {
+    if path.is_dir() {
+        return Ok(Corpus::Directory(path.to_path_buf()));
+    }
+
+    let mut first_line = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut first_line)?;
+
+    match parse_line(&first_line) {
+        Some((hash, _)) if hash.len() == HASH_LENGTH => Ok(Corpus::HashFile(path.to_path_buf())),
+        Some((hash, _)) if hash.len() == HASH_LENGTH - PREFIX_LENGTH => {
+            let prefix = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_uppercase())
+                .filter(|stem| {
+                    stem.len() == PREFIX_LENGTH
+                        && stem.chars().all(|character| character.is_ascii_hexdigit())
+                })
+                .ok_or_else(|| {
+                    HimitsuError::AuditError(format!(
+                        "{} looks like a range file, but is not named after its 5 character hash prefix!",
+                        path.display()
+                    ))
+                })?;
+
+            Ok(Corpus::RangeFile {
+                path: path.to_path_buf(),
+                prefix,
+            })
+        }
+        _ => Err(HimitsuError::AuditError(format!(
+            "{} is not a Have I Been Pwned SHA-1 file!",
+            path.display()
+        ))),
+    }
+}
+
+/// Read the first line that starts at or after a byte position. Returns the line and the
+/// position of the line after it, or `None` if there are no more lines.
+fn read_line_from(
+    reader: &mut BufReader<File>,
+    position: u64,
+) -> Result<Option<(String, u64)>, HimitsuError> {
+    let mut line_start = position;
+    let mut line = String::new();
+
+    if position > 0 {
+        reader.seek(SeekFrom::Start(position - 1))?;
+        line_start = position - 1 + reader.read_line(&mut line)? as u64;
+        line.clear();
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    match reader.read_line(&mut line)? {
+        0 => Ok(None),
+        length => Ok(Some((line, line_start + length as u64))),
+    }
+}
+
+/// Binary search a sorted corpus file for a hash (or hash suffix). Returns the hash's prevalence
+/// count if it is in the file.
+pub fn search_sorted_file(path: &Path, target: &str) -> Result<Option<u64>, HimitsuError> {
+    let file = File::open(path)?;
+    let mut low = 0;
+    let mut high = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+
+        match read_line_from(&mut reader, middle)? {
+            Some((line, next_line_start)) => match parse_line(&line) {
+                Some((hash, prevalence)) => match hash.to_uppercase().as_str().cmp(target) {
+                    Ordering::Less => low = next_line_start,
+                    Ordering::Equal => return Ok(Some(prevalence)),
+                    Ordering::Greater => high = middle,
+                },
+                None => low = next_line_start,
+            },
+            None => high = middle,
+        }
+    }
+
+    Ok(None)
+}
+
+/// Look up a hash's prevalence count in a corpus. Returns `None` if the hash is not in the
+/// corpus, or if the corpus does not cover the hash's range.
+pub fn get_prevalence(corpus: &Corpus, hash: &str) -> Result<Option<u64>, HimitsuError> {
+    let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+
+    match corpus {
+        Corpus::Directory(directory) => {
+            for file_name in [
+                format!("{prefix}.txt"),
+                format!("{}.txt", prefix.to_lowercase()),
+            ] {
+                let range_path = directory.join(file_name);
+
+                if range_path.exists() {
+                    return search_sorted_file(&range_path, suffix);
+                }
+            }
+
+            Ok(None)
+        }
+        Corpus::HashFile(path) => search_sorted_file(path, hash),
+        Corpus::RangeFile {
+            path,
+            prefix: range_prefix,
+        } => {
+            if range_prefix == prefix {
+                search_sorted_file(path, suffix)
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Check every decrypted secret against a corpus. Returns the secrets that were found, most
+/// prevalent first.
+pub fn find_breached_secrets(
+    corpus: &Corpus,
+    decrypted_secrets: &[DecryptedSecret],
+) -> Result<Vec<BreachMatch>, HimitsuError> {
+    let mut breach_matches = Vec::new();
+
+    for decrypted_secret in decrypted_secrets {
+        if let Some(prevalence) = get_prevalence(corpus, &get_sha1_hash(&decrypted_secret.secret))?
+        {
+            breach_matches.push(BreachMatch {
+                category: decrypted_secret.anatomy.category.clone(),
+                label: decrypted_secret.anatomy.label.clone(),
+                prevalence,
+            });
+        }
+    }
+
+    breach_matches.sort_by_key(|breach_match| Reverse(breach_match.prevalence));
+
+    Ok(breach_matches)
+}
+
+#[cfg(test)]
+pub mod test_breaches {
+    use std::{env, fs};
+
+    use rand::{rngs::OsRng, RngCore};
+
+    use super::*;
+
+    /// The SHA-1 hash of `password`.
+    const PASSWORD_HASH: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+    /// Create a sorted hash file containing `password` and a few other hashes.
+    fn create_hash_file(directory: &Path) -> PathBuf {
+        let mut hashes = [
+            format!("{PASSWORD_HASH}:9545824"),
+            format!("{}:3", get_sha1_hash("hunter2")),
+            format!("{}:12", get_sha1_hash("letmein")),
+            "0000000000000000000000000000000000000000:1".to_string(),
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:2".to_string(),
+        ];
+        hashes.sort();
+
+        let path = directory.join("pwned-passwords.txt");
+        fs::write(&path, hashes.join("\r\n")).unwrap();
+
+        path
+    }
+
+    /// Test whether the SHA-1 hash matches the format used by Have I Been Pwned.
+    #[test]
+    fn test_get_sha1_hash() {
+        assert_eq!(get_sha1_hash("password"), PASSWORD_HASH);
+    }
+
+    /// Test whether every hash in a hash file is found, and missing hashes are not.
+    #[test]
+    fn test_search_hash_file() {
+        let directory = env::temp_dir().join(format!("himitsu-breaches-{}", OsRng.next_u64()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let corpus = open_corpus(&create_hash_file(&directory)).unwrap();
+        assert!(matches!(corpus, Corpus::HashFile(_)));
+
+        assert_eq!(
+            get_prevalence(&corpus, PASSWORD_HASH).unwrap(),
+            Some(9545824)
+        );
+        assert_eq!(
+            get_prevalence(&corpus, &get_sha1_hash("hunter2")).unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            get_prevalence(&corpus, "0000000000000000000000000000000000000000").unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            get_prevalence(&corpus, "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF").unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            get_prevalence(&corpus, &get_sha1_hash("correct horse")).unwrap(),
+            None
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    /// Test whether hashes are found in a directory of range files.
+    #[test]
+    fn test_search_range_directory() {
+        let directory = env::temp_dir().join(format!("himitsu-ranges-{}", OsRng.next_u64()));
+        fs::create_dir_all(&directory).unwrap();
+
+        fs::write(
+            directory.join("5BAA6.txt"),
+            "003D68EB55068C33ACE09247EE4C639306B:3\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\nFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:1\n",
+        )
+        .unwrap();
+
+        let corpus = open_corpus(&directory).unwrap();
+        assert_eq!(
+            get_prevalence(&corpus, PASSWORD_HASH).unwrap(),
+            Some(9545824)
+        );
+        assert_eq!(
+            get_prevalence(&corpus, &get_sha1_hash("hunter2")).unwrap(),
+            None
+        );
+
+        let range_file = open_corpus(&directory.join("5BAA6.txt")).unwrap();
+        assert!(matches!(range_file, Corpus::RangeFile { .. }));
+        assert_eq!(
+            get_prevalence(&range_file, PASSWORD_HASH).unwrap(),
+            Some(9545824)
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
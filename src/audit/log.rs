@@ -0,0 +1,494 @@
+//! Contains functions for the vault's append-only audit log.
+//!
+//! Every unlock, use, change, and failed login is appended to the `audit.log` file in the vault
+//! directory. Each event is sealed for the vault's identity, so events can be recorded before the
+//! vault is unlocked while only the vault's owner can read them. Every entry stores the hash of
+//! the entry before it, so modifying or removing an entry breaks the chain. The newest entry
+//! is also remembered in the config directory, so cutting entries off the end of the log is
+//! detected as well.
+//!
+//! Entries are appended while holding a lock on the `audit.lock` file, so processes and threads
+//! recording events at the same time still produce a single chain. The newest entry is tracked in
+//! the `audit.tail` file, so the log is not read every time an event is recorded. Failing to
+//! record an event only prints a warning, so a damaged log never stops the vault from being used.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use ansi_term::Color;
+use chrono::NaiveDateTime;
+use data_encoding::HEXLOWER;
+use directories::ProjectDirs;
+use ring::digest::{Context, SHA256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{
+    errors::HimitsuError,
+    models::{
+        log::{LogAction, LogEntry, LogEvent, LogHead, LogHeads, LogTail},
+        metadata::Anatomy,
+    },
+    sharing::{envelope, identity},
+    utils::{dates, lock, vault},
+};
+
+/// The previous hash of the first entry in the log.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Get the path to the audit log.
+pub fn get_log_path() -> Result<PathBuf, HimitsuError> {
+    Ok(vault::get_vault_dir()?.join("audit.log"))
+}
+
+/// Get the path to the `log_heads.json` file.
+fn get_heads_path() -> Result<PathBuf, HimitsuError> {
+    match ProjectDirs::from("", "", "himitsu") {
+        Some(project_directory) => Ok(project_directory.config_dir().join("log_heads.json")),
+        None => Err(HimitsuError::ApplicationError),
+    }
+}
+
+/// Retrieve the newest entries seen in every vault's audit log.
+fn get_log_heads() -> Result<LogHeads, HimitsuError> {
+    let heads_path = get_heads_path()?;
+
+    if heads_path.exists() {
+        Ok(serde_json::from_str(&fs::read_to_string(heads_path)?)?)
+    } else {
+        Ok(LogHeads::new())
+    }
+}
+
+/// Get the newest entry seen in the current vault's audit log.
+fn get_log_head() -> Result<Option<LogHead>, HimitsuError> {
+    Ok(get_log_heads()?.remove(&vault::get_vault_dir()?.display().to_string()))
+}
+
+/// Remember the newest entry in the current vault's audit log.
+fn store_log_head(head: LogHead) -> Result<(), HimitsuError> {
+    let _lock = lock::lock_exclusive(&get_heads_path()?.with_extension("lock"))?;
+
+    let mut heads = get_log_heads()?;
+    heads.insert(vault::get_vault_dir()?.display().to_string(), head);
+
+    let heads_path = get_heads_path()?;
+    if let Some(parent) = heads_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(heads_path, serde_json::to_string_pretty(&heads)?)?;
+
+    Ok(())
+}
+
+/// Get the name of the user running `himitsu`.
+fn get_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Describe a secret as `category/label` for the audit log.
+pub fn describe_secret(anatomy: &Anatomy) -> String {
+    format!("{}/{}", anatomy.category, anatomy.label)
+}
+
+/// Make sure the vault has an identity, so events can be sealed without the password.
+pub fn open_log(password: &str) -> Result<(), HimitsuError> {
+    identity::get_or_create_identity(password)?;
+
+    Ok(())
+}
+
+/// Compute an entry's hash from the previous entry's hash and the entry's envelope.
+fn compute_hash(previous_hash: &str, entry: &LogEntry) -> Result<String, HimitsuError> {
+    let mut context = Context::new(&SHA256);
+    context.update(previous_hash.as_bytes());
+    context.update(&serde_json::to_vec(&entry.envelope)?);
+
+    Ok(HEXLOWER.encode(context.finish().as_ref()))
+}
+
+/// Read every entry in an audit log.
+pub fn read_entries(log_path: &Path) -> Result<Vec<LogEntry>, HimitsuError> {
+    if !log_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    fs::read_to_string(log_path)?
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|_| HimitsuError::LogError(format!("Entry #{} is corrupted!", index + 1)))
+        })
+        .collect()
+}
+
+/// Find the newest entry in an audit log. The tracked tail is used if it still matches the log.
+/// Otherwise, the log's lines are counted and only the last one is parsed, so a damaged entry
+/// does not stop new entries from being appended.
+fn find_tail(log_path: &Path) -> Result<LogTail, HimitsuError> {
+    let length = match fs::metadata(log_path) {
+        Ok(metadata) => metadata.len(),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(LogTail {
+                count: 0,
+                hash: GENESIS_HASH.to_string(),
+                length: 0,
+            })
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    if let Some(tail) = fs::read_to_string(log_path.with_extension("tail"))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<LogTail>(&contents).ok())
+        .filter(|tail| tail.length == length)
+    {
+        return Ok(tail);
+    }
+
+    let contents = fs::read_to_string(log_path)?;
+
+    Ok(LogTail {
+        count: contents.lines().count(),
+        hash: contents
+            .lines()
+            .last()
+            .and_then(|line| serde_json::from_str::<LogEntry>(line).ok())
+            .map_or(GENESIS_HASH.to_string(), |entry| entry.hash),
+        length,
+    })
+}
+
+/// Seal an event for the recipient and append it to an audit log. Returns the log's new head.
+pub fn append_entry_in(
+    log_path: &Path,
+    recipient: &PublicKey,
+    event: &LogEvent,
+) -> Result<LogHead, HimitsuError> {
+    let _lock = lock::lock_exclusive(&log_path.with_extension("lock"))?;
+    let tail = find_tail(log_path)?;
+
+    let mut entry = LogEntry {
+        envelope: envelope::seal(recipient, &serde_json::to_vec(event)?)?,
+        hash: String::new(),
+        previous_hash: tail.hash,
+    };
+    entry.hash = compute_hash(&entry.previous_hash, &entry)?;
+
+    let mut log_file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(log_path)?;
+    writeln!(log_file, "{}", serde_json::to_string(&entry)?)?;
+
+    let tail = LogTail {
+        count: tail.count + 1,
+        hash: entry.hash,
+        length: log_file.metadata()?.len(),
+    };
+    fs::write(
+        log_path.with_extension("tail"),
+        serde_json::to_string(&tail)?,
+    )?;
+
+    Ok(LogHead {
+        count: tail.count,
+        hash: tail.hash,
+    })
+}
+
+/// Record an event in the current vault's audit log. Nothing is recorded if the vault does not
+/// have an identity yet.
+pub fn record_event(action: LogAction, secret: Option<String>) {
+    record_event_as(action, secret, get_user())
+}
+
+/// Record an event caused by someone other than the user running `himitsu`, such as an
+/// application using `hmu serve`. A warning is printed if the event could not be recorded.
+pub fn record_event_as(action: LogAction, secret: Option<String>, user: String) {
+    let record = || -> Result<(), HimitsuError> {
+        if let Some(public_key) = identity::get_public_key()? {
+            let head = append_entry_in(
+                &get_log_path()?,
+                &public_key,
+                &LogEvent {
+                    action,
+                    date: dates::get_timestamp(),
+                    secret,
+                    user,
+                },
+            )?;
+
+            store_log_head(head)?;
+        }
+
+        Ok(())
+    };
+
+    if let Err(error) = record() {
+        println!(
+            "{}",
+            Color::Fixed(172).bold().paint(format!(
+                "⚠️  This event could not be recorded in the audit log: {error}"
+            ))
+        );
+    }
+}
+
+/// Open a single entry.
+fn open_entry(
+    index: usize,
+    entry: &LogEntry,
+    identity: &StaticSecret,
+) -> Result<LogEvent, HimitsuError> {
+    envelope::open(identity, &entry.envelope)
+        .ok()
+        .and_then(|plaintext| serde_json::from_slice(&plaintext).ok())
+        .ok_or_else(|| {
+            HimitsuError::LogError(format!("Entry #{} could not be decrypted!", index + 1))
+        })
+}
+
+/// Open every entry without checking the chain.
+pub fn open_entries(
+    entries: &[LogEntry],
+    identity: &StaticSecret,
+) -> Result<Vec<LogEvent>, HimitsuError> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| open_entry(index, entry, identity))
+        .collect()
+}
+
+/// Check that every entry is chained to the entry before it and that the log still contains the
+/// newest entry seen by this user. Returns the opened events.
+pub fn verify_entries(
+    entries: &[LogEntry],
+    identity: &StaticSecret,
+    head: Option<&LogHead>,
+) -> Result<Vec<LogEvent>, HimitsuError> {
+    let mut previous_hash = GENESIS_HASH.to_string();
+
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.previous_hash != previous_hash {
+            return Err(HimitsuError::LogError(format!(
+                "Entry #{} does not follow the entry before it. Entries were removed or reordered!",
+                index + 1
+            )));
+        }
+        if compute_hash(&previous_hash, entry)? != entry.hash {
+            return Err(HimitsuError::LogError(format!(
+                "Entry #{} was modified!",
+                index + 1
+            )));
+        }
+
+        previous_hash = entry.hash.clone();
+    }
+
+    if let Some(head) = head.filter(|head| head.count > 0) {
+        if entries.len() < head.count {
+            return Err(HimitsuError::LogError(format!(
+                "The log was truncated! It had at least {} entries, but only {} are left.",
+                head.count,
+                entries.len()
+            )));
+        }
+        if entries[head.count - 1].hash != head.hash {
+            return Err(HimitsuError::LogError(format!(
+                "The log was rewritten! Entry #{} is not the entry seen before.",
+                head.count
+            )));
+        }
+    }
+
+    open_entries(entries, identity)
+}
+
+/// Read the current vault's audit log. The chain is checked first if `verify` is set.
+pub fn read_events(password: &str, verify: bool) -> Result<Vec<LogEvent>, HimitsuError> {
+    let identity = identity::get_or_create_identity(password)?;
+    let entries = read_entries(&get_log_path()?)?;
+
+    if verify {
+        verify_entries(&entries, &identity, get_log_head()?.as_ref())
+    } else {
+        open_entries(&entries, &identity)
+    }
+}
+
+/// Keep only the events that happened at or after a date.
+pub fn filter_since(events: Vec<LogEvent>, since: NaiveDateTime) -> Vec<LogEvent> {
+    events
+        .into_iter()
+        .filter(|event| dates::parse_timestamp(&event.date).is_none_or(|date| date >= since))
+        .collect()
+}
+
+#[cfg(test)]
+pub mod test_log {
+    use super::*;
+
+    use rand::{rngs::OsRng, RngCore};
+
+    /// Create an audit log with a few events in a temporary directory.
+    fn create_log(identity: &StaticSecret, actions: &[LogAction]) -> (PathBuf, Vec<LogHead>) {
+        let log_path = env::temp_dir().join(format!("himitsu-log-{}.log", OsRng.next_u64()));
+
+        let heads = actions
+            .iter()
+            .map(|action| {
+                append_entry_in(
+                    &log_path,
+                    &PublicKey::from(identity),
+                    &LogEvent {
+                        action: *action,
+                        date: dates::get_timestamp(),
+                        secret: Some("cloud/aws".to_string()),
+                        user: "tester".to_string(),
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+
+        (log_path, heads)
+    }
+
+    /// Remove a test log along with its lock and tail files.
+    fn remove_log(log_path: &Path) {
+        for path in [
+            log_path.to_path_buf(),
+            log_path.with_extension("lock"),
+            log_path.with_extension("tail"),
+        ] {
+            if path.exists() {
+                fs::remove_file(path).unwrap();
+            }
+        }
+    }
+
+    /// Test whether an untouched log is verified and every event is opened in order.
+    #[test]
+    fn test_verify_intact_log() {
+        let identity = StaticSecret::random_from_rng(OsRng);
+        let (log_path, heads) = create_log(
+            &identity,
+            &[LogAction::Unlock, LogAction::Use, LogAction::Remove],
+        );
+
+        let events =
+            verify_entries(&read_entries(&log_path).unwrap(), &identity, heads.last()).unwrap();
+
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.action)
+                .collect::<Vec<LogAction>>(),
+            vec![LogAction::Unlock, LogAction::Use, LogAction::Remove]
+        );
+
+        remove_log(&log_path);
+    }
+
+    /// Test whether modified, removed, and truncated entries are detected.
+    #[test]
+    fn test_verify_tampered_log() {
+        let identity = StaticSecret::random_from_rng(OsRng);
+        let (log_path, heads) = create_log(
+            &identity,
+            &[LogAction::Unlock, LogAction::Use, LogAction::FailedLogin],
+        );
+        let head = heads.last();
+
+        let mut modified = read_entries(&log_path).unwrap();
+        modified[1].envelope.ciphertext[0] ^= 1;
+        assert!(verify_entries(&modified, &identity, head).is_err());
+
+        let mut removed = read_entries(&log_path).unwrap();
+        removed.remove(1);
+        assert!(verify_entries(&removed, &identity, head).is_err());
+
+        let mut truncated = read_entries(&log_path).unwrap();
+        truncated.pop();
+        assert!(verify_entries(&truncated, &identity, None).is_ok());
+        assert!(verify_entries(&truncated, &identity, head).is_err());
+
+        remove_log(&log_path);
+    }
+
+    /// Test whether events appended by several threads at once still form a single chain.
+    #[test]
+    fn test_append_concurrently() {
+        let identity = StaticSecret::random_from_rng(OsRng);
+        let (log_path, _) = create_log(&identity, &[]);
+
+        let threads = (0..8)
+            .map(|_| {
+                let log_path = log_path.clone();
+                let recipient = PublicKey::from(&identity);
+
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        append_entry_in(
+                            &log_path,
+                            &recipient,
+                            &LogEvent {
+                                action: LogAction::Use,
+                                date: dates::get_timestamp(),
+                                secret: None,
+                                user: "tester".to_string(),
+                            },
+                        )
+                        .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let entries = read_entries(&log_path).unwrap();
+        assert_eq!(entries.len(), 40);
+        assert!(verify_entries(&entries, &identity, None).is_ok());
+
+        remove_log(&log_path);
+    }
+
+    /// Test whether events are still appended after the log was changed behind the tracked tail,
+    /// even if its last entry is damaged.
+    #[test]
+    fn test_append_to_damaged_log() {
+        let identity = StaticSecret::random_from_rng(OsRng);
+        let (log_path, _) = create_log(&identity, &[LogAction::Unlock, LogAction::Use]);
+
+        let mut log_file = OpenOptions::new().append(true).open(&log_path).unwrap();
+        writeln!(log_file, "not an entry").unwrap();
+
+        let head = append_entry_in(
+            &log_path,
+            &PublicKey::from(&identity),
+            &LogEvent {
+                action: LogAction::Remove,
+                date: dates::get_timestamp(),
+                secret: None,
+                user: "tester".to_string(),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(head.count, 4);
+        assert!(read_entries(&log_path).is_err());
+
+        remove_log(&log_path);
+    }
+}
@@ -0,0 +1,73 @@
+//! Contains functions for auditing the secrets stored in the vault.
+//!
+//! Audits decrypt every secret into memory only. Decrypted secrets are never written to disk.
+
+pub mod breaches;
+pub mod log;
+pub mod report;
+
+use ansi_term::Color;
+use spinners::{Spinner, Spinners};
+
+use crate::{
+    encryption::decrypt,
+    errors::HimitsuError,
+    models::metadata::{Anatomy, LookupTable, Protection, SecretType},
+};
+
+/// Contains a decrypted secret and its metadata.
+pub struct DecryptedSecret {
+    /// The secret's metadata.
+    pub anatomy: Anatomy,
+    /// The decrypted secret.
+    pub secret: String,
+}
+
+/// Decrypt every password in the lookup table, sorted by category and label. TOTP seeds are
+/// skipped, since they are not passwords, and so are secrets sealed with their own passphrase. A
+/// spinner is shown unless `quiet` is set.
+pub fn decrypt_passwords(
+    lookup_table: &LookupTable,
+    password: &str,
+    quiet: bool,
+) -> Result<Vec<DecryptedSecret>, HimitsuError> {
+    let mut decryption_spinner = (!quiet).then(|| {
+        Spinner::new(
+            Spinners::Aesthetic,
+            format!("Decrypting {} secrets...", lookup_table.table.len()),
+        )
+    });
+
+    let mut decrypted_secrets = Vec::new();
+    for (hash_id, anatomy) in lookup_table.table.iter() {
+        if anatomy.secret_type != SecretType::Password
+            || anatomy.protection == Protection::Passphrase
+        {
+            continue;
+        }
+
+        let secret =
+            decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(hash_id)?, password)?;
+
+        decrypted_secrets.push(DecryptedSecret {
+            anatomy: anatomy.clone(),
+            secret,
+        });
+    }
+
+    if let Some(spinner) = decryption_spinner.as_mut() {
+        spinner.stop_and_persist(
+            "🔓",
+            Color::Green
+                .bold()
+                .paint("Decrypted your secrets.")
+                .to_string(),
+        );
+    }
+
+    decrypted_secrets.sort_by(|a, b| {
+        (&a.anatomy.category, &a.anatomy.label).cmp(&(&b.anatomy.category, &b.anatomy.label))
+    });
+
+    Ok(decrypted_secrets)
+}
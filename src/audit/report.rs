@@ -0,0 +1,317 @@
+//! Contains functions for building and printing a vault hygiene report.
+
+use std::collections::HashMap;
+
+use ansi_term::{Color, Style};
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+
+use crate::{errors::HimitsuError, models::metadata::LookupTable, strength, utils::dates};
+
+use super::{breaches::BreachMatch, DecryptedSecret};
+
+/// Secrets scoring below this strength score (out of 4) are reported as weak.
+const MIN_STRENGTH_SCORE: u8 = 3;
+
+/// A section of the printed report: its title, its color, and a name and detail for each row.
+type ReportSection = (String, Color, Vec<(String, String)>);
+
+/// Contains a secret that is reported as weak.
+#[derive(Debug, Serialize)]
+pub struct WeakSecret {
+    /// The secret, recorded as `category/label`.
+    pub name: String,
+    /// The secret's strength score, from 0 to 4.
+    pub score: u8,
+    /// Explanations of what makes the secret weak.
+    pub warnings: Vec<String>,
+}
+
+/// Contains a secret that is reported as old.
+#[derive(Debug, Serialize)]
+pub struct OldSecret {
+    /// The secret, recorded as `category/label`.
+    pub name: String,
+    /// The number of days since the secret was created.
+    pub age_days: i64,
+}
+
+/// Contains the results of an audit. Each secret is recorded as `category/label`.
+#[derive(Debug, Default, Serialize)]
+pub struct AuditReport {
+    /// Secrets that were found in a breach corpus, if one was checked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breached: Option<Vec<BreachMatch>>,
+    /// The number of secrets that were audited.
+    pub checked: usize,
+    /// Secrets that have never been used.
+    pub never_accessed: Vec<String>,
+    /// Secrets that were created more than the chosen number of days ago.
+    pub old: Vec<OldSecret>,
+    /// Groups of secrets that share the same value.
+    pub reused: Vec<Vec<String>>,
+    /// Secrets that are easy to guess.
+    pub weak: Vec<WeakSecret>,
+}
+
+/// Build an audit report for every secret in the lookup table. Reused and weak secrets are found
+/// among the decrypted secrets, while old and never accessed secrets only need each secret's
+/// metadata, so every secret is checked for those, including TOTP secrets and secrets sealed with
+/// their own passphrase. Secrets created more than `max_age_days` days before `now` are reported
+/// as old.
+pub fn build_report(
+    lookup_table: &LookupTable,
+    decrypted_secrets: &[DecryptedSecret],
+    max_age_days: i64,
+    now: NaiveDateTime,
+) -> AuditReport {
+    let mut report = AuditReport {
+        checked: lookup_table.table.len(),
+        ..AuditReport::default()
+    };
+
+    let mut secrets_by_value: HashMap<&str, Vec<String>> = HashMap::new();
+
+    for decrypted_secret in decrypted_secrets {
+        let anatomy = &decrypted_secret.anatomy;
+        let name = format!("{}/{}", anatomy.category, anatomy.label);
+
+        secrets_by_value
+            .entry(decrypted_secret.secret.as_str())
+            .or_default()
+            .push(name.clone());
+
+        let estimate = strength::estimate_strength(&decrypted_secret.secret);
+        if estimate.score < MIN_STRENGTH_SCORE {
+            report.weak.push(WeakSecret {
+                name,
+                score: estimate.score,
+                warnings: estimate.warnings,
+            });
+        }
+    }
+
+    let mut anatomies = lookup_table.table.values().collect::<Vec<_>>();
+    anatomies.sort_by(|a, b| (&a.category, &a.label).cmp(&(&b.category, &b.label)));
+
+    for anatomy in anatomies {
+        let name = format!("{}/{}", anatomy.category, anatomy.label);
+
+        if let Some(date_created) = dates::parse_timestamp(&anatomy.date_created) {
+            let age_days = (now - date_created).num_days();
+
+            if age_days > max_age_days {
+                report.old.push(OldSecret {
+                    name: name.clone(),
+                    age_days,
+                });
+            }
+        }
+
+        if anatomy.last_accessed.is_none() {
+            report.never_accessed.push(name);
+        }
+    }
+
+    report.reused = secrets_by_value
+        .into_values()
+        .filter(|names| names.len() > 1)
+        .collect();
+    report.reused.sort();
+
+    report
+}
+
+/// Build an audit report using the current time.
+pub fn build_current_report(
+    lookup_table: &LookupTable,
+    decrypted_secrets: &[DecryptedSecret],
+    max_age_days: i64,
+) -> AuditReport {
+    build_report(
+        lookup_table,
+        decrypted_secrets,
+        max_age_days,
+        Local::now().naive_local(),
+    )
+}
+
+/// Print the audit report as JSON.
+pub fn print_json_report(report: &AuditReport) -> Result<(), HimitsuError> {
+    println!("{}", serde_json::to_string_pretty(report)?);
+
+    Ok(())
+}
+
+/// Print the audit report as a table.
+pub fn print_report(report: &AuditReport, max_age_days: i64) {
+    println!(
+        "\n{} {}",
+        Style::new().bold().underline().paint("Audit report"),
+        Style::new()
+            .dimmed()
+            .paint(format!("({} secrets)", report.checked))
+    );
+
+    let mut sections: Vec<ReportSection> = vec![
+        (
+            "Reused".to_string(),
+            Color::Red,
+            report
+                .reused
+                .iter()
+                .map(|names| (names.join(", "), format!("{} secrets", names.len())))
+                .collect(),
+        ),
+        (
+            "Weak".to_string(),
+            Color::Yellow,
+            report
+                .weak
+                .iter()
+                .map(|weak_secret| {
+                    (
+                        weak_secret.name.clone(),
+                        format!("score {}/4", weak_secret.score),
+                    )
+                })
+                .collect(),
+        ),
+        (
+            format!("Older than {max_age_days} days"),
+            Color::Cyan,
+            report
+                .old
+                .iter()
+                .map(|old_secret| {
+                    (
+                        old_secret.name.clone(),
+                        format!("{} days", old_secret.age_days),
+                    )
+                })
+                .collect(),
+        ),
+        (
+            "Never accessed".to_string(),
+            Color::Blue,
+            report
+                .never_accessed
+                .iter()
+                .map(|name| (name.clone(), String::new()))
+                .collect(),
+        ),
+    ];
+
+    if let Some(breached) = &report.breached {
+        sections.insert(
+            0,
+            (
+                "Breached".to_string(),
+                Color::Red,
+                breached
+                    .iter()
+                    .map(|breach_match| {
+                        (
+                            format!("{}/{}", breach_match.category, breach_match.label),
+                            format!("seen {} times", breach_match.prevalence),
+                        )
+                    })
+                    .collect(),
+            ),
+        );
+    }
+
+    for (title, color, rows) in sections {
+        println!(
+            "\n{} {}",
+            color.bold().paint(title),
+            Style::new().dimmed().paint(format!("({})", rows.len()))
+        );
+
+        for (name, detail) in rows {
+            println!("    {name:<40} {}", Style::new().dimmed().paint(detail));
+        }
+    }
+
+    println!();
+}
+
+#[cfg(test)]
+pub mod test_report {
+    use crate::models::metadata::{Anatomy, SecretType};
+
+    use super::*;
+
+    /// Test whether reused, weak, old, and never accessed secrets are reported, and whether secrets
+    /// that are not decrypted are still checked for their age and use.
+    #[test]
+    fn test_build_report() {
+        let decrypted_secrets = [
+            (
+                "email",
+                "xK9#mQ2$vL7!pR4&",
+                "01-01-2022 00:00:00",
+                Some("06-01-2022 00:00:00"),
+            ),
+            (
+                "bank",
+                "xK9#mQ2$vL7!pR4&",
+                "05-01-2022 00:00:00",
+                Some("06-01-2022 00:00:00"),
+            ),
+            ("router", "password", "05-15-2022 00:00:00", None),
+        ]
+        .map(
+            |(label, secret, date_created, last_accessed)| DecryptedSecret {
+                anatomy: Anatomy {
+                    last_accessed: last_accessed.map(str::to_string),
+                    ..Anatomy::create_for_test("unclassified", label, date_created)
+                },
+                secret: secret.to_string(),
+            },
+        );
+
+        let mut lookup_table = LookupTable::new();
+        for (index, decrypted_secret) in decrypted_secrets.iter().enumerate() {
+            lookup_table
+                .table
+                .insert(index.to_string(), decrypted_secret.anatomy.clone());
+        }
+        lookup_table.table.insert(
+            "totp".to_string(),
+            Anatomy {
+                secret_type: SecretType::Totp,
+                ..Anatomy::create_for_test("unclassified", "totp", "01-01-2022 00:00:00")
+            },
+        );
+
+        let report = build_report(
+            &lookup_table,
+            &decrypted_secrets,
+            90,
+            dates::parse_timestamp("06-01-2022 00:00:00").unwrap(),
+        );
+
+        assert_eq!(report.checked, 4);
+        assert_eq!(
+            report.reused,
+            vec![vec![
+                "unclassified/email".to_string(),
+                "unclassified/bank".to_string()
+            ]]
+        );
+        assert_eq!(report.weak.len(), 1);
+        assert_eq!(report.weak[0].name, "unclassified/router");
+        assert_eq!(report.old.len(), 2);
+        assert_eq!(report.old[0].name, "unclassified/email");
+        assert_eq!(report.old[0].age_days, 151);
+        assert_eq!(report.old[1].name, "unclassified/totp");
+        assert_eq!(
+            report.never_accessed,
+            vec![
+                "unclassified/router".to_string(),
+                "unclassified/totp".to_string()
+            ]
+        );
+    }
+}
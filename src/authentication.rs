@@ -1,8 +1,22 @@
 //! Contains utilities used for authentication.
 
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
 use argon2::{Config, ThreadMode::Parallel, Variant::Argon2id};
+use data_encoding::HEXLOWER;
+use rand::{rngs::OsRng, RngCore};
+use ring::digest::{digest, SHA256};
 
-use crate::{errors::HimitsuError, models::encryption::Encryption};
+use crate::{
+    audit::log,
+    errors::HimitsuError,
+    lookup::rekey,
+    models::{encryption::Encryption, log::LogAction},
+    utils::{attempts, config},
+};
 
 /// Returns the Argon2 configuration object. This object contains the parameters
 /// used to generate a secure password.
@@ -47,3 +61,180 @@ pub fn check_authorization(
     )
     .map_or_else(|error| Err(HimitsuError::Argon2Error(error)), Ok)
 }
+
+/// Generate a new salt and password hash.
+pub fn generate_salt_and_password_hash(
+    password: &str,
+    uses_keyfile: bool,
+) -> Result<Encryption, HimitsuError> {
+    let mut salt = [0u8; 32];
+    OsRng.fill_bytes(&mut salt);
+
+    Ok(Encryption {
+        password_hash: generate_raw_hash(password, &salt)?,
+        salt,
+        uses_keyfile,
+    })
+}
+
+/// Read a keyfile's contents.
+pub fn read_keyfile(keyfile: &Path) -> Result<Vec<u8>, HimitsuError> {
+    let contents = fs::read(keyfile).map_err(|error| {
+        HimitsuError::KeyfileError(format!("Could not read {}: {error}", keyfile.display()))
+    })?;
+
+    if contents.is_empty() {
+        return Err(HimitsuError::KeyfileError(format!(
+            "{} is empty!",
+            keyfile.display()
+        )));
+    }
+
+    Ok(contents)
+}
+
+/// Combine the master password with a keyfile's contents. The result is used in place of the
+/// master password for every key derivation, so the vault cannot be unlocked without both.
+pub fn combine_with_keyfile(password: &str, keyfile_contents: &[u8]) -> String {
+    format!(
+        "{password}{}",
+        HEXLOWER.encode(digest(&SHA256, keyfile_contents).as_ref())
+    )
+}
+
+/// Get the path to the keyfile used to unlock the vault. A keyfile passed in on the command line
+/// takes precedence over the default keyfile stored in the config.
+pub fn get_keyfile_path(keyfile: &Option<PathBuf>) -> Result<PathBuf, HimitsuError> {
+    match keyfile {
+        Some(keyfile) => Ok(keyfile.to_owned()),
+        None => config::get_config()?.keyfile.ok_or_else(|| {
+            HimitsuError::KeyfileError(
+                "This vault requires a keyfile! Pass it in with --keyfile or set a default with `hmu config --keyfile`."
+                    .to_string(),
+            )
+        }),
+    }
+}
+
+/// Check whether `master_password` is the master password the vault is currently encrypted with.
+/// If the vault uses a keyfile, `password` ends with the keyfile's digest.
+fn is_master_password(master_password: &str, password: &str, uses_keyfile: bool) -> bool {
+    match password.strip_prefix(master_password) {
+        Some(keyfile_digest) if uses_keyfile => keyfile_digest.len() == 64,
+        Some(keyfile_digest) => keyfile_digest.is_empty(),
+        None => false,
+    }
+}
+
+/// Check whether `master_password` unlocks the vault, such as before a protected secret is used.
+/// `password` is the value the current vault is encrypted with. Inside a team vault, that is the
+/// team's data key, so the master password is checked against `crypt.json` as well, combined with
+/// the default keyfile if the vault requires one.
+pub fn verify_master_password(master_password: &str, password: &str) -> Result<bool, HimitsuError> {
+    let encryption_values =
+        config::get_encryption_values()?.ok_or(HimitsuError::ApplicationError)?;
+    if is_master_password(master_password, password, encryption_values.uses_keyfile) {
+        return Ok(true);
+    }
+
+    let candidate = if encryption_values.uses_keyfile {
+        match config::get_config()?.keyfile {
+            Some(keyfile) => combine_with_keyfile(master_password, &read_keyfile(&keyfile)?),
+            None => return Ok(false),
+        }
+    } else {
+        master_password.to_string()
+    };
+
+    check_authorization(&encryption_values, &candidate)
+}
+
+/// Register, replace, or remove the keyfile used to unlock the vault. The whole vault is
+/// re-encrypted if the combination of the master password and keyfile changes.
+///
+/// `master_password` is the master password on its own, while `password` is the value the vault
+/// is currently encrypted with (the master password combined with the current keyfile, if any).
+/// A wrong master password is counted toward the login lockout.
+pub fn set_keyfile(
+    master_password: &str,
+    password: &str,
+    keyfile: Option<&Path>,
+) -> Result<(), HimitsuError> {
+    let uses_keyfile = config::get_encryption_values()?
+        .ok_or(HimitsuError::ApplicationError)?
+        .uses_keyfile;
+    if !attempts::run_counted_check(|| {
+        Ok(is_master_password(master_password, password, uses_keyfile))
+    })? {
+        log::record_event(LogAction::FailedLogin, None);
+
+        return Err(HimitsuError::FailedToLogin);
+    }
+
+    let (new_password, keyfile_path) = match keyfile {
+        Some(keyfile) => {
+            let keyfile_path = fs::canonicalize(keyfile).map_err(|error| {
+                HimitsuError::KeyfileError(format!("Could not read {}: {error}", keyfile.display()))
+            })?;
+
+            (
+                combine_with_keyfile(master_password, &read_keyfile(&keyfile_path)?),
+                Some(keyfile_path),
+            )
+        }
+        None => (master_password.to_string(), None),
+    };
+
+    if new_password != password {
+        rekey::rekey_vault(password, &new_password)?;
+
+        config::store_encryption_values(&generate_salt_and_password_hash(
+            &new_password,
+            keyfile_path.is_some(),
+        )?)?;
+    }
+
+    let mut user_config = config::get_config()?;
+    user_config.keyfile = keyfile_path;
+    config::store_config(&user_config)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_authentication {
+    use super::*;
+
+    /// Test whether the keyfile changes the derived password and the password still matters.
+    #[test]
+    fn test_combine_with_keyfile() {
+        let combined = combine_with_keyfile("password", b"keyfile");
+
+        assert_ne!(combined, "password");
+        assert_eq!(combined, combine_with_keyfile("password", b"keyfile"));
+        assert_ne!(combined, combine_with_keyfile("password", b"other keyfile"));
+        assert_ne!(combined, combine_with_keyfile("other password", b"keyfile"));
+    }
+
+    /// Test whether the master password is recognized within a password combined with a keyfile.
+    #[test]
+    fn test_is_master_password() {
+        let combined = combine_with_keyfile("password", b"keyfile");
+
+        assert!(is_master_password("password", &combined, true));
+        assert!(!is_master_password("passwor", &combined, true));
+        assert!(!is_master_password("password", &combined, false));
+        assert!(is_master_password("password", "password", false));
+        assert!(!is_master_password("other password", "password", false));
+    }
+
+    /// Test whether a password combined with a keyfile is verified against its hash.
+    #[test]
+    fn test_check_authorization_with_keyfile() {
+        let combined = combine_with_keyfile("password", b"keyfile");
+        let encryption_values = generate_salt_and_password_hash(&combined, true).unwrap();
+
+        assert!(check_authorization(&encryption_values, &combined).unwrap());
+        assert!(!check_authorization(&encryption_values, "password").unwrap());
+    }
+}
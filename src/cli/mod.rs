@@ -2,6 +2,8 @@
 
 pub mod subcommands;
 
+use std::path::PathBuf;
+
 use subcommands::SubCommands;
 
 use clap::Parser;
@@ -14,6 +16,15 @@ pub struct Args {
     #[clap(action, long)]
     pub banner: bool,
 
+    /// The path to the keyfile used to unlock the vault, if the vault requires one. Overrides the
+    /// default keyfile set with `hmu config --set-keyfile`.
+    #[clap(global = true, long, value_name = "PATH")]
+    pub keyfile: Option<PathBuf>,
+
+    /// Run the subcommand against a team vault instead of your own vault.
+    #[clap(global = true, long, value_name = "TEAM_DIR")]
+    pub team: Option<PathBuf>,
+
     /// Contains subcommands for `himitsu`.
     #[clap(subcommand)]
     pub subcommand: Option<SubCommands>,
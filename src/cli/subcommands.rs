@@ -1,21 +1,59 @@
 //! Contains subcommands for `himitsu`.
 
-use ansi_term::Color;
+use std::{
+    env,
+    io::{self, Read},
+    path::PathBuf,
+    time::Duration,
+};
+
+use ansi_term::{Color, Style};
 use clap::Subcommand;
+use x25519_dalek::PublicKey;
 
 use crate::{
+    agent,
+    api::{server as api_server, tokens as api_tokens},
+    audit::{self, breaches, log, report},
+    authentication,
+    credentials::{
+        self,
+        aws::AwsCredentials,
+        docker::{self as docker_credential, DockerCredential},
+        env::{self as env_export, Shell},
+        git::{self as git_credential, GitCredential},
+        kube::{self as kube_credential, ExecCredential},
+    },
     encryption::{
         decrypt::{self, DecryptionMode},
         encrypt,
     },
     errors::HimitsuError,
     lookup::{
+        expiry, merge,
         modify::{self, RemovalEvent},
+        query::TextMode,
+        secure,
         utils::{self as lookup_utils, LookupMode},
     },
-    models::metadata::Anatomy,
-    prompts::{add, edit, use_secret, utils},
-    utils::closet,
+    models::{
+        encryption::Encryption,
+        log::LogAction,
+        metadata::{Anatomy, LookupMatch, Protection, SecretType},
+        totp::{TotpAlgorithm, TotpOptions, TotpSecret},
+    },
+    prompts::{
+        add, authenticate, edit, protect, recovery as recovery_prompts, setup, use_secret, utils,
+    },
+    recovery,
+    secret_service::{
+        keyring::{self as secret_keyring, Keyring},
+        service as secret_service,
+    },
+    sharing::{identity, team, transfer},
+    ssh::agent as ssh_agent,
+    totp, tui,
+    utils::{attempts, clipboard, closet, config, dates, editor, git, protocol, vault},
 };
 
 /// Contains subcommands for `himitsu`.
@@ -26,60 +64,1003 @@ pub enum SubCommands {
     /// NOTE: Since the `-t/--tags` flag accepts space-delimited values, this flag should be used
     /// at the end of the command.
     Add {
+        /// The HMAC algorithm TOTP codes are generated with (SHA1, SHA256, or SHA512). Defaults to
+        /// SHA1.
+        #[clap(long, requires = "totp", value_parser)]
+        algorithm: Option<TotpAlgorithm>,
+
         /// Set a category for this secret.
         #[clap(long, short)]
         category: Option<String>,
 
+        /// The number of digits in each TOTP code. Defaults to 6.
+        #[clap(long, requires = "totp")]
+        digits: Option<u32>,
+
+        /// When this secret expires: a duration such as `90d` (`m`, `h`, `d`, and `w` are
+        /// supported) or a date such as `2025-01-31`.
+        #[clap(conflicts_with = "ttl", long, value_name = "WHEN")]
+        expires: Option<String>,
+
         /// The secret's label.
         #[clap(value_parser)]
         label: Option<String>,
 
+        /// The number of seconds each TOTP code is valid for. Defaults to 30.
+        #[clap(long, requires = "totp")]
+        period: Option<u64>,
+
+        /// Require more than an unlocked session to use or edit this secret: `reauth` asks for
+        /// the master password again, and `passphrase` seals the secret with its own passphrase.
+        #[clap(long, value_name = "LEVEL", value_parser)]
+        protect: Option<Protection>,
+
+        /// Remind you to change this secret's value every this many days (see `hmu due`).
+        #[clap(long, value_name = "DAYS")]
+        rotate_every: Option<u32>,
+
         /// Set tags for this secret. Enter multiple values delimited by a space to set multiple
         /// tags.
         #[clap(long, multiple_values = true, short)]
         tags: Option<Vec<String>>,
+
+        /// Store a TOTP seed (an `otpauth://` URI or a base32 seed) instead of a password. The
+        /// seed is never revealed; use `hmu totp` to get its current code.
+        #[clap(action, long)]
+        totp: bool,
+
+        /// Store a temporary secret that is removed automatically once this duration has passed,
+        /// such as `30m` or `12h`.
+        #[clap(long, value_name = "DURATION")]
+        ttl: Option<String>,
+
+        /// The URL this secret logs in to, such as `https://github.com/org/repo`. Credential
+        /// helpers such as `hmu git-credential` look secrets up by their URL.
+        #[clap(long)]
+        url: Option<String>,
+
+        /// The username this secret logs in with.
+        #[clap(long)]
+        username: Option<String>,
+    },
+    /// Keep the vault unlocked in the background, so subcommands and credential helpers do not
+    /// prompt for the master password.
+    Agent {
+        /// Contains subcommands for the agent.
+        #[clap(subcommand)]
+        agent_subcommand: AgentSubCommands,
+    },
+    /// Manage the API tokens applications use to access the vault through `hmu serve`.
+    ApiToken {
+        /// Contains subcommands for API tokens.
+        #[clap(subcommand)]
+        api_token_subcommand: ApiTokenSubCommands,
+    },
+    /// Audit the secrets stored in the vault. Reports reused, weak, old, and never accessed
+    /// secrets.
+    #[clap(args_conflicts_with_subcommands = true)]
+    Audit {
+        /// Contains subcommands for the vault's audit log.
+        #[clap(subcommand)]
+        audit_subcommand: Option<AuditSubCommands>,
+
+        /// Also check every stored secret against a downloaded Have I Been Pwned SHA-1 corpus:
+        /// either a file of `HASH:COUNT` lines sorted by hash, a range file, or a directory of
+        /// range files. No network access is needed.
+        #[clap(long, value_name = "PATH")]
+        breaches: Option<PathBuf>,
+
+        /// Print the report as JSON.
+        #[clap(action, long)]
+        json: bool,
+
+        /// Report secrets created more than this many days ago.
+        #[clap(default_value_t = 365, long, value_name = "DAYS")]
+        older_than: i64,
+    },
+    /// Print a secret's AWS credentials for the `credential_process` setting in `~/.aws/config`.
+    AwsCredentials {
+        /// The secret's exact label, or `category/label` if several secrets share the label.
+        #[clap(value_parser)]
+        label: String,
+    },
+    /// View or change your settings.
+    Config {
+        /// Stop requiring a keyfile to unlock the vault. The vault is re-encrypted.
+        #[clap(action, conflicts_with = "set-keyfile", long)]
+        remove_keyfile: bool,
+
+        /// Register a keyfile, such as a random file on a USB stick, that must be present to
+        /// unlock the vault. The keyfile is combined with your master password, and the vault is
+        /// re-encrypted. The keyfile's path is stored as the default keyfile.
+        #[clap(long, value_name = "PATH")]
+        set_keyfile: Option<PathBuf>,
+
+        /// Wipe the vault after this many failed login attempts in a row. Set to 0 to never wipe
+        /// the vault.
+        #[clap(long, value_name = "ATTEMPTS")]
+        wipe_after: Option<u32>,
+    },
+    /// List secrets that are expired, about to expire, or due for rotation.
+    Due {
+        /// Also list secrets that expire within this many days.
+        #[clap(default_value_t = 7, long, value_name = "DAYS")]
+        within: i64,
+    },
+    /// Act as a Docker credential helper. Docker passes a registry's server URL or a JSON
+    /// credential on stdin. Requires the vault to be unlocked with `hmu agent start`.
+    DockerCredential {
+        /// Contains the operations Docker asks credential helpers to perform.
+        #[clap(subcommand)]
+        docker_credential_subcommand: DockerCredentialSubCommands,
     },
     /// Edit an existing secret (search by label).
     Edit {
-        /// The label corresponding to the secret (supports Regex expressions).
+        /// Edit the secret's value in `$VISUAL` or `$EDITOR` instead, which suits multi-line
+        /// secrets such as keys, JSON files, and notes.
+        #[clap(action, long)]
+        editor: bool,
+
+        /// A query matching the secret, such as `aws tag:prod -tag:old`. Text is fuzzy matched
+        /// against labels, categories, and tags; `cat:`, `tag:`, `type:`, `created:`,
+        /// `modified:`, `accessed:`, and `expires:` filter by metadata, and a leading `-`
+        /// excludes matches.
+        #[clap(multiple_values = true, value_parser)]
+        label: Vec<String>,
+
+        /// Match the query's text against labels as Regex expressions instead.
+        #[clap(action, long)]
+        regex: bool,
+    },
+    /// Print shell statements that export secrets as environment variables, for
+    /// `eval "$(hmu env ...)"` or direnv's `.envrc`. Variable names are derived from labels, such
+    /// as `DB_PASSWORD` for `db-password`, unless a manifest names them.
+    ///
+    /// NOTE: Since the `labels` argument accepts space-delimited values, flags should be used
+    /// before the labels.
+    Env {
+        /// Export every secret in this category, or only look up the labels in this category.
+        #[clap(long, short)]
+        category: Option<String>,
+
+        /// The labels of the secrets to export. Labels are matched exactly.
+        #[clap(multiple_values = true, required_unless_present_any = ["category", "manifest"], value_parser)]
+        labels: Vec<String>,
+
+        /// A file listing the secrets to export, one `NAME=LABEL` or label per line.
+        #[clap(long, short, value_name = "PATH")]
+        manifest: Option<PathBuf>,
+
+        /// The shell to write statements for: bash, zsh, fish, or powershell. Defaults to the
+        /// shell in `$SHELL`.
+        #[clap(long, short, value_parser)]
+        shell: Option<Shell>,
+
+        /// Print statements that remove the variables instead.
+        #[clap(action, long)]
+        unset: bool,
+    },
+    /// Keep the vault's history in a local git repository.
+    Git {
+        /// Contains subcommands for the vault's git repository.
+        #[clap(subcommand)]
+        git_subcommand: GitSubCommands,
+    },
+    /// Act as a git credential helper. git passes the credential it needs as `key=value` lines on
+    /// stdin. Set it up with `git config --global credential.helper '!hmu git-credential'`.
+    GitCredential {
+        /// Contains the operations git asks credential helpers to perform.
+        #[clap(subcommand)]
+        git_credential_subcommand: GitCredentialSubCommands,
+    },
+    /// Show this vault's public key. Share it with others so they can share secrets with you.
+    Identity,
+    /// Print a secret's Kubernetes credential as an `ExecCredential` object for a kubeconfig's
+    /// exec credential plugin.
+    KubeCredential {
+        /// The secret's exact label, or `category/label` if several secrets share the label.
         #[clap(value_parser)]
-        label: Option<String>,
+        label: String,
+    },
+    /// Show the vault's history (requires a git-backed vault).
+    Log,
+    /// Merge another vault into this vault.
+    ///
+    /// Secrets are matched by label and category. Missing secrets are copied over, removals are
+    /// propagated, and secrets that changed on both sides keep the most recently modified version.
+    Merge {
+        /// Always choose which version to keep when a secret changed in both vaults.
+        #[clap(action, long, short)]
+        interactive: bool,
+
+        /// The path to the other vault's data directory (the directory containing its `closet`
+        /// and `lookup` directories).
+        #[clap(value_parser)]
+        vault_dir: PathBuf,
+    },
+    /// Store a secret that was shared with you.
+    Receive {
+        /// The path to the shared secret's file.
+        #[clap(value_parser)]
+        file: PathBuf,
+    },
+    /// Split a recovery key into shares or recover a vault whose master password was forgotten.
+    Recovery {
+        /// Contains subcommands for recovery shares.
+        #[clap(subcommand)]
+        recovery_subcommand: RecoverySubCommands,
     },
     /// Remove an existing secret (search by label).
     Remove {
-        /// The label corresponding to the secret (supports Regex expressions).
+        /// A query matching the secret, such as `aws tag:prod -tag:old`. Text is fuzzy matched
+        /// against labels, categories, and tags; `cat:`, `tag:`, `type:`, `created:`,
+        /// `modified:`, `accessed:`, and `expires:` filter by metadata, and a leading `-`
+        /// excludes matches.
+        #[clap(multiple_values = true, value_parser)]
+        label: Vec<String>,
+
+        /// Match the query's text against labels as Regex expressions instead.
+        #[clap(action, long)]
+        regex: bool,
+    },
+    /// Roll the whole vault back to a revision listed by `hmu log` (requires a git-backed vault).
+    Restore {
+        /// The revision to restore.
         #[clap(value_parser)]
-        label: Option<String>,
+        revision: String,
+    },
+    /// Act as a freedesktop Secret Service provider on the D-Bus session bus, so libsecret clients
+    /// such as `secret-tool` can store and look up secrets. Runs until it is stopped with CTRL+C.
+    SecretService {
+        /// The category secrets are stored in when an application uses the default collection.
+        /// Defaults to `unclassified`.
+        #[clap(long, short)]
+        category: Option<String>,
     },
-    /// Use a stored secret (search by label).
+    /// Serve a local JSON API over a Unix socket, so applications can list, get, put, and delete
+    /// secrets with an API token from `hmu api-token add`. Runs until it is stopped with CTRL+C.
+    Serve {
+        /// The socket to listen on. Defaults to `api.sock` in the runtime directory.
+        #[clap(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+    },
+    /// Share a stored secret with another user (search by label). The secret is written to a file
+    /// that only the recipient can decrypt.
+    Share {
+        /// A query matching the secret, such as `aws tag:prod -tag:old`. Text is fuzzy matched
+        /// against labels, categories, and tags; `cat:`, `tag:`, `type:`, `created:`,
+        /// `modified:`, `accessed:`, and `expires:` filter by metadata, and a leading `-`
+        /// excludes matches.
+        #[clap(multiple_values = true, value_parser)]
+        label: Vec<String>,
+
+        /// Match the query's text against labels as Regex expressions instead.
+        #[clap(action, long)]
+        regex: bool,
+
+        /// The path to write the shared secret to. Defaults to `<LABEL>.himitsu` in the current
+        /// directory.
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+
+        /// The recipient's X25519 public key, encoded like an age recipient (`age1...`) or as hex
+        /// or base64. The shared file can only be opened with `hmu receive`, not with `age`.
+        #[clap(long, short)]
+        to: String,
+    },
+    /// Serve SSH private keys stored as secrets over the ssh-agent protocol, so they never have
+    /// to be written to disk. Keys are stored in OpenSSH's private key format. Runs until it is
+    /// stopped with CTRL+C.
+    SshAgent {
+        /// Serve the keys in this category. Defaults to `ssh` if no tags are given either.
+        #[clap(long, short)]
+        category: Option<String>,
+
+        /// Ask for confirmation before each signature.
+        #[clap(action, long)]
+        confirm: bool,
+
+        /// The socket to listen on. Defaults to `ssh-agent.sock` in the runtime directory.
+        #[clap(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+
+        /// Serve the keys with any of these tags. Enter multiple values delimited by a space.
+        #[clap(long, multiple_values = true, short)]
+        tags: Option<Vec<String>>,
+    },
+    /// Manage team vaults shared between several people.
+    Team {
+        /// Contains subcommands for team vaults.
+        #[clap(subcommand)]
+        team_subcommand: TeamSubCommands,
+    },
+    /// Copy the current code of a stored TOTP secret to the clipboard (search by label).
+    Totp {
+        /// A query matching the secret, such as `aws tag:prod -tag:old`. Text is fuzzy matched
+        /// against labels, categories, and tags; `cat:`, `tag:`, `type:`, `created:`,
+        /// `modified:`, `accessed:`, and `expires:` filter by metadata, and a leading `-`
+        /// excludes matches.
+        #[clap(multiple_values = true, value_parser)]
+        label: Vec<String>,
+
+        /// Match the query's text against labels as Regex expressions instead.
+        #[clap(action, long)]
+        regex: bool,
+    },
+    /// Browse the vault in a full-screen interface. Secrets may be searched, copied, revealed,
+    /// edited, retagged, regenerated, and removed without unlocking the vault again.
+    Tui {
+        /// Lock the vault and quit after being idle for this long, such as `5m` or `1h`.
+        #[clap(default_value = "5m", long, value_name = "DURATION")]
+        lock_after: String,
+
+        /// The number of seconds a revealed secret is shown for.
+        #[clap(default_value = "10", long, value_name = "SECONDS")]
+        reveal_for: u64,
+    },
+    /// Use a stored secret (search by label). TOTP secrets copy their current code instead.
     Use {
-        /// The label corresponding to the secret (supports Regex expressions).
+        /// A query matching the secret, such as `aws tag:prod -tag:old`. Text is fuzzy matched
+        /// against labels, categories, and tags; `cat:`, `tag:`, `type:`, `created:`,
+        /// `modified:`, `accessed:`, and `expires:` filter by metadata, and a leading `-`
+        /// excludes matches.
+        #[clap(multiple_values = true, value_parser)]
+        label: Vec<String>,
+
+        /// Match the query's text against labels as Regex expressions instead.
+        #[clap(action, long)]
+        regex: bool,
+    },
+}
+
+/// Contains subcommands for the agent.
+#[derive(Debug, Subcommand)]
+pub enum AgentSubCommands {
+    /// Serve the vault's password to other `hmu` processes. Started by `hmu agent start`.
+    #[clap(hide = true)]
+    Serve {
+        /// How long to keep the vault unlocked.
+        #[clap(long, value_name = "DURATION")]
+        timeout: String,
+    },
+    /// Unlock the vault and start the agent in the background.
+    Start {
+        /// How long to keep the vault unlocked, such as `30m` or `8h`.
+        #[clap(default_value = "1h", long, value_name = "DURATION")]
+        timeout: String,
+    },
+    /// Check whether the agent is running.
+    Status,
+    /// Lock the vault and stop the agent.
+    Stop,
+}
+
+/// Contains subcommands for API tokens.
+#[derive(Debug, Subcommand)]
+pub enum ApiTokenSubCommands {
+    /// Create a token for an application. The token is only shown once.
+    Add {
+        /// The categories the token may access. Enter multiple values delimited by a space.
+        #[clap(long, multiple_values = true, required_unless_present = "tags", short)]
+        categories: Option<Vec<String>>,
+
+        /// The name of the application, which is recorded in the audit log.
         #[clap(value_parser)]
-        label: Option<String>,
+        name: String,
+
+        /// The tags the token may access. Enter multiple values delimited by a space.
+        #[clap(long, multiple_values = true, short)]
+        tags: Option<Vec<String>>,
+    },
+    /// List the tokens and what they may access.
+    List,
+    /// Revoke a token.
+    Remove {
+        /// The name of the application whose token is revoked.
+        #[clap(value_parser)]
+        name: String,
+    },
+}
+
+/// Contains subcommands for the vault's audit log.
+#[derive(Debug, Subcommand)]
+pub enum AuditSubCommands {
+    /// Show the vault's audit log: every unlock, use, change, and failed login.
+    Log {
+        /// Only show events at or after this date. Accepts a date (`YYYY-MM-DD`), a timestamp, or
+        /// a duration before now (such as `7d`).
+        #[clap(long, value_name = "WHEN")]
+        since: Option<String>,
+
+        /// Check that no entries were modified, removed, or cut off the end of the log.
+        #[clap(action, long)]
+        verify: bool,
+    },
+}
+
+/// Contains subcommands for the vault's git repository.
+#[derive(Debug, Subcommand)]
+pub enum GitSubCommands {
+    /// Turn the vault directory into a git repository. Every change to the vault is committed
+    /// from then on.
+    Init {
+        /// The URL of a (private) remote repository to push the vault's history to.
+        #[clap(long, short)]
+        remote: Option<String>,
+    },
+    /// Push the vault's history to its remote repository.
+    Push,
+}
+
+impl SubCommands {
+    /// Check whether this subcommand's output is read by another program. Nothing but the
+    /// subcommand's output may be written to stdout then.
+    pub fn speaks_protocol(&self) -> bool {
+        matches!(
+            self,
+            SubCommands::AwsCredentials { .. }
+                | SubCommands::DockerCredential { .. }
+                | SubCommands::Env { .. }
+                | SubCommands::GitCredential { .. }
+                | SubCommands::KubeCredential { .. }
+        )
+    }
+
+    /// Check whether this subcommand only runs while the agent keeps the vault unlocked, since
+    /// the program running it cannot answer a password prompt.
+    pub fn requires_agent(&self) -> bool {
+        matches!(self, SubCommands::DockerCredential { .. })
+    }
+
+    /// Check whether this subcommand runs without unlocking the vault.
+    pub fn skips_unlock(&self) -> bool {
+        matches!(
+            self,
+            SubCommands::Agent {
+                agent_subcommand: AgentSubCommands::Serve { .. }
+                    | AgentSubCommands::Status
+                    | AgentSubCommands::Stop
+            }
+        )
+    }
+}
+
+/// Contains the operations Docker asks credential helpers to perform.
+#[derive(Debug, Subcommand)]
+pub enum DockerCredentialSubCommands {
+    /// Remove the credential stored for a registry.
+    Erase,
+    /// Print the credential stored for a registry.
+    Get,
+    /// List every registry with a stored credential.
+    List,
+    /// Store a registry's credential.
+    Store,
+}
+
+/// Contains the operations git asks credential helpers to perform.
+#[derive(Debug, Subcommand)]
+pub enum GitCredentialSubCommands {
+    /// Remove a credential git has rejected.
+    Erase,
+    /// Print the stored credential matching git's request.
+    Get,
+    /// Store a credential git has used successfully, or update the stored credential.
+    Store,
+}
+
+/// Contains subcommands for recovery shares.
+#[derive(Debug, Subcommand)]
+pub enum RecoverySubCommands {
+    /// Recover the vault with recovery shares and set a new master password. Does not require
+    /// the master password.
+    Restore,
+    /// Split a new recovery key into shares and print them. Hand each share to a different person
+    /// or store them in different places. Any previously created shares stop working.
+    Split {
+        /// Print each share as a QR code as well.
+        #[clap(action, long)]
+        qr: bool,
+
+        /// The number of shares to create.
+        #[clap(long, short)]
+        shares: u8,
+
+        /// The number of shares required to recover the vault.
+        #[clap(long, short)]
+        threshold: u8,
+    },
+}
+
+/// Contains subcommands for team vaults.
+#[derive(Debug, Subcommand)]
+pub enum TeamSubCommands {
+    /// Add a member to a team vault.
+    AddMember {
+        /// The new member's public key (printed by `hmu identity`).
+        #[clap(long, short)]
+        key: String,
+
+        /// The new member's name.
+        #[clap(long, short)]
+        name: String,
+
+        /// The path to the team vault.
+        #[clap(value_parser)]
+        team_dir: PathBuf,
+    },
+    /// Create a new team vault with yourself as its first member.
+    Init {
+        /// Your name within the team vault.
+        #[clap(long, short)]
+        name: String,
+
+        /// The path to the new team vault.
+        #[clap(value_parser)]
+        team_dir: PathBuf,
     },
+    /// List the members of a team vault.
+    Members {
+        /// The path to the team vault.
+        #[clap(value_parser)]
+        team_dir: PathBuf,
+    },
+    /// Remove a member from a team vault. The team vault is re-encrypted with a new key.
+    RemoveMember {
+        /// The name of the member to remove.
+        #[clap(long, short)]
+        name: String,
+
+        /// The path to the team vault.
+        #[clap(value_parser)]
+        team_dir: PathBuf,
+    },
+}
+
+/// Recover the vault with recovery shares, then set a new master password. This runs without
+/// unlocking the vault first.
+pub fn run_recovery_restore() -> Result<(), HimitsuError> {
+    let recovery_shares = recovery_prompts::run_get_shares()?;
+    let recovered_password = recovery::recover_password(&recovery_shares)?;
+
+    println!(
+        "{}",
+        Color::Green
+            .bold()
+            .paint("Your vault was recovered. Set a new master password.")
+    );
+
+    let new_password = setup::run_new_password_prompts()?;
+
+    recovery::reset_master_password(&recovered_password, &new_password)?;
+    git::record_vault_change("recover vault")?;
+
+    println!(
+        "{}",
+        Color::Green.bold().paint(
+            "Your master password was reset. Any keyfile and recovery shares no longer apply."
+        )
+    );
+
+    Ok(())
+}
+
+/// Run an agent subcommand that does not unlock the vault first.
+pub fn run_agent_subcommand(
+    encryption_values: &Encryption,
+    agent_subcommand: &AgentSubCommands,
+) -> Result<(), HimitsuError> {
+    match agent_subcommand {
+        AgentSubCommands::Serve { timeout } => {
+            let timeout = parse_agent_timeout(timeout)?;
+
+            // The password is checked like any other login, so this subcommand cannot be used
+            // to guess the password without the backoff.
+            let mut password = String::new();
+            io::stdin().read_to_string(&mut password)?;
+            if !attempts::run_counted_check(|| {
+                authentication::check_authorization(encryption_values, &password)
+            })? {
+                log::record_event(LogAction::FailedLogin, None);
+
+                return Err(HimitsuError::FailedToLogin);
+            }
+
+            agent::serve(&password, timeout)?;
+        }
+        // Starting the agent unlocks the vault first, so it runs with the other subcommands.
+        AgentSubCommands::Start { .. } => {}
+        AgentSubCommands::Status => {
+            if agent::is_running() {
+                println!(
+                    "{}",
+                    Color::Green
+                        .bold()
+                        .paint("The agent is running. Your vault is unlocked.")
+                );
+            } else {
+                println!(
+                    "{}",
+                    Style::new()
+                        .dimmed()
+                        .paint("The agent is not running. Your vault is locked.")
+                );
+            }
+        }
+        AgentSubCommands::Stop => {
+            if agent::stop_agent() {
+                println!(
+                    "{}",
+                    Color::Green
+                        .bold()
+                        .paint("Stopped the agent. Your vault is locked.")
+                );
+            } else {
+                println!(
+                    "{}",
+                    Style::new().dimmed().paint("The agent is not running.")
+                );
+            }
+        }
+    }
+
+    Ok(())
 }
 
 /// Execution blocks for this program's subcommands.
 pub fn run_subcommands(password: &str, subcommand: &SubCommands) -> Result<(), HimitsuError> {
+    let removed_secrets = expiry::remove_expired_secrets(password)?;
+    if !removed_secrets.is_empty() {
+        for removed_secret in &removed_secrets {
+            log::record_event(LogAction::Remove, Some(removed_secret.clone()));
+        }
+        git::record_vault_change("remove expired secrets")?;
+
+        println!(
+            "{}",
+            Style::new().dimmed().paint(format!(
+                "Removed expired temporary secrets: {}",
+                removed_secrets.join(", ")
+            ))
+        );
+    }
+
     match subcommand {
+        SubCommands::Agent { agent_subcommand } => {
+            if let AgentSubCommands::Start { timeout } = agent_subcommand {
+                parse_agent_timeout(timeout)?;
+
+                if agent::is_running() {
+                    println!(
+                        "{}",
+                        Style::new()
+                            .dimmed()
+                            .paint("The agent is already running. Your vault is unlocked.")
+                    );
+                } else {
+                    agent::start_agent(password, timeout)?;
+
+                    println!(
+                        "{}",
+                        Color::Green.bold().paint(format!(
+                            "Your vault is unlocked for {timeout}. Lock it with `hmu agent stop`."
+                        ))
+                    );
+                }
+            }
+        }
+        SubCommands::ApiToken {
+            api_token_subcommand,
+        } => match api_token_subcommand {
+            ApiTokenSubCommands::Add {
+                categories,
+                name,
+                tags,
+            } => {
+                let token = api_tokens::create_token(
+                    name,
+                    categories.as_deref().unwrap_or_default(),
+                    tags.as_deref().unwrap_or_default(),
+                    password,
+                )?;
+
+                println!(
+                    "\n{}\n\n{}\n\n{}\n",
+                    Color::Green
+                        .bold()
+                        .paint(format!("Created an API token for {name}:")),
+                    Color::Fixed(172).bold().paint(token),
+                    Style::new().dimmed().paint(
+                        "This token is not shown again. Revoke it with `hmu api-token remove`."
+                    )
+                );
+            }
+            ApiTokenSubCommands::List => {
+                let tokens = api_tokens::get_tokens(password)?;
+                if tokens.is_empty() {
+                    println!(
+                        "{}",
+                        Style::new().dimmed().paint("No API tokens were created.")
+                    );
+                }
+
+                for token in tokens {
+                    println!(
+                        "🔑 {} {} {}",
+                        Style::new().bold().paint(&token.name),
+                        Style::new().dimmed().paint(format!(
+                            "categories: [{}] tags: [{}]",
+                            token.categories.join(", "),
+                            token.tags.join(", ")
+                        )),
+                        Style::new()
+                            .dimmed()
+                            .paint(format!("(created {})", token.date_created))
+                    );
+                }
+            }
+            ApiTokenSubCommands::Remove { name } => {
+                if !api_tokens::remove_token(name, password)? {
+                    return Err(HimitsuError::ApiError(format!(
+                        "No API token is named \"{name}\"!"
+                    )));
+                }
+
+                println!(
+                    "{}",
+                    Color::Green
+                        .bold()
+                        .paint(format!("Revoked the API token for {name}."))
+                );
+            }
+        },
         SubCommands::Add {
+            algorithm,
             category,
+            digits,
+            expires,
             label,
+            period,
+            protect,
+            rotate_every,
             tags,
+            totp,
+            ttl,
+            url,
+            username,
+        } => {
+            let now = dates::get_now();
+            let expires_at = match (expires, ttl) {
+                (Some(expires), _) => Some(dates::parse_expiry(expires, now).ok_or_else(|| {
+                    HimitsuError::ExpiryError(format!("\"{expires}\" is not a duration or date!"))
+                })?),
+                (None, Some(ttl)) => Some(
+                    now + dates::parse_duration(ttl).ok_or_else(|| {
+                        HimitsuError::ExpiryError(format!("\"{ttl}\" is not a duration!"))
+                    })?,
+                ),
+                (None, None) => None,
+            };
+
+            let (label, secret, category, tags) =
+                add::run_add_secret(category, label, tags, *totp)?;
+            let mut anatomy = Anatomy::create_from(category, label, tags);
+            anatomy.expires_at = expires_at.as_ref().map(dates::format_timestamp);
+            anatomy.rotation_days = *rotate_every;
+            anatomy.temporary = ttl.is_some();
+            anatomy.protection = protect.unwrap_or_default();
+            anatomy.url = url.clone();
+            anatomy.username = username.clone();
+
+            let secret = if *totp {
+                anatomy.secret_type = SecretType::Totp;
+
+                serde_json::to_string(&totp::parse_totp(
+                    &secret,
+                    &TotpOptions {
+                        algorithm: *algorithm,
+                        digits: *digits,
+                        period: *period,
+                    },
+                )?)?
+            } else {
+                secret
+            };
+            let secret = if anatomy.protection == Protection::Passphrase {
+                encrypt::seal_with_passphrase(&protect::run_new_passphrase()?, &secret)?
+            } else {
+                secret
+            };
+
+            let hash_id = encrypt::encrypt_secret(&anatomy, password, secret)?;
+            log::record_event(LogAction::Add, Some(log::describe_secret(&anatomy)));
+            git::record_change("add", &hash_id)?;
+        }
+        SubCommands::Audit {
+            audit_subcommand: Some(AuditSubCommands::Log { since, verify }),
+            ..
+        } => {
+            let mut events = log::read_events(password, *verify)?;
+
+            if let Some(since) = since {
+                let since = dates::parse_since(since, dates::get_now()).ok_or_else(|| {
+                    HimitsuError::LogError(format!("\"{since}\" is not a valid date or duration!"))
+                })?;
+
+                events = log::filter_since(events, since);
+            }
+
+            for event in events {
+                println!(
+                    "{}  {}  {}  {}",
+                    Color::Green.bold().paint(event.date),
+                    Style::new().dimmed().paint(event.user),
+                    Color::Yellow.bold().paint(event.action.to_string()),
+                    Style::new().bold().paint(event.secret.unwrap_or_default())
+                );
+            }
+
+            if *verify {
+                println!(
+                    "{}",
+                    Color::Green.bold().paint("✅ The audit log is intact.")
+                );
+            }
+        }
+        SubCommands::Audit {
+            audit_subcommand: None,
+            breaches,
+            json,
+            older_than,
         } => {
-            let (label, secret, category, tags) = add::run_add_secret(category, label, tags)?;
-            let anatomy = Anatomy::create_from(category, label, tags);
+            let corpus = breaches.as_deref().map(breaches::open_corpus).transpose()?;
+            let lookup_table = secure::decrypt_lookup_table(password)?;
+            let decrypted_secrets = audit::decrypt_passwords(&lookup_table, password, *json)?;
+
+            let mut audit_report =
+                report::build_current_report(&lookup_table, &decrypted_secrets, *older_than);
+            if let Some(corpus) = corpus {
+                audit_report.breached = Some(breaches::find_breached_secrets(
+                    &corpus,
+                    &decrypted_secrets,
+                )?);
+            }
 
-            encrypt::encrypt_secret(&anatomy, password, secret)?;
+            if *json {
+                report::print_json_report(&audit_report)?;
+            } else {
+                report::print_report(&audit_report, *older_than);
+            }
+        }
+        SubCommands::AwsCredentials { label } => {
+            let lookup_match = credentials::find_labeled_secret(label, password)?;
+            let secret = credentials::use_labeled_secret(&lookup_match, password)?;
+
+            protocol::write_output(&serde_json::to_string(&AwsCredentials::parse(
+                &secret,
+                &lookup_match.anatomy,
+            )?)?)?;
         }
-        SubCommands::Edit { label } => {
+        SubCommands::Env {
+            category,
+            labels,
+            manifest,
+            shell,
+            unset,
+        } => {
+            let mut entries = match manifest {
+                Some(manifest) => env_export::read_manifest(manifest)?,
+                None => Vec::new(),
+            };
+            entries.extend(labels.iter().map(|label| env_export::EnvEntry {
+                label: label.clone(),
+                name: env_export::to_variable_name(label),
+            }));
+
+            let shell = shell.unwrap_or_else(Shell::detect);
+            let selected = env_export::select_secrets(entries, category.as_deref(), password)?;
+
+            let mut statements = Vec::new();
+            for (name, lookup_match) in selected {
+                if *unset {
+                    statements.push(shell.unset(&name));
+                } else {
+                    let secret = credentials::use_labeled_secret(&lookup_match, password)?;
+                    if secret.contains('\0') {
+                        return Err(HimitsuError::CredentialError(format!(
+                            "\"{}\" contains a NUL character and cannot be exported!",
+                            lookup_match.anatomy.label
+                        )));
+                    }
+
+                    statements.push(shell.export(&name, &secret));
+                }
+            }
+
+            protocol::write_output(
+                &statements
+                    .iter()
+                    .map(|statement| format!("{statement}\n"))
+                    .collect::<String>(),
+            )?;
+        }
+        SubCommands::Config {
+            remove_keyfile,
+            set_keyfile,
+            wipe_after,
+        } => {
+            if let Some(wipe_after) = wipe_after {
+                let mut user_config = config::get_config()?;
+                user_config.wipe_after = Some(*wipe_after).filter(|attempts| *attempts > 0);
+                config::store_config(&user_config)?;
+
+                println!(
+                    "{}",
+                    Color::Green.bold().paint(match user_config.wipe_after {
+                        Some(attempts) => format!(
+                            "Your vault will be wiped after {attempts} failed login attempts in a row."
+                        ),
+                        None => "Your vault will never be wiped after failed login attempts."
+                            .to_string(),
+                    })
+                );
+            }
+
+            if set_keyfile.is_none() && !remove_keyfile {
+                if wipe_after.is_none() {
+                    print_settings()?;
+                }
+            } else {
+                let master_password =
+                    authenticate::run_reenter_password("Re-enter your master password:")?;
+
+                let had_recovery_shares = recovery::is_configured()?;
+
+                authentication::set_keyfile(&master_password, password, set_keyfile.as_deref())?;
+                git::record_vault_change("rekey")?;
+
+                if had_recovery_shares {
+                    println!(
+                        "{}",
+                        Color::Fixed(172).bold().paint(
+                            "Your recovery shares no longer work. Run `hmu recovery split` to create new ones."
+                        )
+                    );
+                }
+
+                println!(
+                    "{}",
+                    Color::Green.bold().paint(if *remove_keyfile {
+                        "A keyfile is no longer required to unlock your vault."
+                    } else {
+                        "Your keyfile is registered. Keep it safe - your vault cannot be unlocked without it."
+                    })
+                );
+            }
+        }
+        SubCommands::Due { within } => {
+            let lookup_table = secure::decrypt_lookup_table(password)?;
+
+            expiry::print_due_secrets(&expiry::find_due_secrets(
+                &lookup_table,
+                dates::get_now(),
+                *within,
+            ));
+        }
+        SubCommands::Edit {
+            editor,
+            label,
+            regex,
+        } => {
             if !closet::check_for_skeletons()? {
                 return Err(HimitsuError::NoSecretsError);
             }
 
             let label = utils::run_get_label(label)?;
-            let found_matches =
-                lookup_utils::search_in_lookup_table(LookupMode::Search(label), password)?;
+            let found_matches = lookup_utils::search_in_lookup_table(
+                LookupMode::Search(label, get_text_mode(*regex)),
+                password,
+            )?;
 
             if found_matches.is_empty() {
                 let list_all_secrets = utils::run_confirmation_prompt(
@@ -91,79 +1072,229 @@ pub fn run_subcommands(password: &str, subcommand: &SubCommands) -> Result<(), H
                         lookup_utils::search_in_lookup_table(LookupMode::GetAll, password)?;
 
                     let lookup_match = use_secret::run_select_secret(found_matches)?;
-                    let secret = decrypt::decrypt_secret(
-                        DecryptionMode::EditSecret,
-                        &lookup_match.hash,
-                        password,
-                    )?
-                    .unwrap();
-
-                    let update_targets = edit::run_edit_targets()?;
 
-                    let mut new_anatomy = lookup_match.anatomy.clone();
-                    let mut new_secret = None;
-
-                    for target in update_targets {
-                        match target {
-                            "Category" => edit::run_edit_category(&mut new_anatomy)?,
-                            "Label" => edit::run_edit_label(&mut new_anatomy)?,
-                            "Secret" => new_secret = Some(edit::run_edit_secret()?),
-                            "Tags" => edit::run_edit_tags(&mut new_anatomy)?,
-                            _ => {}
-                        }
+                    if *editor {
+                        edit_secret_in_editor(&lookup_match, password)?;
+                    } else {
+                        edit_stored_secret(&lookup_match, password)?;
                     }
-
-                    modify::remove_in_lookup_table(
-                        &lookup_match.hash,
-                        password,
-                        RemovalEvent::Replace,
-                    )?;
-
-                    encrypt::encrypt_secret(&new_anatomy, password, new_secret.unwrap_or(secret))?;
                 } else {
                     println!("\n{}\n", Color::Red.bold().paint("GOODBYE."));
                 }
             } else {
                 let lookup_match = use_secret::run_select_secret(found_matches)?;
-                let secret = decrypt::decrypt_secret(
-                    DecryptionMode::EditSecret,
-                    &lookup_match.hash,
-                    password,
-                )?
-                .unwrap();
 
-                let update_targets = edit::run_edit_targets()?;
+                if *editor {
+                    edit_secret_in_editor(&lookup_match, password)?;
+                } else {
+                    edit_stored_secret(&lookup_match, password)?;
+                }
+            }
+        }
+        SubCommands::Git { git_subcommand } => {
+            let vault_dir = vault::get_vault_dir()?;
+
+            match git_subcommand {
+                GitSubCommands::Init { remote } => {
+                    git::init_repository(&vault_dir, remote.as_deref())?;
 
-                let mut new_anatomy = lookup_match.anatomy.clone();
-                let mut new_secret = None;
+                    println!(
+                        "{}",
+                        Color::Green
+                            .bold()
+                            .paint("Every change to your vault will be committed from now on.")
+                    );
+                }
+                GitSubCommands::Push => {
+                    check_git_backed_vault()?;
+                    git::push(&vault_dir)?;
+
+                    println!(
+                        "{}",
+                        Color::Green.bold().paint("Pushed your vault's history.")
+                    );
+                }
+            }
+        }
+        SubCommands::GitCredential {
+            git_credential_subcommand,
+        } => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            let request = GitCredential::parse(&input);
 
-                for target in update_targets {
-                    match target {
-                        "Category" => edit::run_edit_category(&mut new_anatomy)?,
-                        "Label" => edit::run_edit_label(&mut new_anatomy)?,
-                        "Secret" => new_secret = Some(edit::run_edit_secret()?),
-                        "Tags" => edit::run_edit_tags(&mut new_anatomy)?,
-                        _ => {}
+            match git_credential_subcommand {
+                GitCredentialSubCommands::Erase => {
+                    git_credential::erase_credential(&request, password)?
+                }
+                GitCredentialSubCommands::Get => {
+                    if let Some(credential) = git_credential::get_credential(&request, password)? {
+                        protocol::write_output(&credential.to_output()?)?;
                     }
                 }
+                GitCredentialSubCommands::Store => {
+                    git_credential::store_credential(&request, password)?
+                }
+            }
+        }
+        SubCommands::DockerCredential {
+            docker_credential_subcommand,
+        } => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+
+            match docker_credential_subcommand {
+                DockerCredentialSubCommands::Erase => {
+                    docker_credential::erase_credential(input.trim(), password)?
+                }
+                DockerCredentialSubCommands::Get => {
+                    match docker_credential::get_credential(input.trim(), password)? {
+                        Some(credential) => {
+                            protocol::write_output(&serde_json::to_string(&credential)?)?
+                        }
+                        None => {
+                            protocol::write_output(docker_credential::NOT_FOUND_MESSAGE)?;
 
-                modify::remove_in_lookup_table(
-                    &lookup_match.hash,
-                    password,
-                    RemovalEvent::Replace,
-                )?;
+                            return Err(HimitsuError::CredentialError(
+                                docker_credential::NOT_FOUND_MESSAGE.to_string(),
+                            ));
+                        }
+                    }
+                }
+                DockerCredentialSubCommands::List => {
+                    protocol::write_output(&serde_json::to_string(
+                        &docker_credential::list_credentials(&secure::decrypt_lookup_table(
+                            password,
+                        )?),
+                    )?)?;
+                }
+                DockerCredentialSubCommands::Store => {
+                    let credential: DockerCredential =
+                        serde_json::from_str(&input).map_err(|_| {
+                            HimitsuError::CredentialError(
+                                "Docker passed an invalid credential!".to_string(),
+                            )
+                        })?;
+
+                    docker_credential::store_credential(&credential, password)?;
+                }
+            }
+        }
+        SubCommands::Identity => {
+            identity::get_or_create_identity(password)?;
 
-                encrypt::encrypt_secret(&new_anatomy, password, new_secret.unwrap_or(secret))?;
+            if let Some(public_key) = identity::get_public_key()? {
+                println!(
+                    "\n{}\n\n{}\n",
+                    Style::new().bold().paint(
+                        "Share this public key with others so they can share secrets with you:"
+                    ),
+                    Color::Fixed(172)
+                        .bold()
+                        .paint(identity::encode_recipient(&public_key)?)
+                );
             }
         }
-        SubCommands::Remove { label } => {
+        SubCommands::KubeCredential { label } => {
+            let lookup_match = credentials::find_labeled_secret(label, password)?;
+            let secret = credentials::use_labeled_secret(&lookup_match, password)?;
+
+            protocol::write_output(&serde_json::to_string(&ExecCredential::parse(
+                &secret,
+                &lookup_match.anatomy,
+                kube_credential::get_api_version(env::var("KUBERNETES_EXEC_INFO").ok().as_deref()),
+            )?)?)?;
+        }
+        SubCommands::Log => {
+            let vault_dir = check_git_backed_vault()?;
+
+            for entry in git::get_history(&vault_dir)? {
+                println!(
+                    "{}  {}  {}",
+                    Color::Yellow.bold().paint(entry.revision),
+                    Color::Green.bold().paint(entry.date),
+                    Style::new().bold().paint(entry.message)
+                );
+            }
+        }
+        SubCommands::Merge {
+            interactive,
+            vault_dir,
+        } => {
+            let (other_lookup_table, other_password) =
+                merge::unlock_other_vault(vault_dir, password)?;
+
+            let report = merge::merge_vaults(
+                vault_dir,
+                &other_lookup_table,
+                &other_password,
+                password,
+                *interactive,
+            )?;
+
+            log::record_event(LogAction::Merge, None);
+            git::record_vault_change("merge")?;
+
+            merge::print_merge_report(&report);
+        }
+        SubCommands::Receive { file } => {
+            let hash_id = transfer::receive_secret(file, password)?;
+            log::record_event(
+                LogAction::Add,
+                secure::decrypt_lookup_table(password)?
+                    .table
+                    .get(&hash_id)
+                    .map(log::describe_secret),
+            );
+            git::record_change("add", &hash_id)?;
+        }
+        SubCommands::Recovery {
+            recovery_subcommand,
+        } => match recovery_subcommand {
+            RecoverySubCommands::Restore => run_recovery_restore()?,
+            RecoverySubCommands::Split {
+                qr,
+                shares,
+                threshold,
+            } => {
+                let encoded_shares =
+                    recovery::create_recovery_shares(password, *shares, *threshold)?;
+                git::record_vault_change("create recovery shares")?;
+
+                println!(
+                    "\n{}\n",
+                    Color::Fixed(172).bold().paint(format!(
+                        "Any {threshold} of these {shares} shares recover your vault. Store each share in a different place."
+                    ))
+                );
+
+                for (index, encoded_share) in encoded_shares.iter().enumerate() {
+                    println!(
+                        "{} {}",
+                        Style::new()
+                            .bold()
+                            .paint(format!("Share {}/{}:", index + 1, shares)),
+                        encoded_share
+                    );
+
+                    if *qr {
+                        println!("{}", recovery::render_qr_code(encoded_share)?);
+                    }
+                }
+
+                println!();
+            }
+        },
+        SubCommands::Remove { label, regex } => {
             if !closet::check_for_skeletons()? {
                 return Err(HimitsuError::NoSecretsError);
             }
 
             let label = utils::run_get_label(label)?;
-            let found_matches =
-                lookup_utils::search_in_lookup_table(LookupMode::Search(label), password)?;
+            let found_matches = lookup_utils::search_in_lookup_table(
+                LookupMode::Search(label, get_text_mode(*regex)),
+                password,
+            )?;
 
             if found_matches.is_empty() {
                 let list_all_secrets = utils::run_confirmation_prompt(
@@ -184,6 +1315,11 @@ pub fn run_subcommands(password: &str, subcommand: &SubCommands) -> Result<(), H
                             password,
                             RemovalEvent::Remove,
                         )?;
+                        log::record_event(
+                            LogAction::Remove,
+                            Some(log::describe_secret(&lookup_match.anatomy)),
+                        );
+                        git::record_change("remove", &lookup_match.hash)?;
                     } else {
                         println!("\n{}\n", Color::Red.bold().paint("GOODBYE."));
                     }
@@ -201,19 +1337,287 @@ pub fn run_subcommands(password: &str, subcommand: &SubCommands) -> Result<(), H
                         password,
                         RemovalEvent::Remove,
                     )?;
+                    log::record_event(
+                        LogAction::Remove,
+                        Some(log::describe_secret(&lookup_match.anatomy)),
+                    );
+                    git::record_change("remove", &lookup_match.hash)?;
                 } else {
                     println!("\n{}\n", Color::Red.bold().paint("GOODBYE."));
                 }
             }
         }
-        SubCommands::Use { label } => {
+        SubCommands::Restore { revision } => {
+            let vault_dir = check_git_backed_vault()?;
+
+            if utils::run_confirmation_prompt(&format!(
+                "Are you sure you want to roll your whole vault back to {revision}?"
+            ))? {
+                git::restore_revision(&vault_dir, revision)?;
+                log::record_event(LogAction::Restore, None);
+
+                println!(
+                    "{}",
+                    Color::Green
+                        .bold()
+                        .paint(format!("Restored your vault to {revision}."))
+                );
+            } else {
+                println!("\n{}\n", Color::Red.bold().paint("GOODBYE."));
+            }
+        }
+        SubCommands::Share {
+            label,
+            output,
+            regex,
+            to,
+        } => {
+            let recipient = identity::parse_recipient(to)?;
+
+            if let Some(lookup_match) = find_secret(label, *regex, password)? {
+                let passphrase =
+                    protect::run_unlock_protected_secret(&lookup_match.anatomy, password)?;
+
+                transfer::share_secret(
+                    &lookup_match,
+                    output,
+                    password,
+                    passphrase.as_deref(),
+                    &recipient,
+                )?;
+                modify::update_last_accessed(&lookup_match.hash, password)?;
+                log::record_event(
+                    LogAction::Share,
+                    Some(log::describe_secret(&lookup_match.anatomy)),
+                );
+            } else {
+                println!("\n{}\n", Color::Red.bold().paint("GOODBYE."));
+            }
+        }
+        SubCommands::SecretService { category } => {
+            let keyring = Keyring::load(
+                password,
+                &category.as_deref().unwrap_or("unclassified").to_lowercase(),
+            )?;
+
+            println!(
+                "\n{}\n\n{}\n",
+                Color::Green.bold().paint(format!(
+                    "Serving {} secret{} in {} collection{} as {}.",
+                    keyring.items.len(),
+                    if keyring.items.len() == 1 { "" } else { "s" },
+                    keyring.categories.len(),
+                    if keyring.categories.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    secret_keyring::SERVICE_NAME
+                )),
+                Style::new()
+                    .dimmed()
+                    .paint("Press CTRL+C to stop the provider.")
+            );
+
+            secret_service::serve(keyring)?;
+        }
+        SubCommands::Serve { socket } => {
+            let mut registry =
+                api_tokens::TokenRegistry::open(&api_tokens::get_tokens_path()?, password)?;
+            let token_count = registry.get_tokens(password)?.len();
+            if token_count == 0 {
+                return Err(HimitsuError::ApiError(
+                    "No API tokens were created! Create one with `hmu api-token add` first."
+                        .to_string(),
+                ));
+            }
+
+            let socket_path = api_server::get_socket_path(socket)?;
+
+            println!(
+                "\n{}\n\n{}\n\n{}\n",
+                Color::Green.bold().paint(format!(
+                    "Serving the API to {} application{} on {}. Send one JSON request per line:",
+                    token_count,
+                    if token_count == 1 { "" } else { "s" },
+                    socket_path.display()
+                )),
+                Color::Fixed(172).bold().paint(
+                    r#"{"id": 1, "token": "hmu_...", "method": "get", "params": {"label": "..."}}"#
+                ),
+                Style::new()
+                    .dimmed()
+                    .paint("Press CTRL+C to stop the server.")
+            );
+
+            api_server::serve(&socket_path, registry, password.to_string())?;
+        }
+        SubCommands::SshAgent {
+            category,
+            confirm,
+            socket,
+            tags,
+        } => {
+            let keys =
+                ssh_agent::load_keys(category, tags.as_deref().unwrap_or_default(), password)?;
+            if keys.is_empty() {
+                return Err(HimitsuError::SshError(
+                    "No SSH keys were found! Store OpenSSH private keys in the `ssh` category, or choose keys with --category or --tags.".to_string(),
+                ));
+            }
+
+            let socket_path = ssh_agent::get_socket_path(socket)?;
+
+            println!();
+            for loaded_key in &keys {
+                println!(
+                    "🔑 {} {} {}",
+                    Style::new().bold().paint(&loaded_key.secret),
+                    Style::new()
+                        .dimmed()
+                        .paint(loaded_key.key.get_fingerprint()),
+                    Style::new().dimmed().paint(format!(
+                        "({}, {})",
+                        loaded_key.key.get_key_type(),
+                        loaded_key.key.comment
+                    ))
+                );
+            }
+            println!(
+                "\n{}\n\n{}\n\n{}\n",
+                Color::Green.bold().paint(format!(
+                    "Serving {} key{}. Point SSH at the agent with:",
+                    keys.len(),
+                    if keys.len() == 1 { "" } else { "s" }
+                )),
+                Color::Fixed(172)
+                    .bold()
+                    .paint(format!("export SSH_AUTH_SOCK={}", socket_path.display())),
+                Style::new()
+                    .dimmed()
+                    .paint("Press CTRL+C to stop the agent.")
+            );
+
+            let password = password.to_string();
+            let confirm = *confirm;
+            ssh_agent::serve(&socket_path, keys, move |loaded_key| {
+                if confirm
+                    && !utils::run_confirmation_prompt(&format!(
+                        "Sign with {}?",
+                        loaded_key.secret
+                    ))?
+                {
+                    return Ok(false);
+                }
+
+                modify::update_last_accessed(&loaded_key.hash_id, &password)?;
+                log::record_event(LogAction::Use, Some(loaded_key.secret.clone()));
+
+                println!(
+                    "{}",
+                    Style::new()
+                        .dimmed()
+                        .paint(format!("Signed with {}.", loaded_key.secret))
+                );
+
+                Ok(true)
+            })?;
+        }
+        SubCommands::Team { team_subcommand } => {
+            let identity = identity::get_or_create_identity(password)?;
+
+            match team_subcommand {
+                TeamSubCommands::AddMember {
+                    key,
+                    name,
+                    team_dir,
+                } => {
+                    team::add_member(team_dir, &identity, name, &identity::parse_recipient(key)?)?;
+
+                    println!(
+                        "{}",
+                        Color::Green
+                            .bold()
+                            .paint(format!("Added {name} to the team vault."))
+                    );
+                }
+                TeamSubCommands::Init { name, team_dir } => {
+                    team::init_team_vault(team_dir, name, &PublicKey::from(&identity))?;
+
+                    println!(
+                        "{}",
+                        Color::Green.bold().paint(format!(
+                            "Created a team vault in {}. Use it with `hmu --team {} <SUBCOMMAND>`.",
+                            team_dir.display(),
+                            team_dir.display()
+                        ))
+                    );
+                }
+                TeamSubCommands::Members { team_dir } => {
+                    for member in team::get_members(team_dir)? {
+                        println!(
+                            "{}  {}",
+                            Style::new().bold().paint(&member.name),
+                            Color::Fixed(172).paint(identity::encode_recipient(
+                                &PublicKey::from(member.public_key)
+                            )?)
+                        );
+                    }
+                }
+                TeamSubCommands::RemoveMember { name, team_dir } => {
+                    if utils::run_confirmation_prompt(&format!(
+                        "Are you sure you want to remove {name}? The team vault will be re-encrypted."
+                    ))? {
+                        team::remove_member(team_dir, &identity, name)?;
+
+                        println!(
+                            "{}",
+                            Color::Green.bold().paint(format!(
+                                "Removed {name} and re-encrypted the team vault."
+                            ))
+                        );
+                    } else {
+                        println!("\n{}\n", Color::Red.bold().paint("GOODBYE."));
+                    }
+                }
+            }
+        }
+        SubCommands::Totp { label, regex } => {
+            if let Some(lookup_match) = find_secret(label, *regex, password)? {
+                if lookup_match.anatomy.secret_type != SecretType::Totp {
+                    return Err(HimitsuError::TotpError(format!(
+                        "\"{}\" is not a TOTP secret!",
+                        lookup_match.anatomy.label
+                    )));
+                }
+
+                use_stored_secret(&lookup_match, password)?;
+            } else {
+                println!("\n{}\n", Color::Red.bold().paint("GOODBYE."));
+            }
+        }
+        SubCommands::Tui {
+            lock_after,
+            reveal_for,
+        } => {
+            let lock_after = dates::parse_duration(lock_after)
+                .and_then(|duration| duration.to_std().ok())
+                .ok_or_else(|| {
+                    HimitsuError::TuiError(format!("\"{lock_after}\" is not a valid duration!"))
+                })?;
+
+            tui::run(password, lock_after, Duration::from_secs(*reveal_for))?;
+        }
+        SubCommands::Use { label, regex } => {
             if !closet::check_for_skeletons()? {
                 return Err(HimitsuError::NoSecretsError);
             }
 
             let label = utils::run_get_label(label)?;
-            let found_matches =
-                lookup_utils::search_in_lookup_table(LookupMode::Search(label), password)?;
+            let found_matches = lookup_utils::search_in_lookup_table(
+                LookupMode::Search(label, get_text_mode(*regex)),
+                password,
+            )?;
 
             if found_matches.is_empty() {
                 let list_all_secrets = utils::run_confirmation_prompt(
@@ -226,29 +1630,284 @@ pub fn run_subcommands(password: &str, subcommand: &SubCommands) -> Result<(), H
 
                     let lookup_match = use_secret::run_select_secret(found_matches)?;
 
-                    modify::update_last_accessed(&lookup_match.hash, password)?;
-
-                    let _ = decrypt::decrypt_secret(
-                        DecryptionMode::UseSecret,
-                        &lookup_match.hash,
-                        password,
-                    )?;
+                    use_stored_secret(&lookup_match, password)?;
                 } else {
                     println!("\n{}\n", Color::Red.bold().paint("GOODBYE."));
                 }
             } else {
                 let lookup_match = use_secret::run_select_secret(found_matches)?;
 
-                modify::update_last_accessed(&lookup_match.hash, password)?;
+                use_stored_secret(&lookup_match, password)?;
+            }
+        }
+    }
 
-                let _ = decrypt::decrypt_secret(
-                    DecryptionMode::UseSecret,
-                    &lookup_match.hash,
-                    password,
-                )?;
+    Ok(())
+}
+
+/// Print the user's settings.
+fn print_settings() -> Result<(), HimitsuError> {
+    let user_config = config::get_config()?;
+
+    println!(
+        "{} {}",
+        Style::new().bold().paint("Keyfile:"),
+        Color::Fixed(172).bold().paint(
+            user_config
+                .keyfile
+                .map_or("None".to_string(), |keyfile| keyfile.display().to_string())
+        )
+    );
+    println!(
+        "{} {}",
+        Style::new().bold().paint("Wipe after:"),
+        Color::Fixed(172).bold().paint(
+            user_config
+                .wipe_after
+                .map_or("Never".to_string(), |attempts| format!(
+                    "{attempts} failed login attempts"
+                ))
+        )
+    );
+
+    Ok(())
+}
+
+/// Copy a stored secret to the clipboard. TOTP secrets copy their current code instead, so the
+/// seed is never revealed. The use is only recorded once the secret is unlocked and decrypted.
+fn use_stored_secret(lookup_match: &LookupMatch, password: &str) -> Result<(), HimitsuError> {
+    expiry::warn_if_expired(&lookup_match.anatomy);
+    let passphrase = protect::run_unlock_protected_secret(&lookup_match.anatomy, password)?;
+
+    match (&lookup_match.anatomy.secret_type, passphrase) {
+        (SecretType::Password, None) => {
+            decrypt::decrypt_secret(DecryptionMode::UseSecret, &lookup_match.hash, password)?;
+        }
+        (SecretType::Password, Some(passphrase)) => {
+            let secret =
+                decrypt::decrypt_secret(DecryptionMode::EditSecret, &lookup_match.hash, password)?
+                    .unwrap();
+
+            clipboard::set_clipboard(decrypt::open_with_passphrase(&passphrase, &secret)?)?;
+
+            println!(
+                "{}",
+                Color::Green
+                    .bold()
+                    .paint("\n📋 The secret is copied to your clipboard.")
+            );
+        }
+        (SecretType::Totp, passphrase) => {
+            let mut secret = decrypt::decrypt_secret_in(
+                &decrypt::get_secret_hash_path(&lookup_match.hash)?,
+                password,
+            )?;
+            if let Some(passphrase) = passphrase {
+                secret = decrypt::open_with_passphrase(&passphrase, &secret)?;
             }
+            let totp_secret: TotpSecret = serde_json::from_str(&secret)?;
+
+            let unix_time = totp::get_unix_time();
+            let code = totp::generate_code(&totp_secret, unix_time)?;
+
+            clipboard::set_clipboard(code.clone())?;
+
+            println!(
+                "{} {}",
+                Color::Green
+                    .bold()
+                    .paint(format!("📋 {code} is copied to your clipboard.")),
+                Style::new().dimmed().paint(format!(
+                    "(valid for {} more seconds)",
+                    totp::get_seconds_remaining(&totp_secret, unix_time)
+                ))
+            );
         }
     }
 
+    modify::update_last_accessed(&lookup_match.hash, password)?;
+    log::record_event(
+        LogAction::Use,
+        Some(log::describe_secret(&lookup_match.anatomy)),
+    );
+
     Ok(())
 }
+
+/// Decrypt a stored secret, ask which of its attributes to edit, and store the edited secret in
+/// its place.
+pub fn edit_stored_secret(lookup_match: &LookupMatch, password: &str) -> Result<(), HimitsuError> {
+    let passphrase = protect::run_unlock_protected_secret(&lookup_match.anatomy, password)?;
+
+    let mut secret =
+        decrypt::decrypt_secret(DecryptionMode::EditSecret, &lookup_match.hash, password)?.unwrap();
+    if let Some(passphrase) = &passphrase {
+        secret = decrypt::open_with_passphrase(passphrase, &secret)?;
+    }
+
+    let update_targets = edit::run_edit_targets()?;
+    let changes_protection = update_targets.contains(&"Protection");
+
+    let mut new_anatomy = lookup_match.anatomy.clone();
+    let mut new_secret = None;
+
+    for target in update_targets {
+        match target {
+            "Category" => edit::run_edit_category(&mut new_anatomy)?,
+            "Expiry" => edit::run_edit_expiry(&mut new_anatomy)?,
+            "Label" => edit::run_edit_label(&mut new_anatomy)?,
+            "Login" => edit::run_edit_login(&mut new_anatomy)?,
+            "Protection" => protect::run_edit_protection(&mut new_anatomy)?,
+            "Secret" => new_secret = Some(edit::run_edit_secret(&new_anatomy.secret_type)?),
+            "Tags" => edit::run_edit_tags(&mut new_anatomy)?,
+            _ => {}
+        }
+    }
+
+    new_anatomy.last_modified = Some(dates::get_timestamp());
+    if new_secret.is_some() {
+        new_anatomy.last_rotated = new_anatomy.last_modified.clone();
+    }
+    if new_anatomy.secret_type == SecretType::Totp {
+        new_secret = new_secret
+            .map(|seed| totp::replace_seed(&secret, &seed))
+            .transpose()?;
+    }
+
+    let mut new_secret = new_secret.unwrap_or(secret);
+    if new_anatomy.protection == Protection::Passphrase {
+        let passphrase = match passphrase {
+            Some(passphrase) if !changes_protection => passphrase,
+            _ => protect::run_new_passphrase()?,
+        };
+
+        new_secret = encrypt::seal_with_passphrase(&passphrase, &new_secret)?;
+    }
+
+    modify::remove_in_lookup_table(&lookup_match.hash, password, RemovalEvent::Replace)?;
+
+    let hash_id = encrypt::encrypt_secret(&new_anatomy, password, new_secret)?;
+    log::record_event(LogAction::Edit, Some(log::describe_secret(&new_anatomy)));
+    git::record_change("update", &hash_id)?;
+
+    Ok(())
+}
+
+/// Edit a stored secret's value in the user's editor. The secret's value is only replaced if it
+/// was changed.
+pub fn edit_secret_in_editor(
+    lookup_match: &LookupMatch,
+    password: &str,
+) -> Result<(), HimitsuError> {
+    if lookup_match.anatomy.secret_type == SecretType::Totp {
+        return Err(HimitsuError::LookupError(
+            "TOTP seeds cannot be edited in an editor. Use `hmu edit` without --editor instead!"
+                .to_string(),
+        ));
+    }
+
+    let passphrase = protect::run_unlock_protected_secret(&lookup_match.anatomy, password)?;
+
+    let mut secret =
+        decrypt::decrypt_secret(DecryptionMode::EditSecret, &lookup_match.hash, password)?.unwrap();
+    if let Some(passphrase) = &passphrase {
+        secret = decrypt::open_with_passphrase(passphrase, &secret)?;
+    }
+
+    let mut new_secret = match editor::run_editor(&secret)? {
+        Some(new_secret) if !new_secret.is_empty() => new_secret,
+        Some(_) => {
+            println!(
+                "{}",
+                Color::Fixed(172)
+                    .bold()
+                    .paint("The secret was left empty, so it was not changed.")
+            );
+
+            return Ok(());
+        }
+        None => {
+            println!(
+                "{}",
+                Style::new().dimmed().paint("The secret was not changed.")
+            );
+
+            return Ok(());
+        }
+    };
+
+    let mut new_anatomy = lookup_match.anatomy.clone();
+    new_anatomy.last_modified = Some(dates::get_timestamp());
+    new_anatomy.last_rotated = new_anatomy.last_modified.clone();
+
+    if let Some(passphrase) = &passphrase {
+        new_secret = encrypt::seal_with_passphrase(passphrase, &new_secret)?;
+    }
+
+    modify::remove_in_lookup_table(&lookup_match.hash, password, RemovalEvent::Replace)?;
+
+    let hash_id = encrypt::encrypt_secret(&new_anatomy, password, new_secret)?;
+    log::record_event(LogAction::Edit, Some(log::describe_secret(&new_anatomy)));
+    git::record_change("update", &hash_id)?;
+
+    Ok(())
+}
+
+/// Parse how long the agent keeps the vault unlocked.
+fn parse_agent_timeout(timeout: &str) -> Result<std::time::Duration, HimitsuError> {
+    dates::parse_duration(timeout)
+        .and_then(|duration| duration.to_std().ok())
+        .ok_or_else(|| HimitsuError::AgentError(format!("\"{timeout}\" is not a valid duration!")))
+}
+
+/// Check whether the vault is a git repository. Returns the vault directory if it is.
+fn check_git_backed_vault() -> Result<PathBuf, HimitsuError> {
+    let vault_dir = vault::get_vault_dir()?;
+
+    if git::is_repository(&vault_dir) {
+        Ok(vault_dir)
+    } else {
+        Err(HimitsuError::GitError(
+            "Your vault is not a git repository! Run `hmu git init` first.".to_string(),
+        ))
+    }
+}
+
+/// Get how a query's text is matched from the `--regex` flag.
+fn get_text_mode(regex: bool) -> TextMode {
+    if regex {
+        TextMode::Regex
+    } else {
+        TextMode::Fuzzy
+    }
+}
+
+/// Search for a secret by its label and ask the user to select a match. If there are no matches,
+/// ask whether to list all stored secrets instead. Returns `None` if the user chose not to.
+fn find_secret(
+    label: &[String],
+    regex: bool,
+    password: &str,
+) -> Result<Option<LookupMatch>, HimitsuError> {
+    if !closet::check_for_skeletons()? {
+        return Err(HimitsuError::NoSecretsError);
+    }
+
+    let label = utils::run_get_label(label)?;
+    let found_matches = lookup_utils::search_in_lookup_table(
+        LookupMode::Search(label, get_text_mode(regex)),
+        password,
+    )?;
+
+    if found_matches.is_empty() {
+        if !utils::run_confirmation_prompt("No matches were found. List all stored secrets?")? {
+            return Ok(None);
+        }
+
+        let found_matches = lookup_utils::search_in_lookup_table(LookupMode::GetAll, password)?;
+
+        Ok(Some(use_secret::run_select_secret(found_matches)?))
+    } else {
+        Ok(Some(use_secret::run_select_secret(found_matches)?))
+    }
+}
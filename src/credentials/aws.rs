@@ -0,0 +1,151 @@
+//! Contains the output of AWS's `credential_process` setting.
+//!
+//! The AWS CLI and SDKs run `hmu aws-credentials <LABEL>` and read the credentials it prints as
+//! JSON. The secret's value holds the credentials, either as JSON with the same fields AWS reads
+//! (`AccessKeyId`, `SecretAccessKey`, `SessionToken`, and `Expiration`) or as the `key = value`
+//! lines used in `~/.aws/credentials`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{errors::HimitsuError, models::metadata::Anatomy, utils::dates};
+
+/// The version of the `credential_process` output format.
+pub const CREDENTIAL_PROCESS_VERSION: u8 = 1;
+
+/// Contains credentials in `credential_process`'s output format.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct AwsCredentials {
+    /// The output format's version.
+    #[serde(default, rename = "Version")]
+    pub version: u8,
+    /// The access key ID.
+    #[serde(alias = "aws_access_key_id", rename = "AccessKeyId")]
+    pub access_key_id: String,
+    /// The secret access key.
+    #[serde(alias = "aws_secret_access_key", rename = "SecretAccessKey")]
+    pub secret_access_key: String,
+    /// The session token of temporary credentials.
+    #[serde(
+        alias = "aws_session_token",
+        default,
+        rename = "SessionToken",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub session_token: Option<String>,
+    /// When temporary credentials expire, as an RFC 3339 timestamp.
+    #[serde(
+        default,
+        rename = "Expiration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expiration: Option<String>,
+}
+
+impl AwsCredentials {
+    /// Parse the credentials stored in a secret. Credentials without their own expiration expire
+    /// with the secret, if it expires.
+    pub fn parse(secret: &str, anatomy: &Anatomy) -> Result<Self, HimitsuError> {
+        let secret = secret.trim();
+
+        let credentials = if secret.starts_with('{') {
+            serde_json::from_str(secret).ok()
+        } else {
+            serde_json::from_value(Value::Object(
+                secret
+                    .lines()
+                    .filter_map(|line| line.split_once('='))
+                    .map(|(key, value)| {
+                        (
+                            key.trim().to_string(),
+                            Value::String(value.trim().to_string()),
+                        )
+                    })
+                    .collect::<Map<String, Value>>(),
+            ))
+            .ok()
+        };
+
+        let mut credentials: AwsCredentials = credentials.ok_or_else(|| {
+            HimitsuError::CredentialError(format!(
+                "\"{}\" does not contain AWS credentials! Store them as JSON with an AccessKeyId and a SecretAccessKey.",
+                anatomy.label
+            ))
+        })?;
+
+        credentials.version = CREDENTIAL_PROCESS_VERSION;
+        if credentials.expiration.is_none() {
+            credentials.expiration = anatomy
+                .expires_at
+                .as_deref()
+                .and_then(dates::parse_timestamp)
+                .and_then(|expires_at| dates::format_rfc3339(&expires_at));
+        }
+
+        Ok(credentials)
+    }
+}
+
+#[cfg(test)]
+pub mod test_aws {
+    use super::*;
+
+    /// Test whether credentials are parsed from JSON and from `~/.aws/credentials` lines.
+    #[test]
+    fn test_parse_credentials() {
+        let anatomy = Anatomy::create_from("cloud".to_string(), "aws".to_string(), Vec::new());
+
+        let credentials = AwsCredentials::parse(
+            r#"{"AccessKeyId": "AKIA", "SecretAccessKey": "secret", "SessionToken": "token"}"#,
+            &anatomy,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_string(&credentials).unwrap(),
+            r#"{"Version":1,"AccessKeyId":"AKIA","SecretAccessKey":"secret","SessionToken":"token"}"#
+        );
+
+        assert_eq!(
+            AwsCredentials::parse(
+                "[default]\naws_access_key_id = AKIA\naws_secret_access_key = secret\n",
+                &anatomy
+            )
+            .unwrap(),
+            AwsCredentials {
+                version: CREDENTIAL_PROCESS_VERSION,
+                access_key_id: "AKIA".to_string(),
+                secret_access_key: "secret".to_string(),
+                session_token: None,
+                expiration: None,
+            }
+        );
+
+        assert!(AwsCredentials::parse("hunter2", &anatomy).is_err());
+    }
+
+    /// Test whether credentials without their own expiration expire with the secret.
+    #[test]
+    fn test_expiration_from_secret() {
+        let mut anatomy = Anatomy::create_from("cloud".to_string(), "aws".to_string(), Vec::new());
+        anatomy.expires_at = Some("01-31-2022 12:00:00".to_string());
+
+        let credentials = AwsCredentials::parse(
+            r#"{"AccessKeyId": "AKIA", "SecretAccessKey": "secret"}"#,
+            &anatomy,
+        )
+        .unwrap();
+        assert!(credentials
+            .expiration
+            .is_some_and(|expiration| expiration.starts_with("2022-01-")));
+
+        let credentials = AwsCredentials::parse(
+            r#"{"AccessKeyId": "AKIA", "SecretAccessKey": "secret", "Expiration": "2030-01-01T00:00:00Z"}"#,
+            &anatomy,
+        )
+        .unwrap();
+        assert_eq!(
+            credentials.expiration.as_deref(),
+            Some("2030-01-01T00:00:00Z")
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Contains Docker's credential helper protocol.
+//!
+//! Docker runs `docker-credential-hmu <ACTION>` and passes either a registry's server URL or a
+//! JSON credential on stdin. Registry credentials are stored in the `docker` category, with the
+//! server URL as both the label and the URL.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit::log,
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    lookup::{
+        modify::{self, RemovalEvent},
+        secure,
+    },
+    models::{
+        log::LogAction,
+        metadata::{Anatomy, LookupTable, Protection, SecretType},
+    },
+    prompts::protect,
+    utils::{dates, git},
+};
+
+/// The category registry credentials are stored in.
+pub const DOCKER_CATEGORY: &str = "docker";
+/// The message Docker expects when no credential is stored for a registry.
+pub const NOT_FOUND_MESSAGE: &str = "credentials not found in native keychain";
+
+/// Contains a registry credential in Docker's credential helper protocol.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct DockerCredential {
+    /// The registry's password or token.
+    #[serde(rename = "Secret")]
+    pub secret: String,
+    /// The registry's server URL.
+    #[serde(rename = "ServerURL")]
+    pub server_url: String,
+    /// The username.
+    #[serde(rename = "Username")]
+    pub username: String,
+}
+
+/// Find the secret stored for a registry.
+pub fn find_secret<'a>(
+    lookup_table: &'a LookupTable,
+    server_url: &str,
+) -> Option<(&'a String, &'a Anatomy)> {
+    lookup_table.table.iter().find(|(_, anatomy)| {
+        anatomy.category == DOCKER_CATEGORY
+            && anatomy.secret_type == SecretType::Password
+            && anatomy.url.as_deref() == Some(server_url)
+    })
+}
+
+/// List every registry with a stored credential, mapped to its username.
+pub fn list_credentials(lookup_table: &LookupTable) -> BTreeMap<String, String> {
+    lookup_table
+        .table
+        .values()
+        .filter(|anatomy| {
+            anatomy.category == DOCKER_CATEGORY && anatomy.secret_type == SecretType::Password
+        })
+        .filter_map(|anatomy| {
+            anatomy
+                .url
+                .clone()
+                .map(|server_url| (server_url, anatomy.username.clone().unwrap_or_default()))
+        })
+        .collect()
+}
+
+/// Look up the credential stored for a registry. Returns `None` if there is none.
+pub fn get_credential(
+    server_url: &str,
+    password: &str,
+) -> Result<Option<DockerCredential>, HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+
+    match find_secret(&lookup_table, server_url) {
+        Some((hash_id, anatomy)) => {
+            let secret = protect::run_decrypt_protected_secret(hash_id, anatomy, password)?;
+
+            modify::update_last_accessed(hash_id, password)?;
+            log::record_event(LogAction::Use, Some(log::describe_secret(anatomy)));
+
+            Ok(Some(DockerCredential {
+                secret,
+                server_url: server_url.to_string(),
+                username: anatomy.username.clone().unwrap_or_default(),
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Store a registry credential, replacing the credential stored for the same registry. Protected
+/// secrets are never overwritten.
+pub fn store_credential(credential: &DockerCredential, password: &str) -> Result<(), HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+
+    let (mut anatomy, action) = match find_secret(&lookup_table, &credential.server_url) {
+        Some((_, anatomy)) if anatomy.protection != Protection::None => {
+            return Err(HimitsuError::CredentialError(format!(
+                "The credential for {} is protected and cannot be overwritten!",
+                credential.server_url
+            )));
+        }
+        Some((hash_id, anatomy)) => {
+            let current_secret =
+                decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(hash_id)?, password)?;
+            if current_secret == credential.secret
+                && anatomy.username.as_deref() == Some(credential.username.as_str())
+            {
+                return Ok(());
+            }
+
+            let mut new_anatomy = anatomy.clone();
+            new_anatomy.last_modified = Some(dates::get_timestamp());
+            if current_secret != credential.secret {
+                new_anatomy.last_rotated = new_anatomy.last_modified.clone();
+            }
+
+            modify::remove_in_lookup_table(hash_id, password, RemovalEvent::Replace)?;
+
+            (new_anatomy, LogAction::Edit)
+        }
+        None => (
+            Anatomy::create_from(
+                DOCKER_CATEGORY.to_string(),
+                credential.server_url.clone(),
+                Vec::new(),
+            ),
+            LogAction::Add,
+        ),
+    };
+
+    anatomy.url = Some(credential.server_url.clone());
+    anatomy.username = Some(credential.username.clone());
+
+    let hash_id = encrypt::encrypt_secret(&anatomy, password, credential.secret.clone())?;
+    log::record_event(action, Some(log::describe_secret(&anatomy)));
+    git::record_change(
+        if action == LogAction::Add {
+            "add"
+        } else {
+            "update"
+        },
+        &hash_id,
+    )
+}
+
+/// Remove the credential stored for a registry. Protected secrets are never removed.
+pub fn erase_credential(server_url: &str, password: &str) -> Result<(), HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+
+    match find_secret(&lookup_table, server_url) {
+        Some((_, anatomy)) if anatomy.protection != Protection::None => {
+            Err(HimitsuError::CredentialError(format!(
+                "The credential for {server_url} is protected and cannot be removed!"
+            )))
+        }
+        Some((hash_id, anatomy)) => {
+            modify::remove_in_lookup_table(hash_id, password, RemovalEvent::Remove)?;
+            log::record_event(LogAction::Remove, Some(log::describe_secret(anatomy)));
+            git::record_change("remove", hash_id)
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+pub mod test_docker {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    /// Test whether registries are found by their server URL and listed with their usernames.
+    #[test]
+    fn test_find_and_list_credentials() {
+        let mut lookup_table = LookupTable {
+            removed: Vec::new(),
+            table: HashMap::new(),
+        };
+        for (hash_id, category, server_url) in [
+            ("hub", DOCKER_CATEGORY, "https://index.docker.io/v1/"),
+            ("ghcr", DOCKER_CATEGORY, "ghcr.io"),
+            ("git", "git", "https://github.com"),
+        ] {
+            lookup_table.table.insert(
+                hash_id.to_string(),
+                Anatomy {
+                    url: Some(server_url.to_string()),
+                    username: Some(format!("{hash_id}-user")),
+                    ..Anatomy::create_for_test(category, server_url, "01-01-2022 00:00:00")
+                },
+            );
+        }
+
+        assert_eq!(
+            find_secret(&lookup_table, "ghcr.io").map(|(hash_id, _)| hash_id.as_str()),
+            Some("ghcr")
+        );
+        assert!(find_secret(&lookup_table, "https://github.com").is_none());
+        assert_eq!(
+            list_credentials(&lookup_table),
+            BTreeMap::from([
+                ("ghcr.io".to_string(), "ghcr-user".to_string()),
+                (
+                    "https://index.docker.io/v1/".to_string(),
+                    "hub-user".to_string()
+                ),
+            ])
+        );
+    }
+
+    /// Test whether credentials use the field names Docker expects.
+    #[test]
+    fn test_credential_json() {
+        let credential: DockerCredential = serde_json::from_str(
+            r#"{"ServerURL":"ghcr.io","Username":"octocat","Secret":"token"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(credential.server_url, "ghcr.io");
+        assert_eq!(
+            serde_json::to_string(&credential).unwrap(),
+            r#"{"Secret":"token","ServerURL":"ghcr.io","Username":"octocat"}"#
+        );
+    }
+}
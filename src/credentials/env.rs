@@ -0,0 +1,318 @@
+//! Contains the shell statements printed by `hmu env`, which export secrets as environment
+//! variables with `eval "$(hmu env ...)"` or from direnv's `.envrc`.
+//!
+//! Every value is written as a single-quoted string with the shell's own escaping rules, so no
+//! character in a secret can end the string early and run as shell code.
+
+use std::{env, fs, path::Path, str::FromStr};
+
+use ansi_term::Style;
+
+use crate::{
+    errors::HimitsuError,
+    lookup::{secure, utils as lookup_utils},
+    models::metadata::{LookupMatch, SecretType},
+    prompts::use_secret,
+};
+
+/// Contains variants for the shells `hmu env` writes statements for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    /// POSIX shells such as Bash.
+    Bash,
+    /// The fish shell.
+    Fish,
+    /// Powershell.
+    Powershell,
+    /// Zsh, which uses the same statements as Bash.
+    Zsh,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(shell: &str) -> Result<Self, Self::Err> {
+        match shell.to_lowercase().as_str() {
+            "bash" | "sh" => Ok(Shell::Bash),
+            "fish" => Ok(Shell::Fish),
+            "powershell" | "pwsh" => Ok(Shell::Powershell),
+            "zsh" => Ok(Shell::Zsh),
+            _ => Err(format!(
+                "\"{shell}\" is not a supported shell! Use bash, zsh, fish, or powershell."
+            )),
+        }
+    }
+}
+
+impl Shell {
+    /// Guess the shell from the `SHELL` environment variable. Defaults to Bash.
+    pub fn detect() -> Self {
+        env::var("SHELL")
+            .ok()
+            .and_then(|shell| {
+                Path::new(&shell)
+                    .file_name()
+                    .and_then(|name| name.to_str()?.parse().ok())
+            })
+            .unwrap_or(Shell::Bash)
+    }
+
+    /// Quote a value as a single-quoted string.
+    pub fn quote(&self, value: &str) -> String {
+        match self {
+            // Nothing is special inside single quotes, so each quote ends the string, adds an
+            // escaped quote, and starts a new string.
+            Shell::Bash | Shell::Zsh => format!("'{}'", value.replace('\'', r"'\''")),
+            // Backslashes and quotes are the only escapes inside fish's single quotes.
+            Shell::Fish => format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'")),
+            // Quotes are escaped by doubling them. Powershell also treats the typographic single
+            // quotes as quotes.
+            Shell::Powershell => {
+                let mut quoted = String::from("'");
+                for character in value.chars() {
+                    if matches!(
+                        character,
+                        '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}'
+                    ) {
+                        quoted.push(character);
+                    }
+                    quoted.push(character);
+                }
+                quoted.push('\'');
+
+                quoted
+            }
+        }
+    }
+
+    /// Get the statement that exports a variable.
+    pub fn export(&self, name: &str, value: &str) -> String {
+        let value = self.quote(value);
+
+        match self {
+            Shell::Bash | Shell::Zsh => format!("export {name}={value}"),
+            Shell::Fish => format!("set -gx {name} {value}"),
+            Shell::Powershell => format!("$env:{name} = {value}"),
+        }
+    }
+
+    /// Get the statement that removes an exported variable.
+    pub fn unset(&self, name: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("unset {name}"),
+            Shell::Fish => format!("set -e {name}"),
+            Shell::Powershell => {
+                format!("Remove-Item Env:{name} -ErrorAction SilentlyContinue")
+            }
+        }
+    }
+}
+
+/// Contains a secret chosen for `hmu env` and the variable it is exported as.
+#[derive(Debug, PartialEq, Eq)]
+pub struct EnvEntry {
+    /// The secret's label.
+    pub label: String,
+    /// The variable's name.
+    pub name: String,
+}
+
+/// Get the variable a secret is exported as from its label, such as `DB_PASSWORD` for
+/// `db-password`.
+pub fn to_variable_name(label: &str) -> String {
+    let name = label
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    if name.starts_with(|character: char| character.is_ascii_digit()) || name.is_empty() {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+/// Check whether a variable name is safe to write into a shell statement.
+pub fn is_valid_variable_name(name: &str) -> bool {
+    name.starts_with(|character: char| character.is_ascii_alphabetic() || character == '_')
+        && name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+}
+
+/// Parse a manifest listing the secrets to export. Each line is either `NAME=LABEL` or a label on
+/// its own, whose variable name is derived from the label. Empty lines and lines starting with `#`
+/// are skipped.
+pub fn parse_manifest(contents: &str) -> Result<Vec<EnvEntry>, HimitsuError> {
+    let mut entries = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let entry = match line.split_once('=') {
+            Some((name, label)) => EnvEntry {
+                label: label.trim().to_string(),
+                name: name.trim().to_string(),
+            },
+            None => EnvEntry {
+                label: line.to_string(),
+                name: to_variable_name(line),
+            },
+        };
+
+        if entry.label.is_empty() || !is_valid_variable_name(&entry.name) {
+            return Err(HimitsuError::CredentialError(format!(
+                "Line {} of the manifest is not `NAME=LABEL` with a valid variable name!",
+                index + 1
+            )));
+        }
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Read a manifest file.
+pub fn read_manifest(manifest: &Path) -> Result<Vec<EnvEntry>, HimitsuError> {
+    parse_manifest(&fs::read_to_string(manifest)?)
+}
+
+/// Find the secrets to export. Secrets are chosen by the manifest's entries and the labels that
+/// were passed, only within `category` if one was passed. If neither names a secret, every secret
+/// in `category` is exported. Labels are matched exactly; you are asked to choose if several
+/// secrets share a label.
+pub fn select_secrets(
+    mut entries: Vec<EnvEntry>,
+    category: Option<&str>,
+    password: &str,
+) -> Result<Vec<(String, LookupMatch)>, HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+    let in_category = |secret_category: &str| {
+        category.is_none_or(|category| secret_category.eq_ignore_ascii_case(category))
+    };
+
+    if entries.is_empty() {
+        let mut labels = lookup_table
+            .table
+            .values()
+            .filter(|anatomy| {
+                in_category(&anatomy.category) && anatomy.secret_type == SecretType::Password
+            })
+            .map(|anatomy| anatomy.label.clone())
+            .collect::<Vec<String>>();
+        labels.sort();
+        labels.dedup();
+
+        entries = labels
+            .into_iter()
+            .map(|label| EnvEntry {
+                name: to_variable_name(&label),
+                label,
+            })
+            .collect();
+    }
+
+    let mut selected: Vec<(String, LookupMatch)> = Vec::new();
+    for entry in entries {
+        if selected.iter().any(|(name, _)| *name == entry.name) {
+            return Err(HimitsuError::CredentialError(format!(
+                "Several secrets would be exported as {}! Name them in a manifest instead.",
+                entry.name
+            )));
+        }
+
+        let found_matches = lookup_utils::rank_matches(
+            lookup_table
+                .table
+                .iter()
+                .filter(|(_, anatomy)| {
+                    anatomy.label == entry.label && in_category(&anatomy.category)
+                })
+                .map(|(hash_id, anatomy)| {
+                    (
+                        0,
+                        (
+                            Style::new().bold().paint(&anatomy.label).to_string(),
+                            LookupMatch::create(anatomy.clone(), hash_id.clone()),
+                        ),
+                    )
+                })
+                .collect(),
+        );
+
+        let lookup_match = match found_matches.len() {
+            0 => {
+                return Err(HimitsuError::CredentialError(format!(
+                    "No secret is labeled \"{}\"!",
+                    entry.label
+                )))
+            }
+            1 => found_matches.into_iter().next().unwrap().1,
+            _ => use_secret::run_select_secret(found_matches)?,
+        };
+
+        selected.push((entry.name, lookup_match));
+    }
+
+    Ok(selected)
+}
+
+#[cfg(test)]
+pub mod test_env {
+    use super::*;
+
+    /// Test whether values cannot break out of their quotes in any shell.
+    #[test]
+    fn test_quote() {
+        let value = "it's $(rm -rf ~) `id` \\ \"x\"\nnext";
+
+        assert_eq!(
+            Shell::Bash.export("TOKEN", value),
+            "export TOKEN='it'\\''s $(rm -rf ~) `id` \\ \"x\"\nnext'"
+        );
+        assert_eq!(
+            Shell::Fish.export("TOKEN", value),
+            "set -gx TOKEN 'it\\'s $(rm -rf ~) `id` \\\\ \"x\"\nnext'"
+        );
+        assert_eq!(
+            Shell::Powershell.export("TOKEN", "it's ‘$x’"),
+            "$env:TOKEN = 'it''s ‘‘$x’’'"
+        );
+        assert_eq!(Shell::Zsh.unset("TOKEN"), "unset TOKEN");
+    }
+
+    /// Test whether manifests and labels become valid variable names.
+    #[test]
+    fn test_parse_manifest() {
+        assert_eq!(to_variable_name("db-password"), "DB_PASSWORD");
+        assert_eq!(to_variable_name("2fa backup"), "_2FA_BACKUP");
+
+        let entries = parse_manifest("# Database\nDATABASE_URL = db url\n\napi-key\n").unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                EnvEntry {
+                    label: "db url".to_string(),
+                    name: "DATABASE_URL".to_string(),
+                },
+                EnvEntry {
+                    label: "api-key".to_string(),
+                    name: "API_KEY".to_string(),
+                },
+            ]
+        );
+
+        assert!(parse_manifest("$(id)=db").is_err());
+        assert!(parse_manifest("TOKEN=").is_err());
+    }
+}
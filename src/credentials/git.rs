@@ -0,0 +1,547 @@
+//! Contains git's credential helper protocol.
+//!
+//! git describes the credential it needs as `key=value` lines on stdin, such as `protocol=https`
+//! and `host=github.com`, and reads the credential back in the same format on stdout. Stored
+//! secrets are matched on their URL and username (see `hmu add --url --username`).
+
+use std::cmp::Reverse;
+
+use crate::{
+    audit::log,
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    lookup::{
+        modify::{self, RemovalEvent},
+        secure,
+    },
+    models::{
+        log::LogAction,
+        metadata::{Anatomy, LookupTable, Protection, SecretType},
+    },
+    prompts::protect,
+    utils::{dates, git},
+};
+
+/// The category secrets stored by git are added to.
+pub const GIT_CATEGORY: &str = "git";
+
+/// Contains a credential in git's credential helper protocol.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GitCredential {
+    /// The remote's host, including the port if there is one.
+    pub host: Option<String>,
+    /// The password or token.
+    pub password: Option<String>,
+    /// The path on the remote, such as `org/repo.git`. git only sends a path if
+    /// `credential.useHttpPath` is set.
+    pub path: Option<String>,
+    /// The protocol, such as `https`.
+    pub protocol: Option<String>,
+    /// The username.
+    pub username: Option<String>,
+}
+
+impl GitCredential {
+    /// Parse a credential from `key=value` lines. Parsing stops at the first empty line. Unknown
+    /// keys are ignored, and a `url` key is split into its parts.
+    pub fn parse(input: &str) -> Self {
+        let mut credential = Self::default();
+
+        for line in input.lines() {
+            if line.is_empty() {
+                break;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                match key {
+                    "host" => credential.host = Some(value.to_string()),
+                    "password" => credential.password = Some(value.to_string()),
+                    "path" => credential.path = Some(value.to_string()),
+                    "protocol" => credential.protocol = Some(value.to_string()),
+                    "username" => credential.username = Some(value.to_string()),
+                    "url" => {
+                        if let Some(parsed) = Self::parse_url(value) {
+                            credential = Self {
+                                password: credential.password,
+                                ..parsed
+                            };
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        credential
+    }
+
+    /// Parse a URL such as `https://user@example.com:8443/org/repo.git`.
+    pub fn parse_url(url: &str) -> Option<Self> {
+        let (protocol, rest) = url.trim().split_once("://")?;
+        let (authority, path) = match rest.split_once('/') {
+            Some((authority, path)) => (authority, Some(path)),
+            None => (rest, None),
+        };
+        let (username, host) = match authority.rsplit_once('@') {
+            Some((username, host)) => (Some(username.to_string()), host),
+            None => (None, authority),
+        };
+
+        if protocol.is_empty() || host.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            host: Some(host.to_lowercase()),
+            password: None,
+            path: path.and_then(normalize_path),
+            protocol: Some(protocol.to_lowercase()),
+            username,
+        })
+    }
+
+    /// Get the credential's URL, without its username.
+    pub fn get_url(&self) -> String {
+        let mut url = format!(
+            "{}://{}",
+            self.protocol.as_deref().unwrap_or("https"),
+            self.host.as_deref().unwrap_or_default()
+        );
+        if let Some(path) = self.path.as_deref().and_then(normalize_path) {
+            url.push('/');
+            url.push_str(&path);
+        }
+
+        url
+    }
+
+    /// Format the credential as `key=value` lines. Like git itself, this refuses values
+    /// containing a newline or NUL, since those would start a new line in the protocol, such as
+    /// a stored password injecting a `url=` or `host=` line.
+    pub fn to_output(&self) -> Result<String, HimitsuError> {
+        let mut output = String::new();
+
+        for (key, value) in [
+            ("protocol", &self.protocol),
+            ("host", &self.host),
+            ("path", &self.path),
+            ("username", &self.username),
+            ("password", &self.password),
+        ] {
+            if let Some(value) = value {
+                if value.contains(['\n', '\0']) {
+                    return Err(HimitsuError::CredentialError(format!(
+                        "The credential's {key} contains a newline or NUL, which git does not allow!"
+                    )));
+                }
+
+                output.push_str(&format!("{key}={value}\n"));
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+/// Strip slashes and a `.git` suffix from a path, so `org/repo.git/` and `org/repo` are the same
+/// path. Returns `None` for an empty path.
+fn normalize_path(path: &str) -> Option<String> {
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    (!path.is_empty()).then(|| path.to_string())
+}
+
+/// Get the login stored in a secret's metadata, if it has one.
+fn get_stored_login(anatomy: &Anatomy) -> Option<GitCredential> {
+    let mut stored = GitCredential::parse_url(anatomy.url.as_deref()?)?;
+    if anatomy.username.is_some() {
+        stored.username = anatomy.username.clone();
+    }
+
+    Some(stored)
+}
+
+/// Check whether a stored login matches the requested credential. Returns how specific the match
+/// is: the longer the stored path, the more specific the match.
+fn get_match_specificity(stored: &GitCredential, request: &GitCredential) -> Option<usize> {
+    if request.protocol.is_none() || request.host.is_none() {
+        return None;
+    }
+    if stored.protocol
+        != request
+            .protocol
+            .as_ref()
+            .map(|protocol| protocol.to_lowercase())
+        || stored.host != request.host.as_ref().map(|host| host.to_lowercase())
+    {
+        return None;
+    }
+    if let (Some(stored_username), Some(username)) = (&stored.username, &request.username) {
+        if stored_username != username {
+            return None;
+        }
+    }
+
+    match (
+        &stored.path,
+        request.path.as_deref().and_then(normalize_path),
+    ) {
+        (None, _) => Some(0),
+        (Some(stored_path), Some(path))
+            if path == *stored_path || path.starts_with(&format!("{stored_path}/")) =>
+        {
+            Some(stored_path.len())
+        }
+        _ => None,
+    }
+}
+
+/// Find every stored secret matching the requested credential. The most specific match comes
+/// first, and matches that are equally specific are sorted by when they were last accessed, most
+/// recent first.
+pub fn find_matches<'a>(
+    lookup_table: &'a LookupTable,
+    request: &GitCredential,
+) -> Vec<(&'a String, &'a Anatomy)> {
+    let mut matches = lookup_table
+        .table
+        .iter()
+        .filter(|(_, anatomy)| anatomy.secret_type == SecretType::Password)
+        .filter_map(|(hash_id, anatomy)| {
+            get_stored_login(anatomy)
+                .and_then(|stored| get_match_specificity(&stored, request))
+                .map(|specificity| (specificity, hash_id, anatomy))
+        })
+        .collect::<Vec<_>>();
+
+    matches.sort_by(|a, b| {
+        let last_accessed = |anatomy: &Anatomy| {
+            anatomy
+                .last_accessed
+                .as_deref()
+                .and_then(dates::parse_timestamp)
+        };
+
+        (Reverse(a.0), Reverse(last_accessed(a.2)), a.1).cmp(&(
+            Reverse(b.0),
+            Reverse(last_accessed(b.2)),
+            b.1,
+        ))
+    });
+
+    matches
+        .into_iter()
+        .map(|(_, hash_id, anatomy)| (hash_id, anatomy))
+        .collect()
+}
+
+/// Look up the requested credential. Returns `None` if no stored secret matches.
+pub fn get_credential(
+    request: &GitCredential,
+    password: &str,
+) -> Result<Option<GitCredential>, HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+
+    match find_matches(&lookup_table, request).first() {
+        Some((hash_id, anatomy)) => {
+            let secret = protect::run_decrypt_protected_secret(hash_id, anatomy, password)?;
+
+            modify::update_last_accessed(hash_id, password)?;
+            log::record_event(LogAction::Use, Some(log::describe_secret(anatomy)));
+
+            Ok(Some(GitCredential {
+                password: Some(secret),
+                username: anatomy
+                    .username
+                    .clone()
+                    .or_else(|| request.username.clone()),
+                ..GitCredential::default()
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Store a credential git has used successfully. A secret with the same URL and username is
+/// updated if its value changed, and a new secret is added to the `git` category otherwise.
+/// Protected secrets are never overwritten.
+pub fn store_credential(request: &GitCredential, password: &str) -> Result<(), HimitsuError> {
+    let secret = match (&request.host, &request.password) {
+        (Some(_), Some(secret)) => secret.clone(),
+        _ => return Ok(()),
+    };
+    let url = request.get_url();
+
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+    let existing = lookup_table.table.iter().find(|(_, anatomy)| {
+        get_stored_login(anatomy)
+            .is_some_and(|stored| stored.get_url() == url && stored.username == request.username)
+    });
+
+    match existing {
+        Some((_, anatomy)) if anatomy.protection != Protection::None => Ok(()),
+        Some((hash_id, anatomy)) => {
+            let current_secret =
+                decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(hash_id)?, password)?;
+            if current_secret == secret {
+                return Ok(());
+            }
+
+            let mut new_anatomy = anatomy.clone();
+            new_anatomy.last_modified = Some(dates::get_timestamp());
+            new_anatomy.last_rotated = new_anatomy.last_modified.clone();
+
+            modify::remove_in_lookup_table(hash_id, password, RemovalEvent::Replace)?;
+
+            let hash_id = encrypt::encrypt_secret(&new_anatomy, password, secret)?;
+            log::record_event(LogAction::Edit, Some(log::describe_secret(&new_anatomy)));
+            git::record_change("update", &hash_id)
+        }
+        None => {
+            let label = match &request.username {
+                Some(username) => format!("{username}@{}", url.split_once("://").unwrap().1),
+                None => url.split_once("://").unwrap().1.to_string(),
+            };
+
+            let mut anatomy = Anatomy::create_from(GIT_CATEGORY.to_string(), label, Vec::new());
+            anatomy.url = Some(url);
+            anatomy.username = request.username.clone();
+
+            let hash_id = encrypt::encrypt_secret(&anatomy, password, secret)?;
+            log::record_event(LogAction::Add, Some(log::describe_secret(&anatomy)));
+            git::record_change("add", &hash_id)
+        }
+    }
+}
+
+/// Remove the stored secret matching a credential git has rejected, which is the secret
+/// `get_credential()` returns for it. If git passes the rejected password, the secret is only
+/// removed if it has that value. Protected secrets are never removed.
+pub fn erase_credential(request: &GitCredential, password: &str) -> Result<(), HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+
+    let Some((hash_id, anatomy)) = find_matches(&lookup_table, request).first().copied() else {
+        return Ok(());
+    };
+    if anatomy.protection != Protection::None {
+        return Ok(());
+    }
+    if let Some(rejected) = &request.password {
+        let secret =
+            decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(hash_id)?, password)?;
+        if secret != *rejected {
+            return Ok(());
+        }
+    }
+
+    modify::remove_in_lookup_table(hash_id, password, RemovalEvent::Remove)?;
+    log::record_event(LogAction::Remove, Some(log::describe_secret(anatomy)));
+    git::record_change("remove", hash_id)
+}
+
+#[cfg(test)]
+pub mod test_git {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    /// Create a lookup table with a login for a host and a more specific login for a repository.
+    fn create_lookup_table() -> LookupTable {
+        let mut lookup_table = LookupTable {
+            removed: Vec::new(),
+            table: HashMap::new(),
+        };
+
+        for (hash_id, url, username) in [
+            ("host", "https://example.com", "alice"),
+            ("repo", "https://example.com/org/repo", "alice"),
+            ("other", "https://example.org", "bob"),
+        ] {
+            lookup_table.table.insert(
+                hash_id.to_string(),
+                Anatomy {
+                    url: Some(url.to_string()),
+                    username: Some(username.to_string()),
+                    ..Anatomy::create_for_test(GIT_CATEGORY, url, "01-01-2022 00:00:00")
+                },
+            );
+        }
+
+        lookup_table
+    }
+
+    /// Test whether credentials are parsed from and formatted as `key=value` lines.
+    #[test]
+    fn test_parse_credential() {
+        let credential = GitCredential::parse(
+            "protocol=https\nhost=example.com:8443\nwwwauth[]=Basic\nusername=alice\n\nignored=true\n",
+        );
+
+        assert_eq!(credential.protocol.as_deref(), Some("https"));
+        assert_eq!(credential.host.as_deref(), Some("example.com:8443"));
+        assert_eq!(credential.username.as_deref(), Some("alice"));
+        assert_eq!(
+            credential.to_output().unwrap(),
+            "protocol=https\nhost=example.com:8443\nusername=alice\n"
+        );
+
+        let from_url = GitCredential::parse("url=https://bob@Example.com/org/repo.git/\n");
+        assert_eq!(from_url.username.as_deref(), Some("bob"));
+        assert_eq!(from_url.get_url(), "https://example.com/org/repo");
+    }
+
+    /// Test whether values that would inject extra protocol lines are refused.
+    #[test]
+    fn test_to_output_rejects_injection() {
+        let injected = GitCredential {
+            password: Some("hunter2\nurl=https://attacker.example".to_string()),
+            username: Some("alice".to_string()),
+            ..GitCredential::default()
+        };
+        assert!(injected.to_output().is_err());
+
+        let with_nul = GitCredential {
+            username: Some("alice\0host=attacker.example".to_string()),
+            ..GitCredential::default()
+        };
+        assert!(with_nul.to_output().is_err());
+    }
+
+    /// Test the helper through git itself: git stores, looks up, and erases a login with
+    /// `hmu git-credential` while the agent keeps a new vault unlocked.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_git_credential_helper() {
+        use std::{
+            env, fs,
+            io::Write,
+            path::Path,
+            process::{Command, Stdio},
+            thread,
+            time::Duration,
+        };
+
+        use rand::{rngs::OsRng, RngCore};
+
+        use crate::{authentication, lookup::init};
+
+        const PASSWORD: &str = "correct horse battery staple 42!";
+
+        let home = env::temp_dir().join(format!("himitsu-git-credential-{}", OsRng.next_u64()));
+        let runtime_dir = home.join("run");
+        let config_dir = home.join(".config").join("himitsu");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("crypt.json"),
+            serde_json::to_string(
+                &authentication::generate_salt_and_password_hash(PASSWORD, false).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        init::create_lookup_in(
+            &home.join(".local/share/himitsu/lookup"),
+            PASSWORD,
+            &LookupTable::new(),
+        )
+        .unwrap();
+
+        let hmu = assert_cmd::cargo::cargo_bin("hmu");
+        let command = |program: &Path| {
+            let mut command = Command::new(program);
+            command
+                .env("HOME", &home)
+                .env("XDG_RUNTIME_DIR", &runtime_dir)
+                .env("GIT_CONFIG_NOSYSTEM", "1")
+                .env("GIT_TERMINAL_PROMPT", "0")
+                .env_remove("XDG_CONFIG_HOME")
+                .env_remove("XDG_DATA_HOME")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null());
+
+            command
+        };
+
+        let mut agent = command(&hmu)
+            .args(["agent", "serve", "--timeout", "1m"])
+            .spawn()
+            .unwrap();
+        agent
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(PASSWORD.as_bytes())
+            .unwrap();
+        let socket_path = runtime_dir.join("himitsu").join("agent.sock");
+        for _ in 0..100 {
+            if socket_path.exists() {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        let run_git = |action: &str, input: &str| {
+            let mut git = command(Path::new("git"))
+                .arg("-c")
+                .arg(format!(
+                    "credential.helper=!'{}' git-credential",
+                    hmu.display()
+                ))
+                .args(["credential", action])
+                .spawn()
+                .unwrap();
+            git.stdin
+                .take()
+                .unwrap()
+                .write_all(input.as_bytes())
+                .unwrap();
+
+            String::from_utf8(git.wait_with_output().unwrap().stdout).unwrap()
+        };
+
+        run_git(
+            "approve",
+            "protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n\n",
+        );
+        let filled = run_git("fill", "protocol=https\nhost=example.com\n\n");
+        assert!(filled.contains("username=alice\n"));
+        assert!(filled.contains("password=hunter2\n"));
+
+        run_git(
+            "reject",
+            "protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n\n",
+        );
+        assert!(!run_git("fill", "protocol=https\nhost=example.com\n\n").contains("hunter2"));
+
+        agent.kill().unwrap();
+        agent.wait().unwrap();
+        fs::remove_dir_all(home).unwrap();
+    }
+
+    /// Test whether the most specific login is found first and unrelated logins are ignored.
+    #[test]
+    fn test_find_matches() {
+        let lookup_table = create_lookup_table();
+        let find = |input: &str| {
+            find_matches(&lookup_table, &GitCredential::parse(input))
+                .into_iter()
+                .map(|(hash_id, _)| hash_id.as_str())
+                .collect::<Vec<&str>>()
+        };
+
+        assert_eq!(find("protocol=https\nhost=example.com\n"), vec!["host"]);
+        assert_eq!(
+            find("protocol=https\nhost=example.com\npath=org/repo.git\n"),
+            vec!["repo", "host"]
+        );
+        assert_eq!(
+            find("protocol=https\nhost=example.com\npath=org/other.git\n"),
+            vec!["host"]
+        );
+        assert!(find("protocol=https\nhost=example.com\nusername=bob\n").is_empty());
+        assert!(find("protocol=http\nhost=example.com\n").is_empty());
+    }
+}
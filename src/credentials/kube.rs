@@ -0,0 +1,147 @@
+//! Contains the `ExecCredential` output of client-go's exec credential plugins.
+//!
+//! `kubectl` and other client-go programs run `hmu kube-credential <LABEL>` and read the
+//! `ExecCredential` object it prints as JSON. The secret's value is either a bearer token, or
+//! JSON with a `token` or a `clientCertificateData` and `clientKeyData` pair.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{errors::HimitsuError, models::metadata::Anatomy, utils::dates};
+
+/// The API version used if the caller does not ask for one.
+pub const DEFAULT_API_VERSION: &str = "client.authentication.k8s.io/v1";
+
+/// Contains the credential client-go authenticates with.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecCredentialStatus {
+    /// The PEM-encoded client certificate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_certificate_data: Option<String>,
+    /// The PEM-encoded client certificate's private key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key_data: Option<String>,
+    /// When the credential expires, as an RFC 3339 timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_timestamp: Option<String>,
+    /// The bearer token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// Contains an `ExecCredential` object.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecCredential {
+    /// The API version client-go asked for.
+    pub api_version: String,
+    /// Always `ExecCredential`.
+    pub kind: String,
+    /// The credential.
+    pub status: ExecCredentialStatus,
+}
+
+/// Get the API version client-go asked for in the `KUBERNETES_EXEC_INFO` environment variable.
+pub fn get_api_version(exec_info: Option<&str>) -> String {
+    exec_info
+        .and_then(|exec_info| serde_json::from_str::<Value>(exec_info).ok())
+        .and_then(|exec_info| {
+            exec_info
+                .get("apiVersion")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| DEFAULT_API_VERSION.to_string())
+}
+
+impl ExecCredential {
+    /// Build an `ExecCredential` from the credential stored in a secret. Credentials without their
+    /// own expiration expire with the secret, if it expires.
+    pub fn parse(
+        secret: &str,
+        anatomy: &Anatomy,
+        api_version: String,
+    ) -> Result<Self, HimitsuError> {
+        let secret = secret.trim();
+
+        let mut status = if secret.starts_with('{') {
+            serde_json::from_str(secret).unwrap_or_default()
+        } else {
+            ExecCredentialStatus {
+                token: Some(secret.to_string()).filter(|token| !token.is_empty()),
+                ..Default::default()
+            }
+        };
+
+        if status.token.is_none()
+            && (status.client_certificate_data.is_none() || status.client_key_data.is_none())
+        {
+            return Err(HimitsuError::CredentialError(format!(
+                "\"{}\" does not contain a Kubernetes credential! Store a token, or JSON with a clientCertificateData and a clientKeyData.",
+                anatomy.label
+            )));
+        }
+
+        if status.expiration_timestamp.is_none() {
+            status.expiration_timestamp = anatomy
+                .expires_at
+                .as_deref()
+                .and_then(dates::parse_timestamp)
+                .and_then(|expires_at| dates::format_rfc3339(&expires_at));
+        }
+
+        Ok(ExecCredential {
+            api_version,
+            kind: "ExecCredential".to_string(),
+            status,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test_kube {
+    use super::*;
+
+    /// Test whether tokens and client certificates are turned into `ExecCredential` objects.
+    #[test]
+    fn test_parse_credential() {
+        let anatomy = Anatomy::create_from("cloud".to_string(), "k8s".to_string(), Vec::new());
+
+        assert_eq!(
+            serde_json::to_string(
+                &ExecCredential::parse("token\n", &anatomy, DEFAULT_API_VERSION.to_string())
+                    .unwrap()
+            )
+            .unwrap(),
+            r#"{"apiVersion":"client.authentication.k8s.io/v1","kind":"ExecCredential","status":{"token":"token"}}"#
+        );
+
+        let credential = ExecCredential::parse(
+            r#"{"clientCertificateData": "cert", "clientKeyData": "key"}"#,
+            &anatomy,
+            DEFAULT_API_VERSION.to_string(),
+        )
+        .unwrap();
+        assert_eq!(credential.status.client_key_data.as_deref(), Some("key"));
+
+        assert!(ExecCredential::parse(
+            r#"{"clientCertificateData": "cert"}"#,
+            &anatomy,
+            DEFAULT_API_VERSION.to_string()
+        )
+        .is_err());
+    }
+
+    /// Test whether the API version client-go asked for is used.
+    #[test]
+    fn test_get_api_version() {
+        assert_eq!(
+            get_api_version(Some(
+                r#"{"apiVersion":"client.authentication.k8s.io/v1beta1","kind":"ExecCredential","spec":{"interactive":false}}"#
+            )),
+            "client.authentication.k8s.io/v1beta1"
+        );
+        assert_eq!(get_api_version(None), DEFAULT_API_VERSION);
+    }
+}
@@ -0,0 +1,136 @@
+//! Contains credential helpers that let other programs fetch secrets from the vault.
+//!
+//! Credential helpers are run by other programs rather than by the user, so their output is
+//! written with `utils::protocol` and everything else goes to stderr.
+
+pub mod aws;
+pub mod docker;
+pub mod env;
+pub mod git;
+pub mod kube;
+
+use crate::{
+    audit::log,
+    errors::HimitsuError,
+    lookup::{expiry, modify, secure},
+    models::{
+        log::LogAction,
+        metadata::{LookupMatch, LookupTable, SecretType},
+    },
+    prompts::protect,
+};
+
+/// Find the secret a credential helper was asked for by its exact label, or by its category and
+/// label as `category/label`.
+///
+/// The label is compared as plain text. It is never parsed as a query, fuzzy matched, or
+/// resolved with a prompt, because the programs running credential helpers pass labels through as
+/// they were configured and cannot answer prompts. An error is returned unless exactly one secret
+/// matches.
+pub fn find_labeled_secret(label: &str, password: &str) -> Result<LookupMatch, HimitsuError> {
+    find_labeled_secret_in(&secure::decrypt_lookup_table(password)?, label)
+}
+
+/// Find the secret matching a label exactly in a lookup table.
+fn find_labeled_secret_in(
+    lookup_table: &LookupTable,
+    label: &str,
+) -> Result<LookupMatch, HimitsuError> {
+    let mut found_matches: Vec<LookupMatch> = lookup_table
+        .table
+        .iter()
+        .filter(|(_, anatomy)| {
+            anatomy.label == label || format!("{}/{}", anatomy.category, anatomy.label) == label
+        })
+        .map(|(hash, anatomy)| LookupMatch::create(anatomy.clone(), hash.clone()))
+        .collect();
+
+    match found_matches.len() {
+        0 => Err(HimitsuError::CredentialError(format!(
+            "No secret is labeled \"{label}\"!"
+        ))),
+        1 => Ok(found_matches.remove(0)),
+        _ => {
+            let mut names: Vec<String> = found_matches
+                .iter()
+                .map(|lookup_match| {
+                    format!(
+                        "{}/{}",
+                        lookup_match.anatomy.category, lookup_match.anatomy.label
+                    )
+                })
+                .collect();
+            names.sort();
+
+            Err(HimitsuError::CredentialError(format!(
+                "Several secrets are labeled \"{label}\": {}. Pass the category as well, such as \"{}\".",
+                names.join(", "),
+                names[0]
+            )))
+        }
+    }
+}
+
+/// Decrypt a secret for a credential helper the same way `hmu use` does: expired secrets are
+/// warned about, protected secrets are unlocked, and the use is recorded.
+pub fn use_labeled_secret(
+    lookup_match: &LookupMatch,
+    password: &str,
+) -> Result<String, HimitsuError> {
+    if lookup_match.anatomy.secret_type != SecretType::Password {
+        return Err(HimitsuError::CredentialError(format!(
+            "\"{}\" is a TOTP secret, not a credential!",
+            lookup_match.anatomy.label
+        )));
+    }
+
+    expiry::warn_if_expired(&lookup_match.anatomy);
+    let secret =
+        protect::run_decrypt_protected_secret(&lookup_match.hash, &lookup_match.anatomy, password)?;
+
+    modify::update_last_accessed(&lookup_match.hash, password)?;
+    log::record_event(
+        LogAction::Use,
+        Some(log::describe_secret(&lookup_match.anatomy)),
+    );
+
+    Ok(secret)
+}
+
+#[cfg(test)]
+pub mod test_credentials {
+    use super::*;
+
+    use crate::models::metadata::Anatomy;
+
+    /// Test whether only exact labels, optionally qualified with their category, are found.
+    #[test]
+    fn test_find_labeled_secret_in() {
+        let mut lookup_table = LookupTable::new();
+        for (hash, category, label) in [
+            ("1", "cloud", "aws-prod"),
+            ("2", "cloud", "db"),
+            ("3", "personal", "db"),
+        ] {
+            lookup_table.table.insert(
+                hash.to_string(),
+                Anatomy::create_from(category.to_string(), label.to_string(), Vec::new()),
+            );
+        }
+
+        assert_eq!(
+            find_labeled_secret_in(&lookup_table, "aws-prod")
+                .unwrap()
+                .hash,
+            "1"
+        );
+        assert_eq!(
+            find_labeled_secret_in(&lookup_table, "personal/db")
+                .unwrap()
+                .hash,
+            "3"
+        );
+        assert!(find_labeled_secret_in(&lookup_table, "prod").is_err());
+        assert!(find_labeled_secret_in(&lookup_table, "db").is_err());
+    }
+}
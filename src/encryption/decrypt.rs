@@ -12,10 +12,14 @@ use chacha20poly1305::{
     aead::{Aead, NewAead},
     Key, XChaCha20Poly1305, XNonce,
 };
-use directories::ProjectDirs;
+use data_encoding::HEXLOWER;
 use spinners::{Spinner, Spinners};
 
-use crate::{authentication, errors::HimitsuError, utils::clipboard};
+use crate::{
+    authentication,
+    errors::HimitsuError,
+    utils::{clipboard, vault},
+};
 
 /// This enum contains variants for what should be done with the decrypted secret.
 pub enum DecryptionMode {
@@ -36,19 +40,8 @@ pub fn decrypt_secret(
 
     let hash_path = get_secret_hash_path(hash_id)?;
 
-    let salt = get_secret_salt(&hash_path)?;
-    let raw_nonce = get_secret_nonce(&hash_path)?;
-    let nonce = XNonce::from_slice(&raw_nonce);
-
-    let encrypted_secret = &get_secret(&hash_path)?[..];
-
-    let argon2_config = authentication::get_argon2_config();
-    let key = argon2::hash_raw(password.as_bytes(), &salt, &argon2_config)?;
-
-    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
-
-    match cipher.decrypt(nonce, encrypted_secret) {
-        Ok(decrypted_secret) => {
+    match decrypt_secret_in(&hash_path, password) {
+        Ok(secret) => {
             decryption_spinner.stop_and_persist(
                 "🔓",
                 Color::Green
@@ -57,8 +50,6 @@ pub fn decrypt_secret(
                     .to_string(),
             );
 
-            let secret = String::from_utf8_lossy(&decrypted_secret).to_string();
-
             match decryption_mode {
                 DecryptionMode::EditSecret => Ok(Some(secret)),
                 DecryptionMode::UseSecret => match clipboard::set_clipboard(secret) {
@@ -84,24 +75,63 @@ pub fn decrypt_secret(
                     .paint("SECRET DECRYPTION FAILED!".to_string())
                     .to_string(),
             );
-            Err(HimitsuError::AEADDencryptionError(format!(
-                "Secret decryption error: {}",
-                error
-            )))
+            Err(error)
         }
     }
 }
 
-/// Get the secret's SHA256 hash directory path.
-fn get_secret_hash_path(hash_id: &str) -> Result<PathBuf, HimitsuError> {
-    match ProjectDirs::from("", "", "himitsu") {
-        Some(project_directory) => Ok(project_directory.data_dir().join("closet").join(hash_id)),
-        None => Err(HimitsuError::PathError(
-            "Could not get the path to the himitsu application directory!".to_string(),
-        )),
+/// Decrypt the secret stored in a SHA256 hash directory and return it.
+pub fn decrypt_secret_in(hash_path: &Path, password: &str) -> Result<String, HimitsuError> {
+    let salt = get_secret_salt(hash_path)?;
+    let raw_nonce = get_secret_nonce(hash_path)?;
+    let nonce = XNonce::from_slice(&raw_nonce);
+
+    let encrypted_secret = &get_secret(hash_path)?[..];
+
+    let argon2_config = authentication::get_argon2_config();
+    let key = argon2::hash_raw(password.as_bytes(), &salt, &argon2_config)?;
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+
+    match cipher.decrypt(nonce, encrypted_secret) {
+        Ok(decrypted_secret) => Ok(String::from_utf8_lossy(&decrypted_secret).to_string()),
+        Err(error) => Err(HimitsuError::AEADDencryptionError(format!(
+            "Secret decryption error: {}",
+            error
+        ))),
     }
 }
 
+/// Open a secret sealed with `encrypt::seal_with_passphrase()`.
+pub fn open_with_passphrase(passphrase: &str, sealed: &str) -> Result<String, HimitsuError> {
+    let sealed = HEXLOWER
+        .decode(sealed.as_bytes())
+        .map_err(|error| HimitsuError::AEADDencryptionError(error.to_string()))?;
+    if sealed.len() < 56 {
+        return Err(HimitsuError::AEADDencryptionError(
+            "The sealed secret is corrupted!".to_string(),
+        ));
+    }
+
+    let (salt, rest) = sealed.split_at(32);
+    let (raw_nonce, encrypted_secret) = rest.split_at(24);
+
+    let argon2_config = authentication::get_argon2_config();
+    let key = argon2::hash_raw(passphrase.as_bytes(), salt, &argon2_config)?;
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+
+    cipher
+        .decrypt(XNonce::from_slice(raw_nonce), encrypted_secret)
+        .map(|decrypted_secret| String::from_utf8_lossy(&decrypted_secret).to_string())
+        .map_err(|_| HimitsuError::AEADDencryptionError("The passphrase is incorrect!".to_string()))
+}
+
+/// Get the secret's SHA256 hash directory path.
+pub fn get_secret_hash_path(hash_id: &str) -> Result<PathBuf, HimitsuError> {
+    Ok(vault::get_vault_dir()?.join("closet").join(hash_id))
+}
+
 /// Get the secret's nonce value.
 fn get_secret_nonce(hash_path: &Path) -> Result<[u8; 24], HimitsuError> {
     let mut nonce_file = File::open(hash_path.join("nonce"))?;
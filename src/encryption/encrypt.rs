@@ -1,6 +1,6 @@
 //! Contains encryption functions for `himitsu`.
 
-use std::fmt::Write;
+use std::{fmt::Write, path::Path};
 
 use ansi_term::Color;
 use chacha20poly1305::{
@@ -16,16 +16,16 @@ use crate::{
     authentication,
     errors::HimitsuError,
     lookup::{modify, secure},
-    models::metadata::Anatomy,
+    models::metadata::{Anatomy, LookupTable},
     utils::store,
 };
 
-/// Store the encrypted secret.
+/// Store the encrypted secret. Returns the secret's new hash ID.
 pub fn encrypt_secret(
     anatomy: &Anatomy,
     password: &str,
     secret: String,
-) -> Result<(), HimitsuError> {
+) -> Result<String, HimitsuError> {
     let mut encryption_spinner =
         Spinner::new(Spinners::Aesthetic, "Encrypting your secret...".into());
 
@@ -34,15 +34,9 @@ pub fn encrypt_secret(
     OsRng.fill_bytes(&mut salt);
     OsRng.fill_bytes(&mut secret_nonce);
 
-    // Generate a new hash for this particular secret.
-    let argon2_config = authentication::get_argon2_config();
-    let key = argon2::hash_raw(password.as_bytes(), &salt, &argon2_config)?;
-
-    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
-
     let nonce = XNonce::from_slice(&secret_nonce);
 
-    match cipher.encrypt(nonce, secret.as_bytes()) {
+    match seal_secret(nonce, password, salt, &secret) {
         Ok(ciphertext) => {
             encryption_spinner.stop_and_persist(
                 "✅",
@@ -52,9 +46,7 @@ pub fn encrypt_secret(
                     .to_string(),
             );
 
-            update_lookup_table(anatomy, ciphertext, nonce, password, salt)?;
-
-            Ok(())
+            update_lookup_table(anatomy, ciphertext, nonce, password, salt)
         }
         Err(error) => {
             encryption_spinner.stop_and_persist(
@@ -65,11 +57,94 @@ pub fn encrypt_secret(
                     .to_string(),
             );
 
-            Err(HimitsuError::AEADEncryptionError(error.to_string()))
+            Err(error)
         }
     }
 }
 
+/// Encrypt a secret and add it to an already decrypted lookup table without displaying any
+/// spinners. The lookup table is not re-encrypted, so callers importing many secrets at once only
+/// have to write the lookup table a single time. Returns the secret's new hash ID.
+pub fn import_secret(
+    anatomy: &Anatomy,
+    lookup_table: &mut LookupTable,
+    password: &str,
+    secret: &str,
+) -> Result<String, HimitsuError> {
+    let mut salt = [0u8; 32];
+    let mut secret_nonce = [0u8; 24];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut secret_nonce);
+
+    let nonce = XNonce::from_slice(&secret_nonce);
+
+    let ciphertext = seal_secret(nonce, password, salt, secret)?;
+    let secret_hash = generate_sha256_hash(anatomy, &ciphertext, nonce);
+
+    lookup_table
+        .table
+        .insert(secret_hash.clone(), anatomy.to_owned());
+
+    store::store_secret(ciphertext, nonce, salt, &secret_hash)?;
+
+    Ok(secret_hash)
+}
+
+/// Encrypt a secret and write it to a directory using the same layout as a secret's hash directory
+/// in the `closet`. The secret may be decrypted with `decrypt::decrypt_secret_in()`.
+pub fn encrypt_secret_in(
+    directory: &Path,
+    password: &str,
+    secret: &str,
+) -> Result<(), HimitsuError> {
+    let mut salt = [0u8; 32];
+    let mut secret_nonce = [0u8; 24];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut secret_nonce);
+
+    let nonce = XNonce::from_slice(&secret_nonce);
+
+    let ciphertext = seal_secret(nonce, password, salt, secret)?;
+
+    store::store_secret_in(ciphertext, directory, nonce, salt)
+}
+
+/// Seal a secret a second time with a key derived from a passphrase. The salt, nonce, and
+/// ciphertext are returned as a single hex string, which may be decrypted with
+/// `decrypt::open_with_passphrase()`.
+pub fn seal_with_passphrase(passphrase: &str, secret: &str) -> Result<String, HimitsuError> {
+    let mut salt = [0u8; 32];
+    let mut secret_nonce = [0u8; 24];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut secret_nonce);
+
+    let nonce = XNonce::from_slice(&secret_nonce);
+
+    let mut sealed = salt.to_vec();
+    sealed.extend_from_slice(&secret_nonce);
+    sealed.extend(seal_secret(nonce, passphrase, salt, secret)?);
+
+    Ok(HEXLOWER.encode(&sealed))
+}
+
+/// Encrypt a secret with a key derived from the password and salt.
+fn seal_secret(
+    nonce: &GenericArray<u8, U24>,
+    password: &str,
+    salt: [u8; 32],
+    secret: &str,
+) -> Result<Vec<u8>, HimitsuError> {
+    // Generate a new hash for this particular secret.
+    let argon2_config = authentication::get_argon2_config();
+    let key = argon2::hash_raw(password.as_bytes(), &salt, &argon2_config)?;
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+
+    cipher
+        .encrypt(nonce, secret.as_bytes())
+        .map_err(|error| HimitsuError::AEADEncryptionError(error.to_string()))
+}
+
 /// Generate a SHA256 hash for a new secret.
 fn generate_sha256_hash(
     anatomy: &Anatomy,
@@ -90,14 +165,14 @@ fn generate_sha256_hash(
     HEXLOWER.encode(context.finish().as_ref())
 }
 
-/// Update the lookup table with the secret's hash ID and anatomy.
+/// Update the lookup table with the secret's hash ID and anatomy. Returns the secret's hash ID.
 fn update_lookup_table(
     anatomy: &Anatomy,
     ciphertext: Vec<u8>,
     nonce: &GenericArray<u8, U24>,
     password: &str,
     salt: [u8; 32],
-) -> Result<(), HimitsuError> {
+) -> Result<String, HimitsuError> {
     let mut write_spinner = Spinner::new(Spinners::Noise, "Storing your secret...".into());
 
     let secret_hash = generate_sha256_hash(anatomy, &ciphertext, nonce);
@@ -121,7 +196,7 @@ fn update_lookup_table(
             .to_string(),
     );
 
-    Ok(())
+    Ok(secret_hash)
 }
 
 #[cfg(test)]
@@ -132,13 +207,14 @@ pub mod test_encryption {
     #[test]
     fn test_generate_sha256_hash() {
         let anatomy = Anatomy {
-            category: "unclassified".to_string(),
             date_created: "today".to_string(),
-            label: "something".to_string(),
-            last_accessed: None,
-            tags: vec!["tag".to_string()],
+            ..Anatomy::create_from(
+                "unclassified".to_string(),
+                "something".to_string(),
+                vec!["tag".to_string()],
+            )
         };
-        let ciphertext: Vec<u8> = vec![01, 12, 52, 23];
+        let ciphertext: Vec<u8> = vec![1, 12, 52, 23];
 
         let mut secret_nonce = [0u8; 24];
         OsRng.fill_bytes(&mut secret_nonce);
@@ -149,4 +225,19 @@ pub mod test_encryption {
 
         assert!(test_hash.len() == 64);
     }
+
+    /// Test whether a secret sealed with a passphrase only opens with the same passphrase.
+    #[test]
+    fn test_seal_with_passphrase() {
+        let sealed = seal_with_passphrase("correct horse", "hunter2").unwrap();
+
+        assert_ne!(sealed, "hunter2");
+        assert_eq!(
+            crate::encryption::decrypt::open_with_passphrase("correct horse", &sealed).unwrap(),
+            "hunter2"
+        );
+        assert!(
+            crate::encryption::decrypt::open_with_passphrase("battery staple", &sealed).is_err()
+        );
+    }
 }
@@ -11,6 +11,14 @@ pub enum HimitsuError {
     #[error("Could not access system application directories!")]
     ApplicationError,
 
+    /// An error occurred while starting or talking to the agent.
+    #[error("Agent error: {0}")]
+    AgentError(String),
+
+    /// An error occurred while answering a request sent to `hmu serve`.
+    #[error("API error: {0}")]
+    ApiError(String),
+
     /// An error occurred while performing Argon2 tasks.
     #[error("Argon2 error: {0}")]
     Argon2Error(#[from] argon2::Error),
@@ -23,6 +31,10 @@ pub enum HimitsuError {
     #[error("AEAD encryption error: {0}")]
     AEADEncryptionError(String),
 
+    /// An error occurred while auditing the vault.
+    #[error("Audit error: {0}")]
+    AuditError(String),
+
     /// An error occurred while attempting to get a handle to the system clipboard.
     #[error("Clipboard context error: {0}")]
     ClipboardContextError(String),
@@ -31,6 +43,18 @@ pub enum HimitsuError {
     #[error("Clipboard error: {0}")]
     ClipboardContentsError(String),
 
+    /// A credential helper could not answer a request.
+    #[error("Credential helper error: {0}")]
+    CredentialError(String),
+
+    /// An error occurred while talking to the D-Bus session bus.
+    #[error("D-Bus error: {0}")]
+    DBusError(#[from] zbus::Error),
+
+    /// An error occurred while parsing when a secret expires, or the secret has expired.
+    #[error("Expiry error: {0}")]
+    ExpiryError(String),
+
     /// The user failed to log in within 3 tries.
     #[error("Goodbye.")]
     FailedToLogin,
@@ -39,6 +63,10 @@ pub enum HimitsuError {
     #[error("FromUtf8Error: {0}")]
     FromUtf8Error(#[from] FromUtf8Error),
 
+    /// An error occurred while running a git command in the vault directory.
+    #[error("Git error: {0}")]
+    GitError(String),
+
     /// An error occurred while attempting to process `inquire` prompts.
     #[error("Inquire error: {0}")]
     InquireError(#[from] inquire::error::InquireError),
@@ -47,10 +75,26 @@ pub enum HimitsuError {
     #[error("IO error: {0}")]
     IOError(#[from] io::Error),
 
+    /// An error occurred while reading or registering a keyfile.
+    #[error("Keyfile error: {0}")]
+    KeyfileError(String),
+
+    /// The user has to wait before trying to log in again.
+    #[error("Too many failed login attempts. Try again in {0} seconds.")]
+    LockedOut(i64),
+
+    /// An error occurred while reading or verifying the audit log.
+    #[error("Audit log error: {0}")]
+    LogError(String),
+
     /// An error occurred while traversing the lookup table.
     #[error("Lookup table error: {0}")]
     LookupError(String),
 
+    /// An error occurred while merging another vault into the current vault.
+    #[error("Merge error: {0}")]
+    MergeError(String),
+
     /// This error is used if no secrets are currently stored in the vault and the user attempts to
     /// edit, remove, or use a secret.
     #[error("There are no secrets stored in your vault!")]
@@ -60,6 +104,14 @@ pub enum HimitsuError {
     #[error("Path error: {0}")]
     PathError(String),
 
+    /// An error occurred while parsing a search query.
+    #[error("Query error: {0}")]
+    QueryError(String),
+
+    /// An error occurred while creating recovery shares or recovering the vault.
+    #[error("Recovery error: {0}")]
+    RecoveryError(String),
+
     /// An error occurred while executing a regex expression.
     #[error("Regex error: {0}")]
     RegexError(#[from] regex::Error),
@@ -68,6 +120,18 @@ pub enum HimitsuError {
     #[error("Serde JSON error: {0}")]
     SerdeJsonError(#[from] serde_json::Error),
 
+    /// An error occurred while serving the freedesktop Secret Service.
+    #[error("Secret Service error: {0}")]
+    SecretServiceError(String),
+
+    /// An error occurred while sharing a secret with, or receiving a secret from, another user.
+    #[error("Share error: {0}")]
+    ShareError(String),
+
+    /// An error occurred while loading SSH keys or serving the ssh-agent protocol.
+    #[error("SSH agent error: {0}")]
+    SshError(String),
+
     /// AN error occurred while attempting to store the lookup table.
     #[error("Store lookup table error: {0}")]
     StoreLookupTableError(String),
@@ -84,10 +148,26 @@ pub enum HimitsuError {
     #[error("Store secret error: {0}")]
     StoreSecretError(String),
 
+    /// An error occurred while managing a team vault.
+    #[error("Team vault error: {0}")]
+    TeamError(String),
+
+    /// An error occurred while parsing a TOTP seed or generating a TOTP code.
+    #[error("TOTP error: {0}")]
+    TotpError(String),
+
+    /// An error occurred while running the TUI.
+    #[error("TUI error: {0}")]
+    TuiError(String),
+
     /// The user cancelled mid-prompt.
     #[error("GOODBYE.")]
     UserCancelled,
 
+    /// The vault was wiped after too many failed login attempts.
+    #[error("Too many failed login attempts. The vault was wiped.")]
+    VaultWiped,
+
     /// Catch any other errors that may arise, such as `bail!`s returned via `Anyhow`.
     #[error(transparent)]
     Transparent(#[from] anyhow::Error),
@@ -0,0 +1,234 @@
+//! Contains functions for secrets that expire or have to be rotated.
+
+use ansi_term::{Color, Style};
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{
+    errors::HimitsuError,
+    models::metadata::{Anatomy, LookupTable, Tombstone},
+    utils::dates,
+};
+
+use super::{secure, utils};
+
+/// Contains variants for each reason a secret needs attention.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DueReason {
+    /// The secret expired this many days ago.
+    Expired(i64),
+    /// The secret expires in this many days.
+    Expiring(i64),
+    /// The secret's value is this many days past its rotation interval.
+    RotationDue(i64),
+}
+
+/// Contains a secret that needs attention.
+#[derive(Debug)]
+pub struct DueSecret {
+    /// The secret, recorded as `category/label`.
+    pub name: String,
+    /// Why the secret needs attention.
+    pub reason: DueReason,
+}
+
+/// Check whether a secret has expired.
+pub fn is_expired(anatomy: &Anatomy, now: NaiveDateTime) -> bool {
+    anatomy
+        .expires_at
+        .as_deref()
+        .and_then(dates::parse_timestamp)
+        .is_some_and(|expires_at| expires_at <= now)
+}
+
+/// Check whether a temporary secret has expired, so it must no longer be used.
+pub fn has_lapsed(anatomy: &Anatomy, now: NaiveDateTime) -> bool {
+    anatomy.temporary && is_expired(anatomy, now)
+}
+
+/// Get why a secret needs attention, if it does. Secrets expiring within `within_days` days are
+/// reported as expiring.
+pub fn get_due_reason(
+    anatomy: &Anatomy,
+    now: NaiveDateTime,
+    within_days: i64,
+) -> Option<DueReason> {
+    if let Some(expires_at) = anatomy
+        .expires_at
+        .as_deref()
+        .and_then(dates::parse_timestamp)
+    {
+        if expires_at <= now {
+            return Some(DueReason::Expired((now - expires_at).num_days()));
+        }
+        if expires_at - now <= Duration::days(within_days) {
+            return Some(DueReason::Expiring((expires_at - now).num_days()));
+        }
+    }
+
+    let rotation_days = anatomy.rotation_days?;
+    let rotated_at = dates::parse_timestamp(anatomy.get_rotated_date())?;
+    let rotation_due = rotated_at + Duration::days(rotation_days as i64);
+
+    (rotation_due <= now).then(|| DueReason::RotationDue((now - rotation_due).num_days()))
+}
+
+/// Find every secret that needs attention, sorted by name.
+pub fn find_due_secrets(
+    lookup_table: &LookupTable,
+    now: NaiveDateTime,
+    within_days: i64,
+) -> Vec<DueSecret> {
+    let mut due_secrets = lookup_table
+        .table
+        .values()
+        .filter_map(|anatomy| {
+            Some(DueSecret {
+                name: format!("{}/{}", anatomy.category, anatomy.label),
+                reason: get_due_reason(anatomy, now, within_days)?,
+            })
+        })
+        .collect::<Vec<DueSecret>>();
+
+    due_secrets.sort_by(|a, b| a.name.cmp(&b.name));
+
+    due_secrets
+}
+
+/// Remove every temporary secret that has expired. Returns the removed secrets, recorded as
+/// `category/label`.
+pub fn remove_expired_secrets(password: &str) -> Result<Vec<String>, HimitsuError> {
+    let mut lookup_table = secure::decrypt_lookup_table(password)?;
+    let now = dates::get_now();
+
+    let expired_hashes = lookup_table
+        .table
+        .iter()
+        .filter(|(_, anatomy)| has_lapsed(anatomy, now))
+        .map(|(hash_id, _)| hash_id.clone())
+        .collect::<Vec<String>>();
+
+    let mut removed = Vec::new();
+    for hash_id in &expired_hashes {
+        if let Some(anatomy) = lookup_table.table.remove(hash_id) {
+            lookup_table.removed.push(Tombstone {
+                category: anatomy.category.clone(),
+                date_removed: dates::get_timestamp(),
+                label: anatomy.label.clone(),
+            });
+            removed.push(format!("{}/{}", anatomy.category, anatomy.label));
+        }
+    }
+
+    if !removed.is_empty() {
+        // The table is rewritten before any secret is deleted, so an interrupted cleanup never
+        // leaves the table pointing at a secret that no longer exists.
+        secure::encrypt_lookup_table(password, &mut lookup_table)?;
+
+        for hash_id in &expired_hashes {
+            utils::remove_hash_directory(hash_id)?;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Print a warning if a secret has expired.
+pub fn warn_if_expired(anatomy: &Anatomy) {
+    if is_expired(anatomy, dates::get_now()) {
+        println!(
+            "{}",
+            Color::Fixed(172).bold().paint(format!(
+                "⚠️  This secret expired on {}. Replace it with `hmu edit`.",
+                anatomy.expires_at.as_deref().unwrap_or("N/A")
+            ))
+        );
+    }
+}
+
+/// Print every secret that needs attention.
+pub fn print_due_secrets(due_secrets: &[DueSecret]) {
+    println!(
+        "\n{} {}",
+        Style::new().bold().underline().paint("Due secrets"),
+        Style::new()
+            .dimmed()
+            .paint(format!("({})", due_secrets.len()))
+    );
+
+    if due_secrets.is_empty() {
+        println!(
+            "\n{}",
+            Color::Green
+                .bold()
+                .paint("No secrets are expired or due for rotation.")
+        );
+    } else {
+        println!();
+    }
+
+    for due_secret in due_secrets {
+        let status = match due_secret.reason {
+            DueReason::Expired(days) => Color::Red.bold().paint(format!("expired {days} days ago")),
+            DueReason::Expiring(days) => Color::Yellow
+                .bold()
+                .paint(format!("expires in {days} days")),
+            DueReason::RotationDue(days) => Color::Cyan
+                .bold()
+                .paint(format!("rotation overdue by {days} days")),
+        };
+
+        println!("    {:<40} {status}", due_secret.name);
+    }
+
+    println!();
+}
+
+#[cfg(test)]
+pub mod test_expiry {
+    use super::*;
+
+    /// Test whether expired, expiring, and overdue secrets are reported.
+    #[test]
+    fn test_get_due_reason() {
+        let now = dates::parse_timestamp("03-01-2022 00:00:00").unwrap();
+
+        for (expires_at, rotation_days, due_reason) in [
+            (
+                Some("02-20-2022 00:00:00"),
+                None,
+                Some(DueReason::Expired(9)),
+            ),
+            (
+                Some("03-04-2022 00:00:00"),
+                None,
+                Some(DueReason::Expiring(3)),
+            ),
+            (Some("04-01-2022 00:00:00"), None, None),
+            (None, Some(30), Some(DueReason::RotationDue(29))),
+            (None, Some(90), None),
+            (None, None, None),
+        ] {
+            let anatomy = Anatomy {
+                expires_at: expires_at.map(str::to_string),
+                rotation_days,
+                ..Anatomy::create_for_test("unclassified", "something", "01-01-2022 00:00:00")
+            };
+
+            assert_eq!(get_due_reason(&anatomy, now, 7), due_reason);
+        }
+    }
+
+    /// Test whether rotating a secret resets its rotation interval.
+    #[test]
+    fn test_rotation_resets() {
+        let now = dates::parse_timestamp("03-01-2022 00:00:00").unwrap();
+        let anatomy = Anatomy {
+            last_rotated: Some("02-15-2022 00:00:00".to_string()),
+            rotation_days: Some(30),
+            ..Anatomy::create_for_test("unclassified", "something", "01-01-2022 00:00:00")
+        };
+
+        assert_eq!(get_due_reason(&anatomy, now, 7), None);
+        assert!(!is_expired(&anatomy, now));
+    }
+}
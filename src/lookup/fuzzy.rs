@@ -0,0 +1,167 @@
+//! Contains fuzzy matching for search queries, scored like fzf and skim.
+//!
+//! A pattern matches text if its characters appear in the text in order, such as `ghtk` in
+//! `github token`. Each matched character scores points, with bonuses for characters at the start
+//! of a word and for characters matched in a row, and gaps between matched characters cost points.
+//! Of all the ways a pattern matches, the one with the highest score is used.
+
+/// The points scored by each matched character.
+const SCORE_MATCH: i64 = 16;
+/// The points lost by the first character of a gap between matched characters.
+const PENALTY_GAP_START: i64 = 3;
+/// The points lost by every other character of a gap.
+const PENALTY_GAP_EXTENSION: i64 = 1;
+/// The bonus for a character at the start of a word, such as `t` in `github token`.
+const BONUS_BOUNDARY: i64 = SCORE_MATCH / 2;
+/// The bonus for a character starting a word within camelCase or numbers, such as `T` in
+/// `githubToken`.
+const BONUS_CAMEL: i64 = BONUS_BOUNDARY - 1;
+/// The bonus for a character matched right after the previous matched character.
+const BONUS_CONSECUTIVE: i64 = PENALTY_GAP_START + PENALTY_GAP_EXTENSION;
+/// The pattern's first character counts its bonus this many times.
+const BONUS_FIRST_CHARACTER_MULTIPLIER: i64 = 2;
+
+/// Contains the result of fuzzy matching a pattern against some text.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// The byte indexes of the matched characters in the text.
+    pub positions: Vec<usize>,
+    /// How well the pattern matches; higher is better.
+    pub score: i64,
+}
+
+/// Fold a character's case so matching does not regard case.
+fn fold_case(character: char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+/// Get the bonus for matching a character, which depends on the character before it.
+fn get_bonus(previous: Option<char>, character: char) -> i64 {
+    match previous {
+        None => BONUS_BOUNDARY,
+        Some(previous) if !previous.is_alphanumeric() && character.is_alphanumeric() => {
+            BONUS_BOUNDARY
+        }
+        Some(previous)
+            if (previous.is_lowercase() && character.is_uppercase())
+                || (!previous.is_numeric() && character.is_numeric()) =>
+        {
+            BONUS_CAMEL
+        }
+        _ => 0,
+    }
+}
+
+/// Get the points lost by a gap of `length` characters between matched characters.
+fn get_gap_penalty(length: usize) -> i64 {
+    match length {
+        0 => 0,
+        length => PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (length as i64 - 1),
+    }
+}
+
+/// Match a pattern against some text without regard to case. Returns `None` if the pattern's
+/// characters do not all appear in the text in order, or if the pattern is empty.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern = pattern.chars().map(fold_case).collect::<Vec<char>>();
+    let characters = text.char_indices().collect::<Vec<(usize, char)>>();
+
+    if pattern.is_empty() || pattern.len() > characters.len() {
+        return None;
+    }
+
+    let bonuses = characters
+        .iter()
+        .enumerate()
+        .map(|(index, (_, character))| {
+            get_bonus(
+                index.checked_sub(1).map(|previous| characters[previous].1),
+                *character,
+            )
+        })
+        .collect::<Vec<i64>>();
+
+    // `scores[i][j]` is the best score of matching the first `i + 1` characters of the pattern
+    // with the pattern's `i`th character matched at the text's `j`th character, and `previous[i][j]`
+    // is where the pattern's previous character was matched.
+    let mut scores = vec![vec![None; characters.len()]; pattern.len()];
+    let mut previous = vec![vec![0; characters.len()]; pattern.len()];
+
+    for (pattern_index, pattern_character) in pattern.iter().enumerate() {
+        for (text_index, (_, character)) in characters.iter().enumerate() {
+            if fold_case(*character) != *pattern_character {
+                continue;
+            }
+
+            if pattern_index == 0 {
+                scores[0][text_index] =
+                    Some(SCORE_MATCH + bonuses[text_index] * BONUS_FIRST_CHARACTER_MULTIPLIER);
+                continue;
+            }
+
+            for last_index in 0..text_index {
+                let Some(last_score) = scores[pattern_index - 1][last_index] else {
+                    continue;
+                };
+
+                let bonus = if last_index + 1 == text_index {
+                    bonuses[text_index].max(BONUS_CONSECUTIVE)
+                } else {
+                    bonuses[text_index]
+                };
+                let score =
+                    last_score - get_gap_penalty(text_index - last_index - 1) + SCORE_MATCH + bonus;
+
+                if scores[pattern_index][text_index].is_none_or(|best| score > best) {
+                    scores[pattern_index][text_index] = Some(score);
+                    previous[pattern_index][text_index] = last_index;
+                }
+            }
+        }
+    }
+
+    let (mut text_index, score) = scores[pattern.len() - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(index, score)| score.map(|score| (index, score)))
+        .max_by_key(|(index, score)| (*score, std::cmp::Reverse(*index)))?;
+
+    let mut positions = vec![characters[text_index].0];
+    for pattern_index in (1..pattern.len()).rev() {
+        text_index = previous[pattern_index][text_index];
+        positions.push(characters[text_index].0);
+    }
+    positions.reverse();
+
+    Some(FuzzyMatch { positions, score })
+}
+
+#[cfg(test)]
+pub mod test_fuzzy {
+    use super::*;
+
+    /// Test whether patterns match as subsequences without regard to case.
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(
+            fuzzy_match("ghtk", "GitHub Token").unwrap().positions,
+            vec![0, 3, 7, 9]
+        );
+        assert!(fuzzy_match("c++", "c++ compiler").is_some());
+        assert!(fuzzy_match("tkgh", "GitHub Token").is_none());
+        assert!(fuzzy_match("", "GitHub Token").is_none());
+
+        let positions = fuzzy_match("sé", "très sécret").unwrap().positions;
+        assert_eq!(positions, vec![6, 7]);
+    }
+
+    /// Test whether word starts and consecutive characters score higher than scattered ones.
+    #[test]
+    fn test_fuzzy_match_ranking() {
+        let score = |pattern, text| fuzzy_match(pattern, text).unwrap().score;
+
+        assert!(score("aws", "aws root") > score("aws", "always secret"));
+        assert!(score("db", "prod db") > score("db", "dumb"));
+        assert!(score("gt", "githubToken") > score("gt", "gitlab"));
+    }
+}
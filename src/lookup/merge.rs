@@ -0,0 +1,527 @@
+//! Contains functions for merging another vault into the current vault.
+
+use std::{cmp::Ordering, collections::HashMap, path::Path};
+
+use ansi_term::{Color, Style};
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    models::metadata::{Anatomy, LookupTable, Tombstone},
+    prompts::merge::{self as merge_prompts, MergeSide},
+    utils::dates,
+};
+
+use super::{secure, utils};
+
+/// The number of days a tombstone is kept. A vault copy older than this may bring a removed
+/// secret back when it is merged.
+const TOMBSTONE_RETENTION_DAYS: i64 = 365;
+
+/// Contains the results of a merge, grouped by what happened to each secret. Each secret is
+/// recorded as `category/label`.
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    /// Secrets that only existed in the other vault and were copied over.
+    pub added: Vec<String>,
+    /// Secrets that changed on both sides and kept the current vault's version.
+    pub kept: Vec<String>,
+    /// Secrets that were removed because they were removed in the other vault.
+    pub removed: Vec<String>,
+    /// Secrets that were not copied over because they were removed in the current vault.
+    pub skipped: Vec<String>,
+    /// Secrets that changed on both sides and took the other vault's version.
+    pub updated: Vec<String>,
+}
+
+/// Try to decrypt the other vault's lookup table with the current master password, then prompt
+/// for the other vault's master password if it differs. Returns the other vault's lookup table
+/// and master password.
+pub fn unlock_other_vault(
+    other_vault_dir: &Path,
+    password: &str,
+) -> Result<(LookupTable, String), HimitsuError> {
+    let other_lookup_dir = other_vault_dir.join("lookup");
+    if !other_lookup_dir.join("table").exists() {
+        return Err(HimitsuError::MergeError(format!(
+            "{} does not contain a himitsu vault!",
+            other_vault_dir.display()
+        )));
+    }
+
+    if let Ok(other_lookup_table) = secure::decrypt_lookup_table_in(&other_lookup_dir, password) {
+        return Ok((other_lookup_table, password.to_string()));
+    }
+
+    for _ in 0..3 {
+        let other_password = merge_prompts::run_get_other_password()?;
+
+        if let Ok(other_lookup_table) =
+            secure::decrypt_lookup_table_in(&other_lookup_dir, &other_password)
+        {
+            return Ok((other_lookup_table, other_password));
+        }
+
+        println!(
+            "{}",
+            Color::Fixed(172)
+                .bold()
+                .paint("That password does not unlock the other vault.")
+        );
+    }
+
+    Err(HimitsuError::MergeError(
+        "Could not unlock the other vault!".to_string(),
+    ))
+}
+
+/// Merge the other vault into the current vault. Secrets are matched by their label and category.
+///
+/// * Secrets that only exist in the other vault are copied over unless they were removed in the
+///   current vault after they were last modified.
+/// * Secrets that only exist in the current vault are removed if they were removed in the other
+///   vault after they were last modified.
+/// * Secrets that exist in both vaults but differ in their value or metadata (see
+///   `has_same_metadata()`) keep the most recently modified side as a whole. If neither side is
+///   more recent, or `interactive` is set, the user chooses a side.
+///
+/// Replaced and removed secrets are only deleted from the `closet` once the merged lookup table is
+/// saved. If the merge fails before then, the secrets copied over so far are deleted instead, so
+/// the vault is left as it was.
+pub fn merge_vaults(
+    other_vault_dir: &Path,
+    other_lookup_table: &LookupTable,
+    other_password: &str,
+    password: &str,
+    interactive: bool,
+) -> Result<MergeReport, HimitsuError> {
+    let mut lookup_table = secure::decrypt_lookup_table(password)?;
+    let mut closet_changes = ClosetChanges::default();
+
+    let merged = merge_lookup_tables(
+        other_vault_dir,
+        other_lookup_table,
+        other_password,
+        &mut lookup_table,
+        password,
+        interactive,
+        &mut closet_changes,
+    )
+    .and_then(|report| {
+        secure::encrypt_lookup_table(password, &mut lookup_table)?;
+
+        Ok(report)
+    });
+
+    match merged {
+        Ok(report) => {
+            for hash_id in closet_changes.stale_hashes {
+                utils::remove_hash_directory(&hash_id)?;
+            }
+
+            Ok(report)
+        }
+        Err(error) => {
+            // The merged lookup table was not saved, so nothing refers to the copied secrets.
+            for hash_id in closet_changes.new_hashes {
+                let _ = utils::remove_hash_directory(&hash_id);
+            }
+
+            Err(error)
+        }
+    }
+}
+
+/// Contains the changes a merge makes to the `closet`.
+#[derive(Default)]
+struct ClosetChanges {
+    /// The hash IDs of secrets copied into the `closet`.
+    new_hashes: Vec<String>,
+    /// The hash IDs of secrets to delete from the `closet` once the lookup table is saved.
+    stale_hashes: Vec<String>,
+}
+
+/// Merge the other vault's lookup table into the current vault's lookup table without saving it.
+/// Secrets copied into and due to be deleted from the `closet` are recorded in `closet_changes`.
+fn merge_lookup_tables(
+    other_vault_dir: &Path,
+    other_lookup_table: &LookupTable,
+    other_password: &str,
+    lookup_table: &mut LookupTable,
+    password: &str,
+    interactive: bool,
+    closet_changes: &mut ClosetChanges,
+) -> Result<MergeReport, HimitsuError> {
+    let mut report = MergeReport::default();
+
+    let mut local_hashes: HashMap<(String, String), String> = HashMap::new();
+    for (hash_id, anatomy) in lookup_table.table.iter() {
+        local_hashes.insert(
+            (anatomy.category.clone(), anatomy.label.clone()),
+            hash_id.clone(),
+        );
+    }
+
+    let mut other_entries = other_lookup_table.table.iter().collect::<Vec<_>>();
+    other_entries.sort_by(|a, b| (&a.1.category, &a.1.label).cmp(&(&b.1.category, &b.1.label)));
+
+    for (other_hash, other_anatomy) in other_entries {
+        let key = (other_anatomy.category.clone(), other_anatomy.label.clone());
+        let name = format!("{}/{}", other_anatomy.category, other_anatomy.label);
+        let other_hash_path = other_vault_dir.join("closet").join(other_hash);
+
+        match local_hashes.get(&key).cloned() {
+            Some(local_hash) => {
+                let local_anatomy = lookup_table.table[&local_hash].clone();
+
+                let local_secret = decrypt::decrypt_secret_in(
+                    &decrypt::get_secret_hash_path(&local_hash)?,
+                    password,
+                )?;
+                let other_secret = decrypt::decrypt_secret_in(&other_hash_path, other_password)?;
+
+                if local_secret == other_secret && has_same_metadata(&local_anatomy, other_anatomy)
+                {
+                    continue;
+                }
+
+                let side = match choose_side(&local_anatomy, other_anatomy) {
+                    Some(side) if !interactive => side,
+                    _ => merge_prompts::run_resolve_conflict(&local_anatomy, other_anatomy)?,
+                };
+
+                match side {
+                    MergeSide::Current => report.kept.push(name),
+                    MergeSide::Other => {
+                        let new_hash = encrypt::import_secret(
+                            other_anatomy,
+                            lookup_table,
+                            password,
+                            &other_secret,
+                        )?;
+                        closet_changes.new_hashes.push(new_hash.clone());
+                        local_hashes.insert(key, new_hash);
+
+                        lookup_table.table.remove(&local_hash);
+                        closet_changes.stale_hashes.push(local_hash);
+
+                        report.updated.push(name);
+                    }
+                }
+            }
+            None => {
+                if is_removed(&lookup_table.removed, other_anatomy) {
+                    report.skipped.push(name);
+                    continue;
+                }
+
+                let other_secret = decrypt::decrypt_secret_in(&other_hash_path, other_password)?;
+                let new_hash =
+                    encrypt::import_secret(other_anatomy, lookup_table, password, &other_secret)?;
+                closet_changes.new_hashes.push(new_hash.clone());
+                local_hashes.insert(key, new_hash);
+
+                report.added.push(name);
+            }
+        }
+    }
+
+    let removed_hashes = lookup_table
+        .table
+        .iter()
+        .filter(|(_, anatomy)| {
+            !other_lookup_table.table.values().any(|other_anatomy| {
+                other_anatomy.category == anatomy.category && other_anatomy.label == anatomy.label
+            }) && is_removed(&other_lookup_table.removed, anatomy)
+        })
+        .map(|(hash_id, _)| hash_id.clone())
+        .collect::<Vec<String>>();
+
+    for hash_id in removed_hashes {
+        if let Some(anatomy) = lookup_table.table.remove(&hash_id) {
+            closet_changes.stale_hashes.push(hash_id);
+
+            report
+                .removed
+                .push(format!("{}/{}", anatomy.category, anatomy.label));
+        }
+    }
+
+    for tombstone in other_lookup_table.removed.iter() {
+        if !lookup_table.removed.iter().any(|existing| {
+            existing.category == tombstone.category
+                && existing.label == tombstone.label
+                && existing.date_removed == tombstone.date_removed
+        }) {
+            lookup_table.removed.push(tombstone.clone());
+        }
+    }
+
+    Ok(report)
+}
+
+/// Check whether two versions of a secret have the same metadata, apart from their dates.
+fn has_same_metadata(local_anatomy: &Anatomy, other_anatomy: &Anatomy) -> bool {
+    local_anatomy.expires_at == other_anatomy.expires_at
+        && local_anatomy.protection == other_anatomy.protection
+        && local_anatomy.rotation_days == other_anatomy.rotation_days
+        && local_anatomy.secret_type == other_anatomy.secret_type
+        && local_anatomy.tags == other_anatomy.tags
+        && local_anatomy.temporary == other_anatomy.temporary
+        && local_anatomy.url == other_anatomy.url
+        && local_anatomy.username == other_anatomy.username
+}
+
+/// Choose which side of a conflict to keep based on when each side was last modified, then when
+/// each side was last accessed. Returns `None` if neither side is more recent.
+fn choose_side(local_anatomy: &Anatomy, other_anatomy: &Anatomy) -> Option<MergeSide> {
+    let compare_dates = |local: Option<&str>, other: Option<&str>| match (
+        local.and_then(dates::parse_timestamp),
+        other.and_then(dates::parse_timestamp),
+    ) {
+        (Some(local), Some(other)) => local.cmp(&other),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => Ordering::Equal,
+    };
+
+    let ordering = compare_dates(
+        Some(local_anatomy.get_modified_date()),
+        Some(other_anatomy.get_modified_date()),
+    )
+    .then_with(|| {
+        compare_dates(
+            local_anatomy.last_accessed.as_deref(),
+            other_anatomy.last_accessed.as_deref(),
+        )
+    });
+
+    match ordering {
+        Ordering::Greater => Some(MergeSide::Current),
+        Ordering::Less => Some(MergeSide::Other),
+        Ordering::Equal => None,
+    }
+}
+
+/// Check whether a secret was removed after it was last modified.
+fn is_removed(tombstones: &[Tombstone], anatomy: &Anatomy) -> bool {
+    let modified_date = dates::parse_timestamp(anatomy.get_modified_date());
+
+    tombstones.iter().any(|tombstone| {
+        tombstone.category == anatomy.category
+            && tombstone.label == anatomy.label
+            && match (
+                dates::parse_timestamp(&tombstone.date_removed),
+                modified_date,
+            ) {
+                (Some(date_removed), Some(modified_date)) => date_removed >= modified_date,
+                _ => false,
+            }
+    })
+}
+
+/// Drop tombstones that no longer have to be propagated: tombstones older than
+/// `TOMBSTONE_RETENTION_DAYS`, tombstones of secrets that were added again after they were
+/// removed, and every tombstone of a secret but its most recent one.
+pub fn prune_tombstones(lookup_table: &mut LookupTable, now: NaiveDateTime) {
+    let oldest_kept = now - Duration::days(TOMBSTONE_RETENTION_DAYS);
+    let mut newest: HashMap<(String, String), (NaiveDateTime, Tombstone)> = HashMap::new();
+
+    for tombstone in lookup_table.removed.drain(..) {
+        let date_removed = match dates::parse_timestamp(&tombstone.date_removed) {
+            Some(date_removed) if date_removed >= oldest_kept => date_removed,
+            _ => continue,
+        };
+        let re_added = lookup_table.table.values().any(|anatomy| {
+            anatomy.category == tombstone.category
+                && anatomy.label == tombstone.label
+                && dates::parse_timestamp(anatomy.get_modified_date())
+                    .is_some_and(|modified_date| modified_date > date_removed)
+        });
+        if re_added {
+            continue;
+        }
+
+        let key = (tombstone.category.clone(), tombstone.label.clone());
+        if newest
+            .get(&key)
+            .is_none_or(|(kept_date, _)| date_removed > *kept_date)
+        {
+            newest.insert(key, (date_removed, tombstone));
+        }
+    }
+
+    lookup_table.removed = newest
+        .into_values()
+        .map(|(_, tombstone)| tombstone)
+        .collect();
+    lookup_table
+        .removed
+        .sort_by(|a, b| (&a.category, &a.label).cmp(&(&b.category, &b.label)));
+}
+
+/// Print the merge report.
+pub fn print_merge_report(report: &MergeReport) {
+    let sections = [
+        ("Added", Color::Green, &report.added),
+        ("Updated", Color::Cyan, &report.updated),
+        ("Kept", Color::Blue, &report.kept),
+        ("Removed", Color::Red, &report.removed),
+        (
+            "Skipped (removed in this vault)",
+            Color::Yellow,
+            &report.skipped,
+        ),
+    ];
+
+    println!(
+        "\n{}",
+        Style::new().bold().underline().paint("Merge report")
+    );
+
+    for (title, color, secrets) in sections {
+        println!(
+            "\n{} {}",
+            color.bold().paint(title),
+            Style::new().dimmed().paint(format!("({})", secrets.len()))
+        );
+
+        for secret in secrets {
+            println!("    {secret}");
+        }
+    }
+
+    println!();
+}
+
+#[cfg(test)]
+pub mod test_merge {
+    use super::*;
+
+    use crate::models::metadata::Protection;
+
+    /// Test whether the most recently modified side is chosen.
+    #[test]
+    fn test_choose_side_by_modified_date() {
+        let created = Anatomy::create_for_test("unclassified", "something", "01-01-2022 00:00:00");
+        let local = Anatomy {
+            last_modified: Some("02-01-2022 00:00:00".to_string()),
+            ..created.clone()
+        };
+        let other = Anatomy {
+            last_accessed: Some("03-01-2022 00:00:00".to_string()),
+            ..created
+        };
+
+        assert!(matches!(
+            choose_side(&local, &other),
+            Some(MergeSide::Current)
+        ));
+        assert!(matches!(
+            choose_side(&other, &local),
+            Some(MergeSide::Other)
+        ));
+    }
+
+    /// Test whether the last accessed date breaks ties and identical sides are left to the user.
+    #[test]
+    fn test_choose_side_by_last_accessed() {
+        let local = Anatomy::create_for_test("unclassified", "something", "01-01-2022 00:00:00");
+        let other = Anatomy {
+            last_accessed: Some("03-01-2022 00:00:00".to_string()),
+            ..local.clone()
+        };
+
+        assert!(matches!(
+            choose_side(&local, &other),
+            Some(MergeSide::Other)
+        ));
+        assert!(choose_side(&local, &local.clone()).is_none());
+    }
+
+    /// Test whether differing metadata counts as a conflict, while differing dates do not.
+    #[test]
+    fn test_has_same_metadata() {
+        let local = Anatomy::create_for_test("unclassified", "something", "01-01-2022 00:00:00");
+        let accessed = Anatomy {
+            last_accessed: Some("03-01-2022 00:00:00".to_string()),
+            ..local.clone()
+        };
+        let expires = Anatomy {
+            expires_at: Some("03-01-2022 00:00:00".to_string()),
+            ..local.clone()
+        };
+        let protected = Anatomy {
+            protection: Protection::Reauth,
+            ..local.clone()
+        };
+        let renamed = Anatomy {
+            username: Some("alice".to_string()),
+            ..local.clone()
+        };
+
+        assert!(has_same_metadata(&local, &accessed));
+        assert!(!has_same_metadata(&local, &expires));
+        assert!(!has_same_metadata(&local, &protected));
+        assert!(!has_same_metadata(&local, &renamed));
+    }
+
+    /// Test whether old, superseded, and duplicate tombstones are dropped.
+    #[test]
+    fn test_prune_tombstones() {
+        let tombstone = |label: &str, date_removed: &str| Tombstone {
+            category: "unclassified".to_string(),
+            date_removed: date_removed.to_string(),
+            label: label.to_string(),
+        };
+        let mut lookup_table = LookupTable::new();
+        lookup_table.table.insert(
+            "hash".to_string(),
+            Anatomy::create_for_test("unclassified", "re-added", "03-01-2022 00:00:00"),
+        );
+        lookup_table.removed = vec![
+            tombstone("old", "01-01-2020 00:00:00"),
+            tombstone("re-added", "02-01-2022 00:00:00"),
+            tombstone("twice", "02-01-2022 00:00:00"),
+            tombstone("twice", "04-01-2022 00:00:00"),
+            tombstone("once", "04-01-2022 00:00:00"),
+        ];
+
+        prune_tombstones(
+            &mut lookup_table,
+            dates::parse_timestamp("05-01-2022 00:00:00").unwrap(),
+        );
+
+        let kept = lookup_table
+            .removed
+            .iter()
+            .map(|tombstone| (tombstone.label.as_str(), tombstone.date_removed.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kept,
+            vec![
+                ("once", "04-01-2022 00:00:00"),
+                ("twice", "04-01-2022 00:00:00")
+            ]
+        );
+    }
+
+    /// Test whether a secret is only considered removed if it was removed after its last edit.
+    #[test]
+    fn test_is_removed() {
+        let anatomy = Anatomy {
+            last_modified: Some("02-01-2022 00:00:00".to_string()),
+            ..Anatomy::create_for_test("unclassified", "something", "01-01-2022 00:00:00")
+        };
+        let tombstone = |date_removed: &str| Tombstone {
+            category: "unclassified".to_string(),
+            date_removed: date_removed.to_string(),
+            label: "something".to_string(),
+        };
+
+        assert!(is_removed(&[tombstone("03-01-2022 00:00:00")], &anatomy));
+        assert!(!is_removed(&[tombstone("01-15-2022 00:00:00")], &anatomy));
+        assert!(!is_removed(&[], &anatomy));
+    }
+}
@@ -1,6 +1,11 @@
 //! Contains functions for secrets lookup.
 
+pub mod expiry;
+pub mod fuzzy;
 pub mod init;
+pub mod merge;
 pub mod modify;
+pub mod query;
+pub mod rekey;
 pub mod secure;
 pub mod utils;
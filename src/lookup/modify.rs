@@ -1,12 +1,13 @@
 //! Contains functions that modify the lookup table.
 
 use ansi_term::Color;
-use chrono::Local;
 use spinners::{Spinner, Spinners};
 
 use crate::{
+    encryption::encrypt,
     errors::HimitsuError,
-    models::metadata::{Anatomy, LookupTable},
+    models::metadata::{Anatomy, LookupTable, Tombstone},
+    utils::dates,
 };
 
 use super::{secure, utils};
@@ -34,8 +35,8 @@ pub fn update_last_accessed(hash_id: &str, password: &str) -> Result<(), Himitsu
     let mut lookup_table = secure::decrypt_lookup_table(password)?;
 
     match lookup_table.table.get_mut(hash_id) {
-        Some(mut anatomy) => {
-            anatomy.last_accessed = Some(Local::now().format("%m-%d-%Y %H:%M:%S").to_string());
+        Some(anatomy) => {
+            anatomy.last_accessed = Some(dates::get_timestamp());
 
             secure::encrypt_lookup_table(password, &mut lookup_table)?;
 
@@ -55,6 +56,29 @@ pub enum RemovalEvent {
     Replace,
 }
 
+/// Remove an existing hash and corresponding `Anatomy` in an already decrypted lookup table, and
+/// remove its hash directory, without displaying any spinners. The lookup table is not
+/// re-encrypted.
+pub fn remove_from_lookup_table(
+    lookup_table: &mut LookupTable,
+    hash_id: &str,
+    removal_event: &RemovalEvent,
+) -> Result<(), HimitsuError> {
+    let found_match = lookup_table.table.remove(hash_id).ok_or_else(|| {
+        HimitsuError::LookupError("Could not find an existing Anatomy for this secret!".to_string())
+    })?;
+
+    if let RemovalEvent::Remove = removal_event {
+        lookup_table.removed.push(Tombstone {
+            category: found_match.category,
+            date_removed: dates::get_timestamp(),
+            label: found_match.label,
+        });
+    }
+
+    utils::remove_hash_directory(hash_id)
+}
+
 /// Remove an existing hash and corresponding `Anatomy` in the lookup table.
 pub fn remove_in_lookup_table(
     hash_id: &str,
@@ -71,12 +95,8 @@ pub fn remove_in_lookup_table(
 
     let mut lookup_table = secure::decrypt_lookup_table(password)?;
 
-    match lookup_table.table.get(hash_id) {
-        Some(_found_match) => {
-            lookup_table.table.remove(hash_id);
-
-            utils::remove_hash_directory(hash_id)?;
-
+    match remove_from_lookup_table(&mut lookup_table, hash_id, &removal_event) {
+        Ok(()) => {
             secure::encrypt_lookup_table(password, &mut lookup_table)?;
 
             removal_spinner.stop_and_persist(
@@ -92,7 +112,7 @@ pub fn remove_in_lookup_table(
 
             Ok(())
         }
-        None => {
+        Err(error) => {
             removal_spinner.stop_and_persist(
                 "❗️",
                 Color::Red
@@ -101,9 +121,33 @@ pub fn remove_in_lookup_table(
                     .to_string(),
             );
 
-            Err(HimitsuError::LookupError(
-                "Could not find an existing Anatomy for this secret!".to_string(),
-            ))
+            Err(error)
         }
     }
 }
+
+/// Replace an existing secret with a new value and `Anatomy` without displaying any spinners.
+/// The new secret is written and the lookup table is pointed at it before the old secret's hash
+/// directory is removed, so an interrupted replacement never loses the secret. Returns the new
+/// secret's hash ID.
+pub fn replace_secret(
+    hash_id: &str,
+    anatomy: &Anatomy,
+    password: &str,
+    secret: &str,
+) -> Result<String, HimitsuError> {
+    let mut lookup_table = secure::decrypt_lookup_table(password)?;
+    if !lookup_table.table.contains_key(hash_id) {
+        return Err(HimitsuError::LookupError(
+            "Could not find an existing Anatomy for this secret!".to_string(),
+        ));
+    }
+
+    let new_hash = encrypt::import_secret(anatomy, &mut lookup_table, password, secret)?;
+    lookup_table.table.remove(hash_id);
+    secure::encrypt_lookup_table(password, &mut lookup_table)?;
+
+    utils::remove_hash_directory(hash_id)?;
+
+    Ok(new_hash)
+}
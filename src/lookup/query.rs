@@ -0,0 +1,474 @@
+//! Contains the query language used to search for secrets, such as
+//! `aws tag:prod cat:cloud created:>2024-01-01 -tag:old`.
+//!
+//! A query is made of terms separated by whitespace, and a secret has to match every term. Terms
+//! are either text or `field:value` filters, and a term starting with `-` excludes the secrets it
+//! matches. Values containing spaces are wrapped in double quotes, such as `tag:"team a"`.
+//! Everything is matched without regard to case.
+//!
+//! Text is fuzzy matched against the label, category, and tags (see `lookup::fuzzy`), and the
+//! scores of every text term are added up to rank the results. Excluded text only excludes secrets
+//! containing it as is. With `TextMode::Regex`, text is a regex matched against the label instead.
+
+use std::ops::Range;
+
+use ansi_term::{Color, Style};
+use chrono::{Duration, NaiveDateTime};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    errors::HimitsuError,
+    lookup::fuzzy,
+    models::metadata::{Anatomy, SecretType},
+    utils::dates,
+};
+
+/// Contains variants for how text in a query is matched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextMode {
+    /// Fuzzy match text, such as `ghtk` for `github token`.
+    Fuzzy,
+    /// Match text with Regex expressions (`--regex`).
+    Regex,
+}
+
+/// Contains variants for the dates a query may filter by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateField {
+    /// The date a secret was last accessed (`accessed:`).
+    Accessed,
+    /// The date a secret was created (`created:`).
+    Created,
+    /// The date a secret expires (`expires:`).
+    Expires,
+    /// The date a secret was last modified (`modified:`).
+    Modified,
+}
+
+/// Contains variants for how a date filter compares dates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// After the date (`>`).
+    After,
+    /// At or after the date (`>=`), which is also used for durations without an operator.
+    AtOrAfter,
+    /// At or before the date (`<=`).
+    AtOrBefore,
+    /// Before the date (`<`).
+    Before,
+    /// On the same day as the date, used for dates without an operator.
+    SameDay,
+}
+
+/// Contains variants for each kind of filter in a query.
+#[derive(Debug)]
+pub enum Filter {
+    /// Match the secret's category (`cat:` or `category:`).
+    Category(String),
+    /// Match text the secret's label, category, or tags contain as is. Used for excluded text.
+    Contains {
+        /// Whether only the label is matched (`label:`).
+        label_only: bool,
+        /// The lowercase text.
+        text: String,
+    },
+    /// Compare one of the secret's dates, such as `created:>2024-01-01`.
+    Date(DateField, Comparison, NaiveDateTime),
+    /// Fuzzy match the secret's label, category, or tags.
+    Fuzzy {
+        /// Whether only the label is matched (`label:`).
+        label_only: bool,
+        /// The pattern.
+        pattern: String,
+    },
+    /// Match part of the secret's label with a Regex expression.
+    Regex(Regex),
+    /// Match one of the secret's tags (`tag:`).
+    Tag(String),
+    /// Match the kind of secret (`type:password` or `type:totp`).
+    Type(SecretType),
+}
+
+/// Contains a single term in a query.
+#[derive(Debug)]
+pub struct Term {
+    /// The filter this term applies.
+    pub filter: Filter,
+    /// Whether the term excludes the secrets it matches (a leading `-`).
+    pub negated: bool,
+}
+
+/// Contains a parsed query.
+#[derive(Debug)]
+pub struct Query {
+    /// The terms a secret has to match.
+    pub terms: Vec<Term>,
+}
+
+/// Split a query into words on whitespace. Double quotes group words containing spaces and are
+/// removed.
+fn split_words(query: &str) -> Result<Vec<String>, HimitsuError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_quotes = false;
+    let mut has_word = false;
+
+    for character in query.chars() {
+        match character {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_word = true;
+            }
+            character if character.is_whitespace() && !in_quotes => {
+                if has_word {
+                    words.push(word.clone());
+                    word.clear();
+                    has_word = false;
+                }
+            }
+            character => {
+                word.push(character);
+                has_word = true;
+            }
+        }
+    }
+
+    if in_quotes {
+        return Err(HimitsuError::QueryError(format!(
+            "The query \"{query}\" has an unclosed quote!"
+        )));
+    }
+    if has_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// Build the filter for text in a query.
+fn build_text_filter(
+    text: &str,
+    label_only: bool,
+    negated: bool,
+    text_mode: TextMode,
+) -> Result<Filter, HimitsuError> {
+    Ok(match text_mode {
+        TextMode::Fuzzy if negated => Filter::Contains {
+            label_only,
+            text: text.to_lowercase(),
+        },
+        TextMode::Fuzzy => Filter::Fuzzy {
+            label_only,
+            pattern: text.to_string(),
+        },
+        TextMode::Regex => Filter::Regex(RegexBuilder::new(text).case_insensitive(true).build()?),
+    })
+}
+
+/// Parse a date filter's value, such as `>2024-01-01`, `<=30d`, or `2024-01-01`. Durations count
+/// back from `now`.
+fn parse_date_filter(
+    value: &str,
+    now: NaiveDateTime,
+) -> Result<(Comparison, NaiveDateTime), HimitsuError> {
+    let (comparison, date) = if let Some(date) = value.strip_prefix(">=") {
+        (Some(Comparison::AtOrAfter), date)
+    } else if let Some(date) = value.strip_prefix("<=") {
+        (Some(Comparison::AtOrBefore), date)
+    } else if let Some(date) = value.strip_prefix('>') {
+        (Some(Comparison::After), date)
+    } else if let Some(date) = value.strip_prefix('<') {
+        (Some(Comparison::Before), date)
+    } else {
+        (None, value)
+    };
+
+    let parsed_date = dates::parse_since(date, now).ok_or_else(|| {
+        HimitsuError::QueryError(format!(
+            "\"{date}\" is not a date (YYYY-MM-DD) or a duration (such as 30d)!"
+        ))
+    })?;
+
+    let comparison = comparison.unwrap_or(if dates::parse_duration(date).is_some() {
+        Comparison::AtOrAfter
+    } else {
+        Comparison::SameDay
+    });
+
+    Ok((comparison, parsed_date))
+}
+
+impl Query {
+    /// Parse a query. Durations in date filters count back from `now`.
+    pub fn parse(
+        query: &str,
+        now: NaiveDateTime,
+        text_mode: TextMode,
+    ) -> Result<Self, HimitsuError> {
+        let mut terms = Vec::new();
+
+        for word in split_words(query)? {
+            let (negated, word) = match word.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, word.as_str()),
+            };
+
+            let filter = match word.split_once(':') {
+                Some((field, value)) if !value.is_empty() => match field.to_lowercase().as_str() {
+                    field @ ("accessed" | "created" | "expires" | "modified") => {
+                        let date_field = match field {
+                            "accessed" => DateField::Accessed,
+                            "created" => DateField::Created,
+                            "expires" => DateField::Expires,
+                            _ => DateField::Modified,
+                        };
+                        let (comparison, date) = parse_date_filter(value, now)?;
+
+                        Filter::Date(date_field, comparison, date)
+                    }
+                    "cat" | "category" => Filter::Category(value.to_lowercase()),
+                    "label" => build_text_filter(value, true, negated, text_mode)?,
+                    "tag" => Filter::Tag(value.to_lowercase()),
+                    "type" => match value.to_lowercase().as_str() {
+                        "password" => Filter::Type(SecretType::Password),
+                        "totp" => Filter::Type(SecretType::Totp),
+                        _ => {
+                            return Err(HimitsuError::QueryError(format!(
+                                "\"{value}\" is not a secret type! Use password or totp."
+                            )))
+                        }
+                    },
+                    // Labels may contain colons, such as `git:github.com`.
+                    _ => build_text_filter(word, false, negated, text_mode)?,
+                },
+                _ => build_text_filter(word, false, negated, text_mode)?,
+            };
+
+            terms.push(Term { filter, negated });
+        }
+
+        Ok(Self { terms })
+    }
+
+    /// Score how well a secret matches the query. Returns `None` if the secret does not match
+    /// every term.
+    pub fn score(&self, anatomy: &Anatomy) -> Option<i64> {
+        self.terms.iter().try_fold(0, |total, term| {
+            match (term.filter.score(anatomy), term.negated) {
+                (Some(score), false) => Some(total + score),
+                (None, true) => Some(total),
+                _ => None,
+            }
+        })
+    }
+
+    /// Get the byte ranges of the label matched by the query's text terms.
+    pub fn get_label_ranges(&self, label: &str) -> Vec<Range<usize>> {
+        self.terms
+            .iter()
+            .filter(|term| !term.negated)
+            .flat_map(|term| match &term.filter {
+                Filter::Fuzzy { pattern, .. } => fuzzy::fuzzy_match(pattern, label)
+                    .map(|found| {
+                        found
+                            .positions
+                            .into_iter()
+                            .map(|position| {
+                                let length =
+                                    label[position..].chars().next().map_or(1, char::len_utf8);
+
+                                position..position + length
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                Filter::Regex(regex) => regex
+                    .find(label)
+                    .map(|found| found.range())
+                    .into_iter()
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Paint a secret's label, highlighting the characters matched by the query's text terms.
+    pub fn paint_label(&self, label: &str) -> String {
+        let ranges = self.get_label_ranges(label);
+
+        label
+            .char_indices()
+            .map(|(index, character)| {
+                if ranges.iter().any(|range| range.contains(&index)) {
+                    Color::Red.bold().paint(character.to_string()).to_string()
+                } else {
+                    Style::new().bold().paint(character.to_string()).to_string()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Filter {
+    /// Score how well a secret matches this filter. Returns `None` if the secret does not match.
+    /// Only fuzzy matches score points.
+    pub fn score(&self, anatomy: &Anatomy) -> Option<i64> {
+        let fields = |label_only: bool| {
+            std::iter::once(&anatomy.label).chain(
+                (!label_only)
+                    .then(|| std::iter::once(&anatomy.category).chain(anatomy.tags.iter()))
+                    .into_iter()
+                    .flatten(),
+            )
+        };
+
+        match self {
+            Filter::Contains { label_only, text } => fields(*label_only)
+                .any(|field| field.to_lowercase().contains(text.as_str()))
+                .then_some(0),
+            Filter::Fuzzy {
+                label_only,
+                pattern,
+            } => fields(*label_only)
+                .filter_map(|field| fuzzy::fuzzy_match(pattern, field))
+                .map(|found| found.score)
+                .max(),
+            filter => filter.matches(anatomy).then_some(0),
+        }
+    }
+
+    /// Check whether a secret matches this filter.
+    pub fn matches(&self, anatomy: &Anatomy) -> bool {
+        match self {
+            Filter::Category(category) => anatomy.category.to_lowercase() == *category,
+            Filter::Contains { .. } | Filter::Fuzzy { .. } => self.score(anatomy).is_some(),
+            Filter::Date(date_field, comparison, date) => {
+                let secret_date = match date_field {
+                    DateField::Accessed => anatomy.last_accessed.as_deref(),
+                    DateField::Created => Some(anatomy.date_created.as_str()),
+                    DateField::Expires => anatomy.expires_at.as_deref(),
+                    DateField::Modified => Some(anatomy.get_modified_date()),
+                };
+
+                secret_date
+                    .and_then(dates::parse_timestamp)
+                    .is_some_and(|secret_date| match comparison {
+                        Comparison::After => secret_date > *date,
+                        Comparison::AtOrAfter => secret_date >= *date,
+                        Comparison::AtOrBefore => secret_date <= *date,
+                        Comparison::Before => secret_date < *date,
+                        Comparison::SameDay => {
+                            secret_date >= *date && secret_date < *date + Duration::days(1)
+                        }
+                    })
+            }
+            Filter::Regex(regex) => regex.is_match(&anatomy.label),
+            Filter::Tag(tag) => anatomy
+                .tags
+                .iter()
+                .any(|secret_tag| secret_tag.to_lowercase() == *tag),
+            Filter::Type(secret_type) => anatomy.secret_type == *secret_type,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_query {
+    use super::*;
+
+    /// Test whether terms are combined, negated, and matched without regard to case.
+    #[test]
+    fn test_query_matches() {
+        let now = dates::parse_timestamp("06-01-2024 00:00:00").unwrap();
+        let anatomy = Anatomy {
+            tags: vec!["prod".to_string()],
+            ..Anatomy::create_for_test("Cloud", "AWS Root", "01-01-2024 12:00:00")
+        };
+
+        let query = Query::parse(
+            "aws tag:PROD cat:cloud created:>2023-12-31 -tag:old",
+            now,
+            TextMode::Fuzzy,
+        );
+        assert!(query.unwrap().score(&anatomy).is_some());
+
+        assert!(Query::parse("GitHub", now, TextMode::Fuzzy)
+            .unwrap()
+            .score(&Anatomy::create_for_test(
+                "dev",
+                "github",
+                "01-01-2024 12:00:00"
+            ))
+            .is_some());
+        assert!(Query::parse("aws -tag:prod", now, TextMode::Fuzzy)
+            .unwrap()
+            .score(&anatomy)
+            .is_none());
+        assert!(Query::parse("created:2024-01-01", now, TextMode::Fuzzy)
+            .unwrap()
+            .score(&anatomy)
+            .is_some());
+        assert!(Query::parse("created:<30d", now, TextMode::Fuzzy)
+            .unwrap()
+            .score(&anatomy)
+            .is_some());
+        assert!(Query::parse("accessed:>2020-01-01", now, TextMode::Fuzzy)
+            .unwrap()
+            .score(&anatomy)
+            .is_none());
+        assert!(
+            Query::parse("\"aws root\" type:password", now, TextMode::Fuzzy)
+                .unwrap()
+                .score(&anatomy)
+                .is_some()
+        );
+        assert!(Query::parse("git:github.com", now, TextMode::Fuzzy)
+            .unwrap()
+            .score(&Anatomy::create_for_test(
+                "dev",
+                "git:github.com",
+                "01-01-2024 12:00:00"
+            ))
+            .is_some());
+
+        assert!(Query::parse("awsrt cld", now, TextMode::Fuzzy)
+            .unwrap()
+            .score(&anatomy)
+            .is_some());
+        assert!(Query::parse("aws -oot", now, TextMode::Fuzzy)
+            .unwrap()
+            .score(&anatomy)
+            .is_none());
+        assert!(Query::parse("[prod", now, TextMode::Fuzzy).is_ok());
+        assert!(Query::parse("[prod", now, TextMode::Regex).is_err());
+        assert!(Query::parse("^aws", now, TextMode::Regex)
+            .unwrap()
+            .score(&anatomy)
+            .is_some());
+
+        assert!(Query::parse("type:ssh", now, TextMode::Fuzzy).is_err());
+        assert!(Query::parse("created:>tomorrow", now, TextMode::Fuzzy).is_err());
+        assert!(Query::parse("tag:\"team a", now, TextMode::Fuzzy).is_err());
+    }
+
+    /// Test whether matched characters are highlighted in labels with multibyte characters.
+    #[test]
+    fn test_get_label_ranges() {
+        let now = dates::get_now();
+        let query = Query::parse("SÉCRET", now, TextMode::Regex).unwrap();
+
+        let ranges = query.get_label_ranges("très sécret");
+        assert_eq!(ranges, vec![6..13]);
+        assert_eq!(&"très sécret"[ranges[0].clone()], "sécret");
+
+        let query = Query::parse("tsé", now, TextMode::Fuzzy).unwrap();
+        assert_eq!(
+            query.get_label_ranges("très sécret"),
+            vec![0..1, 6..7, 7..9]
+        );
+        assert!(Query::parse("-très", now, TextMode::Fuzzy)
+            .unwrap()
+            .get_label_ranges("très")
+            .is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! Contains functions for re-encrypting a whole vault with a new password.
+//!
+//! The re-encrypted vault is written to a staging directory first. Once every component is
+//! staged, each component of the vault is moved to a backup directory and replaced with its
+//! staged counterpart. If the swap is interrupted, `recover_rekey_in()` moves the backed up
+//! components back the next time the vault is opened, so the vault is never left half
+//! re-encrypted.
+
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+use crate::{
+    api::tokens as api_tokens,
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    utils::vault,
+};
+
+use super::{init, secure};
+
+/// The directory the re-encrypted vault is written to before it replaces the current vault.
+const STAGING_DIR: &str = ".rekey";
+/// The directory the current vault is moved to while it is being replaced.
+const BACKUP_DIR: &str = ".rekey-old";
+/// The file within the backup directory marking that the vault's components are being swapped,
+/// so the swap is rolled back if it is interrupted.
+const SWAP_MARKER: &str = "swapping";
+/// The components of a vault that are re-encrypted and swapped.
+const COMPONENTS: [&str; 4] = ["closet", "identity", "lookup", "members"];
+
+/// Re-encrypt the user's vault with a new password, along with the API tokens registry, which is
+/// sealed with the same password but stored outside the vault.
+pub fn rekey_vault(old_password: &str, new_password: &str) -> Result<(), HimitsuError> {
+    rekey_user_vault_in(
+        &vault::get_vault_dir()?,
+        &api_tokens::get_tokens_path()?,
+        old_password,
+        new_password,
+    )
+}
+
+/// Re-encrypt a user's vault directory and API tokens registry with a new password. The tokens
+/// are read before the vault is touched, so a registry that cannot be opened stops the
+/// re-encryption instead of being left behind.
+pub fn rekey_user_vault_in(
+    vault_dir: &Path,
+    tokens_path: &Path,
+    old_password: &str,
+    new_password: &str,
+) -> Result<(), HimitsuError> {
+    let tokens = api_tokens::get_tokens_in(tokens_path, old_password)?;
+
+    rekey_vault_in(vault_dir, old_password, new_password)?;
+
+    if tokens_path.exists() {
+        api_tokens::store_tokens_in(tokens_path, new_password, &tokens)?;
+    }
+
+    Ok(())
+}
+
+/// Re-encrypt every secret, the lookup table, and the vault's identity (if there is one) in a
+/// vault directory with a new password.
+///
+/// Recovery shares only recover the password the vault was encrypted with when they were created,
+/// so they are removed and have to be created again.
+pub fn rekey_vault_in(
+    vault_dir: &Path,
+    old_password: &str,
+    new_password: &str,
+) -> Result<(), HimitsuError> {
+    rekey_vault_with(vault_dir, old_password, new_password, |_| Ok(()))
+}
+
+/// Re-encrypt a vault directory with a new password like `rekey_vault_in()`. `stage_extra` is
+/// called with the staging directory once the vault is staged, so other components that depend
+/// on the new password, such as a team vault's `members`, are swapped in along with it.
+pub fn rekey_vault_with(
+    vault_dir: &Path,
+    old_password: &str,
+    new_password: &str,
+    stage_extra: impl FnOnce(&Path) -> Result<(), HimitsuError>,
+) -> Result<(), HimitsuError> {
+    recover_rekey_in(vault_dir)?;
+
+    let staging_dir = vault_dir.join(STAGING_DIR);
+
+    let lookup_table = secure::decrypt_lookup_table_in(&vault_dir.join("lookup"), old_password)?;
+
+    for hash_id in lookup_table.table.keys() {
+        let secret =
+            decrypt::decrypt_secret_in(&vault_dir.join("closet").join(hash_id), old_password)?;
+
+        encrypt::encrypt_secret_in(
+            &staging_dir.join("closet").join(hash_id),
+            new_password,
+            &secret,
+        )?;
+    }
+
+    let identity_dir = vault_dir.join("identity");
+    if identity_dir.join("skeleton").exists() {
+        let identity = decrypt::decrypt_secret_in(&identity_dir, old_password)?;
+
+        encrypt::encrypt_secret_in(&staging_dir.join("identity"), new_password, &identity)?;
+        fs::copy(
+            identity_dir.join("public"),
+            staging_dir.join("identity").join("public"),
+        )?;
+    }
+
+    init::create_lookup_in(&staging_dir.join("lookup"), new_password, &lookup_table)?;
+    fs::create_dir_all(staging_dir.join("closet"))?;
+
+    stage_extra(&staging_dir)?;
+
+    swap_staged_components(vault_dir)?;
+
+    let recovery_dir = vault_dir.join("recovery");
+    if recovery_dir.exists() {
+        fs::remove_dir_all(recovery_dir)?;
+    }
+
+    Ok(())
+}
+
+/// Swap each staged component with the vault's current one. The current components are moved to
+/// the backup directory, which holds the swap marker until every component is swapped, so an
+/// interrupted swap is rolled back by `recover_rekey_in()`.
+fn swap_staged_components(vault_dir: &Path) -> Result<(), HimitsuError> {
+    let staging_dir = vault_dir.join(STAGING_DIR);
+    let backup_dir = vault_dir.join(BACKUP_DIR);
+
+    fs::create_dir_all(&backup_dir)?;
+    File::create(backup_dir.join(SWAP_MARKER))?.sync_all()?;
+
+    for component in COMPONENTS {
+        if !staging_dir.join(component).exists() {
+            continue;
+        }
+
+        // Components the vault does not have yet are backed up as empty directories, so rolling
+        // back removes the staged component again.
+        fs::create_dir_all(vault_dir.join(component))?;
+        fs::rename(vault_dir.join(component), backup_dir.join(component))?;
+        fs::rename(staging_dir.join(component), vault_dir.join(component))?;
+    }
+
+    // Removing the marker completes the swap. Everything left behind is no longer needed.
+    fs::remove_file(backup_dir.join(SWAP_MARKER))?;
+    fs::remove_dir_all(staging_dir)?;
+    fs::remove_dir_all(backup_dir)?;
+
+    Ok(())
+}
+
+/// Roll back a re-encryption that was interrupted while its components were being swapped, then
+/// remove whatever an interrupted re-encryption left behind.
+pub fn recover_rekey_in(vault_dir: &Path) -> Result<(), HimitsuError> {
+    let staging_dir = vault_dir.join(STAGING_DIR);
+    let backup_dir = vault_dir.join(BACKUP_DIR);
+
+    if backup_dir.join(SWAP_MARKER).exists() {
+        for component in COMPONENTS {
+            if !backup_dir.join(component).exists() {
+                continue;
+            }
+
+            if vault_dir.join(component).exists() {
+                fs::remove_dir_all(vault_dir.join(component))?;
+            }
+            fs::rename(backup_dir.join(component), vault_dir.join(component))?;
+        }
+
+        fs::remove_file(backup_dir.join(SWAP_MARKER))?;
+    }
+
+    for directory in [staging_dir, backup_dir] {
+        if directory.exists() {
+            fs::remove_dir_all(directory)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_rekey {
+    use std::env;
+
+    use rand::{rngs::OsRng, RngCore};
+
+    use super::*;
+
+    use crate::models::{
+        api::ApiToken,
+        metadata::{Anatomy, LookupTable},
+    };
+
+    /// Create a vault directory holding a single secret encrypted with `password`.
+    fn create_vault(password: &str) -> std::path::PathBuf {
+        let vault_dir = env::temp_dir().join(format!("himitsu-rekey-{}", OsRng.next_u64()));
+
+        let mut lookup_table = LookupTable::new();
+        lookup_table.table.insert(
+            "0123abcd".to_string(),
+            Anatomy::create_from("unclassified".to_string(), "token".to_string(), vec![]),
+        );
+        init::create_lookup_in(&vault_dir.join("lookup"), password, &lookup_table).unwrap();
+        encrypt::encrypt_secret_in(
+            &vault_dir.join("closet").join("0123abcd"),
+            password,
+            "hunter2",
+        )
+        .unwrap();
+
+        vault_dir
+    }
+
+    /// Test whether a vault is re-encrypted along with extra staged components.
+    #[test]
+    fn test_rekey_vault_with() {
+        let vault_dir = create_vault("old");
+        fs::create_dir_all(vault_dir.join("members")).unwrap();
+        fs::write(vault_dir.join("members").join("bob.json"), "bob").unwrap();
+
+        rekey_vault_with(&vault_dir, "old", "new", |staging_dir| {
+            fs::create_dir_all(staging_dir.join("members"))?;
+            fs::write(staging_dir.join("members").join("alice.json"), "alice")?;
+
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(secure::decrypt_lookup_table_in(&vault_dir.join("lookup"), "new").is_ok());
+        assert_eq!(
+            decrypt::decrypt_secret_in(&vault_dir.join("closet").join("0123abcd"), "new").unwrap(),
+            "hunter2"
+        );
+        assert!(vault_dir.join("members").join("alice.json").exists());
+        assert!(!vault_dir.join("members").join("bob.json").exists());
+        assert!(!vault_dir.join(STAGING_DIR).exists());
+        assert!(!vault_dir.join(BACKUP_DIR).exists());
+
+        fs::remove_dir_all(vault_dir).unwrap();
+    }
+
+    /// Test whether the API tokens registry is re-sealed along with the user's vault.
+    #[test]
+    fn test_rekey_user_vault_in() {
+        let vault_dir = create_vault("old");
+        let tokens_path = vault_dir.with_extension("tokens");
+        let token = ApiToken {
+            categories: vec!["cloud".to_string()],
+            date_created: "01-01-2024 00:00:00".to_string(),
+            hash: api_tokens::hash_token("hmu_secret"),
+            name: "reporting".to_string(),
+            tags: Vec::new(),
+        };
+        api_tokens::store_tokens_in(&tokens_path, "old", &[token]).unwrap();
+
+        rekey_user_vault_in(&vault_dir, &tokens_path, "old", "new").unwrap();
+
+        let tokens = api_tokens::get_tokens_in(&tokens_path, "new").unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert!(api_tokens::find_token(&tokens, "hmu_secret").is_some());
+        assert!(api_tokens::get_tokens_in(&tokens_path, "old").is_err());
+        assert!(secure::decrypt_lookup_table_in(&vault_dir.join("lookup"), "new").is_ok());
+
+        fs::remove_file(tokens_path).unwrap();
+        fs::remove_dir_all(vault_dir).unwrap();
+    }
+
+    /// Test whether a swap interrupted after some components were swapped is rolled back, and
+    /// whether a finished swap's leftovers are removed.
+    #[test]
+    fn test_recover_rekey_in() {
+        let vault_dir = create_vault("old");
+        let staging_dir = vault_dir.join(STAGING_DIR);
+        let backup_dir = vault_dir.join(BACKUP_DIR);
+
+        init::create_lookup_in(&staging_dir.join("lookup"), "new", &LookupTable::new()).unwrap();
+        fs::create_dir_all(staging_dir.join("closet")).unwrap();
+        fs::create_dir_all(&backup_dir).unwrap();
+        File::create(backup_dir.join(SWAP_MARKER)).unwrap();
+        fs::rename(vault_dir.join("closet"), backup_dir.join("closet")).unwrap();
+        fs::rename(staging_dir.join("closet"), vault_dir.join("closet")).unwrap();
+        fs::rename(vault_dir.join("lookup"), backup_dir.join("lookup")).unwrap();
+
+        recover_rekey_in(&vault_dir).unwrap();
+        assert!(secure::decrypt_lookup_table_in(&vault_dir.join("lookup"), "old").is_ok());
+        assert_eq!(
+            decrypt::decrypt_secret_in(&vault_dir.join("closet").join("0123abcd"), "old").unwrap(),
+            "hunter2"
+        );
+        assert!(!staging_dir.exists());
+        assert!(!backup_dir.exists());
+
+        fs::create_dir_all(backup_dir.join("closet")).unwrap();
+        recover_rekey_in(&vault_dir).unwrap();
+        assert!(!backup_dir.exists());
+        assert!(vault_dir.join("closet").join("0123abcd").exists());
+
+        fs::remove_dir_all(vault_dir).unwrap();
+    }
+}
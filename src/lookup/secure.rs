@@ -1,62 +1,133 @@
 //! Contains functions for securely encrypting/decrypting the lookup table.
-
-use std::fs;
+//!
+//! The `"table"` file starts with `TABLE_HEADER`, followed by the salt and nonce the table was
+//! encrypted with, then the encrypted table. A new salt and nonce are generated every time the
+//! table is written, so no key and nonce pair is ever used twice, even across the versions of the
+//! table kept in the vault's history. Vaults created before this format stored a single salt and
+//! nonce in the `"salt"` and `"nonce"` files instead; those files are removed the next time the
+//! table is written.
+
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
 
 use argon2;
 use chacha20poly1305::{
     aead::{Aead, NewAead},
     Key, XChaCha20Poly1305, XNonce,
 };
+use rand::{rngs::OsRng, RngCore};
+
+use crate::{authentication, errors::HimitsuError, models::metadata::LookupTable, utils::dates};
 
-use crate::{authentication, errors::HimitsuError, models::metadata::LookupTable};
+use super::{merge, utils};
 
-use super::utils;
+/// Marks a `"table"` file that carries its own salt and nonce.
+const TABLE_HEADER: &[u8; 8] = b"hmutbl02";
+/// The length of the lookup table's salt.
+const SALT_LENGTH: usize = 32;
+/// The length of the lookup table's nonce.
+const NONCE_LENGTH: usize = 24;
+
+/// Derive the key the lookup table is encrypted with from the password and a salt.
+fn get_cipher(password: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, HimitsuError> {
+    let argon2_config = authentication::get_argon2_config();
+    let key = argon2::hash_raw(password.as_bytes(), salt, &argon2_config)?;
 
-/// Encrypt the lookup table and write the table to the `"table"` file.
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Encrypt the lookup table and write the table to the `"table"` file. Tombstones that no longer
+/// have to be propagated are dropped first (see `merge::prune_tombstones()`).
 pub fn encrypt_lookup_table(
     password: &str,
     updated_lookup: &mut LookupTable,
 ) -> Result<(), HimitsuError> {
-    let lookup_table_path = utils::get_lookup_dir_path()?.join("table");
-
-    let lookup_salt = utils::get_lookup_salt()?;
-
-    let argon2_config = authentication::get_argon2_config();
-    let key = argon2::hash_raw(password.as_bytes(), &lookup_salt, &argon2_config)?;
-
-    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    merge::prune_tombstones(updated_lookup, dates::get_now());
 
-    let lookup_nonce = utils::get_lookup_nonce()?;
-    let nonce = XNonce::from_slice(&lookup_nonce);
+    encrypt_lookup_table_in(&utils::get_lookup_dir_path()?, password, updated_lookup)
+}
 
-    match cipher.encrypt(nonce, serde_json::to_string(updated_lookup)?.as_bytes()) {
-        Ok(encrypted_lookup_table) => {
-            if let Err(error) = fs::write(lookup_table_path, encrypted_lookup_table) {
-                return Err(HimitsuError::StoreLookupTableError(error.to_string()));
-            }
+/// Encrypt the lookup table with a new salt and nonce, then write the table to the `"table"` file
+/// in a particular lookup directory. The file is replaced in a single step, so the previous table
+/// stays intact if writing fails.
+pub fn encrypt_lookup_table_in(
+    lookup_dir: &Path,
+    password: &str,
+    lookup_table: &LookupTable,
+) -> Result<(), HimitsuError> {
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let encrypted_lookup_table = get_cipher(password, &salt)?
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            serde_json::to_string(lookup_table)?.as_bytes(),
+        )
+        .map_err(|error| {
+            HimitsuError::AEADEncryptionError(format!("Lookup table encryption error: {error}"))
+        })?;
+
+    let staged_table_path = lookup_dir.join("table.tmp");
+    let write_table = || -> Result<(), std::io::Error> {
+        let mut table_file = File::create(&staged_table_path)?;
+        table_file.write_all(TABLE_HEADER)?;
+        table_file.write_all(&salt)?;
+        table_file.write_all(&nonce)?;
+        table_file.write_all(&encrypted_lookup_table)?;
+        table_file.sync_all()?;
+
+        fs::rename(&staged_table_path, lookup_dir.join("table"))
+    };
+    if let Err(error) = write_table() {
+        let _ = fs::remove_file(&staged_table_path);
+
+        return Err(HimitsuError::StoreLookupTableError(error.to_string()));
+    }
 
-            Ok(())
+    for legacy_file in ["salt", "nonce"] {
+        if lookup_dir.join(legacy_file).exists() {
+            fs::remove_file(lookup_dir.join(legacy_file))?;
         }
-        Err(error) => Err(HimitsuError::AEADEncryptionError(format!(
-            "Lookup table encryption error: {error}"
-        ))),
     }
+
+    Ok(())
 }
 
 /// Decrypt the lookup table and return its contents.
 pub fn decrypt_lookup_table(password: &str) -> Result<LookupTable, HimitsuError> {
-    let lookup_salt = utils::get_lookup_salt()?;
-    let lookup_nonce = utils::get_lookup_nonce()?;
-    let nonce = XNonce::from_slice(&lookup_nonce);
-
-    let argon2_config = authentication::get_argon2_config();
-    let key = argon2::hash_raw(password.as_bytes(), &lookup_salt, &argon2_config)?;
-
-    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
-
-    let raw_lookup_data = &utils::get_lookup_table()?[..];
+    decrypt_lookup_table_in(&utils::get_lookup_dir_path()?, password)
+}
 
-    match cipher.decrypt(nonce, raw_lookup_data) {
+/// Decrypt the lookup table stored in a particular lookup directory and return its contents.
+pub fn decrypt_lookup_table_in(
+    lookup_dir: &Path,
+    password: &str,
+) -> Result<LookupTable, HimitsuError> {
+    let raw_lookup_data = utils::get_lookup_table(lookup_dir)?;
+
+    let (salt, nonce, encrypted_lookup_table) = match raw_lookup_data
+        .strip_prefix(TABLE_HEADER)
+        .filter(|rest| rest.len() >= SALT_LENGTH + NONCE_LENGTH)
+    {
+        Some(rest) => {
+            let (salt, rest) = rest.split_at(SALT_LENGTH);
+            let (nonce, encrypted_lookup_table) = rest.split_at(NONCE_LENGTH);
+
+            (salt.to_vec(), nonce.to_vec(), encrypted_lookup_table)
+        }
+        None => (
+            utils::get_lookup_salt(lookup_dir)?.to_vec(),
+            utils::get_lookup_nonce(lookup_dir)?.to_vec(),
+            &raw_lookup_data[..],
+        ),
+    };
+
+    match get_cipher(password, &salt)?.decrypt(XNonce::from_slice(&nonce), encrypted_lookup_table) {
         Ok(data) => match String::from_utf8(data) {
             Ok(stringified_data) => Ok(serde_json::from_str(&stringified_data)?),
             Err(error) => Err(HimitsuError::FromUtf8Error(error)),
@@ -67,3 +138,92 @@ pub fn decrypt_lookup_table(password: &str) -> Result<LookupTable, HimitsuError>
         ))),
     }
 }
+
+#[cfg(test)]
+pub mod test_secure {
+    use std::env;
+
+    use super::*;
+
+    use crate::models::metadata::Anatomy;
+
+    /// The password test lookup tables are encrypted with.
+    const PASSWORD: &str = "correct horse battery staple";
+
+    /// Create a lookup table holding a single secret.
+    fn create_lookup_table() -> LookupTable {
+        let mut lookup_table = LookupTable::new();
+        lookup_table.table.insert(
+            "hash".to_string(),
+            Anatomy::create_from("dev".to_string(), "github".to_string(), Vec::new()),
+        );
+
+        lookup_table
+    }
+
+    /// Test whether every write encrypts the lookup table with a new salt and nonce.
+    #[test]
+    fn test_encrypt_lookup_table_in() {
+        let lookup_dir = env::temp_dir().join(format!("himitsu-lookup-{}", OsRng.next_u64()));
+        fs::create_dir_all(&lookup_dir).unwrap();
+        let lookup_table = create_lookup_table();
+
+        encrypt_lookup_table_in(&lookup_dir, PASSWORD, &lookup_table).unwrap();
+        let first_write = fs::read(lookup_dir.join("table")).unwrap();
+        encrypt_lookup_table_in(&lookup_dir, PASSWORD, &lookup_table).unwrap();
+        let second_write = fs::read(lookup_dir.join("table")).unwrap();
+
+        let header_length = TABLE_HEADER.len();
+        let parameters_end = header_length + SALT_LENGTH + NONCE_LENGTH;
+        assert_ne!(
+            first_write[header_length..parameters_end],
+            second_write[header_length..parameters_end]
+        );
+        assert_ne!(
+            first_write[parameters_end..],
+            second_write[parameters_end..]
+        );
+
+        let decrypted_table = decrypt_lookup_table_in(&lookup_dir, PASSWORD).unwrap();
+        assert_eq!(decrypted_table.table["hash"].label, "github");
+        assert!(decrypt_lookup_table_in(&lookup_dir, "wrong password").is_err());
+        assert!(!lookup_dir.join("table.tmp").exists());
+
+        fs::remove_dir_all(lookup_dir).unwrap();
+    }
+
+    /// Test whether lookup tables with a separate salt and nonce are still decrypted, and are
+    /// moved to the new format once they are written.
+    #[test]
+    fn test_decrypt_legacy_lookup_table() {
+        let lookup_dir = env::temp_dir().join(format!("himitsu-legacy-{}", OsRng.next_u64()));
+        fs::create_dir_all(&lookup_dir).unwrap();
+
+        let mut salt = [0u8; SALT_LENGTH];
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let encrypted_lookup_table = get_cipher(PASSWORD, &salt)
+            .unwrap()
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                serde_json::to_string(&create_lookup_table())
+                    .unwrap()
+                    .as_bytes(),
+            )
+            .unwrap();
+        fs::write(lookup_dir.join("salt"), salt).unwrap();
+        fs::write(lookup_dir.join("nonce"), nonce).unwrap();
+        fs::write(lookup_dir.join("table"), encrypted_lookup_table).unwrap();
+
+        let lookup_table = decrypt_lookup_table_in(&lookup_dir, PASSWORD).unwrap();
+        assert_eq!(lookup_table.table["hash"].label, "github");
+
+        encrypt_lookup_table_in(&lookup_dir, PASSWORD, &lookup_table).unwrap();
+        assert!(!lookup_dir.join("salt").exists());
+        assert!(!lookup_dir.join("nonce").exists());
+        assert!(decrypt_lookup_table_in(&lookup_dir, PASSWORD).is_ok());
+
+        fs::remove_dir_all(lookup_dir).unwrap();
+    }
+}
@@ -1,111 +1,130 @@
 //! Contains utilities for the lookup table.
 
 use std::{
-    collections::HashMap,
+    cmp::Reverse,
     fs::{self, File},
     io::Read,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use ansi_term::{Color, Style};
-use directories::ProjectDirs;
-use regex::Regex;
+use ansi_term::Style;
 
-use crate::{errors::HimitsuError, models::metadata::LookupMatch};
+use crate::{
+    errors::HimitsuError,
+    models::metadata::LookupMatch,
+    utils::{dates, vault},
+};
 
-use super::secure::decrypt_lookup_table;
+use super::{
+    query::{Query, TextMode},
+    secure::decrypt_lookup_table,
+};
 
 /// Get the lookup table directory path.
 pub fn get_lookup_dir_path() -> Result<PathBuf, HimitsuError> {
-    match ProjectDirs::from("", "", "himitsu") {
-        Some(project_directory) => Ok(project_directory.data_dir().join("lookup")),
-        None => Err(HimitsuError::PathError(
-            "Could not get the path to the himitsu application directory!".to_string(),
-        )),
-    }
+    Ok(vault::get_vault_dir()?.join("lookup"))
 }
 
 /// Contains variants for lookup table search modes.
 pub enum LookupMode {
     /// Get all secret data stored in the lookup table.
     GetAll,
-    /// Search for a specific secret in the lookup table.
-    Search(String),
+    /// Search for secrets matching a query (see `lookup::query`), matching text as the
+    /// `TextMode` says.
+    Search(String, TextMode),
+}
+
+/// Contains a secret found in the lookup table, with its label painted for the selection prompt.
+pub type FoundMatch = (String, LookupMatch);
+
+/// Sort found secrets from best to worst: by score, then by the date they were last accessed
+/// (most recent first, never accessed last), then by label and hash ID, so secrets are listed in
+/// the same order every time.
+pub fn rank_matches(mut scored_matches: Vec<(i64, FoundMatch)>) -> Vec<FoundMatch> {
+    scored_matches.sort_by_cached_key(|(score, (_, lookup_match))| {
+        (
+            Reverse(*score),
+            Reverse(
+                lookup_match
+                    .anatomy
+                    .last_accessed
+                    .as_deref()
+                    .and_then(dates::parse_timestamp),
+            ),
+            lookup_match.anatomy.label.clone(),
+            lookup_match.hash.clone(),
+        )
+    });
+
+    scored_matches
+        .into_iter()
+        .map(|(_, found_match)| found_match)
+        .collect()
 }
 
-/// Search for a label within the lookup table or return all secrets within the lookup table
-/// depending on the `LookupMode`.
+/// Search for secrets matching a query within the lookup table or return all secrets within the
+/// lookup table depending on the `LookupMode`. Secrets are ranked with `rank_matches()`.
 pub fn search_in_lookup_table(
     lookup_mode: LookupMode,
     password: &str,
-) -> Result<HashMap<String, LookupMatch>, HimitsuError> {
+) -> Result<Vec<FoundMatch>, HimitsuError> {
     let lookup_table = decrypt_lookup_table(password)?;
 
-    let mut found_matches = HashMap::new();
+    let mut scored_matches = Vec::new();
 
     match lookup_mode {
         LookupMode::GetAll => {
             for (hash_key, anatomy) in lookup_table.table.iter() {
-                found_matches.insert(
-                    Style::new().bold().paint(anatomy.label.clone()).to_string(),
-                    LookupMatch::create(anatomy.to_owned(), hash_key.to_string()),
-                );
+                scored_matches.push((
+                    0,
+                    (
+                        Style::new().bold().paint(anatomy.label.clone()).to_string(),
+                        LookupMatch::create(anatomy.to_owned(), hash_key.to_string()),
+                    ),
+                ));
             }
         }
-        LookupMode::Search(label) => {
-            let regex = Regex::new(&label)?;
+        LookupMode::Search(query, text_mode) => {
+            let query = Query::parse(&query, dates::get_now(), text_mode)?;
 
             for (hash_key, anatomy) in lookup_table.table.iter() {
-                if let Some(match_range) = regex.find(&anatomy.label.to_lowercase()) {
-                    let mut painted_label = String::new();
-                    for (index, character) in anatomy.label.chars().enumerate() {
-                        if (match_range.start()..match_range.end()).contains(&index) {
-                            painted_label.push_str(
-                                &Color::Red.bold().paint(format!("{character}")).to_string(),
-                            );
-                        } else {
-                            painted_label.push_str(
-                                &Style::new()
-                                    .bold()
-                                    .paint(&format!("{character}"))
-                                    .to_string(),
-                            )
-                        }
-                    }
-
-                    found_matches.insert(
-                        painted_label,
-                        LookupMatch::create(anatomy.to_owned(), hash_key.to_string()),
-                    );
+                if let Some(score) = query.score(anatomy) {
+                    scored_matches.push((
+                        score,
+                        (
+                            query.paint_label(&anatomy.label),
+                            LookupMatch::create(anatomy.to_owned(), hash_key.to_string()),
+                        ),
+                    ));
                 }
             }
         }
     }
 
-    Ok(found_matches)
+    Ok(rank_matches(scored_matches))
 }
 
-/// Get the lookup table's contents from the lookup directory.
-pub fn get_lookup_table() -> Result<Vec<u8>, HimitsuError> {
-    let mut lookup_file = File::open(&get_lookup_dir_path()?.join("table"))?;
+/// Get the lookup table's contents from a lookup directory.
+pub fn get_lookup_table(lookup_dir: &Path) -> Result<Vec<u8>, HimitsuError> {
+    let mut lookup_file = File::open(lookup_dir.join("table"))?;
     let mut lookup_table = Vec::new();
     lookup_file.read_to_end(&mut lookup_table)?;
 
     Ok(lookup_table)
 }
 
-/// Get the lookup table's salt from the lookup directory.
-pub fn get_lookup_salt() -> Result<[u8; 32], HimitsuError> {
-    let mut lookup_file = File::open(&get_lookup_dir_path()?.join("salt"))?;
+/// Get the lookup table's salt from a lookup directory.
+pub fn get_lookup_salt(lookup_dir: &Path) -> Result<[u8; 32], HimitsuError> {
+    let mut lookup_file = File::open(lookup_dir.join("salt"))?;
     let mut lookup_salt = [0u8; 32];
     lookup_file.read_exact(&mut lookup_salt)?;
 
     Ok(lookup_salt)
 }
 
-/// Get the lookup table's nonce from the lookup directory.
-pub fn get_lookup_nonce() -> Result<[u8; 24], HimitsuError> {
-    let mut lookup_file = File::open(&get_lookup_dir_path()?.join("nonce"))?;
+/// Get the lookup table's nonce from a lookup directory.
+pub fn get_lookup_nonce(lookup_dir: &Path) -> Result<[u8; 24], HimitsuError> {
+    let mut lookup_file = File::open(lookup_dir.join("nonce"))?;
     let mut lookup_nonce = [0u8; 24];
     lookup_file.read_exact(&mut lookup_nonce)?;
 
@@ -114,16 +133,55 @@ pub fn get_lookup_nonce() -> Result<[u8; 24], HimitsuError> {
 
 /// Remove the hash directory on the local machine.
 pub fn remove_hash_directory(hash_id: &str) -> Result<(), HimitsuError> {
-    match ProjectDirs::from("", "", "himitsu") {
-        Some(project_directory) => {
-            let hash_directory = project_directory.data_dir().join("closet").join(hash_id);
+    let hash_directory = vault::get_vault_dir()?.join("closet").join(hash_id);
 
-            fs::remove_dir_all(hash_directory)?;
+    fs::remove_dir_all(hash_directory)?;
 
-            Ok(())
-        }
-        None => Err(HimitsuError::PathError(
-            "Could not get the path to the himitsu application directory!".to_string(),
-        )),
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    use crate::models::metadata::Anatomy;
+
+    /// Create a found secret last accessed at `last_accessed`.
+    fn create_found_match(label: &str, hash: &str, last_accessed: Option<&str>) -> FoundMatch {
+        let anatomy = Anatomy {
+            last_accessed: last_accessed.map(str::to_string),
+            ..Anatomy::create_for_test("unclassified", label, "01-01-2022 00:00:00")
+        };
+
+        (
+            label.to_string(),
+            LookupMatch::create(anatomy, hash.to_string()),
+        )
+    }
+
+    /// Test whether secrets are ranked by score, then by recency, then by label and hash ID.
+    #[test]
+    fn test_rank_matches() {
+        let ranked = rank_matches(vec![
+            (10, create_found_match("never", "a", None)),
+            (
+                10,
+                create_found_match("old", "b", Some("01-01-2022 00:00:00")),
+            ),
+            (20, create_found_match("best", "c", None)),
+            (
+                10,
+                create_found_match("recent", "d", Some("01-01-2024 00:00:00")),
+            ),
+            (10, create_found_match("never", "e", None)),
+        ]);
+
+        assert_eq!(
+            ranked
+                .iter()
+                .map(|(_, lookup_match)| lookup_match.hash.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["c", "d", "b", "a", "e"]
+        );
     }
 }
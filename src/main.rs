@@ -1,17 +1,38 @@
 //! `himitsu` - An encrypted CLI tool for managing secret on your machine.
 
+mod agent;
+mod api;
+mod audit;
 mod authentication;
 mod cli;
+mod credentials;
 mod encryption;
 mod errors;
 mod lookup;
 mod models;
 mod prompts;
+mod recovery;
+mod secret_service;
+mod sharing;
+mod ssh;
+mod strength;
+mod totp;
+mod tui;
 mod utils;
 
-use cli::{subcommands, Args};
+use audit::log;
+use cli::{
+    subcommands::{self, RecoverySubCommands, SubCommands},
+    Args,
+};
+use errors::HimitsuError;
+use lookup::rekey;
+use models::{encryption::Encryption, log::LogAction};
 use prompts::{authenticate, setup};
-use utils::{config, paint};
+use sharing::team;
+use utils::{config, paint, protocol, vault};
+
+use std::{env, ffi::OsString, path::Path, process};
 
 use ansi_term::Color;
 use clap::Parser;
@@ -24,7 +45,18 @@ lazy_static! {
 
 /// Run `himitsu`.
 fn main() {
-    let args = Args::parse();
+    let args = Args::parse_from(get_arguments());
+    let speaks_protocol = args
+        .subcommand
+        .as_ref()
+        .is_some_and(SubCommands::speaks_protocol);
+    let report_error = |error: HimitsuError| {
+        paint::paint_error(error);
+
+        if speaks_protocol {
+            process::exit(1);
+        }
+    };
 
     if args.banner {
         println!(
@@ -32,16 +64,46 @@ fn main() {
             Color::Fixed(172).paint(String::from_utf8_lossy(&ASCII_ART[..]))
         );
     } else {
+        if speaks_protocol {
+            if let Err(error) = protocol::redirect_stdout() {
+                report_error(error);
+                return;
+            }
+        }
+
         match config::get_encryption_values() {
             Ok(crypt_json) => match crypt_json {
-                Some(encryption_values) => {
-                    match authenticate::authenticate_user(&encryption_values) {
+                Some(_)
+                    if matches!(
+                        args.subcommand,
+                        Some(SubCommands::Recovery {
+                            recovery_subcommand: RecoverySubCommands::Restore
+                        })
+                    ) =>
+                {
+                    if args.team.is_some() {
+                        paint::paint_error(HimitsuError::RecoveryError(
+                            "Team vaults cannot be recovered with recovery shares!".to_string(),
+                        ));
+                    } else if let Err(error) = subcommands::run_recovery_restore() {
+                        paint::paint_error(error);
+                    }
+                }
+                Some(encryption_values) => match &args.subcommand {
+                    Some(subcommand @ SubCommands::Agent { agent_subcommand })
+                        if args.team.is_none() && subcommand.skips_unlock() =>
+                    {
+                        if let Err(error) =
+                            subcommands::run_agent_subcommand(&encryption_values, agent_subcommand)
+                        {
+                            paint::paint_error(error);
+                        }
+                    }
+                    _ => match unlock_vault(&encryption_values, &args) {
                         Ok(password) => {
                             if let Some(subcommand) = &args.subcommand {
-                                if let Err(error) =
-                                    subcommands::run_subcommands(&password, subcommand)
-                                {
-                                    paint::paint_error(error);
+                                if let Err(error) = run_subcommand(&args, &password, subcommand) {
+                                    report_error(error);
                                 }
                             } else {
                                 println!(
@@ -51,9 +113,9 @@ fn main() {
                                 );
                             }
                         }
-                        Err(error) => paint::paint_error(error),
-                    }
-                }
+                        Err(error) => report_error(error),
+                    },
+                },
                 None => {
                     println!(
                         "{}",
@@ -65,7 +127,88 @@ fn main() {
                     }
                 }
             },
-            Err(error) => paint::paint_error(error),
+            Err(error) => report_error(error),
+        }
+    }
+}
+
+/// Get the command-line arguments. When `himitsu` is run through a symlink named
+/// `docker-credential-<NAME>`, Docker's action is turned into a `docker-credential` subcommand.
+fn get_arguments() -> Vec<OsString> {
+    let mut arguments: Vec<OsString> = env::args_os().collect();
+
+    if arguments
+        .first()
+        .and_then(|program| Path::new(program).file_name())
+        .is_some_and(|program| program.to_string_lossy().starts_with("docker-credential-"))
+    {
+        arguments.insert(1, OsString::from("docker-credential"));
+    }
+
+    arguments
+}
+
+/// Unlock the vault. The password is taken from the agent while it is running, otherwise the
+/// user is prompted for it. A re-encryption that was interrupted is rolled back first.
+fn unlock_vault(encryption_values: &Encryption, args: &Args) -> Result<String, HimitsuError> {
+    rekey::recover_rekey_in(&vault::get_vault_dir()?)?;
+
+    if let Some(password) = agent::request_password() {
+        if authentication::check_authorization(encryption_values, &password)? {
+            return Ok(password);
+        }
+    }
+
+    if args
+        .subcommand
+        .as_ref()
+        .is_some_and(SubCommands::requires_agent)
+    {
+        return Err(HimitsuError::AgentError(
+            "Your vault is locked. Unlock it with `hmu agent start` first!".to_string(),
+        ));
+    }
+
+    authenticate::authenticate_user(encryption_values, &args.keyfile)
+}
+
+/// Run a subcommand against the user's vault, or against a team vault if `--team` was passed.
+fn run_subcommand(
+    args: &Args,
+    password: &str,
+    subcommand: &SubCommands,
+) -> Result<(), HimitsuError> {
+    match (&args.team, subcommand) {
+        (Some(_), SubCommands::Team { .. }) => Err(HimitsuError::TeamError(
+            "Team subcommands take the team vault's path as an argument instead of --team!"
+                .to_string(),
+        )),
+        (Some(_), SubCommands::Agent { .. }) => Err(HimitsuError::TeamError(
+            "The agent only keeps your own vault unlocked!".to_string(),
+        )),
+        (Some(_), SubCommands::ApiToken { .. } | SubCommands::Serve { .. }) => Err(
+            HimitsuError::TeamError("The API only serves your own vault!".to_string()),
+        ),
+        (Some(_), SubCommands::SecretService { .. }) => Err(HimitsuError::TeamError(
+            "The Secret Service only serves your own vault!".to_string(),
+        )),
+        (Some(_), SubCommands::Tui { .. }) => Err(HimitsuError::TeamError(
+            "The TUI only browses your own vault!".to_string(),
+        )),
+        (Some(_), SubCommands::Config { .. }) => Err(HimitsuError::TeamError(
+            "Team vaults do not have their own settings!".to_string(),
+        )),
+        (Some(_), SubCommands::Recovery { .. }) => Err(HimitsuError::TeamError(
+            "Team vaults are recovered by their other members instead of recovery shares!"
+                .to_string(),
+        )),
+        (Some(team_dir), _) => {
+            let data_key = team::open_team_vault(team_dir, password)?;
+            log::open_log(&data_key)?;
+            log::record_event(LogAction::Unlock, None);
+
+            subcommands::run_subcommands(&data_key, subcommand)
         }
+        (None, _) => subcommands::run_subcommands(password, subcommand),
     }
 }
@@ -0,0 +1,60 @@
+//! Contains structs used for the JSON API served by `hmu serve`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Contains an application's API token. Only the token's hash is stored.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ApiToken {
+    /// The categories the token may access.
+    pub categories: Vec<String>,
+    /// When the token was created.
+    pub date_created: String,
+    /// The SHA-256 hash of the token.
+    pub hash: String,
+    /// The name of the application the token was created for.
+    pub name: String,
+    /// The tags the token may access.
+    pub tags: Vec<String>,
+}
+
+/// Contains the parameters of a request. Which parameters are needed depends on the method.
+#[derive(Debug, Default, Deserialize)]
+pub struct ApiParams {
+    /// The secret's category.
+    pub category: Option<String>,
+    /// The secret's label.
+    pub label: Option<String>,
+    /// The secret's new value.
+    pub secret: Option<String>,
+    /// The secret's tags.
+    pub tags: Option<Vec<String>>,
+}
+
+/// Contains a single request, sent as one line of JSON.
+#[derive(Debug, Deserialize)]
+pub struct ApiRequest {
+    /// An ID chosen by the application, which is echoed in the response.
+    #[serde(default)]
+    pub id: Value,
+    /// The method to call: `list`, `get`, `put`, or `delete`.
+    pub method: String,
+    /// The method's parameters.
+    #[serde(default)]
+    pub params: ApiParams,
+    /// The application's API token.
+    pub token: String,
+}
+
+/// Contains the response to a single request, sent as one line of JSON.
+#[derive(Debug, Serialize)]
+pub struct ApiResponse {
+    /// The error message, if the request failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The ID of the request this response answers.
+    pub id: Value,
+    /// The method's result, if the request succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+}
@@ -0,0 +1,25 @@
+//! Contains structs used for `himitsu`'s configuration.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Contains user settings stored in the `config.json` file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Config {
+    /// The default path to the keyfile used to unlock the vault.
+    #[serde(default)]
+    pub keyfile: Option<PathBuf>,
+    /// Wipe the vault after this many failed login attempts in a row.
+    #[serde(default)]
+    pub wipe_after: Option<u32>,
+}
+
+/// Contains the failed login attempts since the last successful login, stored in the
+/// `attempts.json` file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LoginAttempts {
+    /// The timestamp of each failed login attempt, oldest first.
+    #[serde(default)]
+    pub failed: Vec<String>,
+}
@@ -9,4 +9,7 @@ pub struct Encryption {
     pub password_hash: Vec<u8>,
     /// The salt associated with the master password.
     pub salt: [u8; 32],
+    /// Whether a keyfile is combined with the master password to unlock the vault.
+    #[serde(default)]
+    pub uses_keyfile: bool,
 }
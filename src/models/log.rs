@@ -0,0 +1,107 @@
+//! Contains structs used for the audit log.
+
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use super::sharing::Envelope;
+
+/// The kinds of events recorded in the audit log.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogAction {
+    /// A secret was added.
+    Add,
+    /// A request was refused.
+    Denied,
+    /// A secret was edited.
+    Edit,
+    /// Someone failed to unlock the vault.
+    FailedLogin,
+    /// The vault's secrets were listed.
+    List,
+    /// Another vault was merged into the vault.
+    Merge,
+    /// A secret was removed.
+    Remove,
+    /// The vault was rolled back to an earlier revision.
+    Restore,
+    /// A secret was shared with someone else.
+    Share,
+    /// The vault was unlocked.
+    Unlock,
+    /// A secret was used.
+    Use,
+}
+
+impl fmt::Display for LogAction {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let action = match self {
+            Self::Add => "add",
+            Self::Denied => "denied",
+            Self::Edit => "edit",
+            Self::FailedLogin => "failed login",
+            Self::List => "list",
+            Self::Merge => "merge",
+            Self::Remove => "remove",
+            Self::Restore => "restore",
+            Self::Share => "share",
+            Self::Unlock => "unlock",
+            Self::Use => "use",
+        };
+
+        write!(formatter, "{action}")
+    }
+}
+
+/// Contains a single event recorded in the audit log. Events are sealed into an `Envelope` before
+/// they are written to the log.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct LogEvent {
+    /// What happened.
+    pub action: LogAction,
+    /// When the event happened.
+    pub date: String,
+    /// The secret involved in the event (`category/label`), if any.
+    pub secret: Option<String>,
+    /// The user who caused the event.
+    pub user: String,
+}
+
+/// Contains a single line in the audit log. Every entry is chained to the entry before it, so
+/// removing or modifying an entry breaks the chain.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogEntry {
+    /// The sealed `LogEvent`.
+    pub envelope: Envelope,
+    /// The SHA-256 hash of the previous entry's hash and this entry's envelope.
+    pub hash: String,
+    /// The previous entry's hash.
+    pub previous_hash: String,
+}
+
+/// Contains the newest entry this user has seen in a vault's audit log. Heads are stored outside
+/// the vault, so truncating the log is detected even though the chain itself is still intact.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct LogHead {
+    /// The number of entries in the log.
+    pub count: usize,
+    /// The hash of the newest entry.
+    pub hash: String,
+}
+
+/// Contains the newest entry in an audit log, stored next to the log so appending an entry does not
+/// read the whole log. The log's length is recorded as well, so a tail that no longer matches the
+/// log is not used.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogTail {
+    /// The number of entries in the log.
+    pub count: usize,
+    /// The hash of the newest entry.
+    pub hash: String,
+    /// The length of the log in bytes.
+    pub length: u64,
+}
+
+/// Maps each vault directory to the newest entry seen in its audit log.
+pub type LogHeads = HashMap<String, LogHead>;
@@ -1,10 +1,11 @@
 //! Contains structs used for `himitsu` metadata.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, str::FromStr};
 
-use chrono::Local;
 use serde::{Deserialize, Serialize};
 
+use crate::utils::dates;
+
 /// Contains metadata for each secret.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Anatomy {
@@ -12,12 +13,89 @@ pub struct Anatomy {
     pub category: String,
     /// The date this secret was created.
     pub date_created: String,
+    /// The date this secret expires.
+    #[serde(default)]
+    pub expires_at: Option<String>,
     /// The label associated with this secret.
     pub label: String,
     /// The date this secret was last accessed.
     pub last_accessed: Option<String>,
+    /// The date this secret was last modified.
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    /// The date this secret's value was last changed.
+    #[serde(default)]
+    pub last_rotated: Option<String>,
+    /// What has to be entered again before this secret is used or edited.
+    #[serde(default)]
+    pub protection: Protection,
+    /// The number of days after which this secret's value should be changed.
+    #[serde(default)]
+    pub rotation_days: Option<u32>,
+    /// The kind of secret that is stored.
+    #[serde(default)]
+    pub secret_type: SecretType,
     /// The tags associated with this secret.
     pub tags: Vec<String>,
+    /// Whether this secret is removed automatically once it expires.
+    #[serde(default)]
+    pub temporary: bool,
+    /// The URL this secret logs in to, such as `https://github.com/org/repo`.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// The username this secret logs in with.
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+/// Contains variants for each kind of secret.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretType {
+    /// A plain secret, such as a password or an API token, that is copied as is.
+    #[default]
+    Password,
+    /// A TOTP seed. Only the codes generated from the seed are copied.
+    Totp,
+}
+
+/// Contains variants for how much friction using or editing a secret requires.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protection {
+    /// The secret may be used within an unlocked session.
+    #[default]
+    None,
+    /// The master password has to be entered again.
+    Reauth,
+    /// The secret's own passphrase has to be entered. The secret is sealed a second time with a
+    /// key derived from the passphrase.
+    Passphrase,
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Protection::None => write!(f, "None"),
+            Protection::Reauth => write!(f, "Master password"),
+            Protection::Passphrase => write!(f, "Passphrase"),
+        }
+    }
+}
+
+impl FromStr for Protection {
+    type Err = String;
+
+    fn from_str(protection: &str) -> Result<Self, Self::Err> {
+        match protection.to_lowercase().as_str() {
+            "none" => Ok(Protection::None),
+            "reauth" => Ok(Protection::Reauth),
+            "passphrase" => Ok(Protection::Passphrase),
+            _ => Err(format!(
+                "\"{protection}\" is not a protection level! Use none, reauth, or passphrase."
+            )),
+        }
+    }
 }
 
 impl Anatomy {
@@ -25,12 +103,55 @@ impl Anatomy {
     pub fn create_from(category: String, label: String, tags: Vec<String>) -> Self {
         Self {
             category,
-            date_created: Local::now().format("%m-%d-%Y %H:%M:%S").to_string(),
+            date_created: dates::get_timestamp(),
+            expires_at: None,
             label,
             last_accessed: None,
+            last_modified: None,
+            last_rotated: None,
+            protection: Protection::None,
+            rotation_days: None,
+            secret_type: SecretType::Password,
             tags,
+            temporary: false,
+            url: None,
+            username: None,
+        }
+    }
+
+    /// Create an `Anatomy` for tests with a fixed creation date, so tests that compare dates do
+    /// not depend on when they are run.
+    #[cfg(test)]
+    pub fn create_for_test(category: &str, label: &str, date_created: &str) -> Self {
+        Self {
+            date_created: date_created.to_string(),
+            ..Self::create_from(category.to_string(), label.to_string(), Vec::new())
         }
     }
+
+    /// Get the date this secret was last modified, falling back to the date it was created if it
+    /// has never been edited.
+    pub fn get_modified_date(&self) -> &str {
+        self.last_modified.as_ref().unwrap_or(&self.date_created)
+    }
+
+    /// Get the date this secret's value was last changed, falling back to the date it was created
+    /// if it has never been rotated.
+    pub fn get_rotated_date(&self) -> &str {
+        self.last_rotated.as_ref().unwrap_or(&self.date_created)
+    }
+}
+
+/// Records a secret that was removed from the vault so the removal may be propagated when vaults
+/// are merged.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Tombstone {
+    /// The category the removed secret belonged in.
+    pub category: String,
+    /// The date the secret was removed.
+    pub date_removed: String,
+    /// The label associated with the removed secret.
+    pub label: String,
 }
 
 /// Contains the lookup table for secrets.
@@ -39,6 +160,13 @@ impl Anatomy {
 ///
 /// ```
 /// LookupTable {
+///     removed: [
+///         Tombstone {
+///             category: "unclassified",
+///             date_removed: "2007-07-07 07:07:07",
+///             label: "Some removed secret",
+///         },
+///     ],
 ///     table: {
 ///         "5d5c6f5b57bd22dded4046fa2eae5a64ec5aa5e3d6ba7420084a6245b7284ac4": { // Some hash
 ///             category: "unclassified",
@@ -56,6 +184,9 @@ impl Anatomy {
 /// ```
 #[derive(Debug, Deserialize, Serialize)]
 pub struct LookupTable {
+    /// Secrets that have been removed from the vault.
+    #[serde(default)]
+    pub removed: Vec<Tombstone>,
     /// The values within the lookup table.
     pub table: HashMap<String, Anatomy>,
 }
@@ -64,6 +195,7 @@ impl LookupTable {
     /// Create a new `LookupTable`.
     pub fn new() -> LookupTable {
         LookupTable {
+            removed: Vec::new(),
             table: HashMap::new(),
         }
     }
@@ -71,7 +203,7 @@ impl LookupTable {
 
 /// This struct temporarily holds a modified entry in the lookup table while searching for matches,
 /// which includes the secret's hash ID as well as its `Anatomy`.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct LookupMatch {
     /// The secret's corresponding `Anatomy`.
     pub anatomy: Anatomy,
@@ -109,18 +241,31 @@ pub mod test_metadata {
     fn test_lookuptable_new() {
         let test_lookup_table = LookupTable::new();
 
+        assert!(test_lookup_table.removed.is_empty());
         assert!(test_lookup_table.table.is_empty());
     }
 
+    /// Test whether secrets stored before secret types existed are read as passwords.
+    #[test]
+    fn test_anatomy_default_secret_type() {
+        let anatomy: Anatomy = serde_json::from_str(
+            r#"{"category":"unclassified","date_created":"today","label":"something","last_accessed":null,"tags":[]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(anatomy.secret_type, SecretType::Password);
+    }
+
     /// Test whether a new `LookupMatch` is created.
     #[test]
     fn test_lookupmatch_create() {
         let anatomy = Anatomy {
-            category: "unclassified".to_string(),
             date_created: "today".to_string(),
-            label: "something".to_string(),
-            last_accessed: None,
-            tags: vec!["tag".to_string()],
+            ..Anatomy::create_from(
+                "unclassified".to_string(),
+                "something".to_string(),
+                vec!["tag".to_string()],
+            )
         };
         let hash = "421c76d77563afa1914846b010bd164f395bd34c2102e5e99e0cb9cf173c1d87".to_string();
 
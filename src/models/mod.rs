@@ -1,4 +1,10 @@
 //! Contains models for `himitsu`.
 
+pub mod api;
+pub mod config;
 pub mod encryption;
+pub mod log;
 pub mod metadata;
+pub mod sharing;
+pub mod team;
+pub mod totp;
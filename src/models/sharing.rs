@@ -0,0 +1,37 @@
+//! Contains structs used for sharing secrets with other `himitsu` users.
+
+use serde::{Deserialize, Serialize};
+
+use super::metadata::SecretType;
+
+/// Contains data encrypted for a single recipient's identity. An envelope is sealed with a key
+/// derived from an X25519 key exchange between a random ephemeral key pair and the recipient's
+/// public key.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Envelope {
+    /// The encrypted data.
+    pub ciphertext: Vec<u8>,
+    /// The ephemeral public key used for the key exchange.
+    pub ephemeral_public_key: [u8; 32],
+    /// The nonce used to encrypt the data.
+    pub nonce: [u8; 24],
+    /// The recipient's public key.
+    pub recipient: [u8; 32],
+}
+
+/// Contains a secret shared with another `himitsu` user. This is encrypted into an `Envelope`
+/// before it is written to a file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SharedSecret {
+    /// The category this secret belongs in.
+    pub category: String,
+    /// The label associated with this secret.
+    pub label: String,
+    /// The secret itself.
+    pub secret: String,
+    /// The kind of secret that is shared.
+    #[serde(default)]
+    pub secret_type: SecretType,
+    /// The tags associated with this secret.
+    pub tags: Vec<String>,
+}
@@ -0,0 +1,17 @@
+//! Contains structs used for team vaults.
+
+use serde::{Deserialize, Serialize};
+
+use super::sharing::Envelope;
+
+/// Contains a member of a team vault. Each member has their own copy of the team vault's data key,
+/// sealed for their identity's public key.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Member {
+    /// The member's name.
+    pub name: String,
+    /// The member's public key.
+    pub public_key: [u8; 32],
+    /// The team vault's data key, sealed for this member.
+    pub wrapped_key: Envelope,
+}
@@ -0,0 +1,71 @@
+//! Contains structs used for TOTP secrets.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Contains variants for each HMAC algorithm a TOTP code may be generated with.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub enum TotpAlgorithm {
+    /// HMAC-SHA1, used by almost every service.
+    #[default]
+    #[serde(rename = "SHA1")]
+    Sha1,
+    /// HMAC-SHA256.
+    #[serde(rename = "SHA256")]
+    Sha256,
+    /// HMAC-SHA512.
+    #[serde(rename = "SHA512")]
+    Sha512,
+}
+
+impl fmt::Display for TotpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TotpAlgorithm::Sha1 => write!(f, "SHA1"),
+            TotpAlgorithm::Sha256 => write!(f, "SHA256"),
+            TotpAlgorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+impl FromStr for TotpAlgorithm {
+    type Err = String;
+
+    fn from_str(algorithm: &str) -> Result<Self, Self::Err> {
+        match algorithm.to_uppercase().replace('-', "").as_str() {
+            "SHA1" => Ok(TotpAlgorithm::Sha1),
+            "SHA256" => Ok(TotpAlgorithm::Sha256),
+            "SHA512" => Ok(TotpAlgorithm::Sha512),
+            _ => Err(format!(
+                "\"{algorithm}\" is not a supported algorithm! Use SHA1, SHA256, or SHA512."
+            )),
+        }
+    }
+}
+
+/// Contains a TOTP seed and the parameters its codes are generated with. This is what is
+/// encrypted and stored for a TOTP secret.
+#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct TotpSecret {
+    /// The HMAC algorithm codes are generated with.
+    pub algorithm: TotpAlgorithm,
+    /// The number of digits in each code.
+    pub digits: u32,
+    /// The number of seconds each code is valid for.
+    pub period: u64,
+    /// The base32 encoded seed.
+    pub seed: String,
+}
+
+/// Contains parameters that override the defaults (or the parameters in an `otpauth://` URI)
+/// when a TOTP secret is added.
+#[derive(Debug, Default)]
+pub struct TotpOptions {
+    /// Override the HMAC algorithm.
+    pub algorithm: Option<TotpAlgorithm>,
+    /// Override the number of digits.
+    pub digits: Option<u32>,
+    /// Override the period.
+    pub period: Option<u64>,
+}
@@ -2,16 +2,18 @@
 
 use inquire::{self, validator::StringValidator, Password, PasswordDisplayMode, Text};
 
-use crate::errors::HimitsuError;
+use crate::{errors::HimitsuError, models::totp::TotpOptions, strength, totp, utils::paint};
 
 use super::config::{self, ConfigType};
 
 /// Run the add secret prompts. Ask for a label (if one is not provided), then ask for the secret
-/// itself, and finally asks to set any categories or tags.
+/// itself, and finally asks to set any categories or tags. If `is_totp` is set, the secret must be
+/// an `otpauth://` URI or a base32 seed.
 pub fn run_add_secret(
     category: &Option<String>,
     label: &Option<String>,
     tags: &Option<Vec<String>>,
+    is_totp: bool,
 ) -> Result<(String, String, String, Vec<String>), HimitsuError> {
     let render_config = config::get_inquire_config(ConfigType::Standard, true);
 
@@ -35,14 +37,22 @@ pub fn run_add_secret(
         return Err(HimitsuError::UserCancelled);
     }
 
-    let secret_input = Password::new("Enter your secret:")
-        .with_display_mode(PasswordDisplayMode::Hidden)
-        .with_display_toggle_enabled()
-        .with_render_config(render_config)
-        .with_help_message("Press \"<CTRL> + r\" to reveal input.")
-        .prompt_skippable()?;
-    if secret_input.is_none() {
-        return Err(HimitsuError::UserCancelled);
+    let secret_input = if is_totp {
+        run_get_totp_seed("Enter the otpauth:// URI or base32 seed:")?
+    } else {
+        Password::new("Enter your secret:")
+            .with_display_mode(PasswordDisplayMode::Hidden)
+            .with_display_toggle_enabled()
+            .with_render_config(render_config)
+            .with_help_message("Press \"<CTRL> + r\" to reveal input.")
+            .prompt_skippable()?
+    };
+    match &secret_input {
+        Some(secret) if !is_totp => {
+            paint::paint_strength_meter(&strength::estimate_strength(secret))
+        }
+        Some(_) => {}
+        None => return Err(HimitsuError::UserCancelled),
     }
 
     let category = match category {
@@ -91,3 +101,21 @@ pub fn run_add_secret(
 
     Ok((label_input.unwrap(), secret_input.unwrap(), category, tags))
 }
+
+/// Run the prompt asking for a TOTP seed. The input is hidden and must be an `otpauth://` URI or a
+/// base32 seed.
+pub fn run_get_totp_seed(message: &str) -> Result<Option<String>, HimitsuError> {
+    let seed_validator: StringValidator = &|input| {
+        totp::parse_totp(input, &TotpOptions::default())
+            .map(|_| ())
+            .map_err(|error| error.to_string())
+    };
+
+    Ok(Password::new(message)
+        .with_display_mode(PasswordDisplayMode::Hidden)
+        .with_display_toggle_enabled()
+        .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
+        .with_help_message("Press \"<CTRL> + r\" to reveal input.")
+        .with_validator(seed_validator)
+        .prompt_skippable()?)
+}
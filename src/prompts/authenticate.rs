@@ -1,6 +1,14 @@
 //! Contains the execution flow for authentication.
 
-use crate::{authentication, errors::HimitsuError, models::encryption::Encryption};
+use std::path::PathBuf;
+
+use crate::{
+    audit::log,
+    authentication,
+    errors::HimitsuError,
+    models::{encryption::Encryption, log::LogAction},
+    utils::attempts,
+};
 
 use ansi_term::Color;
 use inquire::{self, Password, PasswordDisplayMode};
@@ -8,11 +16,30 @@ use spinners::{Spinner, Spinners};
 
 use super::config::{self, ConfigType};
 
-/// Run the authentication prompt.
-pub fn authenticate_user(encryption_values: &Encryption) -> Result<String, HimitsuError> {
+/// Run the authentication prompt. If the vault requires a keyfile, the keyfile's contents are
+/// combined with the entered password, and the combined value is returned in place of the
+/// password.
+///
+/// Failed attempts are recorded persistently. Once the free attempts are used up, the user has to
+/// wait longer after every failed attempt, even across separate runs. Each attempt is counted
+/// before the password is checked and only cleared once it succeeds.
+pub fn authenticate_user(
+    encryption_values: &Encryption,
+    keyfile: &Option<PathBuf>,
+) -> Result<String, HimitsuError> {
+    let keyfile_contents = if encryption_values.uses_keyfile {
+        Some(authentication::read_keyfile(
+            &authentication::get_keyfile_path(keyfile)?,
+        )?)
+    } else {
+        None
+    };
+
     let mut try_count = 0;
 
     while try_count < 3 {
+        attempts::check_lockout()?;
+
         let password = Password::new("Enter the password:")
             .with_display_mode(PasswordDisplayMode::Hidden)
             .with_display_toggle_enabled()
@@ -20,7 +47,12 @@ pub fn authenticate_user(encryption_values: &Encryption) -> Result<String, Himit
             .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
             .prompt_skippable()?;
 
-        match password {
+        match password.map(|input| match &keyfile_contents {
+            Some(contents) if !input.is_empty() => {
+                authentication::combine_with_keyfile(&input, contents)
+            }
+            _ => input,
+        }) {
             Some(input) => {
                 let mut login_spinner = Spinner::new(Spinners::Aesthetic, "Logging in...".into());
 
@@ -32,8 +64,24 @@ pub fn authenticate_user(encryption_values: &Encryption) -> Result<String, Himit
                             Color::Fixed(172).bold().paint("Please enter a password.")
                         ),
                     );
-                } else if !authentication::check_authorization(encryption_values, &input)? {
-                    if try_count < 2 {
+
+                    continue;
+                }
+
+                let attempt = match attempts::begin_attempt() {
+                    Ok(attempt) => attempt,
+                    Err(error) => {
+                        login_spinner.stop_with_newline();
+
+                        return Err(error);
+                    }
+                };
+
+                if !authentication::check_authorization(encryption_values, &input)? {
+                    log::record_event(LogAction::FailedLogin, None);
+                    let failures = attempts::record_failed_attempt()?;
+
+                    if try_count < 2 && attempts::get_backoff_seconds(failures) == 0 {
                         login_spinner.stop_and_persist(
                                 "🤔",
                                 format!(
@@ -57,6 +105,22 @@ pub fn authenticate_user(encryption_values: &Encryption) -> Result<String, Himit
                         format!("{}", Color::Green.bold().paint("Success.")),
                     );
 
+                    let login_attempts = attempts::clear_failed_attempts(&attempt)?;
+
+                    log::open_log(&input)?;
+                    log::record_event(LogAction::Unlock, None);
+
+                    if let Some(last_failure) = login_attempts.failed.last() {
+                        println!(
+                            "{}",
+                            Color::Fixed(172).bold().paint(format!(
+                                "{} failed attempt{} since last login (most recent: {last_failure}).",
+                                login_attempts.failed.len(),
+                                if login_attempts.failed.len() == 1 { "" } else { "s" }
+                            ))
+                        );
+                    }
+
                     return Ok(input);
                 }
             }
@@ -66,3 +130,15 @@ pub fn authenticate_user(encryption_values: &Encryption) -> Result<String, Himit
 
     Err(HimitsuError::FailedToLogin)
 }
+
+/// Run the prompt asking the user to re-enter their master password. Returns the master password
+/// on its own, even if the vault also requires a keyfile.
+pub fn run_reenter_password(message: &str) -> Result<String, HimitsuError> {
+    Password::new(message)
+        .with_display_mode(PasswordDisplayMode::Hidden)
+        .with_display_toggle_enabled()
+        .with_help_message("Press \"<CTRL> + r\" to reveal input")
+        .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)
+}
@@ -6,9 +6,17 @@ use inquire::{
     MultiSelect, Password, PasswordDisplayMode, Text,
 };
 
-use crate::{errors::HimitsuError, models::metadata::Anatomy};
+use crate::{
+    credentials::git::GitCredential,
+    errors::HimitsuError,
+    models::metadata::{Anatomy, SecretType},
+    utils::dates,
+};
 
-use super::config::{self, ConfigType};
+use super::{
+    add,
+    config::{self, ConfigType},
+};
 
 /// Run the prompts asking which secret attributes to edit.
 pub fn run_edit_targets<'a>() -> Result<Vec<&'a str>, HimitsuError> {
@@ -32,7 +40,15 @@ pub fn run_edit_targets<'a>() -> Result<Vec<&'a str>, HimitsuError> {
         }
     };
 
-    let options = vec!["Category", "Label", "Secret", "Tags"];
+    let options = vec![
+        "Category",
+        "Expiry",
+        "Label",
+        "Login",
+        "Protection",
+        "Secret",
+        "Tags",
+    ];
 
     let update_targets_input = MultiSelect::new("Select the attributes you want to update:", options)
         .with_formatter(answer_formatter)
@@ -76,6 +92,86 @@ pub fn run_edit_category(new_anatomy: &mut Anatomy) -> Result<(), HimitsuError>
     }
 }
 
+/// Run the prompts asking when this secret expires and how often it should be rotated.
+pub fn run_edit_expiry(new_anatomy: &mut Anatomy) -> Result<(), HimitsuError> {
+    let render_config = config::get_inquire_config(ConfigType::Standard, true);
+
+    let expiry_validator: StringValidator = &|input| {
+        if input.is_empty() || dates::parse_expiry(input, dates::get_now()).is_some() {
+            Ok(())
+        } else {
+            Err("Enter a duration like \"90d\" or a date like \"2025-01-31\"!".to_string())
+        }
+    };
+    let rotation_validator: StringValidator = &|input| {
+        if input.is_empty() || input.parse::<u32>().is_ok_and(|days| days > 0) {
+            Ok(())
+        } else {
+            Err("Enter a number of days!".to_string())
+        }
+    };
+
+    let expires = Text::new("Set when this secret expires:")
+        .with_help_message(&format!(
+            "(OPTIONAL) A duration like \"90d\" or a date like \"2025-01-31\". Currently: {}. Leave empty to never expire",
+            new_anatomy.expires_at.as_deref().unwrap_or("never")
+        ))
+        .with_render_config(render_config)
+        .with_validator(expiry_validator)
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)?;
+
+    let rotation_days = Text::new("Rotate this secret every how many days?")
+        .with_help_message("(OPTIONAL) Leave empty to never remind you to rotate this secret")
+        .with_render_config(render_config)
+        .with_validator(rotation_validator)
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)?;
+
+    new_anatomy.expires_at = dates::parse_expiry(&expires, dates::get_now())
+        .as_ref()
+        .map(dates::format_timestamp);
+    new_anatomy.rotation_days = rotation_days.parse().ok();
+    if new_anatomy.expires_at.is_none() {
+        new_anatomy.temporary = false;
+    }
+
+    Ok(())
+}
+
+/// Run the prompts asking for the URL and username this secret logs in with.
+pub fn run_edit_login(new_anatomy: &mut Anatomy) -> Result<(), HimitsuError> {
+    let render_config = config::get_inquire_config(ConfigType::Standard, true);
+
+    let url_validator: StringValidator = &|input| {
+        if input.is_empty() || GitCredential::parse_url(input).is_some() {
+            Ok(())
+        } else {
+            Err("Enter a URL like \"https://github.com/org/repo\"!".to_string())
+        }
+    };
+
+    let url = Text::new("Enter the URL this secret logs in to:")
+        .with_default(new_anatomy.url.as_deref().unwrap_or_default())
+        .with_help_message("(OPTIONAL) Leave empty to remove the URL")
+        .with_render_config(render_config)
+        .with_validator(url_validator)
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)?;
+
+    let username = Text::new("Enter the username this secret logs in with:")
+        .with_default(new_anatomy.username.as_deref().unwrap_or_default())
+        .with_help_message("(OPTIONAL) Leave empty to remove the username")
+        .with_render_config(render_config)
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)?;
+
+    new_anatomy.url = (!url.is_empty()).then_some(url);
+    new_anatomy.username = (!username.is_empty()).then_some(username);
+
+    Ok(())
+}
+
 /// Run the prompt asking for a new label for this secret.
 pub fn run_edit_label(new_anatomy: &mut Anatomy) -> Result<(), HimitsuError> {
     let render_config = config::get_inquire_config(ConfigType::Standard, true);
@@ -101,16 +197,20 @@ pub fn run_edit_label(new_anatomy: &mut Anatomy) -> Result<(), HimitsuError> {
     Ok(())
 }
 
-/// Run the prompt asking for a new secret.
-pub fn run_edit_secret() -> Result<String, HimitsuError> {
+/// Run the prompt asking for a new secret. TOTP secrets ask for a new `otpauth://` URI or base32
+/// seed instead.
+pub fn run_edit_secret(secret_type: &SecretType) -> Result<String, HimitsuError> {
     let render_config = config::get_inquire_config(ConfigType::Standard, true);
 
-    let secret_input = Password::new("Enter your new secret:")
-        .with_display_mode(PasswordDisplayMode::Hidden)
-        .with_display_toggle_enabled()
-        .with_render_config(render_config)
-        .with_help_message("Press \"<CTRL> + r\" to reveal input.")
-        .prompt_skippable()?;
+    let secret_input = match secret_type {
+        SecretType::Password => Password::new("Enter your new secret:")
+            .with_display_mode(PasswordDisplayMode::Hidden)
+            .with_display_toggle_enabled()
+            .with_render_config(render_config)
+            .with_help_message("Press \"<CTRL> + r\" to reveal input.")
+            .prompt_skippable()?,
+        SecretType::Totp => add::run_get_totp_seed("Enter the new otpauth:// URI or base32 seed:")?,
+    };
     if secret_input.is_none() {
         return Err(HimitsuError::UserCancelled);
     }
@@ -0,0 +1,75 @@
+//! Contains the execution flow for merging another vault into the current vault.
+
+use std::fmt;
+
+use ansi_term::Color;
+use inquire::{self, Password, PasswordDisplayMode, Select};
+
+use crate::{errors::HimitsuError, models::metadata::Anatomy};
+
+use super::config::{self, ConfigType};
+
+/// Contains variants for each side of a merge conflict.
+#[derive(Debug)]
+pub enum MergeSide {
+    /// Keep the secret stored in the current vault.
+    Current,
+    /// Keep the secret stored in the other vault.
+    Other,
+}
+
+impl fmt::Display for MergeSide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeSide::Current => write!(f, "Keep the secret in this vault"),
+            MergeSide::Other => write!(f, "Keep the secret in the other vault"),
+        }
+    }
+}
+
+/// Run the prompt asking for the other vault's master password.
+pub fn run_get_other_password() -> Result<String, HimitsuError> {
+    Password::new("Enter the password for the other vault:")
+        .with_display_mode(PasswordDisplayMode::Hidden)
+        .with_display_toggle_enabled()
+        .with_help_message("Press \"<CTRL> + r\" to reveal input")
+        .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)
+}
+
+/// Run the prompt asking which side of a merge conflict to keep.
+pub fn run_resolve_conflict(
+    local_anatomy: &Anatomy,
+    other_anatomy: &Anatomy,
+) -> Result<MergeSide, HimitsuError> {
+    println!(
+        "\n{} {}/{}",
+        Color::Fixed(172).bold().paint("Conflict:"),
+        local_anatomy.category,
+        local_anatomy.label
+    );
+
+    for (side, anatomy) in [
+        ("This vault", local_anatomy),
+        ("Other vault", other_anatomy),
+    ] {
+        println!(
+            "    {:<12} Modified: {}  Last accessed: {}  Tags: {:?}",
+            side,
+            Color::Green.bold().paint(anatomy.get_modified_date()),
+            Color::Cyan
+                .bold()
+                .paint(anatomy.last_accessed.as_deref().unwrap_or("N/A")),
+            anatomy.tags
+        );
+    }
+
+    Select::new(
+        "Which version do you want to keep?",
+        vec![MergeSide::Current, MergeSide::Other],
+    )
+    .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
+    .prompt_skippable()?
+    .ok_or(HimitsuError::UserCancelled)
+}
@@ -4,6 +4,9 @@ pub mod add;
 pub mod authenticate;
 pub mod config;
 pub mod edit;
+pub mod merge;
+pub mod protect;
+pub mod recovery;
 pub mod setup;
 pub mod use_secret;
 pub mod utils;
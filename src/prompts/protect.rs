@@ -0,0 +1,114 @@
+//! Contains the execution flow for protected secrets.
+
+use ansi_term::Color;
+use inquire::{Password, PasswordDisplayMode, Select};
+
+use crate::{
+    audit::log,
+    authentication,
+    encryption::decrypt,
+    errors::HimitsuError,
+    models::{
+        log::LogAction,
+        metadata::{Anatomy, Protection},
+    },
+    utils::attempts,
+};
+
+use super::config::{self, ConfigType};
+
+/// Run the prompt asking for a hidden value.
+fn run_hidden_prompt(message: &str) -> Result<String, HimitsuError> {
+    Password::new(message)
+        .with_display_mode(PasswordDisplayMode::Hidden)
+        .with_display_toggle_enabled()
+        .with_help_message("Press \"<CTRL> + r\" to reveal input")
+        .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)
+}
+
+/// Ask for whatever a protected secret requires before it is used or edited. Returns the secret's
+/// passphrase if it is sealed with one.
+///
+/// A wrong master password is counted toward the login lockout and recorded in the audit log as a
+/// failed login.
+pub fn run_unlock_protected_secret(
+    anatomy: &Anatomy,
+    password: &str,
+) -> Result<Option<String>, HimitsuError> {
+    match anatomy.protection {
+        Protection::None => Ok(None),
+        Protection::Reauth => {
+            let master_password = run_hidden_prompt(&format!(
+                "\"{}\" is protected. Re-enter your master password:",
+                anatomy.label
+            ))?;
+
+            if attempts::run_counted_check(|| {
+                authentication::verify_master_password(&master_password, password)
+            })? {
+                Ok(None)
+            } else {
+                log::record_event(LogAction::FailedLogin, Some(log::describe_secret(anatomy)));
+
+                Err(HimitsuError::FailedToLogin)
+            }
+        }
+        Protection::Passphrase => Ok(Some(run_hidden_prompt(&format!(
+            "\"{}\" is protected. Enter its passphrase:",
+            anatomy.label
+        ))?)),
+    }
+}
+
+/// Decrypt a stored secret, asking for whatever its protection requires first.
+pub fn run_decrypt_protected_secret(
+    hash_id: &str,
+    anatomy: &Anatomy,
+    password: &str,
+) -> Result<String, HimitsuError> {
+    let passphrase = run_unlock_protected_secret(anatomy, password)?;
+    let secret = decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(hash_id)?, password)?;
+
+    match passphrase {
+        Some(passphrase) => decrypt::open_with_passphrase(&passphrase, &secret),
+        None => Ok(secret),
+    }
+}
+
+/// Run the prompts asking for a new passphrase for a secret.
+pub fn run_new_passphrase() -> Result<String, HimitsuError> {
+    loop {
+        let passphrase = run_hidden_prompt("Set a passphrase for this secret:")?;
+        if passphrase.is_empty() {
+            println!(
+                "{}",
+                Color::Fixed(172).bold().paint("Please enter a passphrase.")
+            );
+            continue;
+        }
+
+        if run_hidden_prompt("Re-enter the passphrase:")? == passphrase {
+            return Ok(passphrase);
+        }
+
+        println!("{}", Color::Red.bold().paint("PASSPHRASES DO NOT MATCH."));
+    }
+}
+
+/// Run the prompt asking how this secret should be protected.
+pub fn run_edit_protection(new_anatomy: &mut Anatomy) -> Result<(), HimitsuError> {
+    let options = vec![Protection::None, Protection::Reauth, Protection::Passphrase];
+
+    new_anatomy.protection = Select::new("Require before using or editing this secret:", options)
+        .with_help_message(&format!(
+            "Currently: {}. A passphrase is never stored, and the secret cannot be recovered without it",
+            new_anatomy.protection
+        ))
+        .with_render_config(config::get_inquire_config(ConfigType::Standard, false))
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)?;
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! Contains the execution flow for recovering the vault with recovery shares.
+
+use inquire::{self, validator::StringValidator, Text};
+
+use crate::{
+    errors::HimitsuError,
+    recovery::{self, RecoveryShare},
+};
+
+use super::config::{self, ConfigType};
+
+/// Run the prompts asking for recovery shares until enough shares to recover the vault were
+/// entered.
+pub fn run_get_shares() -> Result<Vec<RecoveryShare>, HimitsuError> {
+    let share_validator: StringValidator = &|input| {
+        recovery::parse_share(input)
+            .map(|_| ())
+            .map_err(|error| error.to_string())
+    };
+
+    let mut recovery_shares: Vec<RecoveryShare> = Vec::new();
+
+    loop {
+        let encoded_share = Text::new(&format!(
+            "Enter recovery share #{}:",
+            recovery_shares.len() + 1
+        ))
+        .with_help_message(&match recovery_shares.first() {
+            Some(first_share) => format!(
+                "{} of {} shares entered",
+                recovery_shares.len(),
+                first_share.threshold
+            ),
+            None => "Shares look like \"himitsu-recovery-...\"".to_string(),
+        })
+        .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
+        .with_validator(share_validator)
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)?;
+
+        recovery_shares.push(recovery::parse_share(&encoded_share)?);
+
+        if recovery_shares.len() >= recovery_shares[0].threshold as usize {
+            return Ok(recovery_shares);
+        }
+    }
+}
@@ -1,30 +1,23 @@
 //! Contains the execution flow for the initial setup prompts.
 
-use std::io::Write;
+use std::{fs, path::PathBuf};
 
 use ansi_term::Color;
-use inquire::{self, validator::StringValidator, Password, PasswordDisplayMode};
-use rand::{self, rngs::OsRng, RngCore};
-use serde_json;
+use inquire::{self, validator::StringValidator, Password, PasswordDisplayMode, Text};
 use spinners::{Spinner, Spinners};
 
 use crate::{
-    authentication, errors::HimitsuError, lookup::init, models::encryption::Encryption,
+    authentication, errors::HimitsuError, lookup::init, models::encryption::Encryption, strength,
     utils::config,
 };
 
 use super::config::{get_inquire_config, ConfigType};
 
+/// The minimum strength score (out of 4) a new master password needs.
+const MIN_PASSWORD_SCORE: u8 = 3;
+
 /// Run the initial setup's prompt - set a master password to unlock the vault.
 pub fn run_initial_setup_prompts() -> Result<Encryption, HimitsuError> {
-    let password_validator: StringValidator = &|input| {
-        if input.chars().count() < 10 {
-            Err("The password must have at least 10 characters!".to_string())
-        } else {
-            Ok(())
-        }
-    };
-
     println!(
         "                            {}\n",
         Color::Fixed(172)
@@ -35,14 +28,99 @@ pub fn run_initial_setup_prompts() -> Result<Encryption, HimitsuError> {
             .paint("welcome")
     );
 
-    let password;
+    let password = run_new_password_prompts()?;
+
+    let keyfile = run_keyfile_prompt()?;
+    let password = match &keyfile {
+        Some(keyfile_path) => authentication::combine_with_keyfile(
+            &password,
+            &authentication::read_keyfile(keyfile_path)?,
+        ),
+        None => password,
+    };
+
+    let mut loading_bar = Spinner::new(Spinners::Aesthetic, "Generating encryption data...".into());
+
+    let encryption_data =
+        authentication::generate_salt_and_password_hash(&password, keyfile.is_some())?;
+    init::create_lookup(&password)?;
+
+    config::store_encryption_values(&encryption_data)?;
+
+    if keyfile.is_some() {
+        let mut user_config = config::get_config()?;
+        user_config.keyfile = keyfile;
+        config::store_config(&user_config)?;
+    }
+
+    loading_bar.stop_and_persist(
+        "🔒",
+        Color::Green
+            .bold()
+            .paint("Vault is configured.")
+            .to_string(),
+    );
+
+    Ok(encryption_data)
+}
+
+/// Run the prompt asking for an optional keyfile, which is combined with the master password to
+/// unlock the vault. Returns the keyfile's absolute path if one was entered.
+fn run_keyfile_prompt() -> Result<Option<PathBuf>, HimitsuError> {
+    let keyfile_validator: StringValidator = &|input| {
+        if input.is_empty() {
+            return Ok(());
+        }
+
+        match fs::metadata(input) {
+            Ok(metadata) if metadata.is_file() && metadata.len() > 0 => Ok(()),
+            _ => Err("The keyfile must be an existing, non-empty file!".to_string()),
+        }
+    };
+
+    let keyfile = Text::new("Register a keyfile:")
+        .with_help_message(
+            "(OPTIONAL) Enter the path to a file, such as a random file on a USB stick, that must be present to unlock your vault. Leave empty to skip",
+        )
+        .with_render_config(get_inquire_config(ConfigType::Standard, true))
+        .with_validator(keyfile_validator)
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)?;
+
+    if keyfile.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(fs::canonicalize(keyfile)?))
+    }
+}
+
+/// Run the prompts asking for a new master password. The password has to be entered twice and
+/// has to reach the minimum strength score.
+pub fn run_new_password_prompts() -> Result<String, HimitsuError> {
+    let password_validator: StringValidator = &|input| {
+        if input.chars().count() < 10 {
+            return Err("The password must have at least 10 characters!".to_string());
+        }
+
+        let estimate = strength::estimate_strength(input);
+        if estimate.score < MIN_PASSWORD_SCORE {
+            Err(format!(
+                "This password is {} ({}/4). {}",
+                estimate.get_description(),
+                estimate.score,
+                estimate.warnings.join(" ")
+            ))
+        } else {
+            Ok(())
+        }
+    };
 
     loop {
         let first_password = Password::new("Set a password for your vault:")
             .with_display_mode(PasswordDisplayMode::Hidden)
             .with_display_toggle_enabled()
             .with_help_message(
-                "Password must have at least 10 characters. Press \"<CTRL> + r\" to reveal input",
+                "Password must have at least 10 characters and must not be easy to guess. Press \"<CTRL> + r\" to reveal input",
             )
             .with_render_config(get_inquire_config(ConfigType::Standard, true))
             .with_validator(password_validator)
@@ -76,38 +154,7 @@ pub fn run_initial_setup_prompts() -> Result<Encryption, HimitsuError> {
         } else {
             validation_spinner.stop_and_persist("✅", "Passwords match.".into());
 
-            password = first_password.unwrap();
-
-            break;
+            return Ok(first_password.unwrap());
         }
     }
-
-    let mut loading_bar = Spinner::new(Spinners::Aesthetic, "Generating encryption data...".into());
-
-    let encryption_data = generate_salt_and_password_hash(&password)?;
-    init::create_lookup(&password)?;
-
-    let mut crypt_json = config::get_crypt_json()?;
-    crypt_json.write_all(serde_json::to_string(&encryption_data)?.as_bytes())?;
-
-    loading_bar.stop_and_persist(
-        "🔒",
-        Color::Green
-            .bold()
-            .paint("Vault is configured.")
-            .to_string(),
-    );
-
-    Ok(encryption_data)
-}
-
-/// Generate a new salt and password hash.
-fn generate_salt_and_password_hash(password: &str) -> Result<Encryption, HimitsuError> {
-    let mut salt = [0u8; 32];
-    OsRng.fill_bytes(&mut salt);
-
-    Ok(Encryption {
-        password_hash: authentication::generate_raw_hash(password, &salt)?,
-        salt,
-    })
 }
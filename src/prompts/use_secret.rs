@@ -1,25 +1,24 @@
 //! Contains the execution flow for using a stored secret.
 
-use std::collections::HashMap;
+use ansi_term::{Color, Style};
+use inquire::{self, error::InquireError, Select};
 
-use ansi_term::Color;
-use inquire::{self, Select};
-use serde_json;
-
-use crate::{errors::HimitsuError, models::metadata::LookupMatch};
+use crate::{
+    errors::HimitsuError,
+    lookup::utils::FoundMatch,
+    models::metadata::{LookupMatch, Protection},
+};
 
 use super::config::{self, ConfigType};
 
-/// Run the selection prompt if multiple label matches are found in the lookup table.
-pub fn run_select_secret(
-    found_matches: HashMap<String, LookupMatch>,
-) -> Result<LookupMatch, HimitsuError> {
-    let mut pairs = HashMap::new();
+/// Run the selection prompt if multiple label matches are found in the lookup table. Matches are
+/// listed in the order they are passed in.
+pub fn run_select_secret(mut found_matches: Vec<FoundMatch>) -> Result<LookupMatch, HimitsuError> {
     let mut options = Vec::new();
 
     for (painted_label, lookup_match) in found_matches.iter() {
         let option = format!(
-                "{}\n      Created:       {}\n      Last accessed: {}\n      Category:      {}\n      Tags:          {}",
+                "{}\n      Created:       {}\n      Last accessed: {}\n      Category:      {}\n      Tags:          {}\n      Protection:    {}",
                 painted_label,
                 Color::Green
                     .bold()
@@ -39,24 +38,22 @@ pub fn run_select_secret(
                         "None".to_string()
                     } else {
                         format!("{:?}", lookup_match.anatomy.tags)
-                    })
+                    }),
+                match lookup_match.anatomy.protection {
+                    Protection::None => Style::new().dimmed().paint("None"),
+                    protection => Color::Red.bold().paint(format!("🔐 {protection}")),
+                }
             );
 
-        pairs.insert(option.to_string(), serde_json::to_string(lookup_match)?);
-
         options.push(option);
     }
 
     match Select::new("Select a match:", options)
         .with_render_config(config::get_inquire_config(ConfigType::Standard, false))
-        .prompt_skippable()?
+        .raw_prompt()
     {
-        Some(selection) => match pairs.get(&selection) {
-            Some(lookup_match) => Ok(serde_json::from_str(lookup_match)?),
-            None => Err(HimitsuError::LookupError(
-                "Could not find a matching hash ID for this secret!".to_string(),
-            )),
-        },
-        None => Err(HimitsuError::UserCancelled),
+        Ok(selection) => Ok(found_matches.swap_remove(selection.index).1),
+        Err(InquireError::OperationCanceled) => Err(HimitsuError::UserCancelled),
+        Err(error) => Err(error.into()),
     }
 }
@@ -6,25 +6,28 @@ use crate::errors::HimitsuError;
 
 use super::config::{self, ConfigType};
 
-/// Runs the prompt to get the label of the secret the user wants to access.
-pub fn run_get_label(label: &Option<String>) -> Result<String, HimitsuError> {
+/// Runs the prompt to get the query for the secret the user wants to access, unless it was passed
+/// as arguments. Arguments are joined with spaces, so `hmu use aws tag:prod` searches for
+/// `aws tag:prod`.
+pub fn run_get_label(label: &[String]) -> Result<String, HimitsuError> {
     let label_validator: StringValidator = &|input| {
-        if input.is_empty() {
+        if input.trim().is_empty() {
             Err("A label is required!".to_string())
         } else {
             Ok(())
         }
     };
 
-    match label {
-        Some(label_value) => Ok(label_value.to_owned()),
-        None => Text::new("Enter the label of the secret you want to access:")
-            .with_help_message("Also accepts Regex expressions")
-            .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
-            .with_validator(label_validator)
-            .prompt_skippable()?
-            .map_or(Err(HimitsuError::UserCancelled), Ok),
+    if !label.is_empty() {
+        return Ok(label.join(" "));
     }
+
+    Text::new("Enter the label of the secret you want to access:")
+        .with_help_message("Also accepts queries such as `aws tag:prod -tag:old`")
+        .with_render_config(config::get_inquire_config(ConfigType::Standard, true))
+        .with_validator(label_validator)
+        .prompt_skippable()?
+        .ok_or(HimitsuError::UserCancelled)
 }
 
 /// Run a confirmation prompt with a message.
@@ -0,0 +1,210 @@
+//! Contains functions for recovering the vault if the master password is forgotten.
+//!
+//! Splitting creates a random recovery key, encrypts the value the vault is currently encrypted
+//! with (the master password, combined with the keyfile if there is one) with the recovery key,
+//! and stores the result in the `recovery` directory inside the vault. The recovery key itself is
+//! never stored. It is split into Shamir shares instead, so no single share holder can open the
+//! vault.
+
+pub mod shamir;
+
+use std::{fs, path::PathBuf};
+
+use data_encoding::HEXLOWER;
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use rand::{rngs::OsRng, RngCore};
+
+use crate::{
+    authentication,
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    lookup::rekey,
+    utils::{config, vault},
+};
+
+use shamir::Share;
+
+/// The prefix of every encoded share.
+const SHARE_PREFIX: &str = "himitsu-recovery";
+
+/// Contains a share that was parsed from its encoded form.
+#[derive(Debug)]
+pub struct RecoveryShare {
+    /// Identifies the split this share belongs to, so shares from different splits are not mixed.
+    pub split_id: String,
+    /// The number of shares required to recover the vault.
+    pub threshold: u8,
+    /// The share itself.
+    pub share: Share,
+}
+
+/// Get the path to the recovery directory.
+pub fn get_recovery_dir() -> Result<PathBuf, HimitsuError> {
+    Ok(vault::get_vault_dir()?.join("recovery"))
+}
+
+/// Check whether recovery shares have been created for the vault.
+pub fn is_configured() -> Result<bool, HimitsuError> {
+    Ok(get_recovery_dir()?.join("skeleton").exists())
+}
+
+/// Encode a share as a printable string:
+/// `himitsu-recovery-<SPLIT_ID>-<THRESHOLD>-<INDEX>-<HEX_VALUES>`.
+pub fn encode_share(split_id: &str, threshold: u8, share: &Share) -> String {
+    format!(
+        "{SHARE_PREFIX}-{split_id}-{threshold}-{}-{}",
+        share.index,
+        HEXLOWER.encode(&share.values)
+    )
+}
+
+/// Parse a share that was encoded with `encode_share()`.
+pub fn parse_share(encoded_share: &str) -> Result<RecoveryShare, HimitsuError> {
+    let invalid_share = || HimitsuError::RecoveryError("This is not a recovery share!".to_string());
+
+    let fields = encoded_share
+        .trim()
+        .strip_prefix(SHARE_PREFIX)
+        .and_then(|fields| fields.strip_prefix('-'))
+        .ok_or_else(invalid_share)?
+        .split('-')
+        .collect::<Vec<&str>>();
+
+    match fields[..] {
+        [split_id, threshold, index, values] => Ok(RecoveryShare {
+            split_id: split_id.to_lowercase(),
+            threshold: threshold.parse().map_err(|_| invalid_share())?,
+            share: Share {
+                index: index.parse().map_err(|_| invalid_share())?,
+                values: HEXLOWER
+                    .decode(values.to_lowercase().as_bytes())
+                    .map_err(|_| invalid_share())?,
+            },
+        }),
+        _ => Err(invalid_share()),
+    }
+}
+
+/// Render an encoded share as a QR code that may be printed in the terminal.
+pub fn render_qr_code(encoded_share: &str) -> Result<String, HimitsuError> {
+    QrCode::new(encoded_share.as_bytes())
+        .map(|qr_code| qr_code.render::<Dense1x2>().quiet_zone(true).build())
+        .map_err(|error| HimitsuError::RecoveryError(error.to_string()))
+}
+
+/// Create a new recovery key, split it into `shares` shares, any `threshold` of which recover
+/// the vault, and return the encoded shares. Shares from any previous split stop working.
+pub fn create_recovery_shares(
+    password: &str,
+    shares: u8,
+    threshold: u8,
+) -> Result<Vec<String>, HimitsuError> {
+    let mut recovery_key = [0u8; 32];
+    let mut split_id = [0u8; 4];
+    OsRng.fill_bytes(&mut recovery_key);
+    OsRng.fill_bytes(&mut split_id);
+
+    let split_shares = shamir::split(&recovery_key, shares, threshold)?;
+    let split_id = HEXLOWER.encode(&split_id);
+
+    let recovery_dir = get_recovery_dir()?;
+    if recovery_dir.exists() {
+        fs::remove_dir_all(&recovery_dir)?;
+    }
+
+    encrypt::encrypt_secret_in(&recovery_dir, &HEXLOWER.encode(&recovery_key), password)?;
+    fs::write(recovery_dir.join("id"), &split_id)?;
+
+    Ok(split_shares
+        .iter()
+        .map(|share| encode_share(&split_id, threshold, share))
+        .collect())
+}
+
+/// Combine recovery shares and return the value the vault is currently encrypted with.
+pub fn recover_password(recovery_shares: &[RecoveryShare]) -> Result<String, HimitsuError> {
+    if !is_configured()? {
+        return Err(HimitsuError::RecoveryError(
+            "Recovery shares were never created for this vault!".to_string(),
+        ));
+    }
+
+    let split_id = fs::read_to_string(get_recovery_dir()?.join("id"))?;
+    if let Some(stale_share) = recovery_shares
+        .iter()
+        .find(|recovery_share| recovery_share.split_id != split_id.trim())
+    {
+        return Err(HimitsuError::RecoveryError(format!(
+            "Share #{} belongs to a different or outdated set of shares!",
+            stale_share.share.index
+        )));
+    }
+
+    let recovery_key = shamir::combine(
+        &recovery_shares
+            .iter()
+            .map(|recovery_share| recovery_share.share.clone())
+            .collect::<Vec<Share>>(),
+    )?;
+
+    decrypt::decrypt_secret_in(&get_recovery_dir()?, &HEXLOWER.encode(&recovery_key)).map_err(
+        |_| HimitsuError::RecoveryError("These shares do not recover this vault!".to_string()),
+    )
+}
+
+/// Set a new master password after the vault was recovered. The whole vault is re-encrypted, the
+/// keyfile requirement is dropped, and the old recovery shares stop working.
+pub fn reset_master_password(
+    recovered_password: &str,
+    new_password: &str,
+) -> Result<(), HimitsuError> {
+    let encryption_values = config::get_encryption_values()?
+        .ok_or_else(|| HimitsuError::RecoveryError("There is no vault to recover!".to_string()))?;
+    if !authentication::check_authorization(&encryption_values, recovered_password)? {
+        return Err(HimitsuError::RecoveryError(
+            "The recovered password does not unlock this vault!".to_string(),
+        ));
+    }
+
+    rekey::rekey_vault(recovered_password, new_password)?;
+
+    config::store_encryption_values(&authentication::generate_salt_and_password_hash(
+        new_password,
+        false,
+    )?)?;
+
+    let mut user_config = config::get_config()?;
+    user_config.keyfile = None;
+    config::store_config(&user_config)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_recovery {
+    use super::*;
+
+    /// Test whether an encoded share is parsed back into the same share.
+    #[test]
+    fn test_share_round_trip() {
+        let share = Share {
+            index: 3,
+            values: vec![0, 1, 254, 255],
+        };
+
+        let recovery_share = parse_share(&encode_share("0a1b2c3d", 2, &share)).unwrap();
+
+        assert_eq!(recovery_share.split_id, "0a1b2c3d");
+        assert_eq!(recovery_share.threshold, 2);
+        assert_eq!(recovery_share.share, share);
+    }
+
+    /// Test whether malformed shares are rejected.
+    #[test]
+    fn test_parse_invalid_share() {
+        assert!(parse_share("himitsu-recovery-0a1b2c3d-2-3").is_err());
+        assert!(parse_share("himitsu-recovery-0a1b2c3d-2-x-00ff").is_err());
+        assert!(parse_share("himitsu-recovery-0a1b2c3d-2-3-0g").is_err());
+        assert!(parse_share("something-else-0a1b2c3d-2-3-00ff").is_err());
+    }
+}
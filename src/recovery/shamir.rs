@@ -0,0 +1,197 @@
+//! Contains an implementation of Shamir's secret sharing over GF(256).
+//!
+//! Each byte of the secret is the constant term of a random polynomial of degree `threshold - 1`.
+//! A share contains the value of every byte's polynomial at the share's (non-zero) index, so any
+//! `threshold` shares can reconstruct the secret through Lagrange interpolation, while fewer
+//! shares reveal nothing about it.
+
+use rand::{rngs::OsRng, RngCore};
+
+use crate::errors::HimitsuError;
+
+/// Contains a single share of a secret.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Share {
+    /// The x-coordinate this share was evaluated at. Never zero.
+    pub index: u8,
+    /// The y-coordinate of each byte's polynomial at `index`.
+    pub values: Vec<u8>,
+}
+
+/// Multiply two elements of GF(256), reducing by the AES polynomial (x^8 + x^4 + x^3 + x + 1).
+fn multiply(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+
+    product
+}
+
+/// Get the multiplicative inverse of a non-zero element of GF(256), which is `a^254`.
+fn inverse(a: u8) -> u8 {
+    let mut result = 1;
+    let mut base = a;
+    let mut exponent = 254;
+
+    while exponent > 0 {
+        if exponent & 1 != 0 {
+            result = multiply(result, base);
+        }
+        base = multiply(base, base);
+        exponent >>= 1;
+    }
+
+    result
+}
+
+/// Evaluate a polynomial (constant term first) at `x` using Horner's method.
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0, |result, coefficient| multiply(result, x) ^ coefficient)
+}
+
+/// Split a secret into `shares` shares, any `threshold` of which reconstruct the secret.
+pub fn split(secret: &[u8], shares: u8, threshold: u8) -> Result<Vec<Share>, HimitsuError> {
+    if threshold < 2 || threshold > shares {
+        return Err(HimitsuError::RecoveryError(
+            "The threshold must be at least 2 and at most the number of shares!".to_string(),
+        ));
+    }
+
+    let mut split_shares = (1..=shares)
+        .map(|index| Share {
+            index,
+            values: Vec::with_capacity(secret.len()),
+        })
+        .collect::<Vec<Share>>();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for byte in secret {
+        coefficients[0] = *byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+
+        for share in split_shares.iter_mut() {
+            share.values.push(evaluate(&coefficients, share.index));
+        }
+    }
+
+    Ok(split_shares)
+}
+
+/// Reconstruct a secret from its shares. Every share has to be distinct. Combining fewer shares
+/// than the threshold the secret was split with silently produces the wrong secret.
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, HimitsuError> {
+    if shares.len() < 2 {
+        return Err(HimitsuError::RecoveryError(
+            "At least 2 shares are required!".to_string(),
+        ));
+    }
+
+    let length = shares[0].values.len();
+    for (position, share) in shares.iter().enumerate() {
+        if share.index == 0 || share.values.len() != length {
+            return Err(HimitsuError::RecoveryError(format!(
+                "Share #{} is invalid!",
+                share.index
+            )));
+        }
+        if shares[..position]
+            .iter()
+            .any(|other| other.index == share.index)
+        {
+            return Err(HimitsuError::RecoveryError(format!(
+                "Share #{} was entered more than once!",
+                share.index
+            )));
+        }
+    }
+
+    // The Lagrange basis polynomials evaluated at x = 0. Subtraction is XOR in GF(256).
+    let basis = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1, |product, other| {
+                    multiply(
+                        product,
+                        multiply(other.index, inverse(other.index ^ share.index)),
+                    )
+                })
+        })
+        .collect::<Vec<u8>>();
+
+    Ok((0..length)
+        .map(|position| {
+            shares
+                .iter()
+                .zip(basis.iter())
+                .fold(0, |secret, (share, weight)| {
+                    secret ^ multiply(share.values[position], *weight)
+                })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+pub mod test_shamir {
+    use super::*;
+
+    /// Test GF(256) arithmetic against known values.
+    #[test]
+    fn test_field_arithmetic() {
+        assert_eq!(multiply(0x57, 0x83), 0xc1);
+        assert_eq!(multiply(0x57, 0x13), 0xfe);
+
+        for a in 1..=255u8 {
+            assert_eq!(multiply(a, inverse(a)), 1);
+        }
+    }
+
+    /// Test whether any `threshold` shares reconstruct the secret.
+    #[test]
+    fn test_split_and_combine() {
+        let secret = b"a 32 byte recovery key goes here".to_vec();
+        let shares = split(&secret, 5, 3).unwrap();
+
+        assert_eq!(shares.len(), 5);
+        assert_eq!(
+            combine(&[shares[0].clone(), shares[2].clone(), shares[4].clone()]).unwrap(),
+            secret
+        );
+        assert_eq!(
+            combine(&[shares[3].clone(), shares[1].clone(), shares[0].clone()]).unwrap(),
+            secret
+        );
+        assert_eq!(combine(&shares).unwrap(), secret);
+        assert_ne!(
+            combine(&[shares[0].clone(), shares[1].clone()]).unwrap(),
+            secret
+        );
+    }
+
+    /// Test whether invalid parameters and duplicate shares are rejected.
+    #[test]
+    fn test_invalid_shares() {
+        assert!(split(b"secret", 3, 1).is_err());
+        assert!(split(b"secret", 3, 4).is_err());
+
+        let shares = split(b"secret", 3, 2).unwrap();
+        assert!(combine(&[shares[0].clone()]).is_err());
+        assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+    }
+}
@@ -0,0 +1,490 @@
+//! Contains the state shared by every object served by `hmu secret-service`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use ansi_term::Style;
+
+use crate::{
+    audit::log,
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    lookup::{
+        expiry,
+        modify::{self, RemovalEvent},
+        secure,
+    },
+    models::{
+        log::LogAction,
+        metadata::{Anatomy, LookupTable, Protection, SecretType},
+    },
+    utils::{dates, git},
+};
+
+/// The name the provider owns on the session bus.
+pub const SERVICE_NAME: &str = "org.freedesktop.secrets";
+/// The path of the `org.freedesktop.Secret.Service` object.
+pub const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+/// The path of the collection secrets are stored in when an application does not choose one.
+pub const DEFAULT_ALIAS_PATH: &str = "/org/freedesktop/secrets/aliases/default";
+/// The name recorded in the audit log for every event caused through the Secret Service.
+pub const AUDIT_USER: &str = "secret-service";
+
+/// Encode a category or alias as a single element of a D-Bus object path, which may only contain
+/// ASCII letters, digits, and underscores. Every other byte is written as `_` followed by its hex
+/// value.
+pub fn encode_path_element(name: &str) -> String {
+    if name.is_empty() {
+        return "_".to_string();
+    }
+
+    name.bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() {
+                (byte as char).to_string()
+            } else {
+                format!("_{byte:02x}")
+            }
+        })
+        .collect()
+}
+
+/// Get the path of a category's collection.
+pub fn get_collection_path(category: &str) -> String {
+    format!(
+        "{SERVICE_PATH}/collection/{}",
+        encode_path_element(category)
+    )
+}
+
+/// Get the path of an item.
+pub fn get_item_path(category: &str, id: u32) -> String {
+    format!("{}/{id}", get_collection_path(category))
+}
+
+/// Get an item's attributes from its tags. Tags are stored as `key=value`; tags without a `=` are
+/// attributes with an empty value.
+pub fn tags_to_attributes(tags: &[String]) -> HashMap<String, String> {
+    tags.iter()
+        .map(|tag| match tag.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (tag.clone(), String::new()),
+        })
+        .collect()
+}
+
+/// Get the tags an item's attributes are stored as.
+pub fn attributes_to_tags(attributes: &HashMap<String, String>) -> Vec<String> {
+    let mut tags = attributes
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<String>>();
+    tags.sort();
+
+    tags
+}
+
+/// Check whether a secret has every attribute searched for.
+pub fn matches_attributes(anatomy: &Anatomy, attributes: &HashMap<String, String>) -> bool {
+    let stored_attributes = tags_to_attributes(&anatomy.tags);
+
+    attributes
+        .iter()
+        .all(|(key, value)| stored_attributes.get(key) == Some(value))
+}
+
+/// Check whether a secret may be exposed as an item. TOTP secrets and protected secrets are not
+/// exposed, since their values cannot be handed out as they are, and neither are temporary
+/// secrets that have expired.
+pub fn is_exposed(anatomy: &Anatomy) -> bool {
+    anatomy.secret_type == SecretType::Password
+        && anatomy.protection == Protection::None
+        && !expiry::has_lapsed(anatomy, dates::get_now())
+}
+
+/// Get a timestamp stored in a secret's `Anatomy` as seconds since the Unix epoch.
+pub fn get_unix_time(timestamp: &str) -> u64 {
+    dates::parse_timestamp(timestamp)
+        .and_then(|date| dates::to_unix_time(&date))
+        .unwrap_or_default()
+}
+
+/// Contains a secret exposed as an item.
+#[derive(Debug)]
+pub struct StoredItem {
+    /// The secret's `Anatomy`.
+    pub anatomy: Anatomy,
+    /// The secret's hash ID. It changes whenever the secret's value is replaced, while the item's
+    /// path stays the same.
+    pub hash_id: String,
+}
+
+/// Contains the vault's secrets as collections and items.
+#[derive(Debug)]
+pub struct Keyring {
+    /// The categories exposed as collections.
+    pub categories: BTreeSet<String>,
+    /// The category the `default` alias points to.
+    pub default_category: String,
+    /// The items, keyed by the ID in their path.
+    pub items: BTreeMap<u32, StoredItem>,
+    /// The ID given to the next item, session, or prompt.
+    next_id: u32,
+    /// The vault's password. `None` while the keyring is locked.
+    password: Option<String>,
+    /// The IDs of the open sessions.
+    pub sessions: BTreeSet<u32>,
+}
+
+impl Keyring {
+    /// Load the vault's secrets that may be exposed as items (see `is_exposed()`).
+    pub fn load(password: &str, default_category: &str) -> Result<Self, HimitsuError> {
+        let lookup_table = secure::decrypt_lookup_table(password)?;
+
+        let mut secrets = lookup_table
+            .table
+            .into_iter()
+            .filter(|(_, anatomy)| is_exposed(anatomy))
+            .collect::<Vec<(String, Anatomy)>>();
+        secrets.sort_by(|(_, first), (_, second)| {
+            (&first.category, &first.label).cmp(&(&second.category, &second.label))
+        });
+
+        let mut keyring = Self {
+            categories: BTreeSet::from([default_category.to_string()]),
+            default_category: default_category.to_string(),
+            items: BTreeMap::new(),
+            next_id: 1,
+            password: Some(password.to_string()),
+            sessions: BTreeSet::new(),
+        };
+        for (hash_id, anatomy) in secrets {
+            keyring.insert(hash_id, anatomy);
+        }
+
+        Ok(keyring)
+    }
+
+    /// Get a new ID for an item, session, or prompt.
+    pub fn get_next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        id
+    }
+
+    /// Add an item. Returns its ID.
+    fn insert(&mut self, hash_id: String, anatomy: Anatomy) -> u32 {
+        let id = self.get_next_id();
+
+        self.categories.insert(anatomy.category.clone());
+        self.items.insert(id, StoredItem { anatomy, hash_id });
+
+        id
+    }
+
+    /// Check whether the keyring is locked.
+    pub fn is_locked(&self) -> bool {
+        self.password.is_none()
+    }
+
+    /// Lock the keyring. The vault's password is dropped from memory.
+    pub fn lock(&mut self) {
+        self.password = None;
+    }
+
+    /// Unlock the keyring with the vault's password.
+    pub fn unlock(&mut self, password: String) {
+        self.password = Some(password);
+    }
+
+    /// Get the vault's password, or an error if the keyring is locked.
+    fn get_password(&self) -> Result<String, HimitsuError> {
+        self.password
+            .clone()
+            .ok_or_else(|| HimitsuError::SecretServiceError("The keyring is locked!".to_string()))
+    }
+
+    /// Get an item.
+    pub fn get_item(&self, id: u32) -> Result<&StoredItem, HimitsuError> {
+        self.items
+            .get(&id)
+            .ok_or_else(|| HimitsuError::SecretServiceError(format!("Item {id} does not exist!")))
+    }
+
+    /// Re-read an item's secret from the lookup table, since the vault may have been changed
+    /// outside the Secret Service since it was loaded. A secret whose value was replaced is found
+    /// again by its category, label, and creation date. The item is removed if its secret was
+    /// removed or may no longer be exposed.
+    fn refresh_item(&mut self, id: u32, password: &str) -> Result<&StoredItem, HimitsuError> {
+        let lookup_table = secure::decrypt_lookup_table(password)?;
+        let item = self.get_item(id)?;
+
+        match find_current_secret(&lookup_table, item).filter(|(_, anatomy)| is_exposed(anatomy)) {
+            Some((hash_id, anatomy)) => {
+                let hash_id = hash_id.clone();
+                let anatomy = anatomy.clone();
+                self.items.insert(id, StoredItem { anatomy, hash_id });
+
+                self.get_item(id)
+            }
+            None => {
+                self.items.remove(&id);
+
+                Err(HimitsuError::SecretServiceError(format!(
+                    "Item {id} is no longer in the vault!"
+                )))
+            }
+        }
+    }
+
+    /// Find the items in a category, or in every category, that have every attribute searched for.
+    pub fn find_items(
+        &self,
+        category: Option<&str>,
+        attributes: &HashMap<String, String>,
+    ) -> Vec<u32> {
+        self.items
+            .iter()
+            .filter(|(_, item)| {
+                category.is_none_or(|category| item.anatomy.category == category)
+                    && matches_attributes(&item.anatomy, attributes)
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Decrypt an item's value.
+    pub fn get_secret(&mut self, id: u32) -> Result<String, HimitsuError> {
+        let password = self.get_password()?;
+        let item = self.refresh_item(id, &password)?;
+
+        let secret =
+            decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(&item.hash_id)?, &password)?;
+
+        modify::update_last_accessed(&item.hash_id, &password)?;
+        log::record_event_as(
+            LogAction::Use,
+            Some(log::describe_secret(&item.anatomy)),
+            AUDIT_USER.to_string(),
+        );
+
+        println!(
+            "{}",
+            Style::new()
+                .dimmed()
+                .paint(format!("Served {}.", log::describe_secret(&item.anatomy)))
+        );
+
+        Ok(secret)
+    }
+
+    /// Store a new item, or replace the value of the item in the same category with the same
+    /// attributes if `replace` is set. Returns the item's ID and whether it was created.
+    pub fn create_item(
+        &mut self,
+        category: &str,
+        label: String,
+        attributes: &HashMap<String, String>,
+        secret: String,
+        replace: bool,
+    ) -> Result<(u32, bool), HimitsuError> {
+        let password = self.get_password()?;
+
+        if replace {
+            let existing = self.items.iter().find(|(_, item)| {
+                item.anatomy.category == category
+                    && tags_to_attributes(&item.anatomy.tags) == *attributes
+            });
+
+            if let Some((&id, _)) = existing {
+                self.set_secret(id, secret, Some(label))?;
+
+                return Ok((id, false));
+            }
+        }
+
+        let anatomy =
+            Anatomy::create_from(category.to_string(), label, attributes_to_tags(attributes));
+        let hash_id = encrypt::encrypt_secret(&anatomy, &password, secret)?;
+        git::record_change("add", &hash_id)?;
+        log::record_event_as(
+            LogAction::Add,
+            Some(log::describe_secret(&anatomy)),
+            AUDIT_USER.to_string(),
+        );
+
+        Ok((self.insert(hash_id, anatomy), true))
+    }
+
+    /// Replace an item's value, and optionally its label.
+    pub fn set_secret(
+        &mut self,
+        id: u32,
+        secret: String,
+        label: Option<String>,
+    ) -> Result<(), HimitsuError> {
+        let password = self.get_password()?;
+        let item = self.refresh_item(id, &password)?;
+
+        let current_secret =
+            decrypt::decrypt_secret_in(&decrypt::get_secret_hash_path(&item.hash_id)?, &password)?;
+
+        let mut anatomy = item.anatomy.clone();
+        anatomy.last_modified = Some(dates::get_timestamp());
+        if current_secret != secret {
+            anatomy.last_rotated = anatomy.last_modified.clone();
+        }
+        if let Some(label) = label {
+            anatomy.label = label;
+        }
+
+        let hash_id = modify::replace_secret(&item.hash_id, &anatomy, &password, &secret)?;
+        git::record_change("update", &hash_id)?;
+        log::record_event_as(
+            LogAction::Edit,
+            Some(log::describe_secret(&anatomy)),
+            AUDIT_USER.to_string(),
+        );
+
+        self.items.insert(id, StoredItem { anatomy, hash_id });
+
+        Ok(())
+    }
+
+    /// Change an item's label or attributes without touching its value.
+    pub fn update_item<F: FnOnce(&mut Anatomy)>(
+        &mut self,
+        id: u32,
+        update: F,
+    ) -> Result<(), HimitsuError> {
+        let password = self.get_password()?;
+        let item = self.refresh_item(id, &password)?;
+
+        let mut anatomy = item.anatomy.clone();
+        update(&mut anatomy);
+        anatomy.last_modified = Some(dates::get_timestamp());
+
+        let hash_id = item.hash_id.clone();
+        let mut lookup_table = secure::decrypt_lookup_table(&password)?;
+        modify::write_to_lookup_table(anatomy.clone(), &mut lookup_table, &password, &hash_id)?;
+        git::record_change("update", &hash_id)?;
+        log::record_event_as(
+            LogAction::Edit,
+            Some(log::describe_secret(&anatomy)),
+            AUDIT_USER.to_string(),
+        );
+
+        self.items.insert(id, StoredItem { anatomy, hash_id });
+
+        Ok(())
+    }
+
+    /// Remove an item from the vault.
+    pub fn delete_item(&mut self, id: u32) -> Result<(), HimitsuError> {
+        let password = self.get_password()?;
+        let item = self.refresh_item(id, &password)?;
+
+        modify::remove_in_lookup_table(&item.hash_id, &password, RemovalEvent::Remove)?;
+        git::record_change("remove", &item.hash_id)?;
+        log::record_event_as(
+            LogAction::Remove,
+            Some(log::describe_secret(&item.anatomy)),
+            AUDIT_USER.to_string(),
+        );
+
+        self.items.remove(&id);
+
+        Ok(())
+    }
+}
+
+/// Find an item's secret in the lookup table, by its hash ID or, if its value was replaced, by
+/// its category, label, and creation date.
+fn find_current_secret<'a>(
+    lookup_table: &'a LookupTable,
+    item: &StoredItem,
+) -> Option<(&'a String, &'a Anatomy)> {
+    lookup_table.table.get_key_value(&item.hash_id).or_else(|| {
+        lookup_table.table.iter().find(|(_, anatomy)| {
+            anatomy.category == item.anatomy.category
+                && anatomy.label == item.anatomy.label
+                && anatomy.date_created == item.anatomy.date_created
+        })
+    })
+}
+
+#[cfg(test)]
+pub mod test_keyring {
+    use super::*;
+
+    /// Test whether categories are encoded as valid object path elements.
+    #[test]
+    fn test_encode_path_element() {
+        assert_eq!(encode_path_element("cloud"), "cloud");
+        assert_eq!(encode_path_element("my-keys_2"), "my_2dkeys_5f2");
+        assert_eq!(encode_path_element("秘"), "_e7_a7_98");
+        assert_eq!(encode_path_element(""), "_");
+        assert_eq!(
+            get_item_path("cloud", 7),
+            "/org/freedesktop/secrets/collection/cloud/7"
+        );
+    }
+
+    /// Test whether attributes survive being stored as tags.
+    #[test]
+    fn test_attributes_as_tags() {
+        let attributes = HashMap::from([
+            ("service".to_string(), "github.com".to_string()),
+            ("username".to_string(), "octo=cat".to_string()),
+        ]);
+
+        let tags = attributes_to_tags(&attributes);
+        assert_eq!(tags, vec!["service=github.com", "username=octo=cat"]);
+        assert_eq!(tags_to_attributes(&tags), attributes);
+
+        let anatomy = Anatomy::create_from(
+            "unclassified".to_string(),
+            "github".to_string(),
+            [tags, vec!["work".to_string()]].concat(),
+        );
+        assert!(matches_attributes(
+            &anatomy,
+            &HashMap::from([("work".to_string(), String::new())])
+        ));
+        assert!(!matches_attributes(
+            &anatomy,
+            &HashMap::from([("service".to_string(), "gitlab.com".to_string())])
+        ));
+    }
+
+    /// Test whether an item's secret is found again after its value was replaced, and whether
+    /// secrets that may no longer be exposed are not.
+    #[test]
+    fn test_find_current_secret() {
+        let anatomy =
+            Anatomy::create_from("unclassified".to_string(), "github".to_string(), Vec::new());
+        let item = StoredItem {
+            anatomy: anatomy.clone(),
+            hash_id: "old".to_string(),
+        };
+
+        let mut lookup_table = LookupTable::new();
+        assert!(find_current_secret(&lookup_table, &item).is_none());
+
+        let mut replaced = anatomy.clone();
+        replaced.last_modified = Some(dates::get_timestamp());
+        lookup_table.table.insert("new".to_string(), replaced);
+        let (hash_id, found) = find_current_secret(&lookup_table, &item).unwrap();
+        assert_eq!(hash_id, "new");
+        assert!(is_exposed(found));
+
+        let mut protected = anatomy.clone();
+        protected.protection = Protection::Reauth;
+        assert!(!is_exposed(&protected));
+
+        let mut expired = anatomy;
+        expired.temporary = true;
+        expired.expires_at = Some("01-01-2000 00:00:00".to_string());
+        assert!(!is_exposed(&expired));
+    }
+}
@@ -0,0 +1,8 @@
+//! Contains a freedesktop Secret Service provider, so desktop applications and libsecret clients
+//! can use the vault through the `org.freedesktop.secrets` D-Bus API.
+//!
+//! Categories are exposed as collections and secrets as items. An item's attributes are stored as
+//! `key=value` tags, and the whole keyring is locked and unlocked with the master password.
+
+pub mod keyring;
+pub mod service;
@@ -0,0 +1,819 @@
+//! Contains the D-Bus objects served by `hmu secret-service`: the service itself, a collection for
+//! every category, an item for every secret, and the sessions and prompts clients open.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+use ansi_term::{Color, Style};
+use serde::{Deserialize, Serialize};
+use zbus::{
+    blocking::ConnectionBuilder,
+    dbus_interface, fdo,
+    names::ErrorName,
+    zvariant::{OwnedObjectPath, OwnedValue, Type, Value},
+    Connection, DBusError, Message, MessageBuilder, MessageHeader, ObjectServer, SignalContext,
+};
+
+use crate::{agent, authentication, errors::HimitsuError, prompts::authenticate, utils::config};
+
+use super::keyring::{self, Keyring, DEFAULT_ALIAS_PATH, SERVICE_NAME, SERVICE_PATH};
+
+/// The only session algorithm supported. Secrets are sent as they are, which is only as safe as
+/// the session bus itself. libsecret falls back to it when stronger algorithms are refused.
+const PLAIN_ALGORITHM: &str = "plain";
+/// The property holding an item's or collection's label.
+const ITEM_LABEL_PROPERTY: &str = "org.freedesktop.Secret.Item.Label";
+/// The property holding an item's attributes.
+const ITEM_ATTRIBUTES_PROPERTY: &str = "org.freedesktop.Secret.Item.Attributes";
+/// The property holding a new collection's label.
+const COLLECTION_LABEL_PROPERTY: &str = "org.freedesktop.Secret.Collection.Label";
+
+/// The keyring shared by every object.
+type SharedKeyring = Arc<Mutex<Keyring>>;
+
+/// Contains the errors defined by the Secret Service API, as well as the standard D-Bus errors
+/// clients expect, such as `NotSupported` when an algorithm is refused.
+#[derive(Debug)]
+pub enum SecretError {
+    /// A standard D-Bus error.
+    Fdo(fdo::Error),
+    /// The keyring has to be unlocked first.
+    IsLocked(String),
+    /// The session does not exist.
+    NoSession(String),
+    /// The object does not exist.
+    NoSuchObject(String),
+}
+
+impl DBusError for SecretError {
+    fn create_reply(&self, call: &MessageHeader<'_>) -> zbus::Result<Message> {
+        match self {
+            SecretError::Fdo(error) => error.create_reply(call),
+            SecretError::IsLocked(description)
+            | SecretError::NoSession(description)
+            | SecretError::NoSuchObject(description) => {
+                MessageBuilder::error(call, self.name())?.build(&(description,))
+            }
+        }
+    }
+
+    fn name(&self) -> ErrorName<'_> {
+        match self {
+            SecretError::Fdo(error) => error.name(),
+            SecretError::IsLocked(_) => {
+                ErrorName::from_static_str_unchecked("org.freedesktop.Secret.Error.IsLocked")
+            }
+            SecretError::NoSession(_) => {
+                ErrorName::from_static_str_unchecked("org.freedesktop.Secret.Error.NoSession")
+            }
+            SecretError::NoSuchObject(_) => {
+                ErrorName::from_static_str_unchecked("org.freedesktop.Secret.Error.NoSuchObject")
+            }
+        }
+    }
+
+    fn description(&self) -> Option<&str> {
+        match self {
+            SecretError::Fdo(error) => error.description(),
+            SecretError::IsLocked(description)
+            | SecretError::NoSession(description)
+            | SecretError::NoSuchObject(description) => Some(description),
+        }
+    }
+}
+
+impl From<zbus::Error> for SecretError {
+    fn from(error: zbus::Error) -> Self {
+        SecretError::Fdo(error.into())
+    }
+}
+
+impl From<HimitsuError> for SecretError {
+    fn from(error: HimitsuError) -> Self {
+        SecretError::Fdo(fdo::Error::Failed(error.to_string()))
+    }
+}
+
+/// Contains a secret as it is sent over D-Bus.
+#[derive(Debug, Deserialize, Serialize, Type)]
+pub struct Secret {
+    /// The session the secret is sent in.
+    pub session: OwnedObjectPath,
+    /// The algorithm's parameters, which are empty for the `plain` algorithm.
+    pub parameters: Vec<u8>,
+    /// The secret's value.
+    pub value: Vec<u8>,
+    /// The value's content type.
+    pub content_type: String,
+}
+
+/// Lock the keyring for a single call. The keyring is still used if another thread panicked
+/// while holding it, since every change is written to the vault before the keyring is updated.
+fn lock(keyring: &SharedKeyring) -> MutexGuard<'_, Keyring> {
+    keyring.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Convert a path built by this module into an object path.
+fn to_object_path(path: &str) -> Result<OwnedObjectPath, SecretError> {
+    OwnedObjectPath::try_from(path.to_string()).map_err(|error| zbus::Error::from(error).into())
+}
+
+/// Get the path used where the API expects no object, such as when no prompt is needed.
+fn get_empty_path() -> Result<OwnedObjectPath, SecretError> {
+    to_object_path("/")
+}
+
+/// Get a session's path.
+fn get_session_path(id: u32) -> String {
+    format!("{SERVICE_PATH}/session/{id}")
+}
+
+/// Get a prompt's path.
+fn get_prompt_path(id: u32) -> String {
+    format!("{SERVICE_PATH}/prompt/{id}")
+}
+
+/// Refuse calls the provider does not support.
+fn not_supported(message: &str) -> SecretError {
+    SecretError::Fdo(fdo::Error::NotSupported(message.to_string()))
+}
+
+/// Refuse calls that need the keyring to be unlocked.
+fn check_unlocked(keyring: &Keyring) -> Result<(), SecretError> {
+    if keyring.is_locked() {
+        Err(SecretError::IsLocked(
+            "The keyring is locked! Unlock it first.".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Refuse sessions that were not opened or were closed already.
+fn check_session(keyring: &Keyring, session: &OwnedObjectPath) -> Result<(), SecretError> {
+    if keyring
+        .sessions
+        .iter()
+        .any(|id| get_session_path(*id) == session.as_str())
+    {
+        Ok(())
+    } else {
+        Err(SecretError::NoSession(format!(
+            "{} is not an open session!",
+            session.as_str()
+        )))
+    }
+}
+
+/// Find the ID of the item at a path.
+fn find_item_id(keyring: &Keyring, path: &str) -> Result<u32, SecretError> {
+    path.rsplit('/')
+        .next()
+        .and_then(|id| id.parse::<u32>().ok())
+        .filter(|id| {
+            keyring
+                .items
+                .get(id)
+                .is_some_and(|item| keyring::get_item_path(&item.anatomy.category, *id) == path)
+        })
+        .ok_or_else(|| SecretError::NoSuchObject(format!("{path} does not exist!")))
+}
+
+/// Get the paths of some items.
+fn get_item_paths(keyring: &Keyring, ids: &[u32]) -> Result<Vec<OwnedObjectPath>, SecretError> {
+    ids.iter()
+        .map(|id| {
+            let item = keyring.get_item(*id)?;
+
+            to_object_path(&keyring::get_item_path(&item.anatomy.category, *id))
+        })
+        .collect()
+}
+
+/// Decrypt an item's value and wrap it for a session.
+fn get_secret(
+    keyring: &mut Keyring,
+    id: u32,
+    session: &OwnedObjectPath,
+) -> Result<Secret, SecretError> {
+    check_session(keyring, session)?;
+    check_unlocked(keyring)?;
+
+    Ok(Secret {
+        session: session.clone(),
+        parameters: Vec::new(),
+        value: keyring.get_secret(id)?.into_bytes(),
+        content_type: "text/plain; charset=utf8".to_string(),
+    })
+}
+
+/// Read a secret sent by a client. Only text secrets can be stored in the vault.
+fn read_secret(keyring: &Keyring, secret: Secret) -> Result<String, SecretError> {
+    check_session(keyring, &secret.session)?;
+
+    String::from_utf8(secret.value).map_err(|_| {
+        SecretError::Fdo(fdo::Error::InvalidArgs(
+            "Secrets have to be UTF-8 text!".to_string(),
+        ))
+    })
+}
+
+/// Read a string property, such as a label.
+fn get_string_property(properties: &HashMap<String, OwnedValue>, name: &str) -> Option<String> {
+    match properties.get(name).map(|value| &**value) {
+        Some(Value::Str(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Read the attributes property of a new item.
+fn get_attributes_property(
+    properties: &HashMap<String, OwnedValue>,
+) -> Result<HashMap<String, String>, SecretError> {
+    match properties.get(ITEM_ATTRIBUTES_PROPERTY) {
+        Some(value) => HashMap::<String, String>::try_from(value.clone())
+            .map_err(|error| zbus::Error::from(error).into()),
+        None => Ok(HashMap::new()),
+    }
+}
+
+/// Get the vault's password from the agent, if it is running.
+fn request_agent_password() -> Option<String> {
+    let encryption_values = config::get_encryption_values().ok()??;
+
+    agent::request_password().filter(|password| {
+        authentication::check_authorization(&encryption_values, password).unwrap_or(false)
+    })
+}
+
+/// Serves `org.freedesktop.Secret.Service` at `/org/freedesktop/secrets`.
+pub struct Service {
+    /// The shared keyring.
+    keyring: SharedKeyring,
+}
+
+#[dbus_interface(name = "org.freedesktop.Secret.Service")]
+impl Service {
+    /// Open a session secrets are sent in. Only the `plain` algorithm is supported.
+    async fn open_session(
+        &self,
+        algorithm: &str,
+        _input: Value<'_>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> Result<(OwnedValue, OwnedObjectPath), SecretError> {
+        if algorithm != PLAIN_ALGORITHM {
+            return Err(not_supported(&format!(
+                "The \"{algorithm}\" algorithm is not supported. Use \"{PLAIN_ALGORITHM}\"."
+            )));
+        }
+
+        let id = {
+            let mut keyring = lock(&self.keyring);
+            let id = keyring.get_next_id();
+            keyring.sessions.insert(id);
+
+            id
+        };
+
+        let path = get_session_path(id);
+        server
+            .at(
+                path.as_str(),
+                Session {
+                    id,
+                    keyring: Arc::clone(&self.keyring),
+                },
+            )
+            .await?;
+
+        Ok((Value::from("").into(), to_object_path(&path)?))
+    }
+
+    /// Create a collection, which is a category that has no secrets yet.
+    async fn create_collection(
+        &self,
+        properties: HashMap<String, OwnedValue>,
+        _alias: &str,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> Result<(OwnedObjectPath, OwnedObjectPath), SecretError> {
+        let category = get_string_property(&properties, COLLECTION_LABEL_PROPERTY)
+            .filter(|label| !label.trim().is_empty())
+            .ok_or_else(|| not_supported("Collections need a label, which becomes a category."))?
+            .to_lowercase();
+
+        let is_new = lock(&self.keyring).categories.insert(category.clone());
+        let path = keyring::get_collection_path(&category);
+        if is_new {
+            server
+                .at(
+                    path.as_str(),
+                    Collection {
+                        category,
+                        keyring: Arc::clone(&self.keyring),
+                    },
+                )
+                .await?;
+        }
+
+        Ok((to_object_path(&path)?, get_empty_path()?))
+    }
+
+    /// Find the items with every attribute searched for in every collection.
+    fn search_items(
+        &self,
+        attributes: HashMap<String, String>,
+    ) -> Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>), SecretError> {
+        let keyring = lock(&self.keyring);
+        let item_paths = get_item_paths(&keyring, &keyring.find_items(None, &attributes))?;
+
+        if keyring.is_locked() {
+            Ok((Vec::new(), item_paths))
+        } else {
+            Ok((item_paths, Vec::new()))
+        }
+    }
+
+    /// Unlock the keyring. The vault's password is taken from the agent if it is running.
+    /// Otherwise, a prompt is returned that asks for the master password in the terminal running
+    /// `hmu secret-service`.
+    async fn unlock(
+        &self,
+        objects: Vec<OwnedObjectPath>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> Result<(Vec<OwnedObjectPath>, OwnedObjectPath), SecretError> {
+        if !lock(&self.keyring).is_locked() {
+            return Ok((objects, get_empty_path()?));
+        }
+
+        if let Some(password) = request_agent_password() {
+            lock(&self.keyring).unlock(password);
+            println!(
+                "{}",
+                Style::new()
+                    .dimmed()
+                    .paint("Unlocked the keyring with the agent.")
+            );
+
+            return Ok((objects, get_empty_path()?));
+        }
+
+        let path = get_prompt_path(lock(&self.keyring).get_next_id());
+        server
+            .at(
+                path.as_str(),
+                Prompt {
+                    keyring: Arc::clone(&self.keyring),
+                    objects,
+                    path: path.clone(),
+                },
+            )
+            .await?;
+
+        Ok((Vec::new(), to_object_path(&path)?))
+    }
+
+    /// Lock the keyring. Every collection is locked at once, since they are all unlocked by the
+    /// master password.
+    fn lock(
+        &self,
+        objects: Vec<OwnedObjectPath>,
+    ) -> Result<(Vec<OwnedObjectPath>, OwnedObjectPath), SecretError> {
+        lock(&self.keyring).lock();
+        println!("{}", Style::new().dimmed().paint("Locked the keyring."));
+
+        Ok((objects, get_empty_path()?))
+    }
+
+    /// Decrypt several items' values at once.
+    fn get_secrets(
+        &self,
+        items: Vec<OwnedObjectPath>,
+        session: OwnedObjectPath,
+    ) -> Result<HashMap<OwnedObjectPath, Secret>, SecretError> {
+        let mut keyring = lock(&self.keyring);
+        check_session(&keyring, &session)?;
+        check_unlocked(&keyring)?;
+
+        let mut secrets = HashMap::new();
+        for item in items {
+            let id = find_item_id(&keyring, item.as_str())?;
+            secrets.insert(item, get_secret(&mut keyring, id, &session)?);
+        }
+
+        Ok(secrets)
+    }
+
+    /// Get the collection an alias points to. Only the `default` alias exists.
+    fn read_alias(&self, name: &str) -> Result<OwnedObjectPath, SecretError> {
+        if name == "default" {
+            to_object_path(&keyring::get_collection_path(
+                &lock(&self.keyring).default_category,
+            ))
+        } else {
+            get_empty_path()
+        }
+    }
+
+    /// Point an alias to another collection, which is not supported.
+    fn set_alias(&self, _name: &str, _collection: OwnedObjectPath) -> Result<(), SecretError> {
+        Err(not_supported(
+            "The default collection is chosen with `hmu secret-service --category`.",
+        ))
+    }
+
+    /// The paths of every collection.
+    #[dbus_interface(property)]
+    fn collections(&self) -> Vec<OwnedObjectPath> {
+        lock(&self.keyring)
+            .categories
+            .iter()
+            .filter_map(|category| to_object_path(&keyring::get_collection_path(category)).ok())
+            .collect()
+    }
+}
+
+/// Serves `org.freedesktop.Secret.Collection` for a category.
+pub struct Collection {
+    /// The category this collection exposes.
+    category: String,
+    /// The shared keyring.
+    keyring: SharedKeyring,
+}
+
+#[dbus_interface(name = "org.freedesktop.Secret.Collection")]
+impl Collection {
+    /// Delete the collection, which is not supported since it would remove every secret in the
+    /// category.
+    fn delete(&self) -> Result<OwnedObjectPath, SecretError> {
+        Err(not_supported(
+            "Collections are categories and cannot be deleted through the Secret Service.",
+        ))
+    }
+
+    /// Find the items in this collection with every attribute searched for.
+    fn search_items(
+        &self,
+        attributes: HashMap<String, String>,
+    ) -> Result<Vec<OwnedObjectPath>, SecretError> {
+        let keyring = lock(&self.keyring);
+
+        get_item_paths(
+            &keyring,
+            &keyring.find_items(Some(&self.category), &attributes),
+        )
+    }
+
+    /// Store a secret in this collection's category. Its attributes are stored as `key=value`
+    /// tags.
+    async fn create_item(
+        &self,
+        properties: HashMap<String, OwnedValue>,
+        secret: Secret,
+        replace: bool,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> Result<(OwnedObjectPath, OwnedObjectPath), SecretError> {
+        let attributes = get_attributes_property(&properties)?;
+        let label = get_string_property(&properties, ITEM_LABEL_PROPERTY)
+            .filter(|label| !label.trim().is_empty())
+            .unwrap_or_else(|| "untitled".to_string());
+
+        let (id, is_new) = {
+            let mut keyring = lock(&self.keyring);
+            check_unlocked(&keyring)?;
+            let secret = read_secret(&keyring, secret)?;
+
+            keyring.create_item(&self.category, label, &attributes, secret, replace)?
+        };
+
+        let path = keyring::get_item_path(&self.category, id);
+        if is_new {
+            server
+                .at(
+                    path.as_str(),
+                    Item {
+                        id,
+                        keyring: Arc::clone(&self.keyring),
+                    },
+                )
+                .await?;
+        }
+
+        Ok((to_object_path(&path)?, get_empty_path()?))
+    }
+
+    /// The paths of this collection's items.
+    #[dbus_interface(property)]
+    fn items(&self) -> Vec<OwnedObjectPath> {
+        let keyring = lock(&self.keyring);
+
+        get_item_paths(
+            &keyring,
+            &keyring.find_items(Some(&self.category), &HashMap::new()),
+        )
+        .unwrap_or_default()
+    }
+
+    /// The collection's label, which is its category.
+    #[dbus_interface(property)]
+    fn label(&self) -> String {
+        self.category.clone()
+    }
+
+    /// Whether the keyring is locked.
+    #[dbus_interface(property)]
+    fn locked(&self) -> bool {
+        lock(&self.keyring).is_locked()
+    }
+
+    /// When the collection's oldest item was created.
+    #[dbus_interface(property)]
+    fn created(&self) -> u64 {
+        lock(&self.keyring)
+            .items
+            .values()
+            .filter(|item| item.anatomy.category == self.category)
+            .map(|item| keyring::get_unix_time(&item.anatomy.date_created))
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// When one of the collection's items was last modified.
+    #[dbus_interface(property)]
+    fn modified(&self) -> u64 {
+        lock(&self.keyring)
+            .items
+            .values()
+            .filter(|item| item.anatomy.category == self.category)
+            .map(|item| keyring::get_unix_time(item.anatomy.get_modified_date()))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// Serves `org.freedesktop.Secret.Item` for a secret.
+pub struct Item {
+    /// The item's ID.
+    id: u32,
+    /// The shared keyring.
+    keyring: SharedKeyring,
+}
+
+#[dbus_interface(name = "org.freedesktop.Secret.Item")]
+impl Item {
+    /// Remove the secret from the vault.
+    async fn delete(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> Result<OwnedObjectPath, SecretError> {
+        let path = {
+            let mut keyring = lock(&self.keyring);
+            check_unlocked(&keyring)?;
+            let path =
+                keyring::get_item_path(&keyring.get_item(self.id)?.anatomy.category, self.id);
+            keyring.delete_item(self.id)?;
+
+            path
+        };
+
+        server.remove::<Item, _>(path.as_str()).await?;
+
+        get_empty_path()
+    }
+
+    /// Decrypt the secret's value.
+    fn get_secret(&self, session: OwnedObjectPath) -> Result<(Secret,), SecretError> {
+        Ok((get_secret(&mut lock(&self.keyring), self.id, &session)?,))
+    }
+
+    /// Replace the secret's value.
+    fn set_secret(&self, secret: Secret) -> Result<(), SecretError> {
+        let mut keyring = lock(&self.keyring);
+        check_unlocked(&keyring)?;
+        let secret = read_secret(&keyring, secret)?;
+
+        Ok(keyring.set_secret(self.id, secret, None)?)
+    }
+
+    /// Whether the keyring is locked.
+    #[dbus_interface(property)]
+    fn locked(&self) -> bool {
+        lock(&self.keyring).is_locked()
+    }
+
+    /// The secret's attributes, taken from its `key=value` tags.
+    #[dbus_interface(property)]
+    fn attributes(&self) -> HashMap<String, String> {
+        lock(&self.keyring)
+            .get_item(self.id)
+            .map(|item| keyring::tags_to_attributes(&item.anatomy.tags))
+            .unwrap_or_default()
+    }
+
+    /// Replace the secret's attributes.
+    #[dbus_interface(property)]
+    fn set_attributes(&mut self, attributes: HashMap<String, String>) -> fdo::Result<()> {
+        lock(&self.keyring)
+            .update_item(self.id, |anatomy| {
+                anatomy.tags = keyring::attributes_to_tags(&attributes);
+            })
+            .map_err(|error| fdo::Error::Failed(error.to_string()))
+    }
+
+    /// The secret's label.
+    #[dbus_interface(property)]
+    fn label(&self) -> String {
+        lock(&self.keyring)
+            .get_item(self.id)
+            .map(|item| item.anatomy.label.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replace the secret's label.
+    #[dbus_interface(property)]
+    fn set_label(&mut self, label: String) -> fdo::Result<()> {
+        lock(&self.keyring)
+            .update_item(self.id, |anatomy| anatomy.label = label)
+            .map_err(|error| fdo::Error::Failed(error.to_string()))
+    }
+
+    /// When the secret was created.
+    #[dbus_interface(property)]
+    fn created(&self) -> u64 {
+        lock(&self.keyring)
+            .get_item(self.id)
+            .map(|item| keyring::get_unix_time(&item.anatomy.date_created))
+            .unwrap_or_default()
+    }
+
+    /// When the secret was last modified.
+    #[dbus_interface(property)]
+    fn modified(&self) -> u64 {
+        lock(&self.keyring)
+            .get_item(self.id)
+            .map(|item| keyring::get_unix_time(item.anatomy.get_modified_date()))
+            .unwrap_or_default()
+    }
+}
+
+/// Serves `org.freedesktop.Secret.Session` for an open session.
+pub struct Session {
+    /// The session's ID.
+    id: u32,
+    /// The shared keyring.
+    keyring: SharedKeyring,
+}
+
+#[dbus_interface(name = "org.freedesktop.Secret.Session")]
+impl Session {
+    /// Close the session.
+    async fn close(&self, #[zbus(object_server)] server: &ObjectServer) -> fdo::Result<()> {
+        lock(&self.keyring).sessions.remove(&self.id);
+        server
+            .remove::<Session, _>(get_session_path(self.id).as_str())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Serves `org.freedesktop.Secret.Prompt` while the keyring waits to be unlocked.
+pub struct Prompt {
+    /// The shared keyring.
+    keyring: SharedKeyring,
+    /// The objects the client asked to unlock.
+    objects: Vec<OwnedObjectPath>,
+    /// The prompt's path.
+    path: String,
+}
+
+impl Prompt {
+    /// Report the prompt's result and remove it.
+    fn complete(
+        connection: &Connection,
+        path: &str,
+        dismissed: bool,
+        objects: Vec<OwnedObjectPath>,
+    ) {
+        let _ = zbus::block_on(async {
+            let context = SignalContext::new(connection, path)?;
+            Prompt::completed(&context, dismissed, Value::from(objects)).await?;
+
+            connection.object_server().remove::<Prompt, _>(path).await
+        });
+    }
+}
+
+#[dbus_interface(name = "org.freedesktop.Secret.Prompt")]
+impl Prompt {
+    /// Ask for the master password in the terminal running `hmu secret-service`. The result is
+    /// reported with the `Completed` signal.
+    fn prompt(&self, _window_id: &str, #[zbus(connection)] connection: &Connection) {
+        let connection = connection.clone();
+        let keyring = Arc::clone(&self.keyring);
+        let objects = self.objects.clone();
+        let path = self.path.clone();
+
+        thread::spawn(move || {
+            println!(
+                "\n{}",
+                Color::Fixed(172)
+                    .bold()
+                    .paint("An application asked to unlock the keyring.")
+            );
+
+            let password =
+                config::get_encryption_values()
+                    .ok()
+                    .flatten()
+                    .and_then(|encryption_values| {
+                        authenticate::authenticate_user(&encryption_values, &None).ok()
+                    });
+
+            match password {
+                Some(password) => {
+                    lock(&keyring).unlock(password);
+                    Prompt::complete(&connection, &path, false, objects);
+                }
+                None => Prompt::complete(&connection, &path, true, Vec::new()),
+            }
+        });
+    }
+
+    /// Dismiss the prompt without unlocking the keyring.
+    fn dismiss(&self, #[zbus(connection)] connection: &Connection) {
+        let connection = connection.clone();
+        let path = self.path.clone();
+
+        thread::spawn(move || Prompt::complete(&connection, &path, true, Vec::new()));
+    }
+
+    /// Emitted once the prompt is completed or dismissed.
+    #[dbus_interface(signal)]
+    async fn completed(
+        context: &SignalContext<'_>,
+        dismissed: bool,
+        result: Value<'_>,
+    ) -> zbus::Result<()>;
+}
+
+/// Serve the keyring on the session bus until the process is stopped.
+pub fn serve(keyring: Keyring) -> Result<(), HimitsuError> {
+    let default_category = keyring.default_category.clone();
+    let categories = keyring.categories.clone();
+    let items = keyring
+        .items
+        .iter()
+        .map(|(id, item)| (*id, item.anatomy.category.clone()))
+        .collect::<Vec<(u32, String)>>();
+    let keyring = Arc::new(Mutex::new(keyring));
+
+    let mut builder = ConnectionBuilder::session()?
+        .serve_at(
+            SERVICE_PATH,
+            Service {
+                keyring: Arc::clone(&keyring),
+            },
+        )?
+        .serve_at(
+            DEFAULT_ALIAS_PATH,
+            Collection {
+                category: default_category,
+                keyring: Arc::clone(&keyring),
+            },
+        )?;
+    for category in categories {
+        builder = builder.serve_at(
+            keyring::get_collection_path(&category),
+            Collection {
+                category,
+                keyring: Arc::clone(&keyring),
+            },
+        )?;
+    }
+    for (id, category) in items {
+        builder = builder.serve_at(
+            keyring::get_item_path(&category, id),
+            Item {
+                id,
+                keyring: Arc::clone(&keyring),
+            },
+        )?;
+    }
+
+    let _connection = builder
+        .name(SERVICE_NAME)?
+        .build()
+        .map_err(|error| match error {
+            zbus::Error::NameTaken => HimitsuError::SecretServiceError(format!(
+                "Another provider, such as gnome-keyring, already owns {SERVICE_NAME}!"
+            )),
+            error => HimitsuError::DBusError(error),
+        })?;
+
+    loop {
+        thread::park();
+    }
+}
@@ -0,0 +1,128 @@
+//! Contains functions for sealing data for a recipient's public key and opening data sealed for
+//! this vault's identity.
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{errors::HimitsuError, models::sharing::Envelope};
+
+/// The HKDF context used to derive envelope keys.
+const ENVELOPE_INFO: &[u8] = b"himitsu envelope v1";
+
+/// Derive the key used to encrypt an envelope from the X25519 shared secret. Both public keys are
+/// mixed into the derivation so the key is bound to this particular exchange.
+fn derive_envelope_key(
+    shared_secret: &[u8; 32],
+    ephemeral_public_key: &[u8; 32],
+    recipient: &[u8; 32],
+) -> Result<[u8; 32], HimitsuError> {
+    let mut salt = Vec::with_capacity(64);
+    salt.extend_from_slice(ephemeral_public_key);
+    salt.extend_from_slice(recipient);
+
+    let mut key = [0u8; 32];
+    Salt::new(HKDF_SHA256, &salt)
+        .extract(shared_secret)
+        .expand(&[ENVELOPE_INFO], HKDF_SHA256)
+        .and_then(|okm| okm.fill(&mut key))
+        .map_err(|_| HimitsuError::ShareError("Could not derive the envelope key!".to_string()))?;
+
+    Ok(key)
+}
+
+/// Seal data for a recipient's public key.
+pub fn seal(recipient: &PublicKey, plaintext: &[u8]) -> Result<Envelope, HimitsuError> {
+    let ephemeral_secret = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public_key = PublicKey::from(&ephemeral_secret);
+
+    let shared_secret = ephemeral_secret.diffie_hellman(recipient);
+    if !shared_secret.was_contributory() {
+        return Err(HimitsuError::ShareError(
+            "The recipient's public key is invalid!".to_string(),
+        ));
+    }
+
+    let key = derive_envelope_key(
+        shared_secret.as_bytes(),
+        ephemeral_public_key.as_bytes(),
+        recipient.as_bytes(),
+    )?;
+
+    let mut nonce = [0u8; 24];
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+
+    match cipher.encrypt(XNonce::from_slice(&nonce), plaintext) {
+        Ok(ciphertext) => Ok(Envelope {
+            ciphertext,
+            ephemeral_public_key: ephemeral_public_key.to_bytes(),
+            nonce,
+            recipient: recipient.to_bytes(),
+        }),
+        Err(error) => Err(HimitsuError::AEADEncryptionError(format!(
+            "Envelope encryption error: {error}"
+        ))),
+    }
+}
+
+/// Open an envelope sealed for an identity.
+pub fn open(identity: &StaticSecret, envelope: &Envelope) -> Result<Vec<u8>, HimitsuError> {
+    let public_key = PublicKey::from(identity);
+    if public_key.as_bytes() != &envelope.recipient {
+        return Err(HimitsuError::ShareError(
+            "This was not shared with your identity!".to_string(),
+        ));
+    }
+
+    let shared_secret = identity.diffie_hellman(&PublicKey::from(envelope.ephemeral_public_key));
+    let key = derive_envelope_key(
+        shared_secret.as_bytes(),
+        &envelope.ephemeral_public_key,
+        &envelope.recipient,
+    )?;
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+
+    cipher
+        .decrypt(
+            XNonce::from_slice(&envelope.nonce),
+            &envelope.ciphertext[..],
+        )
+        .map_err(|error| {
+            HimitsuError::AEADDencryptionError(format!("Envelope decryption error: {error}"))
+        })
+}
+
+#[cfg(test)]
+pub mod test_envelope {
+    use super::*;
+
+    /// Test whether an envelope is only opened by its recipient.
+    #[test]
+    fn test_seal_and_open() {
+        let recipient = StaticSecret::random_from_rng(OsRng);
+        let someone_else = StaticSecret::random_from_rng(OsRng);
+
+        let envelope = seal(&PublicKey::from(&recipient), b"hunter2").unwrap();
+
+        assert_eq!(open(&recipient, &envelope).unwrap(), b"hunter2");
+        assert!(open(&someone_else, &envelope).is_err());
+    }
+
+    /// Test whether a tampered envelope is rejected.
+    #[test]
+    fn test_open_tampered_envelope() {
+        let recipient = StaticSecret::random_from_rng(OsRng);
+
+        let mut envelope = seal(&PublicKey::from(&recipient), b"hunter2").unwrap();
+        envelope.ciphertext[0] ^= 1;
+
+        assert!(open(&recipient, &envelope).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Contains functions for managing this vault's identity key pair.
+//!
+//! The identity is an X25519 key pair stored in the `identity` directory inside the vault. The
+//! public key is stored in plaintext so others may seal data for this vault, and the private key
+//! is encrypted with the master password just like any other secret.
+
+use std::{fs, path::PathBuf};
+
+use bech32::{FromBase32, ToBase32, Variant};
+use data_encoding::{BASE64, HEXLOWER, HEXLOWER_PERMISSIVE};
+use rand::rngs::OsRng;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    utils::vault,
+};
+
+/// The human-readable part used when encoding public keys as age recipients.
+const RECIPIENT_HRP: &str = "age";
+
+/// Get the path to the identity directory.
+pub fn get_identity_dir() -> Result<PathBuf, HimitsuError> {
+    Ok(vault::get_vault_dir()?.join("identity"))
+}
+
+/// Get this vault's public key if an identity has been generated.
+pub fn get_public_key() -> Result<Option<PublicKey>, HimitsuError> {
+    let public_key_path = get_identity_dir()?.join("public");
+
+    if !public_key_path.exists() {
+        return Ok(None);
+    }
+
+    let raw_public_key: [u8; 32] = fs::read(public_key_path)?.try_into().map_err(|_| {
+        HimitsuError::ShareError("The stored identity public key is corrupted!".to_string())
+    })?;
+
+    Ok(Some(PublicKey::from(raw_public_key)))
+}
+
+/// Get this vault's identity, generating and storing a new identity if one does not exist yet.
+pub fn get_or_create_identity(password: &str) -> Result<StaticSecret, HimitsuError> {
+    let identity_dir = get_identity_dir()?;
+
+    if identity_dir.join("skeleton").exists() {
+        let raw_secret = HEXLOWER
+            .decode(decrypt::decrypt_secret_in(&identity_dir, password)?.as_bytes())
+            .map_err(|error| HimitsuError::ShareError(error.to_string()))?;
+        let raw_secret: [u8; 32] = raw_secret.try_into().map_err(|_| {
+            HimitsuError::ShareError("The stored identity is corrupted!".to_string())
+        })?;
+
+        Ok(StaticSecret::from(raw_secret))
+    } else {
+        let identity = StaticSecret::random_from_rng(OsRng);
+
+        encrypt::encrypt_secret_in(
+            &identity_dir,
+            password,
+            &HEXLOWER.encode(identity.as_bytes()),
+        )?;
+        fs::write(
+            identity_dir.join("public"),
+            PublicKey::from(&identity).as_bytes(),
+        )?;
+
+        Ok(identity)
+    }
+}
+
+/// Encode a public key the way age encodes its recipients (`age1...`). Only the encoding is
+/// compatible with age; secrets are sealed in `himitsu`'s own envelope format (see `envelope`).
+pub fn encode_recipient(public_key: &PublicKey) -> Result<String, HimitsuError> {
+    bech32::encode(
+        RECIPIENT_HRP,
+        public_key.as_bytes().to_base32(),
+        Variant::Bech32,
+    )
+    .map_err(|error| HimitsuError::ShareError(error.to_string()))
+}
+
+/// Parse a recipient's public key. Accepts age recipients (`age1...`) as well as hex or base64
+/// encoded X25519 public keys.
+pub fn parse_recipient(recipient: &str) -> Result<PublicKey, HimitsuError> {
+    let recipient = recipient.trim();
+
+    let raw_public_key = if recipient.to_lowercase().starts_with("age1") {
+        match bech32::decode(recipient) {
+            Ok((hrp, data, Variant::Bech32)) if hrp == RECIPIENT_HRP => {
+                Vec::<u8>::from_base32(&data).ok()
+            }
+            _ => None,
+        }
+    } else {
+        HEXLOWER_PERMISSIVE
+            .decode(recipient.as_bytes())
+            .or_else(|_| BASE64.decode(recipient.as_bytes()))
+            .ok()
+    };
+
+    match raw_public_key.and_then(|raw_public_key| <[u8; 32]>::try_from(raw_public_key).ok()) {
+        Some(raw_public_key) => Ok(PublicKey::from(raw_public_key)),
+        None => Err(HimitsuError::ShareError(format!(
+            "\"{recipient}\" is not a valid X25519 public key or age recipient!"
+        ))),
+    }
+}
+
+#[cfg(test)]
+pub mod test_identity {
+    use super::*;
+
+    /// Test whether a public key survives being encoded as an age recipient.
+    #[test]
+    fn test_recipient_round_trip() {
+        let public_key = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+        let recipient = encode_recipient(&public_key).unwrap();
+
+        assert!(recipient.starts_with("age1"));
+        assert_eq!(
+            parse_recipient(&recipient).unwrap().as_bytes(),
+            public_key.as_bytes()
+        );
+    }
+
+    /// Test whether hex and base64 encoded public keys are accepted and garbage is rejected.
+    #[test]
+    fn test_parse_recipient_encodings() {
+        let public_key = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+
+        let hex_recipient = HEXLOWER.encode(public_key.as_bytes());
+        let base64_recipient = BASE64.encode(public_key.as_bytes());
+
+        assert_eq!(
+            parse_recipient(&hex_recipient).unwrap().as_bytes(),
+            public_key.as_bytes()
+        );
+        assert_eq!(
+            parse_recipient(&base64_recipient).unwrap().as_bytes(),
+            public_key.as_bytes()
+        );
+        assert!(parse_recipient("age1notarecipient").is_err());
+        assert!(parse_recipient("deadbeef").is_err());
+    }
+}
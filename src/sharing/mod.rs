@@ -0,0 +1,6 @@
+//! Contains functions for sharing secrets with other `himitsu` users.
+
+pub mod envelope;
+pub mod identity;
+pub mod team;
+pub mod transfer;
@@ -0,0 +1,307 @@
+//! Contains functions for managing team vaults.
+//!
+//! A team vault is a vault directory shared between several people, such as a directory on a
+//! shared drive or in a git repository. Its secrets are encrypted with a random data key instead
+//! of a master password. Each member has their own copy of the data key in the `members`
+//! directory, sealed for their identity's public key, so no member ever needs another member's
+//! master password.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use data_encoding::HEXLOWER;
+use rand::{rngs::OsRng, RngCore};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{
+    errors::HimitsuError,
+    lookup::{init, rekey},
+    models::{metadata::LookupTable, team::Member},
+    utils::{git, vault},
+};
+
+use super::{envelope, identity};
+
+/// The directory containing each member's sealed copy of the data key.
+const MEMBERS_DIR: &str = "members";
+
+/// Generate a new data key. The key is hex encoded so it may be used in place of a master
+/// password.
+fn generate_data_key() -> String {
+    let mut data_key = [0u8; 32];
+    OsRng.fill_bytes(&mut data_key);
+
+    HEXLOWER.encode(&data_key)
+}
+
+/// Get the path to a member's file. Member names may only contain alphanumeric characters,
+/// dashes, underscores, and periods.
+fn get_member_path(team_dir: &Path, name: &str) -> Result<PathBuf, HimitsuError> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name.chars().all(|character| {
+            character.is_alphanumeric() || character == '-' || character == '_' || character == '.'
+        })
+    {
+        return Err(HimitsuError::TeamError(format!(
+            "\"{name}\" is not a valid member name! Use alphanumeric characters, dashes, underscores, and periods."
+        )));
+    }
+
+    Ok(team_dir.join(MEMBERS_DIR).join(format!("{name}.json")))
+}
+
+/// Seal the data key for a member and write the member's file.
+fn write_member(
+    team_dir: &Path,
+    data_key: &str,
+    name: &str,
+    public_key: &PublicKey,
+) -> Result<(), HimitsuError> {
+    let member_path = get_member_path(team_dir, name)?;
+
+    let member = Member {
+        name: name.to_string(),
+        public_key: public_key.to_bytes(),
+        wrapped_key: envelope::seal(public_key, data_key.as_bytes())?,
+    };
+
+    fs::create_dir_all(team_dir.join(MEMBERS_DIR))?;
+    fs::write(member_path, serde_json::to_string_pretty(&member)?)?;
+
+    Ok(())
+}
+
+/// Record a membership change in the team vault's history if the team vault is a git repository.
+fn record_membership_change(team_dir: &Path, message: &str) -> Result<(), HimitsuError> {
+    if git::is_repository(team_dir) {
+        git::commit_changes(team_dir, message)?;
+    }
+
+    Ok(())
+}
+
+/// Get every member of a team vault, sorted by name.
+pub fn get_members(team_dir: &Path) -> Result<Vec<Member>, HimitsuError> {
+    let members_dir = team_dir.join(MEMBERS_DIR);
+    if !members_dir.exists() {
+        return Err(HimitsuError::TeamError(format!(
+            "{} is not a team vault!",
+            team_dir.display()
+        )));
+    }
+
+    let mut members = Vec::new();
+    for entry in fs::read_dir(members_dir)? {
+        let path = entry?.path();
+
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            members.push(serde_json::from_str::<Member>(&fs::read_to_string(path)?)?);
+        }
+    }
+
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(members)
+}
+
+/// Create a new team vault with a single member.
+pub fn init_team_vault(
+    team_dir: &Path,
+    name: &str,
+    public_key: &PublicKey,
+) -> Result<(), HimitsuError> {
+    if team_dir.join("lookup").exists() || team_dir.join(MEMBERS_DIR).exists() {
+        return Err(HimitsuError::TeamError(format!(
+            "{} already contains a vault!",
+            team_dir.display()
+        )));
+    }
+
+    let data_key = generate_data_key();
+
+    init::create_lookup_in(&team_dir.join("lookup"), &data_key, &LookupTable::new())?;
+    fs::create_dir_all(team_dir.join("closet"))?;
+
+    write_member(team_dir, &data_key, name, public_key)?;
+
+    Ok(())
+}
+
+/// Unseal the team vault's data key with an identity. A membership change that was interrupted
+/// is finished or undone first, so the members' files match the data key the vault is encrypted
+/// with.
+pub fn unlock_team_vault(team_dir: &Path, identity: &StaticSecret) -> Result<String, HimitsuError> {
+    rekey::recover_rekey_in(team_dir)?;
+
+    let public_key = PublicKey::from(identity);
+
+    match get_members(team_dir)?
+        .into_iter()
+        .find(|member| &member.public_key == public_key.as_bytes())
+    {
+        Some(member) => Ok(String::from_utf8(envelope::open(
+            identity,
+            &member.wrapped_key,
+        )?)?),
+        None => Err(HimitsuError::TeamError(
+            "You are not a member of this team vault!".to_string(),
+        )),
+    }
+}
+
+/// Unlock a team vault with this vault's identity, then point every vault operation at the team
+/// vault. Returns the team vault's data key, which is used in place of the master password.
+pub fn open_team_vault(team_dir: &Path, password: &str) -> Result<String, HimitsuError> {
+    let identity = identity::get_or_create_identity(password)?;
+    let data_key = unlock_team_vault(team_dir, &identity)?;
+
+    vault::set_vault_dir(team_dir.to_path_buf());
+
+    Ok(data_key)
+}
+
+/// Add a member to a team vault.
+pub fn add_member(
+    team_dir: &Path,
+    identity: &StaticSecret,
+    name: &str,
+    public_key: &PublicKey,
+) -> Result<(), HimitsuError> {
+    let data_key = unlock_team_vault(team_dir, identity)?;
+
+    if get_members(team_dir)?
+        .iter()
+        .any(|member| member.name == name || &member.public_key == public_key.as_bytes())
+    {
+        return Err(HimitsuError::TeamError(format!(
+            "\"{name}\" or their public key is already a member of this team vault!"
+        )));
+    }
+
+    write_member(team_dir, &data_key, name, public_key)?;
+    record_membership_change(team_dir, &format!("add member {name}"))?;
+
+    Ok(())
+}
+
+/// Remove a member from a team vault. The whole team vault is re-encrypted with a new data key,
+/// which is sealed for each remaining member, so the removed member's copy of the old data key is
+/// useless.
+pub fn remove_member(
+    team_dir: &Path,
+    identity: &StaticSecret,
+    name: &str,
+) -> Result<(), HimitsuError> {
+    let old_data_key = unlock_team_vault(team_dir, identity)?;
+
+    let members = get_members(team_dir)?;
+    if !members.iter().any(|member| member.name == name) {
+        return Err(HimitsuError::TeamError(format!(
+            "\"{name}\" is not a member of this team vault!"
+        )));
+    }
+    if members.len() == 1 {
+        return Err(HimitsuError::TeamError(
+            "Cannot remove the last member of a team vault!".to_string(),
+        ));
+    }
+
+    // The new data key is sealed for the remaining members in the staging directory, so their
+    // files are swapped in along with the re-encrypted vault, and the removed member's file is
+    // only dropped once every remaining member can open the vault.
+    let new_data_key = generate_data_key();
+    rekey::rekey_vault_with(team_dir, &old_data_key, &new_data_key, |staging_dir| {
+        for member in members.iter().filter(|member| member.name != name) {
+            write_member(
+                staging_dir,
+                &new_data_key,
+                &member.name,
+                &PublicKey::from(member.public_key),
+            )?;
+        }
+
+        Ok(())
+    })?;
+
+    record_membership_change(team_dir, &format!("remove member {name}"))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_team {
+    use std::env;
+
+    use crate::{
+        encryption::{decrypt, encrypt},
+        lookup::secure,
+        models::metadata::Anatomy,
+    };
+
+    use super::*;
+
+    /// Test whether members are added, removed, and locked out once removed.
+    #[test]
+    fn test_team_membership() {
+        let team_dir = env::temp_dir().join(format!("himitsu-team-{}", OsRng.next_u64()));
+
+        let alice = StaticSecret::random_from_rng(OsRng);
+        let bob = StaticSecret::random_from_rng(OsRng);
+
+        init_team_vault(&team_dir, "alice", &PublicKey::from(&alice)).unwrap();
+        assert!(unlock_team_vault(&team_dir, &bob).is_err());
+
+        add_member(&team_dir, &alice, "bob", &PublicKey::from(&bob)).unwrap();
+        let old_data_key = unlock_team_vault(&team_dir, &bob).unwrap();
+        assert_eq!(unlock_team_vault(&team_dir, &alice).unwrap(), old_data_key);
+        assert!(add_member(&team_dir, &bob, "bob", &PublicKey::from(&alice)).is_err());
+
+        let mut lookup_table = LookupTable::new();
+        lookup_table.table.insert(
+            "0123abcd".to_string(),
+            Anatomy::create_from("unclassified".to_string(), "token".to_string(), vec![]),
+        );
+        init::create_lookup_in(&team_dir.join("lookup"), &old_data_key, &lookup_table).unwrap();
+        encrypt::encrypt_secret_in(
+            &team_dir.join("closet").join("0123abcd"),
+            &old_data_key,
+            "hunter2",
+        )
+        .unwrap();
+
+        remove_member(&team_dir, &alice, "bob").unwrap();
+        assert!(unlock_team_vault(&team_dir, &bob).is_err());
+
+        let new_data_key = unlock_team_vault(&team_dir, &alice).unwrap();
+        assert_ne!(new_data_key, old_data_key);
+        assert!(secure::decrypt_lookup_table_in(&team_dir.join("lookup"), &new_data_key).is_ok());
+        assert!(secure::decrypt_lookup_table_in(&team_dir.join("lookup"), &old_data_key).is_err());
+        assert_eq!(
+            decrypt::decrypt_secret_in(&team_dir.join("closet").join("0123abcd"), &new_data_key)
+                .unwrap(),
+            "hunter2"
+        );
+
+        assert!(remove_member(&team_dir, &alice, "alice").is_err());
+        assert_eq!(get_members(&team_dir).unwrap().len(), 1);
+
+        fs::remove_dir_all(team_dir).unwrap();
+    }
+
+    /// Test whether member names that could escape the `members` directory are rejected.
+    #[test]
+    fn test_get_member_path() {
+        let team_dir = Path::new("team");
+
+        assert!(get_member_path(team_dir, "alice.smith").is_ok());
+        assert!(get_member_path(team_dir, "../alice").is_err());
+        assert!(get_member_path(team_dir, ".hidden").is_err());
+        assert!(get_member_path(team_dir, "").is_err());
+    }
+}
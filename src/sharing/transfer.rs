@@ -0,0 +1,114 @@
+//! Contains functions for writing secrets to files shared with other users and storing secrets
+//! received from other users.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ansi_term::Color;
+use x25519_dalek::PublicKey;
+
+use crate::{
+    encryption::{
+        decrypt::{self, DecryptionMode},
+        encrypt,
+    },
+    errors::HimitsuError,
+    models::{
+        metadata::{Anatomy, LookupMatch},
+        sharing::{Envelope, SharedSecret},
+    },
+};
+
+use super::{envelope, identity};
+
+/// Encrypt a stored secret for a recipient and write it to a file. Secrets sealed with their own
+/// passphrase are opened with `passphrase` first, so the recipient gets the secret itself. Returns
+/// the path of the file.
+pub fn share_secret(
+    lookup_match: &LookupMatch,
+    output: &Option<PathBuf>,
+    password: &str,
+    passphrase: Option<&str>,
+    recipient: &PublicKey,
+) -> Result<PathBuf, HimitsuError> {
+    let mut secret =
+        decrypt::decrypt_secret(DecryptionMode::EditSecret, &lookup_match.hash, password)?.unwrap();
+    if let Some(passphrase) = passphrase {
+        secret = decrypt::open_with_passphrase(passphrase, &secret)?;
+    }
+
+    let shared_secret = SharedSecret {
+        category: lookup_match.anatomy.category.clone(),
+        label: lookup_match.anatomy.label.clone(),
+        secret,
+        secret_type: lookup_match.anatomy.secret_type.clone(),
+        tags: lookup_match.anatomy.tags.clone(),
+    };
+
+    let sealed = envelope::seal(recipient, serde_json::to_string(&shared_secret)?.as_bytes())?;
+
+    let output_path = output.clone().unwrap_or_else(|| {
+        PathBuf::from(format!("{}.himitsu", sanitize_label(&shared_secret.label)))
+    });
+
+    fs::write(&output_path, serde_json::to_string(&sealed)?)?;
+
+    println!(
+        "{}",
+        Color::Green.bold().paint(format!(
+            "\n📨 The secret was shared in {}. Only the recipient can open it.",
+            output_path.display()
+        ))
+    );
+
+    Ok(output_path)
+}
+
+/// Decrypt a secret shared with this vault's identity and store it as a new secret. Returns the
+/// new secret's hash ID.
+pub fn receive_secret(file: &Path, password: &str) -> Result<String, HimitsuError> {
+    let sealed: Envelope = serde_json::from_str(&fs::read_to_string(file)?).map_err(|_| {
+        HimitsuError::ShareError(format!("{} is not a shared secret!", file.display()))
+    })?;
+
+    let identity = identity::get_or_create_identity(password)?;
+    let shared_secret: SharedSecret = serde_json::from_slice(&envelope::open(&identity, &sealed)?)?;
+
+    let mut anatomy = Anatomy::create_from(
+        shared_secret.category,
+        shared_secret.label,
+        shared_secret.tags,
+    );
+    anatomy.secret_type = shared_secret.secret_type;
+
+    encrypt::encrypt_secret(&anatomy, password, shared_secret.secret)
+}
+
+/// Replace every character that may not be used in a file name with an underscore.
+fn sanitize_label(label: &str) -> String {
+    label
+        .chars()
+        .map(|character| {
+            if character.is_alphanumeric() || character == '-' || character == '_' {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod test_transfer {
+    use super::*;
+
+    /// Test whether labels are turned into safe file names.
+    #[test]
+    fn test_sanitize_label() {
+        assert_eq!(sanitize_label("github token"), "github_token");
+        assert_eq!(sanitize_label("../../etc/passwd"), "______etc_passwd");
+        assert_eq!(sanitize_label("秘密-key_1"), "秘密-key_1");
+    }
+}
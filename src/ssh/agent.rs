@@ -0,0 +1,339 @@
+//! Contains the ssh-agent protocol.
+//!
+//! Clients such as `ssh` and `ssh-add` connect to the socket named by `SSH_AUTH_SOCK` and send
+//! length-prefixed messages. Only listing identities and signing are supported; requests to add,
+//! remove, or lock keys are refused, since keys are managed in the vault.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    errors::HimitsuError,
+    lookup::{expiry, secure},
+    models::metadata::{Anatomy, SecretType},
+    prompts::protect,
+    ssh::{
+        key::SshKey,
+        wire::{self, Reader},
+    },
+    utils::{dates, runtime},
+};
+
+/// The category SSH keys are loaded from if no category or tags are given.
+pub const SSH_CATEGORY: &str = "ssh";
+
+/// The agent could not fulfill a request.
+const SSH_AGENT_FAILURE: u8 = 5;
+/// Asks the agent to list its keys.
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+/// Lists the agent's keys.
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+/// Asks the agent to sign data.
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+/// Contains a signature.
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+/// The largest message accepted, as in OpenSSH's agent.
+const MAX_MESSAGE_LENGTH: usize = 256 * 1024;
+
+/// Contains a key served by the agent.
+pub struct LoadedKey {
+    /// The secret's hash ID.
+    pub hash_id: String,
+    /// The key.
+    pub key: SshKey,
+    /// When the key stops being served, if it was stored as a temporary secret.
+    pub lapses_at: Option<NaiveDateTime>,
+    /// The secret's `category/label`.
+    pub secret: String,
+}
+
+impl LoadedKey {
+    /// Check whether the key may still be served, since temporary secrets are no longer served
+    /// once they expire.
+    pub fn is_served(&self, now: NaiveDateTime) -> bool {
+        self.lapses_at.is_none_or(|lapses_at| now < lapses_at)
+    }
+}
+
+/// Get the path to the agent's socket. Defaults to `ssh-agent.sock` in the runtime directory.
+pub fn get_socket_path(socket: &Option<PathBuf>) -> Result<PathBuf, HimitsuError> {
+    match socket {
+        Some(socket_path) => Ok(socket_path.clone()),
+        None => Ok(runtime::get_runtime_dir()?.join("ssh-agent.sock")),
+    }
+}
+
+/// Check whether a secret is selected by the category and tags passed to `hmu ssh-agent`.
+pub fn is_selected(anatomy: &Anatomy, category: &Option<String>, tags: &[String]) -> bool {
+    if anatomy.secret_type != SecretType::Password {
+        return false;
+    }
+
+    match category {
+        Some(category) => {
+            anatomy.category.eq_ignore_ascii_case(category)
+                || anatomy.tags.iter().any(|tag| tags.contains(tag))
+        }
+        None if !tags.is_empty() => anatomy.tags.iter().any(|tag| tags.contains(tag)),
+        None => anatomy.category == SSH_CATEGORY,
+    }
+}
+
+/// Decrypt and parse every selected SSH key. Protected secrets ask for the master password or
+/// their passphrase once, when they are loaded.
+pub fn load_keys(
+    category: &Option<String>,
+    tags: &[String],
+    password: &str,
+) -> Result<Vec<LoadedKey>, HimitsuError> {
+    let lookup_table = secure::decrypt_lookup_table(password)?;
+    let now = dates::get_now();
+
+    let mut selected = lookup_table
+        .table
+        .iter()
+        .filter(|(_, anatomy)| {
+            is_selected(anatomy, category, tags) && !expiry::has_lapsed(anatomy, now)
+        })
+        .collect::<Vec<(&String, &Anatomy)>>();
+    selected.sort_by(|(_, first), (_, second)| first.label.cmp(&second.label));
+
+    let mut loaded_keys = Vec::new();
+    for (hash_id, anatomy) in selected {
+        let secret = format!("{}/{}", anatomy.category, anatomy.label);
+        let private_key = protect::run_decrypt_protected_secret(hash_id, anatomy, password)?;
+
+        let key = SshKey::parse(&private_key).and_then(|key| {
+            key.verify_public_key()?;
+            Ok(key)
+        });
+        match key {
+            Ok(key) => loaded_keys.push(LoadedKey {
+                hash_id: hash_id.clone(),
+                key,
+                lapses_at: anatomy
+                    .temporary
+                    .then(|| {
+                        anatomy
+                            .expires_at
+                            .as_deref()
+                            .and_then(dates::parse_timestamp)
+                    })
+                    .flatten(),
+                secret,
+            }),
+            Err(error) => println!("Skipped {secret}: {error}"),
+        }
+    }
+
+    Ok(loaded_keys)
+}
+
+/// Answer a single request at `now`. Keys that are no longer served are left out, and `approve`
+/// is asked before each signature.
+pub fn handle_request(
+    keys: &[LoadedKey],
+    request: &[u8],
+    now: NaiveDateTime,
+    approve: &mut dyn FnMut(&LoadedKey) -> Result<bool, HimitsuError>,
+) -> Vec<u8> {
+    let keys = keys
+        .iter()
+        .filter(|loaded_key| loaded_key.is_served(now))
+        .collect::<Vec<&LoadedKey>>();
+    let mut reader = Reader::new(request);
+
+    let response = match reader.read_u8() {
+        Ok(SSH_AGENTC_REQUEST_IDENTITIES) => {
+            let mut response = vec![SSH_AGENT_IDENTITIES_ANSWER];
+            wire::write_u32(&mut response, keys.len() as u32);
+
+            for loaded_key in &keys {
+                wire::write_string(&mut response, &loaded_key.key.public_key_blob);
+                wire::write_string(&mut response, loaded_key.secret.as_bytes());
+            }
+
+            Some(response)
+        }
+        Ok(SSH_AGENTC_SIGN_REQUEST) => sign(&keys, &mut reader, approve).ok().flatten(),
+        _ => None,
+    };
+
+    response.unwrap_or_else(|| vec![SSH_AGENT_FAILURE])
+}
+
+/// Answer a sign request. Returns `None` if the key is unknown or the signature was refused.
+fn sign(
+    keys: &[&LoadedKey],
+    reader: &mut Reader,
+    approve: &mut dyn FnMut(&LoadedKey) -> Result<bool, HimitsuError>,
+) -> Result<Option<Vec<u8>>, HimitsuError> {
+    let public_key_blob = reader.read_string()?;
+    let data = reader.read_string()?;
+    let flags = reader.read_u32()?;
+
+    match keys
+        .iter()
+        .find(|loaded_key| loaded_key.key.public_key_blob == public_key_blob)
+    {
+        Some(loaded_key) if approve(loaded_key)? => {
+            let mut response = vec![SSH_AGENT_SIGN_RESPONSE];
+            wire::write_string(&mut response, &loaded_key.key.sign(data, flags)?);
+
+            Ok(Some(response))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Answer every request sent over a connection until the client disconnects.
+#[cfg(unix)]
+fn handle_connection<F>(mut stream: UnixStream, keys: &[LoadedKey], approve: &Mutex<F>)
+where
+    F: FnMut(&LoadedKey) -> Result<bool, HimitsuError>,
+{
+    loop {
+        let mut length = [0; 4];
+        if stream.read_exact(&mut length).is_err() {
+            return;
+        }
+
+        let length = u32::from_be_bytes(length) as usize;
+        if length == 0 || length > MAX_MESSAGE_LENGTH {
+            return;
+        }
+
+        let mut request = vec![0; length];
+        if stream.read_exact(&mut request).is_err() {
+            return;
+        }
+
+        let response = match approve.lock() {
+            Ok(mut approve) => handle_request(keys, &request, dates::get_now(), &mut *approve),
+            Err(_) => vec![SSH_AGENT_FAILURE],
+        };
+
+        let mut message = Vec::new();
+        wire::write_string(&mut message, &response);
+        if stream.write_all(&message).is_err() {
+            return;
+        }
+    }
+}
+
+/// Serve keys over the ssh-agent protocol until the process is stopped. Every client is served on
+/// its own thread, while `approve` is only ever asked one signature at a time.
+#[cfg(unix)]
+pub fn serve<F>(socket_path: &Path, keys: Vec<LoadedKey>, approve: F) -> Result<(), HimitsuError>
+where
+    F: FnMut(&LoadedKey) -> Result<bool, HimitsuError> + Send + 'static,
+{
+    let listener = runtime::bind_socket(socket_path)?;
+
+    let keys = Arc::new(keys);
+    let approve = Arc::new(Mutex::new(approve));
+
+    for stream in listener.incoming().flatten() {
+        let keys = Arc::clone(&keys);
+        let approve = Arc::clone(&approve);
+
+        thread::spawn(move || handle_connection(stream, &keys, &approve));
+    }
+
+    Ok(())
+}
+
+/// The agent is served over a Unix socket, which is not available on this platform.
+#[cfg(not(unix))]
+pub fn serve<F>(_socket_path: &Path, _keys: Vec<LoadedKey>, _approve: F) -> Result<(), HimitsuError>
+where
+    F: FnMut(&LoadedKey) -> Result<bool, HimitsuError> + Send + 'static,
+{
+    Err(HimitsuError::SshError(
+        "The SSH agent is only available on Unix-like systems!".to_string(),
+    ))
+}
+
+#[cfg(test)]
+pub mod test_agent {
+    use super::*;
+
+    use crate::ssh::key::test_key::ED25519_KEY;
+
+    /// Test whether identities are listed and sign requests are only answered once approved.
+    #[test]
+    fn test_handle_request() {
+        let keys = vec![LoadedKey {
+            hash_id: "hash".to_string(),
+            key: SshKey::parse(ED25519_KEY).unwrap(),
+            lapses_at: None,
+            secret: "ssh/deploy".to_string(),
+        }];
+        let now = dates::get_now();
+
+        let response = handle_request(&keys, &[SSH_AGENTC_REQUEST_IDENTITIES], now, &mut |_| {
+            Ok(true)
+        });
+        let mut reader = Reader::new(&response);
+        assert_eq!(reader.read_u8().unwrap(), SSH_AGENT_IDENTITIES_ANSWER);
+        assert_eq!(reader.read_u32().unwrap(), 1);
+        assert_eq!(reader.read_string().unwrap(), keys[0].key.public_key_blob);
+        assert_eq!(reader.read_string().unwrap(), b"ssh/deploy");
+
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+        wire::write_string(&mut request, &keys[0].key.public_key_blob);
+        wire::write_string(&mut request, b"session");
+        wire::write_u32(&mut request, 0);
+
+        assert_eq!(
+            handle_request(&keys, &request, now, &mut |_| Ok(true))[0],
+            SSH_AGENT_SIGN_RESPONSE
+        );
+        assert_eq!(
+            handle_request(&keys, &request, now, &mut |_| Ok(false)),
+            vec![SSH_AGENT_FAILURE]
+        );
+        assert_eq!(
+            handle_request(&keys, &[17], now, &mut |_| Ok(true)),
+            vec![SSH_AGENT_FAILURE]
+        );
+    }
+
+    /// Test whether a temporary key is no longer listed or used once it expires.
+    #[test]
+    fn test_handle_request_after_expiry() {
+        let now = dates::get_now();
+        let keys = vec![LoadedKey {
+            hash_id: "hash".to_string(),
+            key: SshKey::parse(ED25519_KEY).unwrap(),
+            lapses_at: Some(now),
+            secret: "ssh/deploy".to_string(),
+        }];
+
+        let response = handle_request(&keys, &[SSH_AGENTC_REQUEST_IDENTITIES], now, &mut |_| {
+            Ok(true)
+        });
+        let mut reader = Reader::new(&response);
+        assert_eq!(reader.read_u8().unwrap(), SSH_AGENT_IDENTITIES_ANSWER);
+        assert_eq!(reader.read_u32().unwrap(), 0);
+
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+        wire::write_string(&mut request, &keys[0].key.public_key_blob);
+        wire::write_string(&mut request, b"session");
+        wire::write_u32(&mut request, 0);
+
+        assert_eq!(
+            handle_request(&keys, &request, now, &mut |_| Ok(true)),
+            vec![SSH_AGENT_FAILURE]
+        );
+    }
+}
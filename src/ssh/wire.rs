@@ -0,0 +1,110 @@
+//! Contains the SSH wire encoding shared by OpenSSH's private key format and the ssh-agent
+//! protocol.
+
+use crate::errors::HimitsuError;
+
+/// Reads SSH-encoded values from a buffer.
+pub struct Reader<'a> {
+    /// The bytes that were not read yet.
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Create a reader for a buffer.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Read a number of raw bytes.
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], HimitsuError> {
+        if self.data.len() < length {
+            return Err(HimitsuError::SshError("The data is truncated!".to_string()));
+        }
+
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+
+        Ok(bytes)
+    }
+
+    /// Read a single byte.
+    pub fn read_u8(&mut self) -> Result<u8, HimitsuError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// Read a big-endian `uint32`.
+    pub fn read_u32(&mut self) -> Result<u32, HimitsuError> {
+        let bytes = self.read_bytes(4)?;
+
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read a `string`: a `uint32` length followed by that many bytes.
+    pub fn read_string(&mut self) -> Result<&'a [u8], HimitsuError> {
+        let length = self.read_u32()? as usize;
+
+        self.read_bytes(length)
+    }
+
+    /// Read an `mpint` without its sign padding.
+    pub fn read_mpint(&mut self) -> Result<&'a [u8], HimitsuError> {
+        Ok(strip_leading_zeros(self.read_string()?))
+    }
+}
+
+/// Strip the leading zero bytes of a big-endian unsigned integer.
+pub fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+
+    &bytes[start..]
+}
+
+/// Write a big-endian `uint32`.
+pub fn write_u32(output: &mut Vec<u8>, value: u32) {
+    output.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Write a `string`.
+pub fn write_string(output: &mut Vec<u8>, value: &[u8]) {
+    write_u32(output, value.len() as u32);
+    output.extend_from_slice(value);
+}
+
+/// Write a big-endian unsigned integer as an `mpint`, which is padded with a zero byte if its
+/// highest bit is set so it is not read as a negative number.
+pub fn write_mpint(output: &mut Vec<u8>, value: &[u8]) {
+    let value = strip_leading_zeros(value);
+
+    if value.first().is_some_and(|byte| byte & 0x80 != 0) {
+        write_u32(output, value.len() as u32 + 1);
+        output.push(0);
+        output.extend_from_slice(value);
+    } else {
+        write_string(output, value);
+    }
+}
+
+#[cfg(test)]
+pub mod test_wire {
+    use super::*;
+
+    /// Test whether written values are read back, and whether `mpint`s are padded correctly.
+    #[test]
+    fn test_round_trip() {
+        let mut output = Vec::new();
+        write_string(&mut output, b"ssh-ed25519");
+        write_mpint(&mut output, &[0x00, 0x80, 0x01]);
+        write_mpint(&mut output, &[0x7f]);
+
+        assert_eq!(&output[15..23], &[0, 0, 0, 3, 0x00, 0x80, 0x01, 0]);
+
+        let mut reader = Reader::new(&output);
+        assert_eq!(reader.read_string().unwrap(), b"ssh-ed25519");
+        assert_eq!(reader.read_mpint().unwrap(), &[0x80, 0x01]);
+        assert_eq!(reader.read_mpint().unwrap(), &[0x7f]);
+        assert!(reader.read_u32().is_err());
+    }
+}
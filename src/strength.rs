@@ -0,0 +1,675 @@
+//! Contains a password strength estimator in the style of zxcvbn.
+//!
+//! The estimator looks for patterns an attacker would try before brute force (common passwords
+//! and words, including reversed and l33t spellings, keyboard walks, repeats, sequences, and
+//! dates), estimates how many guesses each pattern takes, and finds the cheapest way to cover the
+//! whole password with patterns and brute-forced characters. The score is derived from the
+//! number of guesses that cheapest cover takes.
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, Local};
+use lazy_static::lazy_static;
+
+/// Common passwords and words, most common first. A word's rank is the number of guesses it
+/// takes to find it.
+#[rustfmt::skip]
+const COMMON_WORDS: &[&str] = &[
+    "password", "123456", "12345678", "qwerty", "123456789", "12345", "1234", "111111",
+    "1234567", "dragon", "123123", "baseball", "abc123", "football", "monkey", "letmein",
+    "696969", "shadow", "master", "666666", "qwertyuiop", "123321", "mustang", "1234567890",
+    "michael", "654321", "superman", "1qaz2wsx", "7777777", "121212", "000000", "qazwsx",
+    "123qwe", "killer", "trustno1", "jordan", "jennifer", "zxcvbnm", "asdfgh", "hunter",
+    "buster", "soccer", "harley", "batman", "andrew", "tigger", "sunshine", "iloveyou", "2000",
+    "charlie", "robert", "thomas", "hockey", "ranger", "daniel", "starwars", "klaster",
+    "112233", "george", "computer", "michelle", "jessica", "pepper", "1111", "zxcvbn",
+    "555555", "11111111", "131313", "freedom", "777777", "pass", "maggie", "159753", "aaaaaa",
+    "ginger", "princess", "joshua", "cheese", "amanda", "summer", "love", "ashley", "nicole",
+    "chelsea", "biteme", "matthew", "access", "yankees", "987654321", "dallas", "austin",
+    "thunder", "taylor", "matrix", "admin", "welcome", "login", "passw0rd", "hello", "secret",
+    "monday", "flower", "whatever", "qwerty123", "changeme", "default", "letmein1", "welcome1",
+    "password1", "google", "internet", "samsung", "apple", "orange", "banana", "winter",
+    "spring", "autumn", "dog", "cat", "horse", "house", "money", "music", "family", "friend",
+    "friends", "happy", "angel", "baby", "lucky", "pokemon", "naruto", "minecraft", "ninja",
+    "pirate", "dragon1", "purple", "yellow", "green", "black", "white", "silver", "golden",
+    "diamond", "forever", "heaven", "secure", "security", "private", "himitsu", "vault",
+    "master1", "root", "toor", "user", "guest", "test", "testing", "server", "database",
+    "system", "office", "company", "january", "february", "march", "april", "june", "july",
+    "august", "september", "october", "november", "december", "sunday", "tuesday", "wednesday",
+    "thursday", "friday", "saturday", "correct", "battery", "staple", "the", "and", "you",
+    "that", "this", "with", "have", "from", "they", "will", "would", "there", "their", "what",
+    "about", "which", "when", "make", "like", "time", "just", "know", "take", "people", "into",
+    "year", "good", "some", "could", "them", "see", "other", "than", "then", "now", "look",
+    "only", "come", "over", "think", "also", "back", "after", "use", "two", "how", "our",
+    "work", "first", "well", "way", "even", "new", "want", "because", "any", "these", "give",
+    "day", "most", "red", "blue", "water", "fire", "earth", "star", "moon", "sun", "king",
+    "queen", "prince", "knight", "magic", "power",
+];
+
+/// The rows of a QWERTY keyboard, used to find keyboard walks.
+const KEYBOARD_ROWS: [&str; 4] = [
+    "1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+];
+
+/// Common l33t substitutions and the letters they stand for.
+const L33T_SUBSTITUTIONS: [(char, char); 13] = [
+    ('4', 'a'),
+    ('@', 'a'),
+    ('8', 'b'),
+    ('(', 'c'),
+    ('3', 'e'),
+    ('6', 'g'),
+    ('1', 'i'),
+    ('!', 'i'),
+    ('0', 'o'),
+    ('$', 's'),
+    ('5', 's'),
+    ('7', 't'),
+    ('+', 't'),
+];
+
+/// The minimum length of a pattern.
+const MIN_PATTERN_LENGTH: usize = 3;
+/// Only the first characters of very long inputs are searched for patterns.
+const MAX_ANALYZED_LENGTH: usize = 100;
+
+lazy_static! {
+    /// Maps each common word to its rank.
+    static ref WORD_RANKS: HashMap<&'static str, usize> = COMMON_WORDS
+        .iter()
+        .enumerate()
+        .map(|(index, word)| (*word, index + 1))
+        .collect();
+
+    /// Maps each key to its row and column on a QWERTY keyboard.
+    static ref KEY_POSITIONS: HashMap<char, (i32, i32)> = KEYBOARD_ROWS
+        .iter()
+        .enumerate()
+        .flat_map(|(row, keys)| {
+            keys.chars()
+                .enumerate()
+                .map(move |(column, key)| (key, (row as i32, column as i32)))
+        })
+        .collect();
+}
+
+/// Contains variants for each kind of pattern the estimator looks for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Pattern {
+    /// A date, such as `1990` or `01-15-1990`.
+    Date,
+    /// A common password or word.
+    Dictionary {
+        /// Whether the word contains l33t substitutions.
+        l33t: bool,
+        /// The word's rank in the list of common words.
+        rank: usize,
+        /// Whether the word is spelled backwards.
+        reversed: bool,
+    },
+    /// Keys next to each other on the keyboard, such as `qwerty` or `zaq1`.
+    Keyboard,
+    /// A repeated character or substring, such as `aaa` or `abcabc`.
+    Repeat,
+    /// A run of consecutive characters, such as `abc` or `9876`.
+    Sequence,
+}
+
+/// Contains a pattern found in a password.
+#[derive(Clone, Debug)]
+struct PatternMatch {
+    /// The index of the pattern's first character.
+    start: usize,
+    /// The index after the pattern's last character.
+    end: usize,
+    /// The base-10 logarithm of the number of guesses it takes to find the pattern.
+    guesses_log10: f64,
+    /// The kind of pattern.
+    pattern: Pattern,
+}
+
+/// Contains the result of estimating a password's strength.
+#[derive(Debug)]
+pub struct StrengthEstimate {
+    /// A score from 0 (too guessable) to 4 (very unguessable).
+    pub score: u8,
+    /// Explanations of what makes the password weak.
+    pub warnings: Vec<String>,
+}
+
+impl StrengthEstimate {
+    /// Get a description of the score.
+    pub fn get_description(&self) -> &'static str {
+        match self.score {
+            0 => "very weak",
+            1 => "weak",
+            2 => "fair",
+            3 => "strong",
+            _ => "very strong",
+        }
+    }
+}
+
+/// Estimate a password's strength.
+pub fn estimate_strength(password: &str) -> StrengthEstimate {
+    let characters = password
+        .chars()
+        .take(MAX_ANALYZED_LENGTH)
+        .collect::<Vec<char>>();
+    let (guesses_log10, matches) = find_cheapest_cover(&characters);
+
+    let score = match guesses_log10 {
+        guesses if guesses < 3.0 => 0,
+        guesses if guesses < 6.0 => 1,
+        guesses if guesses < 8.0 => 2,
+        guesses if guesses < 10.0 => 3,
+        _ => 4,
+    };
+
+    let mut warnings = Vec::new();
+    for pattern_match in matches.iter() {
+        let warning = match pattern_match.pattern {
+            Pattern::Date => "Dates are often easy to guess.",
+            Pattern::Dictionary { rank, .. } if rank <= 100 => {
+                "This is one of the most common passwords."
+            }
+            Pattern::Dictionary { l33t: true, .. } => {
+                "Predictable substitutions like \"@\" instead of \"a\" do not help much."
+            }
+            Pattern::Dictionary { reversed: true, .. } => {
+                "Words spelled backwards are easy to guess."
+            }
+            Pattern::Dictionary { .. } => "Common words are easy to guess.",
+            Pattern::Keyboard => "Rows or patterns of keys on the keyboard are easy to guess.",
+            Pattern::Repeat => "Repeated characters like \"aaa\" or \"abcabc\" are easy to guess.",
+            Pattern::Sequence => "Sequences like \"abc\" or \"6543\" are easy to guess.",
+        };
+
+        if !warnings.iter().any(|existing| existing == warning) {
+            warnings.push(warning.to_string());
+        }
+    }
+    if score < 3 && characters.len() < 12 {
+        warnings.push("Longer passwords are harder to guess.".to_string());
+    }
+
+    StrengthEstimate { score, warnings }
+}
+
+/// Find the cheapest way to cover a password with patterns and brute-forced characters. Returns
+/// the base-10 logarithm of the number of guesses and the patterns used.
+fn find_cheapest_cover(characters: &[char]) -> (f64, Vec<PatternMatch>) {
+    let brute_force_log10 = get_cardinality(characters).log10();
+
+    let mut matches_by_end: HashMap<usize, Vec<PatternMatch>> = HashMap::new();
+    for pattern_match in find_patterns(characters) {
+        matches_by_end
+            .entry(pattern_match.end)
+            .or_default()
+            .push(pattern_match);
+    }
+
+    let mut cheapest: Vec<(f64, Option<PatternMatch>)> = vec![(0.0, None)];
+    for end in 1..=characters.len() {
+        let mut best = (cheapest[end - 1].0 + brute_force_log10, None);
+
+        for pattern_match in matches_by_end.get(&end).into_iter().flatten() {
+            let guesses_log10 = cheapest[pattern_match.start].0 + pattern_match.guesses_log10;
+
+            if guesses_log10 < best.0 {
+                best = (guesses_log10, Some(pattern_match.clone()));
+            }
+        }
+
+        cheapest.push(best);
+    }
+
+    let mut matches = Vec::new();
+    let mut end = characters.len();
+    while end > 0 {
+        match &cheapest[end].1 {
+            Some(pattern_match) => {
+                end = pattern_match.start;
+                matches.push(pattern_match.clone());
+            }
+            None => end -= 1,
+        }
+    }
+    matches.reverse();
+
+    (cheapest[characters.len()].0, matches)
+}
+
+/// Get the number of characters an attacker has to try for each brute-forced character, based on
+/// the kinds of characters in the password.
+fn get_cardinality(characters: &[char]) -> f64 {
+    let mut cardinality = 0.0;
+
+    if characters
+        .iter()
+        .any(|character| character.is_ascii_lowercase())
+    {
+        cardinality += 26.0;
+    }
+    if characters
+        .iter()
+        .any(|character| character.is_ascii_uppercase())
+    {
+        cardinality += 26.0;
+    }
+    if characters
+        .iter()
+        .any(|character| character.is_ascii_digit())
+    {
+        cardinality += 10.0;
+    }
+    if characters
+        .iter()
+        .any(|character| character.is_ascii() && !character.is_ascii_alphanumeric())
+    {
+        cardinality += 33.0;
+    }
+    if characters.iter().any(|character| !character.is_ascii()) {
+        cardinality += 100.0;
+    }
+
+    f64::max(cardinality, 10.0)
+}
+
+/// Find every pattern in a password.
+fn find_patterns(characters: &[char]) -> Vec<PatternMatch> {
+    let mut matches = Vec::new();
+
+    matches.extend(find_dictionary_words(characters));
+    matches.extend(find_keyboard_walks(characters));
+    matches.extend(find_repeats(characters));
+    matches.extend(find_sequences(characters));
+    matches.extend(find_dates(characters));
+
+    matches
+}
+
+/// Get the base-10 logarithm of the number of ways a word's letters may be capitalized the way
+/// they are.
+fn get_uppercase_variations_log10(word: &[char]) -> f64 {
+    let uppercase_count = word
+        .iter()
+        .filter(|character| character.is_uppercase())
+        .count();
+
+    if uppercase_count == 0 {
+        0.0
+    } else if uppercase_count == word.len() || (uppercase_count == 1 && word[0].is_uppercase()) {
+        2_f64.log10()
+    } else {
+        uppercase_count as f64 * 2_f64.log10()
+    }
+}
+
+/// Find common passwords and words, including reversed and l33t spellings.
+fn find_dictionary_words(characters: &[char]) -> Vec<PatternMatch> {
+    let lowercase = characters
+        .iter()
+        .map(|character| character.to_lowercase().next().unwrap_or(*character))
+        .collect::<Vec<char>>();
+
+    let mut matches = Vec::new();
+    for start in 0..lowercase.len() {
+        for end in start + MIN_PATTERN_LENGTH..=lowercase.len() {
+            let token = &lowercase[start..end];
+            let uppercase_log10 = get_uppercase_variations_log10(&characters[start..end]);
+
+            let substitutions = token
+                .iter()
+                .filter(|character| {
+                    L33T_SUBSTITUTIONS
+                        .iter()
+                        .any(|(substitute, _)| substitute == *character)
+                })
+                .count();
+
+            let candidates = [
+                (token.iter().collect::<String>(), false, false),
+                (token.iter().rev().collect::<String>(), false, true),
+                (unl33t(token, 'i'), true, false),
+                (unl33t(token, 'l'), true, false),
+            ];
+
+            for (candidate, l33t, reversed) in candidates {
+                if l33t && substitutions == 0 {
+                    continue;
+                }
+
+                if let Some(rank) = WORD_RANKS.get(candidate.as_str()) {
+                    let mut guesses_log10 = (*rank as f64).log10() + uppercase_log10;
+                    if l33t {
+                        guesses_log10 += substitutions as f64 * 2_f64.log10();
+                    }
+                    if reversed {
+                        guesses_log10 += 2_f64.log10();
+                    }
+
+                    matches.push(PatternMatch {
+                        start,
+                        end,
+                        guesses_log10,
+                        pattern: Pattern::Dictionary {
+                            l33t,
+                            rank: *rank,
+                            reversed,
+                        },
+                    });
+
+                    break;
+                }
+            }
+        }
+    }
+
+    matches
+}
+
+/// Undo l33t substitutions in a lowercase token. `one_as` is the letter `1` stands for, since it
+/// may stand for either `i` or `l`.
+fn unl33t(token: &[char], one_as: char) -> String {
+    token
+        .iter()
+        .map(|character| match character {
+            '1' => one_as,
+            _ => L33T_SUBSTITUTIONS
+                .iter()
+                .find(|(substitute, _)| substitute == character)
+                .map_or(*character, |(_, letter)| *letter),
+        })
+        .collect()
+}
+
+/// Find runs of keys that are next to each other on a QWERTY keyboard.
+fn find_keyboard_walks(characters: &[char]) -> Vec<PatternMatch> {
+    let positions = characters
+        .iter()
+        .map(|character| KEY_POSITIONS.get(&character.to_ascii_lowercase()).copied())
+        .collect::<Vec<Option<(i32, i32)>>>();
+
+    let is_adjacent = |(row_a, column_a): (i32, i32), (row_b, column_b): (i32, i32)| {
+        matches!(
+            (row_b - row_a, column_b - column_a),
+            (0, -1) | (0, 1) | (-1, 0) | (-1, 1) | (1, -1) | (1, 0)
+        )
+    };
+
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start < characters.len() {
+        let mut end = start + 1;
+        let mut turns = 0;
+        let mut direction = None;
+
+        while end < characters.len() {
+            match (positions[end - 1], positions[end]) {
+                (Some(previous), Some(current)) if is_adjacent(previous, current) => {
+                    let step = (current.0 - previous.0, current.1 - previous.1);
+                    if direction.is_some_and(|direction| direction != step) {
+                        turns += 1;
+                    }
+
+                    direction = Some(step);
+                    end += 1;
+                }
+                _ => break,
+            }
+        }
+
+        if end - start > MIN_PATTERN_LENGTH {
+            let length = (end - start) as f64;
+
+            matches.push(PatternMatch {
+                start,
+                end,
+                guesses_log10: (KEY_POSITIONS.len() as f64).log10()
+                    + length.log10()
+                    + turns as f64 * 6_f64.log10(),
+                pattern: Pattern::Keyboard,
+            });
+        }
+
+        start = end;
+    }
+
+    matches
+}
+
+/// Find repeated characters and repeated substrings.
+fn find_repeats(characters: &[char]) -> Vec<PatternMatch> {
+    let mut matches = Vec::new();
+
+    for start in 0..characters.len() {
+        for base_length in 1..=(characters.len() - start) / 2 {
+            let base = &characters[start..start + base_length];
+
+            let mut repeat_count = 1;
+            while characters[start..]
+                .chunks(base_length)
+                .nth(repeat_count)
+                .is_some_and(|chunk| chunk == base)
+            {
+                repeat_count += 1;
+            }
+
+            let end = start + base_length * repeat_count;
+            if repeat_count < 2 || end - start < MIN_PATTERN_LENGTH {
+                continue;
+            }
+
+            let base_guesses_log10 = if base_length == 1 {
+                get_cardinality(base).log10()
+            } else {
+                find_cheapest_cover(base).0
+            };
+
+            matches.push(PatternMatch {
+                start,
+                end,
+                guesses_log10: base_guesses_log10 + (repeat_count as f64).log10(),
+                pattern: Pattern::Repeat,
+            });
+        }
+    }
+
+    matches
+}
+
+/// Find runs of consecutive characters, such as `abc`, `9876`, or `xyz`.
+fn find_sequences(characters: &[char]) -> Vec<PatternMatch> {
+    let mut matches = Vec::new();
+
+    let mut start = 0;
+    while start + 1 < characters.len() {
+        let delta = characters[start + 1] as i64 - characters[start] as i64;
+        if delta.abs() != 1 {
+            start += 1;
+            continue;
+        }
+
+        let mut end = start + 2;
+        while end < characters.len() && characters[end] as i64 - characters[end - 1] as i64 == delta
+        {
+            end += 1;
+        }
+
+        if end - start >= MIN_PATTERN_LENGTH {
+            let first = characters[start];
+            let start_guesses: f64 = if matches!(first, 'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9') {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction_guesses: f64 = if delta < 0 { 2.0 } else { 1.0 };
+
+            matches.push(PatternMatch {
+                start,
+                end,
+                guesses_log10: (start_guesses * direction_guesses * (end - start) as f64).log10(),
+                pattern: Pattern::Sequence,
+            });
+        }
+
+        start = end - 1;
+    }
+
+    matches
+}
+
+/// Parse a year, accepting both 2 and 4 digit years. Returns `None` for 4 digit years outside of
+/// 1900 to 2099.
+fn parse_year(digits: &str) -> Option<i32> {
+    let year = digits.parse::<i32>().ok()?;
+
+    match digits.len() {
+        2 => Some(if year > 50 { 1900 + year } else { 2000 + year }),
+        4 if (1900..=2099).contains(&year) => Some(year),
+        _ => None,
+    }
+}
+
+/// Check whether a day and month could form a date.
+fn is_day_and_month(day: &str, month: &str) -> bool {
+    matches!(
+        (day.parse::<u32>(), month.parse::<u32>()),
+        (Ok(1..=31), Ok(1..=12))
+    )
+}
+
+/// Check whether a token is a date, either on its own (`1990`, `01151990`) or with separators
+/// (`1-15-90`, `1990.01.15`). Returns the date's year.
+fn parse_date(token: &str) -> Option<i32> {
+    let parts = match token.chars().find(|character| "-/._ ".contains(*character)) {
+        Some(separator) => token.split(separator).collect::<Vec<&str>>(),
+        None => match token.len() {
+            4 => return parse_year(token),
+            6 => vec![&token[..2], &token[2..4], &token[4..]],
+            8 => {
+                return [
+                    (&token[..4], &token[4..6], &token[6..]),
+                    (&token[4..], &token[..2], &token[2..4]),
+                ]
+                .into_iter()
+                .find_map(|(year, first, second)| {
+                    let year = parse_year(year)?;
+
+                    (is_day_and_month(first, second) || is_day_and_month(second, first))
+                        .then_some(year)
+                })
+            }
+            _ => return None,
+        },
+    };
+
+    match parts[..] {
+        [first, second, third]
+            if parts
+                .iter()
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())) =>
+        {
+            [(third, first, second), (first, second, third)]
+                .into_iter()
+                .find_map(|(year, first, second)| {
+                    if first.len() > 2 || second.len() > 2 {
+                        return None;
+                    }
+                    let year = parse_year(year)?;
+
+                    (is_day_and_month(first, second) || is_day_and_month(second, first))
+                        .then_some(year)
+                })
+        }
+        _ => None,
+    }
+}
+
+/// Find dates.
+fn find_dates(characters: &[char]) -> Vec<PatternMatch> {
+    let current_year = Local::now().year();
+    let mut matches = Vec::new();
+
+    for start in 0..characters.len() {
+        for end in start + 4..=(start + 10).min(characters.len()) {
+            let token = characters[start..end].iter().collect::<String>();
+
+            if let Some(year) = parse_date(&token) {
+                let year_guesses = i32::max((year - current_year).abs(), 20) as f64;
+                let guesses = if token.len() == 4 {
+                    year_guesses
+                } else {
+                    year_guesses * 365.0
+                };
+
+                matches.push(PatternMatch {
+                    start,
+                    end,
+                    guesses_log10: guesses.log10(),
+                    pattern: Pattern::Date,
+                });
+            }
+        }
+    }
+
+    matches
+}
+
+#[cfg(test)]
+pub mod test_strength {
+    use super::*;
+
+    /// Check whether a password's estimate includes a warning containing `expected`.
+    fn has_warning(password: &str, expected: &str) -> bool {
+        estimate_strength(password)
+            .warnings
+            .iter()
+            .any(|warning| warning.contains(expected))
+    }
+
+    /// Test whether passwords made of a single pattern are scored as weak.
+    #[test]
+    fn test_weak_passwords() {
+        for password in [
+            "aaaaaaaaaa",
+            "password",
+            "P@ssw0rd",
+            "qwertyuiop",
+            "abcdefghij",
+            "0123456789",
+            "01-15-1990",
+            "drowssap",
+        ] {
+            assert!(
+                estimate_strength(password).score <= 1,
+                "{password} should be weak"
+            );
+        }
+    }
+
+    /// Test whether random passwords are scored as strong.
+    #[test]
+    fn test_strong_passwords() {
+        assert_eq!(estimate_strength("xK9#mQ2$vL7!pR4&").score, 4);
+        assert!(estimate_strength("correct horse battery staple").score >= 3);
+    }
+
+    /// Test whether each pattern produces its warning.
+    #[test]
+    fn test_warnings() {
+        assert!(has_warning("aaaaaaaaaa", "Repeated"));
+        assert!(has_warning("zxcvfdsa!", "keyboard"));
+        assert!(has_warning("mnopqr!!", "Sequences"));
+        assert!(has_warning("s3cur1ty", "substitutions"));
+        assert!(has_warning("x9k12/25/1987", "Dates"));
+        assert!(has_warning("password", "most common"));
+        assert!(estimate_strength("xK9#mQ2$vL7!pR4&").warnings.is_empty());
+    }
+}
@@ -0,0 +1,290 @@
+//! Contains functions for TOTP secrets (RFC 6238).
+//!
+//! TOTP secrets store a seed instead of a password. The seed is only ever used to generate codes
+//! and is never copied to the clipboard or printed.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::BASE32_NOPAD;
+use ring::hmac;
+
+use crate::{
+    errors::HimitsuError,
+    models::totp::{TotpAlgorithm, TotpOptions, TotpSecret},
+};
+
+/// The number of digits in a code if none is specified.
+const DEFAULT_DIGITS: u32 = 6;
+/// The number of seconds a code is valid for if no period is specified.
+const DEFAULT_PERIOD: u64 = 30;
+
+/// Decode a base32 seed. Spaces, dashes, padding, and lowercase letters are accepted, since
+/// services often display seeds in groups.
+fn decode_seed(seed: &str) -> Result<Vec<u8>, HimitsuError> {
+    let normalized_seed = seed
+        .chars()
+        .filter(|character| !matches!(character, ' ' | '-' | '='))
+        .collect::<String>()
+        .to_uppercase();
+
+    match BASE32_NOPAD.decode(normalized_seed.as_bytes()) {
+        Ok(seed) if !seed.is_empty() => Ok(seed),
+        _ => Err(HimitsuError::TotpError(
+            "The seed is not a valid base32 string!".to_string(),
+        )),
+    }
+}
+
+/// Decode percent-encoded characters in an `otpauth://` URI component.
+fn percent_decode(component: &str) -> Result<String, HimitsuError> {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+                let hex = component.get(index + 1..index + 3).ok_or_else(|| {
+                    HimitsuError::TotpError("The URI contains an invalid escape!".to_string())
+                })?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| {
+                    HimitsuError::TotpError("The URI contains an invalid escape!".to_string())
+                })?);
+
+                index += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    Ok(String::from_utf8(decoded)?)
+}
+
+/// Parse an `otpauth://totp/...` URI or a bare base32 seed into a TOTP secret. Parameters in
+/// `options` take precedence over the URI's parameters and the defaults.
+pub fn parse_totp(input: &str, options: &TotpOptions) -> Result<TotpSecret, HimitsuError> {
+    let input = input.trim();
+
+    let mut totp_secret = TotpSecret {
+        algorithm: TotpAlgorithm::default(),
+        digits: DEFAULT_DIGITS,
+        period: DEFAULT_PERIOD,
+        seed: String::new(),
+    };
+
+    if input.to_lowercase().starts_with("otpauth://") {
+        let (path, query) = input["otpauth://".len()..]
+            .split_once('?')
+            .unwrap_or((&input["otpauth://".len()..], ""));
+
+        if !path.to_lowercase().starts_with("totp/") {
+            return Err(HimitsuError::TotpError(
+                "Only otpauth://totp/ URIs are supported!".to_string(),
+            ));
+        }
+
+        for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            let value = percent_decode(value)?;
+
+            match key.to_lowercase().as_str() {
+                "algorithm" => {
+                    totp_secret.algorithm = value.parse().map_err(HimitsuError::TotpError)?
+                }
+                "digits" => {
+                    totp_secret.digits = value.parse().map_err(|_| {
+                        HimitsuError::TotpError(format!("\"{value}\" is not a valid digit count!"))
+                    })?
+                }
+                "period" => {
+                    totp_secret.period = value.parse().map_err(|_| {
+                        HimitsuError::TotpError(format!("\"{value}\" is not a valid period!"))
+                    })?
+                }
+                "secret" => totp_secret.seed = value,
+                _ => {}
+            }
+        }
+    } else {
+        totp_secret.seed = input.to_string();
+    }
+
+    if let Some(algorithm) = options.algorithm {
+        totp_secret.algorithm = algorithm;
+    }
+    if let Some(digits) = options.digits {
+        totp_secret.digits = digits;
+    }
+    if let Some(period) = options.period {
+        totp_secret.period = period;
+    }
+
+    if !(6..=8).contains(&totp_secret.digits) {
+        return Err(HimitsuError::TotpError(
+            "Codes must have between 6 and 8 digits!".to_string(),
+        ));
+    }
+    if totp_secret.period == 0 {
+        return Err(HimitsuError::TotpError(
+            "The period must be at least 1 second!".to_string(),
+        ));
+    }
+
+    totp_secret.seed = BASE32_NOPAD.encode(&decode_seed(&totp_secret.seed)?);
+
+    Ok(totp_secret)
+}
+
+/// Parse a new seed for an existing TOTP secret and return the secret to store. A bare seed keeps
+/// the existing secret's parameters, while an `otpauth://` URI brings its own.
+pub fn replace_seed(stored_secret: &str, input: &str) -> Result<String, HimitsuError> {
+    let options = if input.trim().to_lowercase().starts_with("otpauth://") {
+        TotpOptions::default()
+    } else {
+        let existing_secret: TotpSecret = serde_json::from_str(stored_secret)?;
+
+        TotpOptions {
+            algorithm: Some(existing_secret.algorithm),
+            digits: Some(existing_secret.digits),
+            period: Some(existing_secret.period),
+        }
+    };
+
+    Ok(serde_json::to_string(&parse_totp(input, &options)?)?)
+}
+
+/// Get the current Unix time in seconds.
+pub fn get_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Generate the code for a Unix time.
+pub fn generate_code(totp_secret: &TotpSecret, unix_time: u64) -> Result<String, HimitsuError> {
+    let algorithm = match totp_secret.algorithm {
+        TotpAlgorithm::Sha1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+        TotpAlgorithm::Sha256 => hmac::HMAC_SHA256,
+        TotpAlgorithm::Sha512 => hmac::HMAC_SHA512,
+    };
+    let key = hmac::Key::new(algorithm, &decode_seed(&totp_secret.seed)?);
+
+    let counter = unix_time / totp_secret.period;
+    let tag = hmac::sign(&key, &counter.to_be_bytes());
+    let digest = tag.as_ref();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    Ok(format!(
+        "{:0width$}",
+        truncated % 10_u32.pow(totp_secret.digits),
+        width = totp_secret.digits as usize
+    ))
+}
+
+/// Get the number of seconds the code for a Unix time remains valid for.
+pub fn get_seconds_remaining(totp_secret: &TotpSecret, unix_time: u64) -> u64 {
+    totp_secret.period - unix_time % totp_secret.period
+}
+
+#[cfg(test)]
+pub mod test_totp {
+    use super::*;
+
+    /// Create a TOTP secret with 8 digit codes from one of RFC 6238's ASCII test seeds.
+    fn create_rfc_secret(algorithm: TotpAlgorithm, seed: &str) -> TotpSecret {
+        TotpSecret {
+            algorithm,
+            digits: 8,
+            period: 30,
+            seed: BASE32_NOPAD.encode(seed.as_bytes()),
+        }
+    }
+
+    /// Test whether the generated codes match RFC 6238's test vectors.
+    #[test]
+    fn test_rfc_6238_vectors() {
+        let sha1 = create_rfc_secret(TotpAlgorithm::Sha1, "12345678901234567890");
+        let sha256 = create_rfc_secret(TotpAlgorithm::Sha256, "12345678901234567890123456789012");
+        let sha512 = create_rfc_secret(
+            TotpAlgorithm::Sha512,
+            "1234567890123456789012345678901234567890123456789012345678901234",
+        );
+
+        let vectors = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+
+        for (unix_time, sha1_code, sha256_code, sha512_code) in vectors {
+            assert_eq!(generate_code(&sha1, unix_time).unwrap(), sha1_code);
+            assert_eq!(generate_code(&sha256, unix_time).unwrap(), sha256_code);
+            assert_eq!(generate_code(&sha512, unix_time).unwrap(), sha512_code);
+        }
+    }
+
+    /// Test whether `otpauth://` URIs and bare seeds are parsed, and options override both.
+    #[test]
+    fn test_parse_totp() {
+        let uri_secret = parse_totp(
+            "otpauth://totp/ACME%20Co:alice@example.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60",
+            &TotpOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(uri_secret.algorithm, TotpAlgorithm::Sha256);
+        assert_eq!(uri_secret.digits, 8);
+        assert_eq!(uri_secret.period, 60);
+        assert_eq!(uri_secret.seed, "JBSWY3DPEHPK3PXP");
+
+        let seed_secret = parse_totp(
+            "jbsw y3dp ehpk 3pxp",
+            &TotpOptions {
+                digits: Some(7),
+                ..TotpOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(seed_secret.algorithm, TotpAlgorithm::Sha1);
+        assert_eq!(seed_secret.digits, 7);
+        assert_eq!(seed_secret.period, 30);
+        assert_eq!(seed_secret.seed, "JBSWY3DPEHPK3PXP");
+
+        assert!(parse_totp("not base32!", &TotpOptions::default()).is_err());
+        assert!(parse_totp(
+            "otpauth://hotp/x?secret=JBSWY3DPEHPK3PXP",
+            &TotpOptions::default()
+        )
+        .is_err());
+        assert!(parse_totp(
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&digits=4",
+            &TotpOptions::default()
+        )
+        .is_err());
+    }
+
+    /// Test whether the remaining seconds count down to the end of the period.
+    #[test]
+    fn test_get_seconds_remaining() {
+        let totp_secret = parse_totp("JBSWY3DPEHPK3PXP", &TotpOptions::default()).unwrap();
+
+        assert_eq!(get_seconds_remaining(&totp_secret, 0), 30);
+        assert_eq!(get_seconds_remaining(&totp_secret, 59), 1);
+    }
+}
@@ -0,0 +1,476 @@
+//! Contains the state of `hmu tui` and how it changes when keys are pressed.
+//!
+//! Nothing in this module touches the vault or the terminal. Keys that need either are turned
+//! into an `Action` for the event loop in `tui::run()` to carry out.
+
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{
+    lookup::{
+        expiry,
+        query::{Query, TextMode},
+        utils as lookup_utils,
+    },
+    models::metadata::{LookupMatch, LookupTable},
+    utils::dates,
+};
+
+/// The number of rows Page Up and Page Down move the selection by.
+const PAGE_SIZE: usize = 10;
+
+/// Contains variants for the actions the event loop carries out for the selected secret.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Copy the secret's value, or a TOTP secret's current code, to the clipboard.
+    Copy,
+    /// Remove the secret.
+    Delete,
+    /// Edit the secret's value in `$VISUAL` or `$EDITOR`.
+    Edit,
+    /// Edit the secret's attributes with the same prompts as `hmu edit`.
+    EditAttributes,
+    /// Replace the secret's value with a generated password.
+    Generate,
+    /// Quit the TUI.
+    Quit,
+    /// Replace the secret's tags.
+    Retag(Vec<String>),
+    /// Show the secret's value, or a TOTP secret's current code, for a while.
+    Reveal,
+}
+
+/// Contains variants for what the keyboard is used for.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Move through the secrets and run actions.
+    Browse,
+    /// Confirm an action that cannot be undone with `y`.
+    Confirm(Action),
+    /// Type the selected secret's new tags, delimited by spaces.
+    Retag(String),
+    /// Type the search query.
+    Search,
+}
+
+/// Contains a message shown at the bottom of the screen until the next key is pressed.
+#[derive(Debug)]
+pub struct Status {
+    /// Whether the message describes an error.
+    pub is_error: bool,
+    /// The message.
+    pub message: String,
+}
+
+/// Contains a secret's value shown in the details pane until it is hidden again.
+pub struct Revealed {
+    /// The secret's hash ID.
+    pub hash: String,
+    /// When the value is hidden again.
+    pub until: Instant,
+    /// The value.
+    pub value: String,
+}
+
+/// Contains the state of the TUI.
+pub struct App {
+    /// When a key was last pressed.
+    pub last_activity: Instant,
+    /// How long the TUI may be idle before the vault is locked.
+    pub lock_after: Duration,
+    /// The secrets matching the query, best match first.
+    pub matches: Vec<LookupMatch>,
+    /// What the keyboard is used for.
+    pub mode: Mode,
+    /// The parsed search query, `None` if the query is empty, or the error parsing it.
+    pub query: Result<Option<Query>, String>,
+    /// The search query as it was typed.
+    pub query_text: String,
+    /// How long a revealed value is shown for.
+    pub reveal_for: Duration,
+    /// The revealed value, if any.
+    pub revealed: Option<Revealed>,
+    /// Every secret in the vault.
+    pub secrets: Vec<LookupMatch>,
+    /// The index of the selected secret within `matches`.
+    pub selected: usize,
+    /// The message shown at the bottom of the screen, if any.
+    pub status: Option<Status>,
+}
+
+impl App {
+    /// Create the TUI's state from the vault's lookup table.
+    pub fn new(lookup_table: &LookupTable, lock_after: Duration, reveal_for: Duration) -> Self {
+        let mut app = Self {
+            last_activity: Instant::now(),
+            lock_after,
+            matches: Vec::new(),
+            mode: Mode::Browse,
+            query: Ok(None),
+            query_text: String::new(),
+            reveal_for,
+            revealed: None,
+            secrets: Vec::new(),
+            selected: 0,
+            status: None,
+        };
+        app.load(lookup_table);
+
+        app
+    }
+
+    /// Replace the secrets with those in the lookup table, such as after a secret was changed.
+    /// Temporary secrets that have expired are left out. The selected secret stays selected if it
+    /// still exists.
+    pub fn load(&mut self, lookup_table: &LookupTable) {
+        let now = dates::get_now();
+
+        self.secrets = lookup_table
+            .table
+            .iter()
+            .filter(|(_, anatomy)| !expiry::has_lapsed(anatomy, now))
+            .map(|(hash_id, anatomy)| LookupMatch::create(anatomy.clone(), hash_id.clone()))
+            .collect();
+
+        let selected_hash = self
+            .get_selected()
+            .map(|lookup_match| lookup_match.hash.clone());
+        self.filter();
+
+        if let Some(hash) = selected_hash {
+            self.select(&hash);
+        }
+    }
+
+    /// Select the secret with this hash ID if it matches the query.
+    pub fn select(&mut self, hash: &str) {
+        if let Some(index) = self
+            .matches
+            .iter()
+            .position(|lookup_match| lookup_match.hash == hash)
+        {
+            self.selected = index;
+        }
+    }
+
+    /// Search the secrets with the query and rank the matches the same way `hmu use` does.
+    fn filter(&mut self) {
+        self.query = if self.query_text.trim().is_empty() {
+            Ok(None)
+        } else {
+            Query::parse(&self.query_text, dates::get_now(), TextMode::Fuzzy)
+                .map(Some)
+                .map_err(|error| error.to_string())
+        };
+
+        let scored_matches = match &self.query {
+            Ok(query) => self
+                .secrets
+                .iter()
+                .filter_map(|lookup_match| {
+                    query
+                        .as_ref()
+                        .map_or(Some(0), |query| query.score(&lookup_match.anatomy))
+                        .map(|score| (score, (String::new(), lookup_match.clone())))
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        self.matches = lookup_utils::rank_matches(scored_matches)
+            .into_iter()
+            .map(|(_, lookup_match)| lookup_match)
+            .collect();
+        self.selected = 0;
+    }
+
+    /// Get the selected secret.
+    pub fn get_selected(&self) -> Option<&LookupMatch> {
+        self.matches.get(self.selected)
+    }
+
+    /// Move the selection by `offset` rows, stopping at the first and last secret.
+    fn move_selection(&mut self, offset: isize) {
+        let last_index = self.matches.len().saturating_sub(1);
+
+        self.selected = self.selected.saturating_add_signed(offset).min(last_index);
+        self.revealed = None;
+    }
+
+    /// Show a message at the bottom of the screen.
+    pub fn set_status(&mut self, message: String) {
+        self.status = Some(Status {
+            is_error: false,
+            message,
+        });
+    }
+
+    /// Show an error at the bottom of the screen.
+    pub fn set_error(&mut self, message: String) {
+        self.status = Some(Status {
+            is_error: true,
+            message,
+        });
+    }
+
+    /// Show a secret's value until `reveal_for` has passed.
+    pub fn reveal(&mut self, hash: String, value: String) {
+        self.revealed = Some(Revealed {
+            hash,
+            until: Instant::now() + self.reveal_for,
+            value,
+        });
+    }
+
+    /// Hide the revealed value once its time is up.
+    pub fn hide_expired_value(&mut self, now: Instant) {
+        if self
+            .revealed
+            .as_ref()
+            .is_some_and(|revealed| now >= revealed.until)
+        {
+            self.revealed = None;
+        }
+    }
+
+    /// Check whether no key was pressed for `lock_after`.
+    pub fn is_idle(&self, now: Instant) -> bool {
+        now.duration_since(self.last_activity) >= self.lock_after
+    }
+
+    /// Update the state for a pressed key. Returns what the event loop has to do, if anything.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        self.last_activity = Instant::now();
+        self.status = None;
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.handle_browse_key(key),
+            Mode::Confirm(action) => {
+                if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                    Some(action)
+                } else {
+                    self.set_status("Cancelled.".to_string());
+
+                    None
+                }
+            }
+            Mode::Retag(mut tags) => {
+                match key.code {
+                    KeyCode::Backspace => {
+                        tags.pop();
+                    }
+                    KeyCode::Char(character) => tags.push(character),
+                    KeyCode::Enter => {
+                        let mut new_tags: Vec<String> = Vec::new();
+                        for tag in tags.split_whitespace() {
+                            if !new_tags.iter().any(|new_tag| new_tag == tag) {
+                                new_tags.push(tag.to_string());
+                            }
+                        }
+
+                        return Some(Action::Retag(new_tags));
+                    }
+                    KeyCode::Esc => return None,
+                    _ => {}
+                }
+
+                self.mode = Mode::Retag(tags);
+
+                None
+            }
+            Mode::Search => {
+                match key.code {
+                    KeyCode::Backspace => {
+                        self.query_text.pop();
+                        self.filter();
+                    }
+                    KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.query_text.clear();
+                        self.filter();
+                    }
+                    KeyCode::Char(character) => {
+                        self.query_text.push(character);
+                        self.filter();
+                    }
+                    KeyCode::Down => self.move_selection(1),
+                    KeyCode::Enter => return None,
+                    KeyCode::Esc => {
+                        self.query_text.clear();
+                        self.filter();
+
+                        return None;
+                    }
+                    KeyCode::Up => self.move_selection(-1),
+                    _ => {}
+                }
+
+                self.mode = Mode::Search;
+
+                None
+            }
+        }
+    }
+
+    /// Update the state for a key pressed while browsing.
+    fn handle_browse_key(&mut self, key: KeyEvent) -> Option<Action> {
+        let has_selection = self.get_selected().is_some();
+
+        match key.code {
+            KeyCode::Char('q') => return Some(Action::Quit),
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Esc if !self.query_text.is_empty() => {
+                self.query_text.clear();
+                self.filter();
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown => self.move_selection(PAGE_SIZE as isize),
+            KeyCode::PageUp => self.move_selection(-(PAGE_SIZE as isize)),
+            KeyCode::Home => self.move_selection(isize::MIN),
+            KeyCode::End => self.move_selection(isize::MAX),
+            KeyCode::Enter | KeyCode::Char('c') if has_selection => return Some(Action::Copy),
+            KeyCode::Char('r') if has_selection => {
+                if self.revealed.is_some() {
+                    self.revealed = None;
+                } else {
+                    return Some(Action::Reveal);
+                }
+            }
+            KeyCode::Char('e') if has_selection => return Some(Action::Edit),
+            KeyCode::Char('E') if has_selection => return Some(Action::EditAttributes),
+            KeyCode::Char('d') if has_selection => self.mode = Mode::Confirm(Action::Delete),
+            KeyCode::Char('g') if has_selection => self.mode = Mode::Confirm(Action::Generate),
+            KeyCode::Char('t') => {
+                if let Some(lookup_match) = self.get_selected() {
+                    self.mode = Mode::Retag(lookup_match.anatomy.tags.join(" "));
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+pub mod test_app {
+    use super::*;
+
+    use crate::models::metadata::Anatomy;
+
+    /// Create an `App` for a lookup table holding secrets with these labels and categories.
+    fn create_app(secrets: &[(&str, &str)]) -> App {
+        let mut lookup_table = LookupTable::new();
+        for (index, (label, category)) in secrets.iter().enumerate() {
+            lookup_table.table.insert(
+                format!("hash-{index}"),
+                Anatomy::create_from(category.to_string(), label.to_string(), Vec::new()),
+            );
+        }
+
+        App::new(
+            &lookup_table,
+            Duration::from_secs(300),
+            Duration::from_secs(10),
+        )
+    }
+
+    /// Press a key without modifiers.
+    fn press(app: &mut App, code: KeyCode) -> Option<Action> {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    /// Test whether temporary secrets that have expired are not listed.
+    #[test]
+    fn test_load_skips_lapsed() {
+        let mut app = create_app(&[("github token", "dev")]);
+
+        let mut lookup_table = LookupTable::new();
+        lookup_table.table.insert(
+            "lapsed".to_string(),
+            Anatomy {
+                expires_at: Some("01-01-2000 00:00:00".to_string()),
+                temporary: true,
+                ..Anatomy::create_for_test("dev", "ci token", "01-01-2022 00:00:00")
+            },
+        );
+        app.load(&lookup_table);
+        assert!(app.secrets.is_empty());
+    }
+
+    /// Test whether typing a query narrows the secrets down and Escape clears it.
+    #[test]
+    fn test_search() {
+        let mut app = create_app(&[("github token", "dev"), ("aws root", "cloud")]);
+        assert_eq!(app.matches.len(), 2);
+
+        press(&mut app, KeyCode::Char('/'));
+        for character in "ghtk".chars() {
+            press(&mut app, KeyCode::Char(character));
+        }
+        assert_eq!(app.matches.len(), 1);
+        assert_eq!(app.get_selected().unwrap().anatomy.label, "github token");
+
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.mode, Mode::Browse);
+        assert_eq!(app.matches.len(), 1);
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.matches.len(), 2);
+
+        app.query_text = "created:>soon".to_string();
+        app.filter();
+        assert!(app.query.is_err());
+        assert!(app.matches.is_empty());
+    }
+
+    /// Test whether actions are only carried out once confirmed, and tags are read from the
+    /// retag prompt.
+    #[test]
+    fn test_actions() {
+        let mut app = create_app(&[("github token", "dev")]);
+
+        assert_eq!(press(&mut app, KeyCode::Char('c')), Some(Action::Copy));
+
+        press(&mut app, KeyCode::Char('d'));
+        assert_eq!(press(&mut app, KeyCode::Char('n')), None);
+        assert!(app.status.is_some());
+        press(&mut app, KeyCode::Char('d'));
+        assert_eq!(press(&mut app, KeyCode::Char('y')), Some(Action::Delete));
+
+        press(&mut app, KeyCode::Char('t'));
+        for character in "ci ci prod".chars() {
+            press(&mut app, KeyCode::Char(character));
+        }
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            Some(Action::Retag(vec!["ci".to_string(), "prod".to_string()]))
+        );
+
+        assert_eq!(
+            app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Action::Quit)
+        );
+    }
+
+    /// Test whether the selection stays within the secrets and hides revealed values.
+    #[test]
+    fn test_move_selection() {
+        let mut app = create_app(&[("a", "dev"), ("b", "dev"), ("c", "dev")]);
+
+        press(&mut app, KeyCode::End);
+        assert_eq!(app.selected, 2);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.selected, 2);
+
+        app.reveal("hash-0".to_string(), "hunter2".to_string());
+        press(&mut app, KeyCode::Home);
+        assert_eq!(app.selected, 0);
+        assert!(app.revealed.is_none());
+    }
+}
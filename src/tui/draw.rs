@@ -0,0 +1,365 @@
+//! Contains how the state of `hmu tui` is drawn to the terminal.
+//!
+//! The screen is laid out as follows:
+//!
+//! ```text
+//!  himitsu  2 of 14 secrets                                  Locks in 4:58
+//!  Search: gh tag:prod
+//! ─────────────────────────────────────────────────────────────────────────
+//!  GitHub Token          │ Label          GitHub Token
+//!  GitHub Deploy Key     │ Category       dev
+//!                        │ ...
+//!  📋 "GitHub Token" is copied to your clipboard.
+//!  ↑↓ move  / search  ⏎ copy  r reveal  ...
+//! ```
+
+use std::{io::Write, ops::Range, time::Instant};
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Print, PrintStyledContent, Stylize},
+    terminal::{Clear, ClearType},
+};
+
+use crate::{
+    errors::HimitsuError,
+    models::metadata::{Anatomy, SecretType},
+};
+
+use super::app::{Action, App, Mode};
+
+/// The number of rows above the list of secrets.
+const HEADER_HEIGHT: u16 = 3;
+/// The number of rows below the list of secrets.
+const FOOTER_HEIGHT: u16 = 2;
+/// The narrowest the list of secrets may be.
+const MIN_LIST_WIDTH: usize = 20;
+/// The width of the names in the details pane.
+const NAME_WIDTH: usize = 15;
+/// The key bindings shown while browsing.
+const BROWSE_HELP: &str = "↑↓ move  / search  ⏎ copy  r reveal  e edit  E attributes  t retag  g generate  d delete  q quit";
+/// The key bindings shown while searching.
+const SEARCH_HELP: &str = "Type to search  ⏎ done  Esc clear";
+
+/// Shorten text to at most `width` characters, ending it with `…` if anything was cut off.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else if width == 0 {
+        String::new()
+    } else {
+        let mut truncated = text.chars().take(width - 1).collect::<String>();
+        truncated.push('…');
+
+        truncated
+    }
+}
+
+/// Pad text with spaces to `width` characters, truncating it first if it is too long.
+fn pad(text: &str, width: usize) -> String {
+    format!("{:width$}", truncate(text, width))
+}
+
+/// Format a duration in seconds as minutes and seconds, such as `4:05`.
+fn format_countdown(seconds: u64) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Get the rows of the details pane for a secret.
+fn get_details(anatomy: &Anatomy) -> Vec<(&'static str, String)> {
+    let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| "—".to_string());
+
+    vec![
+        ("Label", anatomy.label.clone()),
+        ("Category", anatomy.category.clone()),
+        (
+            "Tags",
+            if anatomy.tags.is_empty() {
+                "—".to_string()
+            } else {
+                anatomy.tags.join(", ")
+            },
+        ),
+        (
+            "Type",
+            match anatomy.secret_type {
+                SecretType::Password => "Password".to_string(),
+                SecretType::Totp => "TOTP".to_string(),
+            },
+        ),
+        ("Protection", anatomy.protection.to_string()),
+        ("Username", or_none(&anatomy.username)),
+        ("URL", or_none(&anatomy.url)),
+        ("Created", anatomy.date_created.clone()),
+        ("Modified", anatomy.get_modified_date().to_string()),
+        ("Rotated", anatomy.get_rotated_date().to_string()),
+        (
+            "Last accessed",
+            anatomy
+                .last_accessed
+                .clone()
+                .unwrap_or_else(|| "Never".to_string()),
+        ),
+        (
+            "Expires",
+            anatomy
+                .expires_at
+                .clone()
+                .unwrap_or_else(|| "Never".to_string()),
+        ),
+        (
+            "Rotate every",
+            anatomy
+                .rotation_days
+                .map_or_else(|| "—".to_string(), |days| format!("{days} days")),
+        ),
+        (
+            "Temporary",
+            if anatomy.temporary { "Yes" } else { "No" }.to_string(),
+        ),
+    ]
+}
+
+/// Draw a label in the list of secrets, highlighting the parts matched by the query.
+fn draw_label<W: Write>(
+    writer: &mut W,
+    label: &str,
+    label_ranges: &[Range<usize>],
+    width: usize,
+    is_selected: bool,
+) -> Result<(), HimitsuError> {
+    let label = pad(&format!(" {label}"), width);
+
+    // Group the characters into runs that are either highlighted or not, shifting the ranges by
+    // the leading space.
+    let mut runs: Vec<(bool, String)> = Vec::new();
+    for (index, character) in label.char_indices() {
+        let is_highlighted = index > 0
+            && label_ranges
+                .iter()
+                .any(|range| range.contains(&(index - 1)));
+
+        match runs.last_mut() {
+            Some((highlighted, run)) if *highlighted == is_highlighted => run.push(character),
+            _ => runs.push((is_highlighted, character.to_string())),
+        }
+    }
+
+    for (is_highlighted, run) in runs {
+        let mut content = run.stylize();
+        if is_highlighted {
+            content = content.yellow().bold();
+        }
+        if is_selected {
+            content = content.reverse();
+        }
+
+        queue!(writer, PrintStyledContent(content))?;
+    }
+
+    Ok(())
+}
+
+/// Draw the TUI's state on a terminal of `width` columns and `height` rows.
+pub fn draw<W: Write>(
+    writer: &mut W,
+    app: &App,
+    width: u16,
+    height: u16,
+) -> Result<(), HimitsuError> {
+    let columns = width as usize;
+    let list_width = (columns * 2 / 5).max(MIN_LIST_WIDTH).min(columns);
+    let details_width = columns.saturating_sub(list_width + 1);
+    let list_height = height.saturating_sub(HEADER_HEIGHT + FOOTER_HEIGHT) as usize;
+    let now = Instant::now();
+
+    // The header, with the number of matches and how long until the vault is locked.
+    let title = format!(
+        " himitsu  {} of {} secrets",
+        app.matches.len(),
+        app.secrets.len()
+    );
+    let countdown = format!(
+        "Locks in {} ",
+        format_countdown(
+            app.lock_after
+                .saturating_sub(now.duration_since(app.last_activity))
+                .as_secs()
+        )
+    );
+    queue!(
+        writer,
+        MoveTo(0, 0),
+        PrintStyledContent(
+            pad(
+                &format!(
+                    "{title}{countdown:>width$}",
+                    width = columns.saturating_sub(title.chars().count())
+                ),
+                columns
+            )
+            .bold()
+            .reverse()
+        ),
+    )?;
+
+    // The search query, or the error parsing it.
+    queue!(writer, MoveTo(0, 1))?;
+    match &app.query {
+        Err(error) => queue!(
+            writer,
+            PrintStyledContent(truncate(&format!(" {error}"), columns).red())
+        )?,
+        Ok(_) if app.mode == Mode::Search => queue!(
+            writer,
+            Print(truncate(&format!(" Search: {}█", app.query_text), columns))
+        )?,
+        Ok(_) if app.query_text.is_empty() => queue!(
+            writer,
+            PrintStyledContent(truncate(" Press / to search", columns).dim())
+        )?,
+        Ok(_) => queue!(
+            writer,
+            Print(truncate(&format!(" Search: {}", app.query_text), columns))
+        )?,
+    }
+    queue!(
+        writer,
+        Clear(ClearType::UntilNewLine),
+        MoveTo(0, 2),
+        PrintStyledContent("─".repeat(columns).dim()),
+    )?;
+
+    // The list of secrets next to the selected secret's details. The list scrolls just far
+    // enough to keep the selected secret in view.
+    let offset = (app.selected + 1).saturating_sub(list_height);
+    let selected = app.get_selected();
+    let mut details = selected
+        .map(|lookup_match| get_details(&lookup_match.anatomy))
+        .unwrap_or_default();
+    if let Some(lookup_match) = selected {
+        let secret = match &app.revealed {
+            Some(revealed) if revealed.hash == lookup_match.hash => format!(
+                "{}  (hidden in {}s)",
+                revealed.value,
+                revealed.until.saturating_duration_since(now).as_secs() + 1
+            ),
+            _ => "••••••••  (r to reveal)".to_string(),
+        };
+
+        details.push(("", String::new()));
+        details.push(("Secret", secret));
+    }
+
+    for row in 0..list_height {
+        queue!(writer, MoveTo(0, HEADER_HEIGHT + row as u16))?;
+
+        match app.matches.get(offset + row) {
+            Some(lookup_match) => {
+                let label_ranges = match &app.query {
+                    Ok(Some(query)) => query.get_label_ranges(&lookup_match.anatomy.label),
+                    _ => Vec::new(),
+                };
+
+                draw_label(
+                    writer,
+                    &lookup_match.anatomy.label,
+                    &label_ranges,
+                    list_width,
+                    offset + row == app.selected,
+                )?;
+            }
+            None if row == 0 && app.matches.is_empty() => queue!(
+                writer,
+                PrintStyledContent(pad(" No matches.", list_width).dim())
+            )?,
+            None => queue!(writer, Print(" ".repeat(list_width)))?,
+        }
+
+        if details_width > 0 {
+            queue!(writer, PrintStyledContent("│".dim()))?;
+
+            if let Some((name, value)) = details.get(row) {
+                queue!(
+                    writer,
+                    PrintStyledContent(pad(&format!(" {name}"), NAME_WIDTH).dim()),
+                    Print(truncate(value, details_width.saturating_sub(NAME_WIDTH))),
+                )?;
+            }
+        }
+
+        queue!(writer, Clear(ClearType::UntilNewLine))?;
+    }
+
+    // The status message, and the key bindings or the prompt for the current mode.
+    queue!(writer, MoveTo(0, height.saturating_sub(FOOTER_HEIGHT)))?;
+    if let Some(status) = &app.status {
+        let message = truncate(&format!(" {}", status.message), columns);
+
+        if status.is_error {
+            queue!(writer, PrintStyledContent(message.red().bold()))?;
+        } else {
+            queue!(writer, PrintStyledContent(message.green()))?;
+        }
+    }
+    queue!(
+        writer,
+        Clear(ClearType::UntilNewLine),
+        MoveTo(0, height.saturating_sub(1))
+    )?;
+
+    let label = selected.map_or("", |lookup_match| lookup_match.anatomy.label.as_str());
+    match &app.mode {
+        Mode::Browse => queue!(
+            writer,
+            PrintStyledContent(truncate(&format!(" {BROWSE_HELP}"), columns).dim())
+        )?,
+        Mode::Confirm(action) => queue!(
+            writer,
+            PrintStyledContent(
+                truncate(
+                    &match action {
+                        Action::Generate => {
+                            format!(" Replace \"{label}\" with a generated password? (y/N)")
+                        }
+                        _ => format!(" Permanently delete \"{label}\"? (y/N)"),
+                    },
+                    columns
+                )
+                .yellow()
+                .bold()
+            )
+        )?,
+        Mode::Retag(tags) => queue!(
+            writer,
+            Print(truncate(
+                &format!(
+                    " Tags for \"{label}\" (delimited by spaces, ⏎ save, Esc cancel): {tags}█"
+                ),
+                columns
+            ))
+        )?,
+        Mode::Search => queue!(
+            writer,
+            PrintStyledContent(truncate(&format!(" {SEARCH_HELP}"), columns).dim())
+        )?,
+    }
+    queue!(writer, Clear(ClearType::UntilNewLine))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_draw {
+    use super::*;
+
+    /// Test whether text is shortened by characters rather than bytes.
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("github token", 20), "github token");
+        assert_eq!(truncate("github token", 7), "github…");
+        assert_eq!(truncate("très sécret", 5), "très…");
+        assert_eq!(truncate("github token", 0), "");
+    }
+}
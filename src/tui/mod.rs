@@ -0,0 +1,340 @@
+//! Contains `hmu tui`, a full-screen interface for browsing and managing the vault.
+//!
+//! The vault is unlocked once when the TUI starts. Prompts that are part of an action, such as
+//! the master password of a protected secret or the editor opened by `e`, are shown after leaving
+//! the full-screen interface and return to it once they are done.
+
+pub mod app;
+pub mod draw;
+
+use std::{
+    io::{self, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+use ansi_term::Color;
+use crossterm::{
+    cursor, event,
+    event::Event,
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::{
+    audit::log,
+    cli::subcommands,
+    encryption::{decrypt, encrypt},
+    errors::HimitsuError,
+    lookup::{
+        expiry,
+        modify::{self, RemovalEvent},
+        secure,
+    },
+    models::{
+        log::LogAction,
+        metadata::{LookupMatch, Protection, SecretType},
+        totp::TotpSecret,
+    },
+    prompts::protect,
+    totp,
+    utils::{clipboard, closet, dates, generate, git},
+};
+
+use app::{Action, App};
+
+/// How often the screen is redrawn while no key is pressed, so countdowns stay current.
+const TICK: Duration = Duration::from_millis(250);
+
+/// Owns the terminal while the TUI runs. The terminal is restored when this is dropped, even if
+/// the TUI returns early because of an error.
+struct Terminal {
+    /// The terminal's stdout.
+    stdout: Stdout,
+}
+
+impl Terminal {
+    /// Switch the terminal to raw mode and the alternate screen.
+    fn enter() -> Result<Self, HimitsuError> {
+        let mut terminal = Self {
+            stdout: io::stdout(),
+        };
+        terminal.resume()?;
+
+        Ok(terminal)
+    }
+
+    /// Switch the terminal to raw mode and the alternate screen, and hide the cursor.
+    fn resume(&mut self) -> Result<(), HimitsuError> {
+        terminal::enable_raw_mode()?;
+        execute!(self.stdout, EnterAlternateScreen, cursor::Hide)?;
+
+        Ok(())
+    }
+
+    /// Restore the terminal to how it was before the TUI started.
+    fn leave(&mut self) -> Result<(), HimitsuError> {
+        execute!(self.stdout, cursor::Show, LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+
+        Ok(())
+    }
+
+    /// Draw the TUI's state.
+    fn draw(&mut self, app: &App) -> Result<(), HimitsuError> {
+        let (width, height) = terminal::size()?;
+
+        draw::draw(&mut self.stdout, app, width, height)?;
+        self.stdout.flush()?;
+
+        Ok(())
+    }
+
+    /// Leave the full-screen interface while `f` runs, so it can show prompts or open an editor.
+    fn suspend<T>(
+        &mut self,
+        f: impl FnOnce() -> Result<T, HimitsuError>,
+    ) -> Result<T, HimitsuError> {
+        self.leave()?;
+        let result = f();
+        self.resume()?;
+
+        result
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+/// Run the TUI until the user quits or it is idle for `lock_after`.
+pub fn run(password: &str, lock_after: Duration, reveal_for: Duration) -> Result<(), HimitsuError> {
+    if !closet::check_for_skeletons()? {
+        return Err(HimitsuError::NoSecretsError);
+    }
+
+    let mut app = App::new(
+        &secure::decrypt_lookup_table(password)?,
+        lock_after,
+        reveal_for,
+    );
+    let mut terminal = Terminal::enter()?;
+
+    loop {
+        let now = Instant::now();
+        if app.is_idle(now) {
+            drop(app);
+            drop(terminal);
+
+            println!(
+                "{}",
+                Color::Fixed(172)
+                    .bold()
+                    .paint("🔒 The vault was locked after being idle.")
+            );
+
+            return Ok(());
+        }
+
+        app.hide_expired_value(now);
+        terminal.draw(&app)?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+
+        let Some(action) = app.handle_key(key) else {
+            continue;
+        };
+        if action == Action::Quit {
+            return Ok(());
+        }
+        let Some(lookup_match) = app.get_selected().cloned() else {
+            continue;
+        };
+
+        let new_hash = match run_action(&mut terminal, &mut app, action, &lookup_match, password) {
+            Ok(new_hash) => new_hash,
+            Err(HimitsuError::UserCancelled) => {
+                app.set_status("Cancelled.".to_string());
+                None
+            }
+            Err(error) => {
+                app.set_error(error.to_string());
+                None
+            }
+        };
+
+        app.load(&secure::decrypt_lookup_table(password)?);
+        if let Some(new_hash) = new_hash {
+            app.select(&new_hash);
+        }
+        app.last_activity = Instant::now();
+    }
+}
+
+/// Carry out an action for the selected secret. Returns the secret's new hash ID if the action
+/// replaced it.
+fn run_action(
+    terminal: &mut Terminal,
+    app: &mut App,
+    action: Action,
+    lookup_match: &LookupMatch,
+    password: &str,
+) -> Result<Option<String>, HimitsuError> {
+    let anatomy = &lookup_match.anatomy;
+
+    // The TUI may stay open past a temporary secret's expiry, so it is checked again here.
+    if action != Action::Delete && expiry::has_lapsed(anatomy, dates::get_now()) {
+        return Err(HimitsuError::ExpiryError(format!(
+            "\"{}\" has expired and is no longer available!",
+            anatomy.label
+        )));
+    }
+
+    match action {
+        Action::Copy | Action::Reveal => {
+            let (secret, _) = open_secret(terminal, lookup_match, password)?;
+            let value = get_usable_value(&anatomy.secret_type, secret)?;
+
+            log::record_event(LogAction::Use, Some(log::describe_secret(anatomy)));
+            modify::update_last_accessed(&lookup_match.hash, password)?;
+
+            if action == Action::Copy {
+                clipboard::set_clipboard(value)?;
+                app.set_status(format!(
+                    "📋 \"{}\" is copied to your clipboard.",
+                    anatomy.label
+                ));
+            } else {
+                app.reveal(lookup_match.hash.clone(), value);
+            }
+        }
+        Action::Delete => {
+            let mut lookup_table = secure::decrypt_lookup_table(password)?;
+            modify::remove_from_lookup_table(
+                &mut lookup_table,
+                &lookup_match.hash,
+                &RemovalEvent::Remove,
+            )?;
+            secure::encrypt_lookup_table(password, &mut lookup_table)?;
+
+            log::record_event(LogAction::Remove, Some(log::describe_secret(anatomy)));
+            git::record_change("remove", &lookup_match.hash)?;
+
+            app.set_status(format!("🗑 \"{}\" was removed.", anatomy.label));
+        }
+        Action::Edit => {
+            terminal.suspend(|| subcommands::edit_secret_in_editor(lookup_match, password))?;
+        }
+        Action::EditAttributes => {
+            terminal.suspend(|| subcommands::edit_stored_secret(lookup_match, password))?;
+        }
+        Action::Generate => {
+            if anatomy.secret_type == SecretType::Totp {
+                return Err(HimitsuError::TotpError(
+                    "TOTP seeds cannot be generated!".to_string(),
+                ));
+            }
+
+            let (_, passphrase) = open_secret(terminal, lookup_match, password)?;
+
+            let new_value = generate::generate_password(generate::DEFAULT_LENGTH);
+            let new_secret = match &passphrase {
+                Some(passphrase) => encrypt::seal_with_passphrase(passphrase, &new_value)?,
+                None => new_value.clone(),
+            };
+
+            let mut new_anatomy = anatomy.clone();
+            new_anatomy.last_modified = Some(dates::get_timestamp());
+            new_anatomy.last_rotated = new_anatomy.last_modified.clone();
+
+            let mut lookup_table = secure::decrypt_lookup_table(password)?;
+            let hash_id =
+                encrypt::import_secret(&new_anatomy, &mut lookup_table, password, &new_secret)?;
+            modify::remove_from_lookup_table(
+                &mut lookup_table,
+                &lookup_match.hash,
+                &RemovalEvent::Replace,
+            )?;
+            secure::encrypt_lookup_table(password, &mut lookup_table)?;
+
+            log::record_event(LogAction::Edit, Some(log::describe_secret(&new_anatomy)));
+            git::record_change("update", &hash_id)?;
+
+            app.reveal(hash_id.clone(), new_value);
+            app.set_status(format!(
+                "🔑 \"{}\" has a new password. Press c to copy it.",
+                anatomy.label
+            ));
+
+            return Ok(Some(hash_id));
+        }
+        Action::Quit => {}
+        Action::Retag(tags) => {
+            open_secret(terminal, lookup_match, password)?;
+
+            let mut lookup_table = secure::decrypt_lookup_table(password)?;
+            let new_anatomy = lookup_table
+                .table
+                .get_mut(&lookup_match.hash)
+                .ok_or_else(|| {
+                    HimitsuError::LookupError(
+                        "Could not find an existing Anatomy for this secret!".to_string(),
+                    )
+                })?;
+            new_anatomy.tags = tags;
+            new_anatomy.last_modified = Some(dates::get_timestamp());
+            let description = log::describe_secret(new_anatomy);
+            secure::encrypt_lookup_table(password, &mut lookup_table)?;
+
+            log::record_event(LogAction::Edit, Some(description));
+            git::record_change("update", &lookup_match.hash)?;
+
+            app.set_status(format!("🏷 \"{}\" was retagged.", anatomy.label));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Decrypt a stored secret, asking for whatever its protection requires first. Returns the
+/// secret and its passphrase if it is sealed with one.
+fn open_secret(
+    terminal: &mut Terminal,
+    lookup_match: &LookupMatch,
+    password: &str,
+) -> Result<(String, Option<String>), HimitsuError> {
+    let passphrase = match lookup_match.anatomy.protection {
+        Protection::None => None,
+        Protection::Reauth | Protection::Passphrase => terminal
+            .suspend(|| protect::run_unlock_protected_secret(&lookup_match.anatomy, password))?,
+    };
+
+    let mut secret = decrypt::decrypt_secret_in(
+        &decrypt::get_secret_hash_path(&lookup_match.hash)?,
+        password,
+    )?;
+    if let Some(passphrase) = &passphrase {
+        secret = decrypt::open_with_passphrase(passphrase, &secret)?;
+    }
+
+    Ok((secret, passphrase))
+}
+
+/// Get the value that is copied or revealed for a decrypted secret. TOTP secrets use their
+/// current code, so the seed is never revealed.
+fn get_usable_value(secret_type: &SecretType, secret: String) -> Result<String, HimitsuError> {
+    match secret_type {
+        SecretType::Password => Ok(secret),
+        SecretType::Totp => {
+            let totp_secret: TotpSecret = serde_json::from_str(&secret)?;
+
+            totp::generate_code(&totp_secret, totp::get_unix_time())
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Contains utilities for tracking failed login attempts.
+//!
+//! Failed attempts are stored with their timestamps in the `attempts.json` file in the config
+//! directory, so the backoff between attempts is enforced across separate runs of `himitsu`
+//! rather than only within a single run.
+//!
+//! Every attempt is counted as failed before the password is checked, and the lockout is checked
+//! and the attempt counted while holding a lock on the `attempts.lock` file. Running several
+//! `himitsu` processes at once, or stopping one while it checks the password, cannot skip the
+//! backoff.
+
+use std::{fs, path::PathBuf};
+
+use chrono::{Duration, Local, NaiveDateTime};
+use directories::ProjectDirs;
+
+use crate::{errors::HimitsuError, models::config::LoginAttempts};
+
+use super::{config, dates, lock, vault};
+
+/// The number of failed attempts in a row that are allowed before the backoff kicks in.
+const FREE_ATTEMPTS: usize = 3;
+/// The longest time, in seconds, the user has to wait between two attempts.
+const MAX_BACKOFF_SECONDS: i64 = 60 * 60;
+
+/// Identifies a login attempt counted by `begin_attempt()`.
+pub struct Attempt {
+    /// The attempt's position among the failed attempts.
+    index: usize,
+    /// When the attempt was made.
+    timestamp: String,
+}
+
+/// Get the path to the `attempts.json` file.
+fn get_attempts_path() -> Result<PathBuf, HimitsuError> {
+    match ProjectDirs::from("", "", "himitsu") {
+        Some(project_directory) => Ok(project_directory.config_dir().join("attempts.json")),
+        None => Err(HimitsuError::ApplicationError),
+    }
+}
+
+/// Lock the `attempts.json` file until the returned lock is dropped.
+fn lock_login_attempts() -> Result<lock::FileLock, HimitsuError> {
+    lock::lock_exclusive(&get_attempts_path()?.with_extension("lock"))
+}
+
+/// Retrieve the failed login attempts since the last successful login.
+pub fn get_login_attempts() -> Result<LoginAttempts, HimitsuError> {
+    let attempts_path = get_attempts_path()?;
+
+    if attempts_path.exists() {
+        Ok(serde_json::from_str(&fs::read_to_string(attempts_path)?)?)
+    } else {
+        Ok(LoginAttempts::default())
+    }
+}
+
+/// Store the failed login attempts in the `attempts.json` file.
+fn store_login_attempts(login_attempts: &LoginAttempts) -> Result<(), HimitsuError> {
+    let attempts_path = get_attempts_path()?;
+    if let Some(parent) = attempts_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(attempts_path, serde_json::to_string_pretty(login_attempts)?)?;
+
+    Ok(())
+}
+
+/// Get the number of seconds the user has to wait after a number of failed attempts in a row.
+/// The wait doubles with every failed attempt once the free attempts are used up.
+pub fn get_backoff_seconds(failures: usize) -> i64 {
+    if failures < FREE_ATTEMPTS {
+        0
+    } else {
+        2_i64
+            .checked_pow((failures - FREE_ATTEMPTS + 1) as u32)
+            .map_or(MAX_BACKOFF_SECONDS, |seconds| {
+                seconds.min(MAX_BACKOFF_SECONDS)
+            })
+    }
+}
+
+/// Get the number of seconds left until the user may try to log in again. Returns 0 if the user
+/// may try right away.
+pub fn get_remaining_lockout(login_attempts: &LoginAttempts, now: NaiveDateTime) -> i64 {
+    let last_failure = match login_attempts
+        .failed
+        .last()
+        .and_then(|timestamp| dates::parse_timestamp(timestamp))
+    {
+        Some(last_failure) => last_failure,
+        None => return 0,
+    };
+
+    let unlocked_at =
+        last_failure + Duration::seconds(get_backoff_seconds(login_attempts.failed.len()));
+
+    (unlocked_at - now).num_seconds().max(0)
+}
+
+/// Return an error if the user has to wait before trying to log in again.
+pub fn check_lockout() -> Result<(), HimitsuError> {
+    match get_remaining_lockout(&get_login_attempts()?, Local::now().naive_local()) {
+        0 => Ok(()),
+        remaining => Err(HimitsuError::LockedOut(remaining)),
+    }
+}
+
+/// Count a login attempt as failed before its password is checked. Returns an error if the user
+/// has to wait before trying again. Returns the attempt otherwise, which is passed to
+/// `clear_failed_attempts()` if the password was correct.
+pub fn begin_attempt() -> Result<Attempt, HimitsuError> {
+    let _lock = lock_login_attempts()?;
+
+    let mut login_attempts = get_login_attempts()?;
+    match get_remaining_lockout(&login_attempts, Local::now().naive_local()) {
+        0 => {}
+        remaining => return Err(HimitsuError::LockedOut(remaining)),
+    }
+
+    let timestamp = dates::get_timestamp();
+    login_attempts.failed.push(timestamp.clone());
+    store_login_attempts(&login_attempts)?;
+
+    Ok(Attempt {
+        index: login_attempts.failed.len() - 1,
+        timestamp,
+    })
+}
+
+/// Enforce the user's wipe policy after an attempt counted by `begin_attempt()` failed. Wipes the
+/// vault and returns an error if the policy was reached. Returns the number of failed attempts in
+/// a row otherwise.
+pub fn record_failed_attempt() -> Result<usize, HimitsuError> {
+    let _lock = lock_login_attempts()?;
+
+    let failures = get_login_attempts()?.failed.len();
+
+    if let Some(wipe_after) = config::get_config()?.wipe_after {
+        if wipe_after > 0 && failures >= wipe_after as usize {
+            wipe_vault()?;
+
+            return Err(HimitsuError::VaultWiped);
+        }
+    }
+
+    Ok(failures)
+}
+
+/// Clear the failed login attempts after a successful login. Returns the failed attempts since
+/// the last successful login, without the successful attempt itself.
+pub fn clear_failed_attempts(attempt: &Attempt) -> Result<LoginAttempts, HimitsuError> {
+    let _lock = lock_login_attempts()?;
+
+    let login_attempts = remove_attempt(get_login_attempts()?, attempt);
+
+    let attempts_path = get_attempts_path()?;
+    if attempts_path.exists() {
+        fs::remove_file(attempts_path)?;
+    }
+
+    Ok(login_attempts)
+}
+
+/// Remove an attempt from the failed attempts. Nothing is removed if the attempts were cleared
+/// since the attempt was counted.
+fn remove_attempt(mut login_attempts: LoginAttempts, attempt: &Attempt) -> LoginAttempts {
+    if login_attempts.failed.get(attempt.index) == Some(&attempt.timestamp) {
+        login_attempts.failed.remove(attempt.index);
+    }
+
+    login_attempts
+}
+
+/// Check a password the way a login is checked, such as before a protected secret is used. The
+/// attempt is refused while the user is locked out, counted as failed before `check` runs, and
+/// only cleared if `check` returns `true`. Returns whether the password was correct.
+pub fn run_counted_check(
+    check: impl FnOnce() -> Result<bool, HimitsuError>,
+) -> Result<bool, HimitsuError> {
+    let attempt = begin_attempt()?;
+
+    if check()? {
+        clear_failed_attempts(&attempt)?;
+
+        Ok(true)
+    } else {
+        record_failed_attempt()?;
+
+        Ok(false)
+    }
+}
+
+/// Delete the vault, its encryption values, and the failed login attempts. The user's settings are
+/// kept.
+fn wipe_vault() -> Result<(), HimitsuError> {
+    let vault_dir = vault::get_vault_dir()?;
+    if vault_dir.exists() {
+        fs::remove_dir_all(vault_dir)?;
+    }
+
+    match ProjectDirs::from("", "", "himitsu") {
+        Some(project_directory) => {
+            for file in ["crypt.json", "attempts.json"] {
+                let path = project_directory.config_dir().join(file);
+
+                if path.exists() {
+                    fs::remove_file(path)?;
+                }
+            }
+
+            Ok(())
+        }
+        None => Err(HimitsuError::ApplicationError),
+    }
+}
+
+#[cfg(test)]
+pub mod test_attempts {
+    use super::*;
+
+    /// Test whether the backoff only starts after the free attempts and doubles from there.
+    #[test]
+    fn test_get_backoff_seconds() {
+        assert_eq!(get_backoff_seconds(0), 0);
+        assert_eq!(get_backoff_seconds(FREE_ATTEMPTS - 1), 0);
+        assert_eq!(get_backoff_seconds(FREE_ATTEMPTS), 2);
+        assert_eq!(get_backoff_seconds(FREE_ATTEMPTS + 1), 4);
+        assert_eq!(get_backoff_seconds(FREE_ATTEMPTS + 2), 8);
+        assert_eq!(get_backoff_seconds(100), MAX_BACKOFF_SECONDS);
+    }
+
+    /// Test whether the remaining lockout is measured from the last failed attempt.
+    #[test]
+    fn test_get_remaining_lockout() {
+        let login_attempts = LoginAttempts {
+            failed: vec![
+                "01-01-2022 00:00:00".to_string(),
+                "01-01-2022 00:00:01".to_string(),
+                "01-01-2022 00:00:02".to_string(),
+                "01-01-2022 00:00:03".to_string(),
+            ],
+        };
+        let at = |timestamp: &str| dates::parse_timestamp(timestamp).unwrap();
+
+        assert_eq!(
+            get_remaining_lockout(&login_attempts, at("01-01-2022 00:00:04")),
+            3
+        );
+        assert_eq!(
+            get_remaining_lockout(&login_attempts, at("01-01-2022 00:00:07")),
+            0
+        );
+        assert_eq!(
+            get_remaining_lockout(&LoginAttempts::default(), at("01-01-2022 00:00:04")),
+            0
+        );
+    }
+
+    /// Test whether only the attempt itself is removed, even if another attempt was made in the
+    /// same second.
+    #[test]
+    fn test_remove_attempt() {
+        let failed = vec![
+            "01-01-2022 00:00:00".to_string(),
+            "01-01-2022 00:00:01".to_string(),
+            "01-01-2022 00:00:01".to_string(),
+        ];
+        let attempt = Attempt {
+            index: 1,
+            timestamp: "01-01-2022 00:00:01".to_string(),
+        };
+
+        let remaining = remove_attempt(
+            LoginAttempts {
+                failed: failed.clone(),
+            },
+            &attempt,
+        );
+        assert_eq!(remaining.failed.len(), 2);
+        assert_eq!(remaining.failed[0], "01-01-2022 00:00:00");
+
+        let cleared = remove_attempt(LoginAttempts::default(), &attempt);
+        assert!(cleared.failed.is_empty());
+
+        let stale = Attempt {
+            index: 0,
+            timestamp: "01-01-2022 00:00:01".to_string(),
+        };
+        assert_eq!(
+            remove_attempt(LoginAttempts { failed }, &stale)
+                .failed
+                .len(),
+            3
+        );
+    }
+}
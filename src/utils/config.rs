@@ -2,13 +2,16 @@
 
 use std::{
     fs::{self, File},
-    io::Read,
+    io::{Read, Write},
 };
 
 use directories::ProjectDirs;
 use serde_json;
 
-use crate::{errors::HimitsuError, models::encryption::Encryption};
+use crate::{
+    errors::HimitsuError,
+    models::{config::Config, encryption::Encryption},
+};
 
 /// Retrieve the stored encryption data if the `crypt.json` file exists.
 pub fn get_encryption_values() -> Result<Option<Encryption>, HimitsuError> {
@@ -57,3 +60,48 @@ pub fn get_crypt_json() -> Result<File, HimitsuError> {
         None => Err(HimitsuError::ApplicationError),
     }
 }
+
+/// Store new encryption data in the `crypt.json` file.
+pub fn store_encryption_values(encryption_values: &Encryption) -> Result<(), HimitsuError> {
+    let mut crypt_json = get_crypt_json()?;
+    crypt_json.write_all(serde_json::to_string(encryption_values)?.as_bytes())?;
+
+    Ok(())
+}
+
+/// Retrieve the user's settings from the `config.json` file. Returns the default settings if the
+/// file does not exist.
+pub fn get_config() -> Result<Config, HimitsuError> {
+    match ProjectDirs::from("", "", "himitsu") {
+        Some(project_directory) => {
+            let config_json_path = project_directory.config_dir().join("config.json");
+
+            if config_json_path.exists() {
+                Ok(serde_json::from_str(&fs::read_to_string(
+                    config_json_path,
+                )?)?)
+            } else {
+                Ok(Config::default())
+            }
+        }
+        None => Err(HimitsuError::ApplicationError),
+    }
+}
+
+/// Store the user's settings in the `config.json` file.
+pub fn store_config(config: &Config) -> Result<(), HimitsuError> {
+    match ProjectDirs::from("", "", "himitsu") {
+        Some(project_directory) => {
+            let config_dir = project_directory.config_dir();
+            fs::create_dir_all(config_dir)?;
+
+            fs::write(
+                config_dir.join("config.json"),
+                serde_json::to_string_pretty(config)?,
+            )?;
+
+            Ok(())
+        }
+        None => Err(HimitsuError::ApplicationError),
+    }
+}
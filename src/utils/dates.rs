@@ -0,0 +1,143 @@
+//! Contains date utilities for `himitsu`.
+
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+
+/// The format used for every timestamp stored in a secret's `Anatomy`.
+pub const TIMESTAMP_FORMAT: &str = "%m-%d-%Y %H:%M:%S";
+
+/// Get the current local time as a formatted timestamp.
+pub fn get_timestamp() -> String {
+    Local::now().format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// Parse a timestamp stored in a secret's `Anatomy`. Returns `None` if the timestamp does not
+/// follow `TIMESTAMP_FORMAT`.
+pub fn parse_timestamp(timestamp: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()
+}
+
+/// Get the current local time.
+pub fn get_now() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
+/// Format a date as a timestamp.
+pub fn format_timestamp(date: &NaiveDateTime) -> String {
+    date.format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// Format a local date as an RFC 3339 timestamp in UTC, such as `2022-01-31T12:00:00Z`.
+pub fn format_rfc3339(date: &NaiveDateTime) -> Option<String> {
+    Local.from_local_datetime(date).earliest().map(|date| {
+        date.with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    })
+}
+
+/// Get the number of seconds between the Unix epoch and a local date.
+pub fn to_unix_time(date: &NaiveDateTime) -> Option<u64> {
+    Local
+        .from_local_datetime(date)
+        .earliest()
+        .and_then(|date| u64::try_from(date.timestamp()).ok())
+}
+
+/// Parse a duration made of a number and a unit: `m` (minutes), `h` (hours), `d` (days), or `w`
+/// (weeks), such as `30m` or `90d`.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let unit = input.chars().last()?;
+    let amount = input[..input.len() - unit.len_utf8()].parse::<i64>().ok()?;
+
+    if amount <= 0 {
+        return None;
+    }
+
+    match unit {
+        'm' => Some(Duration::minutes(amount)),
+        'h' => Some(Duration::hours(amount)),
+        'd' => Some(Duration::days(amount)),
+        'w' => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+/// Parse when something expires: either a duration from `now` (see `parse_duration`), a date
+/// (`YYYY-MM-DD`, which expires at the start of that day), or a timestamp.
+pub fn parse_expiry(input: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    if let Some(duration) = parse_duration(input) {
+        return Some(now + duration);
+    }
+
+    NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .or_else(|| parse_timestamp(input.trim()))
+}
+
+/// Parse when a query starts: either a duration before `now` (see `parse_duration`), a date
+/// (`YYYY-MM-DD`, which starts at the start of that day), or a timestamp.
+pub fn parse_since(input: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    match parse_duration(input) {
+        Some(duration) => Some(now - duration),
+        None => parse_expiry(input, now),
+    }
+}
+
+#[cfg(test)]
+pub mod test_dates {
+    use super::*;
+
+    /// Test whether a generated timestamp can be parsed back.
+    #[test]
+    fn test_timestamp_round_trip() {
+        assert!(parse_timestamp(&get_timestamp()).is_some());
+    }
+
+    /// Test whether timestamps are compared chronologically rather than lexically.
+    #[test]
+    fn test_parse_timestamp_ordering() {
+        let older = parse_timestamp("12-31-2021 23:59:59").unwrap();
+        let newer = parse_timestamp("01-01-2022 00:00:00").unwrap();
+
+        assert!(older < newer);
+        assert!(parse_timestamp("today").is_none());
+    }
+
+    /// Test whether durations and dates are parsed into expiry dates.
+    #[test]
+    fn test_parse_expiry() {
+        let now = parse_timestamp("01-01-2022 00:00:00").unwrap();
+
+        assert_eq!(
+            parse_expiry("90m", now),
+            parse_timestamp("01-01-2022 01:30:00")
+        );
+        assert_eq!(
+            parse_expiry("2w", now),
+            parse_timestamp("01-15-2022 00:00:00")
+        );
+        assert_eq!(
+            parse_expiry("2022-03-01", now),
+            parse_timestamp("03-01-2022 00:00:00")
+        );
+        assert!(parse_expiry("0d", now).is_none());
+        assert!(parse_expiry("soon", now).is_none());
+    }
+
+    /// Test whether durations are counted backwards when parsing the start of a query.
+    #[test]
+    fn test_parse_since() {
+        let now = parse_timestamp("01-15-2022 00:00:00").unwrap();
+
+        assert_eq!(
+            parse_since("2w", now),
+            parse_timestamp("01-01-2022 00:00:00")
+        );
+        assert_eq!(
+            parse_since("2022-01-10", now),
+            parse_timestamp("01-10-2022 00:00:00")
+        );
+        assert!(parse_since("yesterday", now).is_none());
+    }
+}
@@ -0,0 +1,260 @@
+//! Contains utilities for editing secrets in an external editor, such as `$EDITOR`.
+//!
+//! The secret is written to a file only the current user may access, in the runtime directory,
+//! which usually lives in memory. Editors that keep swap, backup, or undo files are told not to,
+//! and the file is overwritten before it is removed once the editor exits.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ansi_term::{Color, Style};
+use data_encoding::HEXLOWER;
+use rand::{rngs::OsRng, RngCore};
+
+use crate::{errors::HimitsuError, utils::runtime};
+
+/// The editor used if neither `$VISUAL` nor `$EDITOR` is set.
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Contains a file holding a decrypted secret. The file is overwritten and removed when this is
+/// dropped, including when editing fails.
+pub struct SecretFile {
+    /// The file's path.
+    path: PathBuf,
+}
+
+impl SecretFile {
+    /// Write a secret to a new file in the runtime directory.
+    pub fn create(secret: &str) -> Result<Self, HimitsuError> {
+        let mut random_bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut random_bytes);
+        let path = runtime::get_runtime_dir()?
+            .join(format!("edit-{}.txt", HEXLOWER.encode(&random_bytes)));
+
+        let mut options = OpenOptions::new();
+        options.create_new(true).write(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(&path)?;
+        let secret_file = Self { path };
+
+        file.write_all(secret.as_bytes())?;
+        file.sync_all()?;
+
+        Ok(secret_file)
+    }
+
+    /// Get the file's path.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the file's contents.
+    pub fn read(&self) -> Result<String, HimitsuError> {
+        Ok(fs::read_to_string(&self.path)?)
+    }
+
+    /// Overwrite the file with zeros and remove it.
+    fn shred(&self) -> Result<(), HimitsuError> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+
+        let length = fs::metadata(&self.path)?.len() as usize;
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+        file.write_all(&vec![0u8; length])?;
+        file.sync_all()?;
+
+        Ok(fs::remove_file(&self.path)?)
+    }
+}
+
+impl Drop for SecretFile {
+    fn drop(&mut self) {
+        if let Err(error) = self.shred() {
+            println!(
+                "{}",
+                Color::Red
+                    .bold()
+                    .paint(format!("Could not remove {}: {error}", self.path.display()))
+            );
+        }
+    }
+}
+
+/// Get the editor command from `$VISUAL` or `$EDITOR`. Arguments are split on whitespace, such as
+/// in `code --wait`.
+pub fn get_editor_command() -> Vec<String> {
+    let editor = env::var("VISUAL")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+
+    editor.split_whitespace().map(str::to_string).collect()
+}
+
+/// Get the arguments that stop an editor from writing copies of the secret elsewhere, such as
+/// swap, backup, undo, and history files. GUI editors are also told to wait until the file is
+/// closed. Returns `None` for editors without known arguments.
+pub fn get_editor_hints(program: &str) -> Option<Vec<String>> {
+    let name = Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program);
+
+    let hints: &[&str] = match name {
+        "vi" | "vim" | "nvim" | "view" => &[
+            "-n",
+            "-i",
+            "NONE",
+            "--cmd",
+            "set nobackup nowritebackup noundofile",
+        ],
+        "gvim" | "mvim" => &[
+            "-f",
+            "-n",
+            "-i",
+            "NONE",
+            "--cmd",
+            "set nobackup nowritebackup noundofile",
+        ],
+        "emacs" => &[
+            "--eval",
+            "(setq make-backup-files nil auto-save-default nil create-lockfiles nil)",
+        ],
+        "micro" => &["-backup", "false", "-savehistory", "false"],
+        "code" | "codium" | "subl" | "zed" => &["--wait"],
+        "hx" | "kak" | "nano" => &[],
+        _ => return None,
+    };
+
+    Some(hints.iter().map(|hint| hint.to_string()).collect())
+}
+
+/// Remove the line break most editors add at the end of a file, unless the secret already ended
+/// with one.
+pub fn strip_added_newline(original: &str, edited: String) -> String {
+    if original.ends_with('\n') {
+        return edited;
+    }
+
+    match edited
+        .strip_suffix("\r\n")
+        .or_else(|| edited.strip_suffix('\n'))
+    {
+        Some(stripped) => stripped.to_string(),
+        None => edited,
+    }
+}
+
+/// Open a secret in the user's editor. Returns the edited secret, or `None` if it was not changed.
+pub fn run_editor(secret: &str) -> Result<Option<String>, HimitsuError> {
+    let command = get_editor_command();
+    let (program, arguments) = command
+        .split_first()
+        .ok_or_else(|| HimitsuError::PathError("No editor is set!".to_string()))?;
+
+    if env::var_os("XDG_RUNTIME_DIR").is_none() {
+        println!(
+            "{}",
+            Color::Fixed(172).bold().paint(
+                "$XDG_RUNTIME_DIR is not set, so the secret is written to the temporary directory, which may not live in memory."
+            )
+        );
+    }
+
+    let hints = get_editor_hints(program);
+    if hints.is_none() {
+        println!(
+            "{}",
+            Style::new().dimmed().paint(format!(
+                "Make sure {program} does not keep swap, backup, or undo files of the secret."
+            ))
+        );
+    }
+
+    let secret_file = SecretFile::create(secret)?;
+
+    let status = Command::new(program)
+        .args(arguments)
+        .args(hints.unwrap_or_default())
+        .arg(secret_file.get_path())
+        .status()
+        .map_err(|error| HimitsuError::PathError(format!("Could not run {program}: {error}")))?;
+    if !status.success() {
+        return Err(HimitsuError::PathError(format!(
+            "{program} exited with {status}, so the secret was not changed."
+        )));
+    }
+
+    let edited = strip_added_newline(secret, secret_file.read()?);
+    drop(secret_file);
+
+    Ok(Some(edited).filter(|edited| edited != secret))
+}
+
+#[cfg(test)]
+pub mod test_editor {
+    use super::*;
+
+    /// Test whether editors are told not to keep copies of the secret.
+    #[test]
+    fn test_get_editor_hints() {
+        assert!(get_editor_hints("/usr/bin/vim")
+            .unwrap()
+            .contains(&"-n".to_string()));
+        assert_eq!(get_editor_hints("code"), Some(vec!["--wait".to_string()]));
+        assert_eq!(get_editor_hints("nano"), Some(Vec::new()));
+        assert_eq!(get_editor_hints("ed"), None);
+    }
+
+    /// Test whether the line break added by editors is removed.
+    #[test]
+    fn test_strip_added_newline() {
+        assert_eq!(
+            strip_added_newline("hunter2", "hunter3\n".to_string()),
+            "hunter3"
+        );
+        assert_eq!(
+            strip_added_newline("hunter2", "hunter3\r\n".to_string()),
+            "hunter3"
+        );
+        assert_eq!(
+            strip_added_newline("-----END KEY-----\n", "-----END KEY-----\n".to_string()),
+            "-----END KEY-----\n"
+        );
+    }
+
+    /// Test whether the file is only readable by the user and is removed once dropped.
+    #[test]
+    fn test_secret_file() {
+        let secret_file = SecretFile::create("hunter2").unwrap();
+        let path = secret_file.get_path().to_path_buf();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            assert_eq!(
+                fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+        assert_eq!(secret_file.read().unwrap(), "hunter2");
+
+        drop(secret_file);
+        assert!(!path.exists());
+    }
+}
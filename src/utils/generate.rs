@@ -0,0 +1,59 @@
+//! Contains a random password generator.
+
+use rand::{rngs::OsRng, seq::SliceRandom};
+
+/// The length of generated passwords.
+pub const DEFAULT_LENGTH: usize = 24;
+
+/// The character sets every generated password draws at least one character from.
+const CHARACTER_SETS: [&[u8]; 4] = [
+    b"ABCDEFGHJKLMNPQRSTUVWXYZ",
+    b"abcdefghijkmnopqrstuvwxyz",
+    b"23456789",
+    b"!#$%&*+-=?@^_~",
+];
+
+/// Generate a random password of `length` characters using the operating system's random number
+/// generator. The password contains uppercase and lowercase letters, digits, and symbols, but no
+/// characters that are easily confused, such as `l`, `1`, `O`, and `0`.
+pub fn generate_password(length: usize) -> String {
+    let alphabet = CHARACTER_SETS.concat();
+
+    loop {
+        let password = (0..length)
+            .filter_map(|_| alphabet.choose(&mut OsRng).map(|byte| *byte as char))
+            .collect::<String>();
+
+        if length < CHARACTER_SETS.len()
+            || CHARACTER_SETS.iter().all(|character_set| {
+                password
+                    .bytes()
+                    .any(|character| character_set.contains(&character))
+            })
+        {
+            return password;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_generate {
+    use super::*;
+
+    use crate::strength;
+
+    /// Test whether generated passwords have the requested length, mix every character set, and
+    /// are estimated to be very strong.
+    #[test]
+    fn test_generate_password() {
+        let password = generate_password(DEFAULT_LENGTH);
+
+        assert_eq!(password.chars().count(), DEFAULT_LENGTH);
+        assert!(password.chars().any(|character| character.is_ascii_digit()));
+        assert!(password
+            .chars()
+            .any(|character| !character.is_alphanumeric()));
+        assert_eq!(strength::estimate_strength(&password).score, 4);
+        assert_ne!(password, generate_password(DEFAULT_LENGTH));
+    }
+}
@@ -0,0 +1,259 @@
+//! Contains utilities for keeping the vault's history in a local git repository.
+//!
+//! The vault directory only contains encrypted secrets and the encrypted lookup table, so its
+//! history may be committed and pushed to a private remote without exposing any secrets. Each
+//! secret is encrypted once with its own salt and nonce, and the lookup table is encrypted with a
+//! new salt and nonce every time it is written, so no two versions in the history share a key and
+//! nonce. Commit messages only contain a prefix of the secret's hash ID.
+
+use std::{
+    path::Path,
+    process::{Command, Output},
+};
+
+use crate::errors::HimitsuError;
+
+use super::vault;
+
+/// The number of characters of a secret's hash ID that are included in commit messages.
+const HASH_PREFIX_LENGTH: usize = 8;
+
+/// Contains a single commit in the vault's history.
+#[derive(Debug)]
+pub struct HistoryEntry {
+    /// The date the commit was made.
+    pub date: String,
+    /// The commit message.
+    pub message: String,
+    /// The abbreviated commit hash.
+    pub revision: String,
+}
+
+/// Run a git command inside a repository directory and return its output. Returns an error
+/// containing git's `stderr` if the command did not succeed.
+fn run_git(repo_dir: &Path, args: &[&str]) -> Result<Output, HimitsuError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_dir)
+        .args(args)
+        .output()
+        .map_err(|error| HimitsuError::GitError(format!("Could not run git: {error}")))?;
+
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(HimitsuError::GitError(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Check whether a directory is the root of a git repository.
+pub fn is_repository(repo_dir: &Path) -> bool {
+    repo_dir.join(".git").exists()
+}
+
+/// Initialize a git repository in a directory, optionally adding a remote named `origin`, then
+/// commit its current contents.
+pub fn init_repository(repo_dir: &Path, remote: Option<&str>) -> Result<(), HimitsuError> {
+    if is_repository(repo_dir) {
+        return Err(HimitsuError::GitError(format!(
+            "{} is already a git repository!",
+            repo_dir.display()
+        )));
+    }
+
+    run_git(repo_dir, &["init", "--quiet"])?;
+
+    if let Some(remote) = remote {
+        run_git(repo_dir, &["remote", "add", "origin", remote])?;
+    }
+
+    commit_changes(repo_dir, "initialize vault history")?;
+
+    Ok(())
+}
+
+/// Stage and commit every change in the repository. Nothing is committed if the working tree is
+/// clean. Returns whether a commit was made.
+pub fn commit_changes(repo_dir: &Path, message: &str) -> Result<bool, HimitsuError> {
+    run_git(repo_dir, &["add", "--all"])?;
+
+    let status = run_git(repo_dir, &["status", "--porcelain"])?;
+    if status.stdout.is_empty() {
+        return Ok(false);
+    }
+
+    let mut args = vec![];
+
+    // Fall back to a generic identity so commits do not fail on machines without a configured
+    // git identity.
+    if run_git(repo_dir, &["config", "user.email"]).is_err() {
+        args.extend([
+            "-c",
+            "user.name=himitsu",
+            "-c",
+            "user.email=himitsu@localhost",
+        ]);
+    }
+    args.extend(["commit", "--quiet", "--message", message]);
+
+    run_git(repo_dir, &args)?;
+
+    Ok(true)
+}
+
+/// Get the repository's history, starting with the most recent commit.
+pub fn get_history(repo_dir: &Path) -> Result<Vec<HistoryEntry>, HimitsuError> {
+    let output = run_git(
+        repo_dir,
+        &[
+            "log",
+            "--date=format:%m-%d-%Y %H:%M:%S",
+            "--format=%h%x1f%ad%x1f%s",
+        ],
+    )?;
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\x1f');
+
+            Some(HistoryEntry {
+                revision: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                message: fields.next()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Roll the whole repository back to the state it was in at a revision. The rollback is recorded
+/// as a new commit, so it may be undone by restoring an earlier revision. The audit log is never
+/// rolled back.
+pub fn restore_revision(repo_dir: &Path, revision: &str) -> Result<(), HimitsuError> {
+    let commit = format!("{revision}^{{commit}}");
+    run_git(repo_dir, &["rev-parse", "--quiet", "--verify", &commit])
+        .map_err(|_| HimitsuError::GitError(format!("Unknown revision: {revision}")))?;
+
+    run_git(
+        repo_dir,
+        &[
+            "restore",
+            "--source",
+            revision,
+            "--staged",
+            "--worktree",
+            "--",
+            ".",
+            ":(exclude)audit.log",
+            ":(exclude)audit.tail",
+        ],
+    )?;
+
+    commit_changes(repo_dir, &format!("restore {revision}"))?;
+
+    Ok(())
+}
+
+/// Push the repository's current branch to its `origin` remote.
+pub fn push(repo_dir: &Path) -> Result<(), HimitsuError> {
+    run_git(
+        repo_dir,
+        &["push", "--quiet", "--set-upstream", "origin", "HEAD"],
+    )?;
+
+    Ok(())
+}
+
+/// Record a change to a secret in the vault's history if the vault is a git repository. The
+/// commit message only contains the action and a prefix of the secret's hash ID.
+pub fn record_change(action: &str, hash_id: &str) -> Result<(), HimitsuError> {
+    let vault_dir = vault::get_vault_dir()?;
+
+    if is_repository(&vault_dir) {
+        let hash_prefix = &hash_id[..hash_id.len().min(HASH_PREFIX_LENGTH)];
+
+        commit_changes(&vault_dir, &format!("{action} {hash_prefix}"))?;
+    }
+
+    Ok(())
+}
+
+/// Record a change that is not tied to a single secret, such as a merge, in the vault's history
+/// if the vault is a git repository.
+pub fn record_vault_change(message: &str) -> Result<(), HimitsuError> {
+    let vault_dir = vault::get_vault_dir()?;
+
+    if is_repository(&vault_dir) {
+        commit_changes(&vault_dir, message)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_git {
+    use std::{env, fs, path::PathBuf};
+
+    use rand::{rngs::OsRng, RngCore};
+
+    use super::*;
+
+    /// Create an empty, uniquely named directory in the system's temporary directory.
+    fn create_temp_dir(name: &str) -> PathBuf {
+        let temp_dir = env::temp_dir().join(format!("himitsu-{name}-{}", OsRng.next_u64()));
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        temp_dir
+    }
+
+    /// Test whether changes are committed, listed, pushed to a bare remote, and restored.
+    #[test]
+    fn test_history_round_trip() {
+        let remote_dir = create_temp_dir("remote");
+        let vault_dir = create_temp_dir("vault");
+
+        Command::new("git")
+            .args(["init", "--quiet", "--bare"])
+            .arg(&remote_dir)
+            .status()
+            .unwrap();
+
+        fs::write(vault_dir.join("table"), "first").unwrap();
+        init_repository(&vault_dir, Some(&remote_dir.to_string_lossy())).unwrap();
+        assert!(is_repository(&vault_dir));
+
+        fs::write(vault_dir.join("table"), "second").unwrap();
+        fs::write(vault_dir.join("skeleton"), "added").unwrap();
+        assert!(commit_changes(&vault_dir, "update 0123abcd").unwrap());
+        assert!(!commit_changes(&vault_dir, "nothing changed").unwrap());
+
+        let history = get_history(&vault_dir).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].message, "update 0123abcd");
+        assert_eq!(history[1].message, "initialize vault history");
+
+        push(&vault_dir).unwrap();
+        let remote_log = Command::new("git")
+            .arg("-C")
+            .arg(&remote_dir)
+            .args(["log", "--format=%s"])
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&remote_log.stdout).contains("update 0123abcd"));
+
+        restore_revision(&vault_dir, &history[1].revision).unwrap();
+        assert_eq!(
+            fs::read_to_string(vault_dir.join("table")).unwrap(),
+            "first"
+        );
+        assert!(!vault_dir.join("skeleton").exists());
+        assert_eq!(get_history(&vault_dir).unwrap().len(), 3);
+
+        assert!(restore_revision(&vault_dir, "does-not-exist").is_err());
+
+        fs::remove_dir_all(remote_dir).unwrap();
+        fs::remove_dir_all(vault_dir).unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+//! Contains utilities for locking files that several `himitsu` processes may change at once.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    path::Path,
+};
+
+use crate::errors::HimitsuError;
+
+/// Holds an exclusive lock on a lock file. The lock is released when this is dropped, since
+/// closing the file releases it.
+pub struct FileLock {
+    /// The locked file.
+    _file: File,
+}
+
+/// Take an exclusive lock on a lock file, waiting until no other process holds it. The lock file
+/// is created if it does not exist yet.
+pub fn lock_exclusive(lock_path: &Path) -> Result<FileLock, HimitsuError> {
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+
+    lock_file(&file)?;
+
+    Ok(FileLock { _file: file })
+}
+
+/// Wait for an exclusive lock on an open file.
+#[cfg(unix)]
+fn lock_file(file: &File) -> Result<(), HimitsuError> {
+    use std::{io, os::unix::io::AsRawFd};
+
+    // SAFETY: `file` owns an open file descriptor for the whole call, and `flock` does not touch
+    // any memory.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+/// Files cannot be locked on this platform, so the lock file is only created.
+#[cfg(not(unix))]
+fn lock_file(_file: &File) -> Result<(), HimitsuError> {
+    Ok(())
+}
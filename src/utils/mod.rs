@@ -1,7 +1,16 @@
 //! Contains utilities used throughout `himitsu`.
 
+pub mod attempts;
 pub mod clipboard;
 pub mod closet;
 pub mod config;
+pub mod dates;
+pub mod editor;
+pub mod generate;
+pub mod git;
+pub mod lock;
 pub mod paint;
+pub mod protocol;
+pub mod runtime;
 pub mod store;
+pub mod vault;
@@ -2,9 +2,36 @@
 
 use ansi_term::Color;
 
-use crate::errors::HimitsuError;
+use crate::{errors::HimitsuError, strength::StrengthEstimate};
 
 /// Neatly paints and formats the error raised.
 pub fn paint_error(error: HimitsuError) {
     println!("\n{}\n", Color::Red.bold().paint(error.to_string()));
 }
+
+/// Paint a strength meter for a secret, followed by any warnings about what makes it weak.
+pub fn paint_strength_meter(estimate: &StrengthEstimate) {
+    let color = match estimate.score {
+        0 | 1 => Color::Red,
+        2 => Color::Yellow,
+        _ => Color::Green,
+    };
+    let filled = estimate.score as usize + 1;
+
+    println!(
+        "{} {} {}",
+        Color::White.bold().paint("Strength:"),
+        color
+            .bold()
+            .paint(format!("{}{}", "█".repeat(filled), "░".repeat(5 - filled))),
+        color.paint(format!(
+            "({}/4, {})",
+            estimate.score,
+            estimate.get_description()
+        ))
+    );
+
+    for warning in estimate.warnings.iter() {
+        println!("    {} {warning}", Color::Fixed(172).bold().paint("!"));
+    }
+}
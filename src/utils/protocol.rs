@@ -0,0 +1,58 @@
+//! Contains utilities for subcommands whose output is read by other programs, such as credential
+//! helpers.
+//!
+//! Nothing but the protocol may be written to stdout while such a subcommand runs. Every prompt,
+//! spinner, and message is redirected to stderr instead, and the protocol's output is written to
+//! the original stdout.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+    sync::OnceLock,
+};
+
+use crate::errors::HimitsuError;
+
+/// The original stdout, once stdout has been redirected to stderr.
+static PROTOCOL_OUTPUT: OnceLock<File> = OnceLock::new();
+
+/// Redirect everything written to stdout to stderr for the rest of the program. Protocol output
+/// is still written to the original stdout with `write_output()`.
+#[cfg(unix)]
+pub fn redirect_stdout() -> Result<(), HimitsuError> {
+    use std::os::unix::io::FromRawFd;
+
+    io::stdout().flush()?;
+
+    let original_stdout = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if original_stdout < 0 || unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(HimitsuError::IOError(io::Error::last_os_error()));
+    }
+
+    let _ = PROTOCOL_OUTPUT.set(unsafe { File::from_raw_fd(original_stdout) });
+
+    Ok(())
+}
+
+/// Stdout cannot be redirected on this platform, so prompts and protocol output share stdout.
+#[cfg(not(unix))]
+pub fn redirect_stdout() -> Result<(), HimitsuError> {
+    Ok(())
+}
+
+/// Write protocol output to the original stdout.
+pub fn write_output(output: &str) -> Result<(), HimitsuError> {
+    match PROTOCOL_OUTPUT.get() {
+        Some(mut original_stdout) => {
+            original_stdout.write_all(output.as_bytes())?;
+            original_stdout.flush()?;
+        }
+        None => {
+            let mut stdout = io::stdout();
+            stdout.write_all(output.as_bytes())?;
+            stdout.flush()?;
+        }
+    }
+
+    Ok(())
+}
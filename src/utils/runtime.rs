@@ -0,0 +1,74 @@
+//! Contains utilities for the runtime directory, which holds sockets and other files that only
+//! exist while `himitsu` is running.
+
+use std::{env, fs, path::PathBuf};
+
+#[cfg(unix)]
+use std::{
+    fs::Permissions,
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+};
+
+use crate::errors::HimitsuError;
+
+/// Get the runtime directory, creating it if it does not exist yet. The directory is only
+/// accessible to the current user.
+///
+/// `$XDG_RUNTIME_DIR` is used if it is set, since it usually lives in memory and is cleared on
+/// logout. Otherwise, a per-user directory in the temporary directory is used.
+#[cfg(unix)]
+pub fn get_runtime_dir() -> Result<PathBuf, HimitsuError> {
+    let runtime_dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(xdg_runtime_dir) => PathBuf::from(xdg_runtime_dir).join("himitsu"),
+        // SAFETY: `getuid` cannot fail and does not touch any memory.
+        None => env::temp_dir().join(format!("himitsu-{}", unsafe { libc::getuid() })),
+    };
+
+    fs::create_dir_all(&runtime_dir)?;
+    fs::set_permissions(&runtime_dir, Permissions::from_mode(0o700))?;
+
+    Ok(runtime_dir)
+}
+
+/// Get the runtime directory, creating it if it does not exist yet. The temporary directory is
+/// already private to the current user on this platform.
+#[cfg(not(unix))]
+pub fn get_runtime_dir() -> Result<PathBuf, HimitsuError> {
+    let runtime_dir = env::temp_dir().join("himitsu");
+    fs::create_dir_all(&runtime_dir)?;
+
+    Ok(runtime_dir)
+}
+
+/// Bind a Unix socket only the current user may access. The socket is created with these
+/// permissions, so it is never accessible to anyone else, even briefly. A socket left behind by a
+/// process that is no longer running is replaced, but a socket another process is listening on is
+/// not.
+#[cfg(unix)]
+pub fn bind_socket(socket_path: &Path) -> Result<UnixListener, HimitsuError> {
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(HimitsuError::PathError(format!(
+                "Another process is already listening on {}!",
+                socket_path.display()
+            )));
+        }
+
+        fs::remove_file(socket_path)?;
+    }
+
+    // SAFETY: `umask` cannot fail and does not touch any memory.
+    let previous_umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(socket_path);
+    // SAFETY: As above.
+    unsafe { libc::umask(previous_umask) };
+
+    let listener = listener?;
+    fs::set_permissions(socket_path, Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
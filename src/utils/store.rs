@@ -1,12 +1,13 @@
 //! Contains utilities for storing secrets.
 
-use std::fs;
+use std::{fs, path::Path};
 
 use chacha20poly1305::aead::{consts::U24, generic_array::GenericArray};
-use directories::ProjectDirs;
 
 use crate::errors::HimitsuError;
 
+use super::vault;
+
 /// Store the secret onto the machine.
 pub fn store_secret(
     ciphertext: Vec<u8>,
@@ -14,29 +15,30 @@ pub fn store_secret(
     salt: [u8; 32],
     secret_hash: &str,
 ) -> Result<(), HimitsuError> {
-    match ProjectDirs::from("", "", "himitsu") {
-        Some(project_directory) => {
-            let closet_path = project_directory
-                .data_dir()
-                .join("closet")
-                .join(secret_hash);
-
-            fs::create_dir_all(&closet_path)?;
-
-            if let Err(error) = fs::write(closet_path.join("skeleton"), ciphertext) {
-                return Err(HimitsuError::StoreSecretError(error.to_string()));
-            }
-            if let Err(error) = fs::write(closet_path.join("salt"), salt) {
-                return Err(HimitsuError::StoreSaltError(error.to_string()));
-            }
-            if let Err(error) = fs::write(closet_path.join("nonce"), nonce) {
-                return Err(HimitsuError::StoreNonceError(error.to_string()));
-            }
-
-            Ok(())
-        }
-        None => Err(HimitsuError::PathError(
-            "Could not get the path to the himitsu application directory!".to_string(),
-        )),
+    let closet_path = vault::get_vault_dir()?.join("closet").join(secret_hash);
+
+    store_secret_in(ciphertext, &closet_path, nonce, salt)
+}
+
+/// Store the secret in a directory using the same layout as a secret's hash directory in the
+/// `closet`.
+pub fn store_secret_in(
+    ciphertext: Vec<u8>,
+    directory: &Path,
+    nonce: &GenericArray<u8, U24>,
+    salt: [u8; 32],
+) -> Result<(), HimitsuError> {
+    fs::create_dir_all(directory)?;
+
+    if let Err(error) = fs::write(directory.join("skeleton"), ciphertext) {
+        return Err(HimitsuError::StoreSecretError(error.to_string()));
     }
+    if let Err(error) = fs::write(directory.join("salt"), salt) {
+        return Err(HimitsuError::StoreSaltError(error.to_string()));
+    }
+    if let Err(error) = fs::write(directory.join("nonce"), nonce) {
+        return Err(HimitsuError::StoreNonceError(error.to_string()));
+    }
+
+    Ok(())
 }
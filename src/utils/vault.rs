@@ -0,0 +1,30 @@
+//! Contains utilities for locating the vault.
+
+use std::{path::PathBuf, sync::OnceLock};
+
+use directories::ProjectDirs;
+
+use crate::errors::HimitsuError;
+
+/// Overrides the vault directory for the rest of the program, such as when a team vault is used.
+static VAULT_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Get the path to the vault directory, which contains the `closet` and `lookup` directories.
+pub fn get_vault_dir() -> Result<PathBuf, HimitsuError> {
+    if let Some(vault_dir) = VAULT_DIR_OVERRIDE.get() {
+        return Ok(vault_dir.clone());
+    }
+
+    match ProjectDirs::from("", "", "himitsu") {
+        Some(project_directory) => Ok(project_directory.data_dir().to_path_buf()),
+        None => Err(HimitsuError::PathError(
+            "Could not get the path to the himitsu application directory!".to_string(),
+        )),
+    }
+}
+
+/// Use a different vault directory for the rest of the program. The vault directory may only be
+/// overridden once.
+pub fn set_vault_dir(vault_dir: PathBuf) {
+    let _ = VAULT_DIR_OVERRIDE.set(vault_dir);
+}